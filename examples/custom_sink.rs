@@ -0,0 +1,42 @@
+//! Example: Shipping records to a custom sink
+//! Run with: cargo run --example custom_sink
+
+use custom_tracing_logger::LogSink;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{info, warn};
+
+/// Stand-in for a NATS/Redis/agent client
+struct CountingSink {
+    records: AtomicUsize,
+}
+
+impl LogSink for CountingSink {
+    fn write_event(&self, json: &[u8]) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+        eprintln!("[sink] {}", String::from_utf8_lossy(json));
+    }
+
+    fn flush(&self) {
+        eprintln!(
+            "[sink] flushed after {} records",
+            self.records.load(Ordering::Relaxed)
+        );
+    }
+}
+
+fn main() {
+    custom_tracing_logger::builder()
+        .add_sink(Box::new(CountingSink {
+            records: AtomicUsize::new(0),
+        }))
+        // Closures work as sinks too
+        .add_sink(Box::new(|json: &[u8]| {
+            let _ = json.len();
+        }))
+        .init();
+
+    info!(order_id = 42, "Order created");
+    warn!(order_id = 42, "Payment retry");
+
+    custom_tracing_logger::flush();
+}
@@ -1,7 +1,7 @@
 //! Example: Performance monitoring with structured helpers
 //! Run with: cargo run --example performance_monitoring
 
-use custom_tracing_logger::structured;
+use custom_tracing_logger::{structured, timed};
 use std::time::Instant;
 use tracing::{info, instrument, warn};
 
@@ -36,7 +36,8 @@ fn run_job(job_id: u64) {
     info!(job_id = job_id, "Starting job");
 
     for batch_id in 1..=3 {
-        match process_batch(batch_id, 1000) {
+        // Logs start and end of the batch with duration_ms and success
+        match timed!("process_batch", { process_batch(batch_id, 1000) }) {
             Ok(_) => info!(batch_id = batch_id, "Batch completed"),
             Err(e) => warn!(batch_id = batch_id, error = e, "Batch failed"),
         }
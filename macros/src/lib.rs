@@ -0,0 +1,85 @@
+//! Procedural macros for custom-tracing-logger
+//!
+//! Use them through the `macros` feature of `custom-tracing-logger`, which
+//! re-exports them.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, ItemFn, LitStr, ReturnType, Type};
+
+/// Run a test with its own log capture, available as `logs`
+///
+/// Wraps the test body in
+/// `let logs = custom_tracing_logger::testing::per_test_subscriber();`, so
+/// each test sees only its own records and tests run in parallel. Adds
+/// `#[test]` unless the function is `async` or already has a test
+/// attribute such as `#[tokio::test]`, which must come first.
+#[proc_macro_attribute]
+pub fn logged_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "#[logged_test] takes no arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let mut function = parse_macro_input!(item as ItemFn);
+    let has_test_attr = function.attrs.iter().any(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "test")
+    });
+    let body = &function.block;
+    function.block = parse_quote!({
+        #[allow(unused_variables)]
+        let logs = ::custom_tracing_logger::testing::per_test_subscriber();
+        #body
+    });
+    let test_attr = if has_test_attr || function.sig.asyncness.is_some() {
+        quote!()
+    } else {
+        quote!(#[test])
+    };
+    quote!(#test_attr #function).into()
+}
+
+/// Log the start, end and `duration_ms` of every call of a function
+///
+/// The operation is named after the function, or the string argument
+/// (`#[log_timed("import_users")]`). A function returning a `Result` also
+/// logs `success`, see `custom_tracing_logger::timing`. Works on `async`
+/// functions, timing until their future completes.
+#[proc_macro_attribute]
+pub fn log_timed(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name = if attr.is_empty() {
+        None
+    } else {
+        Some(parse_macro_input!(attr as LitStr).value())
+    };
+    let mut function = parse_macro_input!(item as ItemFn);
+    let operation = name.unwrap_or_else(|| function.sig.ident.to_string());
+    // `impl Trait` cannot be named in a binding, so it is left to inference
+    let output = match &function.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) if matches!(**ty, Type::ImplTrait(_)) => quote!(_),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let body = &function.block;
+    // Running the body in a closure or async block keeps `return` and `?` inside the timing
+    let run = if function.sig.asyncness.is_some() {
+        quote!(async move #body.await)
+    } else {
+        quote!((move || #body)())
+    };
+    function.block = parse_quote!({
+        let __log_timer = ::custom_tracing_logger::timing::Timer::start(#operation);
+        let __log_result: #output = #run;
+        #[allow(unused_imports)]
+        use ::custom_tracing_logger::timing::{OtherOutcome as _, ResultOutcome as _};
+        __log_timer.finish((&::custom_tracing_logger::timing::Probe(&__log_result)).outcome());
+        __log_result
+    });
+    quote!(#function).into()
+}
@@ -0,0 +1,587 @@
+//! Admin HTTP endpoint
+//!
+//! With `LOG_ADMIN_ADDR` (e.g. "127.0.0.1:9898") a background thread serves
+//! a small HTTP/1.1 API for controlling the live logger:
+//! - `GET /filter`: current `RUST_LOG` directives
+//! - `PUT /filter`: replace them with the request body
+//! - `POST /flush`: flush the log file and registered sinks
+//! - `GET /stats`: event counters and configured destinations as JSON
+//! - `GET /metrics`: the logger's counters for Prometheus, see [`render_prometheus`](crate::render_prometheus)
+//!
+//! Each connection is served on its own thread, up to [`MAX_CONNECTIONS`]
+//! at once, and closed after the response; a client has 5 seconds to send
+//! its whole request and 5 more to take the response.
+//! With `LOG_ADMIN_TOKEN` set, every request must carry
+//! `Authorization: Bearer <token>`. Without a token the endpoint only
+//! starts on a loopback address.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::reload::{ReloadError, ReloadHandle};
+
+/// Target of records reporting changes made through the endpoint
+const TARGET: &str = "admin";
+
+/// Largest accepted request, headers and body together
+const MAX_REQUEST_BYTES: u64 = 64 * 1024;
+
+/// Time a client has to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time a client has to take the response
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections served at once; further ones are closed unanswered
+const MAX_CONNECTIONS: usize = 8;
+
+/// Admin endpoint settings read from the environment
+#[derive(Debug, Clone)]
+pub(crate) struct AdminConfig {
+    pub(crate) addr: String,
+    pub(crate) token: Option<String>,
+}
+
+impl AdminConfig {
+    /// Read `LOG_ADMIN_ADDR` and `LOG_ADMIN_TOKEN`, if the endpoint is enabled
+    pub(crate) fn from_env() -> Option<Self> {
+        let addr = crate::config_file::var("LOG_ADMIN_ADDR").ok()?;
+        let addr = addr.trim();
+        if addr.is_empty() {
+            return None;
+        }
+        let token = crate::config_file::var("LOG_ADMIN_TOKEN")
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
+        Some(Self {
+            addr: addr.to_string(),
+            token,
+        })
+    }
+
+    /// Refuse to serve without a token on an address other hosts can reach
+    pub(crate) fn check_exposure(&self, addr: SocketAddr) -> Result<(), String> {
+        if self.token.is_none() && !addr.ip().is_loopback() {
+            return Err(format!(
+                "{} is reachable from other hosts, set LOG_ADMIN_TOKEN or bind to loopback",
+                addr
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Start serving the admin endpoint for the installed logger
+pub(crate) fn spawn(config: AdminConfig, handle: ReloadHandle) {
+    let listener = match TcpListener::bind(&config.addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!(
+                "Log admin endpoint disabled, cannot bind '{}': {}",
+                config.addr, e
+            );
+            return;
+        }
+    };
+    if let Err(e) = listener
+        .local_addr()
+        .map_err(|e| e.to_string())
+        .and_then(|addr| config.check_exposure(addr))
+    {
+        eprintln!("Log admin endpoint disabled: {}", e);
+        return;
+    }
+    let server = Arc::new(Server {
+        handle,
+        token: config.token,
+        started: Instant::now(),
+    });
+    let spawned = thread::Builder::new()
+        .name("log-admin".to_string())
+        .spawn(move || accept(listener, server));
+    if let Err(e) = spawned {
+        eprintln!("Failed to spawn log admin thread: {}", e);
+    }
+}
+
+/// Serve every connection of `listener` on a thread of its own
+fn accept(listener: TcpListener, server: Arc<Server>) {
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming().flatten() {
+        // A slow client holds its thread until the request deadline, not the listener
+        if active.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+            active.fetch_sub(1, Ordering::AcqRel);
+            continue;
+        }
+        let server = Arc::clone(&server);
+        let done = Arc::clone(&active);
+        let connection = thread::Builder::new()
+            .name("log-admin-conn".to_string())
+            .spawn(move || {
+                if let Err(e) = server.serve(stream) {
+                    eprintln!("Log admin request failed: {}", e);
+                }
+                done.fetch_sub(1, Ordering::AcqRel);
+            });
+        if let Err(e) = connection {
+            active.fetch_sub(1, Ordering::AcqRel);
+            eprintln!("Log admin connection dropped, cannot spawn a thread: {}", e);
+        }
+    }
+}
+
+struct Server {
+    handle: ReloadHandle,
+    token: Option<String>,
+    started: Instant,
+}
+
+/// Parsed HTTP request
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: String,
+}
+
+/// HTTP response, always sent with `Connection: close`
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        let mut body = body.into();
+        body.push('\n');
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body,
+        }
+    }
+
+    fn prometheus(body: String) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body,
+        }
+    }
+
+    fn json(value: Value) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: format!("{}\n", value),
+        }
+    }
+
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        write!(
+            w,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        w.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+impl Server {
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let deadline = Deadline {
+            stream: &stream,
+            until: Instant::now() + REQUEST_TIMEOUT,
+        };
+        let mut reader = BufReader::new(deadline.take(MAX_REQUEST_BYTES));
+        let response = match read_request(&mut reader) {
+            Ok(request) => self.route(&request),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Response::text(400, e.to_string()),
+            Err(e) if e.kind() == io::ErrorKind::FileTooLarge => {
+                Response::text(413, "Request too large")
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                match reader.get_ref().limit() {
+                    0 => Response::text(413, "Request too large"),
+                    // Connected and closed without a request, e.g. a port check
+                    MAX_REQUEST_BYTES => return Ok(()),
+                    _ => Response::text(400, "Incomplete request"),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        response.write_to(&mut &stream)
+    }
+
+    fn route(&self, request: &Request) -> Response {
+        if !self.authorized(request) {
+            return Response::text(401, "Missing or invalid bearer token");
+        }
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/filter") => match self.handle.filter() {
+                Some(filter) => Response::text(200, filter),
+                None => Response::text(503, ReloadError::NotInstalled.to_string()),
+            },
+            ("PUT", "/filter") => {
+                let directives = request.body.trim();
+                match self.handle.set_filter(directives) {
+                    Ok(()) => {
+                        tracing::info!(target: TARGET, filter = %directives, "Log filter changed");
+                        Response::text(200, directives)
+                    }
+                    Err(e @ ReloadError::InvalidFilter(_)) => Response::text(400, e.to_string()),
+                    Err(e) => Response::text(503, e.to_string()),
+                }
+            }
+            ("POST", "/flush") => {
+                crate::sink::flush();
+                let _ = io::stdout().flush();
+                match crate::file::flush_installed() {
+                    Ok(()) => Response::text(200, "Flushed"),
+                    Err(e) => Response::text(503, format!("Flushing the log file failed: {}", e)),
+                }
+            }
+            ("GET", "/stats") => Response::json(self.stats()),
+            ("GET", "/metrics") => Response::prometheus(crate::metrics::render_prometheus()),
+            (_, "/filter" | "/flush" | "/stats" | "/metrics") => {
+                Response::text(405, "Method not allowed")
+            }
+            _ => Response::text(404, "Not found"),
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let given = request
+            .authorization
+            .as_deref()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default()
+            .trim();
+        // Compare every byte so the response time does not reveal the matching prefix
+        given.len() == token.len()
+            && given
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    fn stats(&self) -> Value {
+        let pipeline = crate::reentrant::stats();
+        let logger = crate::metrics::stats();
+        let destinations = serde_json::from_str::<Value>(&crate::config::export_redacted_json())
+            .ok()
+            .and_then(|mut export| export.get_mut("destinations").map(Value::take))
+            .unwrap_or(Value::Null);
+        json!({
+            "filter": self.handle.filter(),
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "events_written": pipeline.written,
+            "events_reentrant": pipeline.reentrant,
+            "pipeline_panics": pipeline.panics,
+            "events_by_level": logger.events_by_level,
+            "bytes_written": logger.bytes_written,
+            "write_errors": logger.write_errors,
+            "sink_failures": logger.sink_failures,
+            "dropped_events": logger.dropped_events,
+            "dropped_bytes": logger.dropped_bytes,
+            "ring_buffered": crate::ring::buffered(),
+            "sinks": crate::sink::installed_count(),
+            "destinations": destinations,
+        })
+    }
+}
+
+/// Reads a connection until a fixed point in time, however slowly data trickles in
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Read one request
+///
+/// Fails with `UnexpectedEof` when it ends early and `FileTooLarge` when
+/// its body exceeds the size limit.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), target.to_string())
+        }
+        _ => return Err(invalid("Malformed request line")),
+    };
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut authorization = None;
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid("Malformed header"))?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .parse::<usize>()
+                .map_err(|_| invalid("Invalid Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        }
+    }
+
+    if content_length as u64 > MAX_REQUEST_BYTES {
+        return Err(io::ErrorKind::FileTooLarge.into());
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|_| invalid("Body is not UTF-8"))?;
+
+    Ok(Request {
+        method,
+        path,
+        authorization,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debug_users::DebugUserFilter;
+    use tracing_subscriber::layer::{Layer, SubscriberExt};
+    use tracing_subscriber::{reload, EnvFilter};
+
+    fn parse(raw: &str) -> io::Result<Request> {
+        read_request(&mut raw.as_bytes())
+    }
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            authorization: None,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request = parse(
+            "PUT /filter?x=1 HTTP/1.1\r\nHost: localhost\r\nauthorization: Bearer s3cret\r\nContent-Length: 5\r\n\r\ndebug",
+        )
+        .unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/filter");
+        assert_eq!(request.authorization.as_deref(), Some("Bearer s3cret"));
+        assert_eq!(request.body, "debug");
+
+        let malformed = parse("nonsense\r\n\r\n").unwrap_err();
+        assert_eq!(malformed.kind(), io::ErrorKind::InvalidData);
+        let truncated = parse("GET /stats HTTP/1.1\r\nHost: localhost\r\n").unwrap_err();
+        assert_eq!(truncated.kind(), io::ErrorKind::UnexpectedEof);
+        let large = parse("PUT /filter HTTP/1.1\r\nContent-Length: 999999\r\n\r\n").unwrap_err();
+        assert_eq!(large.kind(), io::ErrorKind::FileTooLarge);
+    }
+
+    #[test]
+    fn test_routes() {
+        let filter = DebugUserFilter::new(EnvFilter::new("info"), None);
+        let (filter, handle) = reload::Layer::new(filter);
+        let server = Server {
+            handle: ReloadHandle::new(handle),
+            token: None,
+            started: Instant::now(),
+        };
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::sink)
+                .with_filter(filter),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let response = server.route(&request("GET", "/filter", ""));
+            assert_eq!((response.status, response.body.as_str()), (200, "info\n"));
+
+            let response = server.route(&request("PUT", "/filter", "debug\n"));
+            assert_eq!(response.status, 200);
+            assert_eq!(server.handle.filter().as_deref(), Some("debug"));
+
+            let response = server.route(&request("PUT", "/filter", "debug[="));
+            assert_eq!(response.status, 400);
+            assert_eq!(server.handle.filter().as_deref(), Some("debug"));
+
+            let response = server.route(&request("GET", "/stats", ""));
+            let stats: Value = serde_json::from_str(&response.body).unwrap();
+            assert_eq!(stats["filter"], "debug");
+            assert!(stats["destinations"].is_array());
+
+            let response = server.route(&request("GET", "/metrics", ""));
+            assert!(response
+                .content_type
+                .starts_with("text/plain; version=0.0.4"));
+            assert!(response.body.contains("log_events_total{level=\"info\"}"));
+
+            assert_eq!(server.route(&request("POST", "/flush", "")).status, 200);
+            assert_eq!(server.route(&request("DELETE", "/filter", "")).status, 405);
+            assert_eq!(server.route(&request("GET", "/", "")).status, 404);
+        });
+    }
+
+    #[test]
+    fn test_bearer_token_required() {
+        let (_, handle) = reload::Layer::<_, tracing_subscriber::Registry>::new(
+            DebugUserFilter::new(EnvFilter::new("info"), None),
+        );
+        let server = Server {
+            handle: ReloadHandle::new(handle),
+            token: Some("s3cret".to_string()),
+            started: Instant::now(),
+        };
+
+        let mut request = request("GET", "/stats", "");
+        assert_eq!(server.route(&request).status, 401);
+        request.authorization = Some("Bearer wrong!".to_string());
+        assert_eq!(server.route(&request).status, 401);
+        request.authorization = Some("Bearer s3cret".to_string());
+        assert_eq!(server.route(&request).status, 200);
+    }
+
+    #[test]
+    fn test_stalled_client_does_not_block_others() {
+        let (_, handle) = reload::Layer::<_, tracing_subscriber::Registry>::new(
+            DebugUserFilter::new(EnvFilter::new("info"), None),
+        );
+        let server = Arc::new(Server {
+            handle: ReloadHandle::new(handle),
+            token: None,
+            started: Instant::now(),
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || accept(listener, server));
+
+        // Connected but silent until the request deadline
+        let _stalled = TcpStream::connect(addr).unwrap();
+        let started = Instant::now();
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .write_all(b"GET /stats HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(started.elapsed() < REQUEST_TIMEOUT);
+
+        // Closed without a request: no response at all
+        drop(TcpStream::connect(addr).unwrap());
+        let mut probe = TcpStream::connect(addr).unwrap();
+        probe.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut response = String::new();
+        probe.read_to_string(&mut response).unwrap();
+        assert_eq!(response, "");
+    }
+
+    #[test]
+    fn test_trickling_client_hits_the_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let writer = thread::spawn(move || {
+            // One byte at a time, each well within a per-read timeout
+            for byte in b"GET /stats HTTP/1.1\r\n" {
+                if client.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let mut deadline = Deadline {
+            stream: &stream,
+            until: Instant::now() + Duration::from_millis(200),
+        };
+        let mut received = Vec::new();
+        let error = deadline.read_to_end(&mut received).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(received.len() < 10);
+        drop(stream);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_unauthenticated_endpoint_stays_on_loopback() {
+        let mut config = AdminConfig {
+            addr: "0.0.0.0:9898".to_string(),
+            token: None,
+        };
+        assert!(config
+            .check_exposure("127.0.0.1:9898".parse().unwrap())
+            .is_ok());
+        assert!(config.check_exposure("[::1]:9898".parse().unwrap()).is_ok());
+        assert!(config
+            .check_exposure("0.0.0.0:9898".parse().unwrap())
+            .is_err());
+        config.token = Some("s3cret".to_string());
+        assert!(config
+            .check_exposure("0.0.0.0:9898".parse().unwrap())
+            .is_ok());
+    }
+}
@@ -0,0 +1,162 @@
+//! Callbacks on ERROR events
+//!
+//! [`Builder::on_error`](crate::Builder::on_error) registers a function
+//! called with the target and fields of ERROR events, so an application can
+//! page someone or post to a chat channel without writing its own layer.
+//! Calls are limited to one per interval (`LOG_ALERT_INTERVAL_MS`, default
+//! 10 seconds); events in between are counted in the next call's
+//! [`suppressed`](ErrorEvent::suppressed).
+//!
+//! The callback runs on the thread that logged the event, after the outputs
+//! wrote it. Hand slow work (an HTTP request) to another thread. Records the
+//! callback itself emits are written to stderr instead of the outputs, so an
+//! alert failing to send cannot trigger another alert.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::reentrant::FieldMap;
+
+/// Default shortest time between two callbacks
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Callback registered with [`Builder::on_error`](crate::Builder::on_error)
+pub type ErrorCallback = Box<dyn Fn(&ErrorEvent) + Send + Sync>;
+
+/// An ERROR event passed to the callback
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorEvent {
+    /// Target of the event, usually its module path
+    pub target: String,
+    /// The event's message, if it has one
+    pub message: Option<String>,
+    /// Every other field of the event
+    pub fields: Map<String, Value>,
+    /// ERROR events since the previous call that were not reported
+    pub suppressed: u64,
+}
+
+/// Read `LOG_ALERT_INTERVAL_MS`, the shortest time between two callbacks
+pub(crate) fn interval_from_env() -> Duration {
+    crate::config_file::var("LOG_ALERT_INTERVAL_MS")
+        .ok()
+        .and_then(|ms| ms.trim().parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_INTERVAL)
+}
+
+struct State {
+    last_call: Option<Instant>,
+    suppressed: u64,
+}
+
+/// Layer calling the error callback; runs after the output layers
+pub(crate) struct AlertLayer {
+    callback: ErrorCallback,
+    interval: Duration,
+    state: Mutex<State>,
+}
+
+impl AlertLayer {
+    pub(crate) fn new(callback: ErrorCallback, interval: Duration) -> Self {
+        Self {
+            callback,
+            interval,
+            state: Mutex::new(State {
+                last_call: None,
+                suppressed: 0,
+            }),
+        }
+    }
+
+    /// Whether a callback is due at `now`; returns the events suppressed since the last one
+    fn due(&self, now: Instant) -> Option<u64> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state
+            .last_call
+            .is_some_and(|last| now.saturating_duration_since(last) < self.interval)
+        {
+            state.suppressed += 1;
+            return None;
+        }
+        state.last_call = Some(now);
+        Some(std::mem::take(&mut state.suppressed))
+    }
+}
+
+impl<S: Subscriber> Layer<S> for AlertLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() != Level::ERROR || metadata.is_span() {
+            return;
+        }
+        let Some(suppressed) = self.due(Instant::now()) else {
+            return;
+        };
+        let mut fields = FieldMap(Map::new());
+        event.record(&mut fields);
+        let mut fields = fields.0;
+        let message = match fields.remove("message") {
+            Some(Value::String(message)) => Some(message),
+            Some(other) => Some(other.to_string()),
+            None => None,
+        };
+        (self.callback)(&ErrorEvent {
+            target: metadata.target().to_string(),
+            message,
+            fields,
+            suppressed,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_callback_receives_rate_limited_errors() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let layer = AlertLayer::new(
+            Box::new(move |event: &ErrorEvent| sink.lock().unwrap().push(event.clone())),
+            Duration::from_secs(3600),
+        );
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("not an error");
+            tracing::error!(
+                target: "payments",
+                order_id = 42,
+                items = %crate::structured::json(&["A1", "B2"]),
+                "Charge failed"
+            );
+            tracing::error!("suppressed");
+        });
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].target, "payments");
+        assert_eq!(received[0].message.as_deref(), Some("Charge failed"));
+        assert_eq!(received[0].fields["order_id"], 42);
+        assert_eq!(received[0].fields["items"], serde_json::json!(["A1", "B2"]));
+        assert_eq!(received[0].suppressed, 0);
+    }
+
+    #[test]
+    fn test_suppressed_count_reported_with_next_call() {
+        let layer = AlertLayer::new(Box::new(|_: &ErrorEvent| {}), Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(layer.due(start), Some(0));
+        assert_eq!(layer.due(start + Duration::from_secs(1)), None);
+        assert_eq!(layer.due(start + Duration::from_secs(2)), None);
+        assert_eq!(layer.due(start + Duration::from_secs(11)), Some(2));
+    }
+}
@@ -0,0 +1,175 @@
+//! Field allowlist
+//!
+//! In strict mode only explicitly permitted field names reach the outputs,
+//! so an ad-hoc `info!(card_number = ...)` can never leak into production
+//! logs. Other event and span fields are dropped, or moved under an `extra`
+//! object when they should stay visible but clearly unvetted.
+
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+/// Keys always kept in event and span field objects
+const ALWAYS_ALLOWED: [&str; 2] = ["message", "name"];
+
+/// Key collecting fields that are not allowlisted in [`AllowlistMode::Extra`]
+pub const EXTRA: &str = "extra";
+
+/// What happens to fields that are not allowlisted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllowlistMode {
+    /// Remove them from the record
+    #[default]
+    Drop,
+    /// Move them under an `extra` object
+    Extra,
+}
+
+impl std::str::FromStr for AllowlistMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "drop" => Ok(AllowlistMode::Drop),
+            "extra" => Ok(AllowlistMode::Extra),
+            other => Err(format!(
+                "unknown allowlist mode '{}', expected drop or extra",
+                other
+            )),
+        }
+    }
+}
+
+/// Field names permitted in records
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    fields: HashSet<String>,
+    mode: AllowlistMode,
+}
+
+impl Allowlist {
+    /// Create an allowlist permitting only `message` and span names
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Permit fields named `name`
+    pub fn allow(mut self, name: impl Into<String>) -> Self {
+        self.fields.insert(name.into());
+        self
+    }
+
+    /// Select what happens to fields that are not permitted
+    pub fn mode(mut self, mode: AllowlistMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Read the allowlist from the environment, if one is configured
+    ///
+    /// - `LOG_FIELD_ALLOWLIST`: Permitted field names (e.g. "user_id,order_id,status")
+    /// - `LOG_FIELD_ALLOWLIST_MODE`: "drop" (default) or "extra"
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(allowlist) = Self::fields_from_env() else {
+            return Ok(None);
+        };
+        match crate::config_file::var("LOG_FIELD_ALLOWLIST_MODE") {
+            Ok(mode) => Ok(Some(allowlist.mode(mode.parse()?))),
+            Err(_) => Ok(Some(allowlist)),
+        }
+    }
+
+    /// Like [`from_env`](Self::from_env), failing closed: an invalid mode
+    /// falls back to dropping unlisted fields
+    pub(crate) fn from_env_lenient() -> Option<Self> {
+        Self::from_env().unwrap_or_else(|e| {
+            eprintln!(
+                "Invalid LOG_FIELD_ALLOWLIST_MODE: {}; dropping unlisted fields",
+                e
+            );
+            Self::fields_from_env()
+        })
+    }
+
+    fn fields_from_env() -> Option<Self> {
+        let fields = crate::config_file::var("LOG_FIELD_ALLOWLIST").ok()?;
+        let allowlist = fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .fold(Self::new(), Self::allow);
+        Some(allowlist)
+    }
+
+    /// Filter the event and span fields of a formatted record in place
+    pub(crate) fn apply(&self, record: &mut Map<String, Value>) {
+        for key in ["fields", "span"] {
+            if let Some(Value::Object(fields)) = record.get_mut(key) {
+                self.filter(fields);
+            }
+        }
+        if let Some(Value::Array(spans)) = record.get_mut("spans") {
+            for span in spans {
+                if let Value::Object(fields) = span {
+                    self.filter(fields);
+                }
+            }
+        }
+    }
+
+    /// Filter one object of event or span fields in place
+    pub(crate) fn filter(&self, fields: &mut Map<String, Value>) {
+        let mut extra = Map::new();
+        fields.retain(|key, value| {
+            if ALWAYS_ALLOWED.contains(&key.as_str()) || self.fields.contains(key) {
+                return true;
+            }
+            if self.mode == AllowlistMode::Extra {
+                extra.insert(key.clone(), value.take());
+            }
+            false
+        });
+        if !extra.is_empty() {
+            fields.insert(EXTRA.to_string(), Value::Object(extra));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record() -> Value {
+        json!({
+            "level": "INFO",
+            "fields": {"message": "paid", "order_id": 1, "card_number": "4111"},
+            "span": {"name": "checkout", "user_id": 7, "token": "abc"}
+        })
+    }
+
+    #[test]
+    fn test_drop_mode_removes_unlisted_fields() {
+        let allowlist = Allowlist::new().allow("order_id").allow("user_id");
+        let mut record = record();
+        allowlist.apply(record.as_object_mut().unwrap());
+        assert_eq!(record["fields"], json!({"message": "paid", "order_id": 1}));
+        assert_eq!(record["span"], json!({"name": "checkout", "user_id": 7}));
+        assert_eq!(record["level"], "INFO");
+    }
+
+    #[test]
+    fn test_extra_mode_moves_unlisted_fields() {
+        let allowlist = Allowlist::new()
+            .allow("order_id")
+            .mode(AllowlistMode::Extra);
+        let mut record = record();
+        allowlist.apply(record.as_object_mut().unwrap());
+        assert_eq!(record["fields"]["extra"], json!({"card_number": "4111"}));
+        assert_eq!(record["fields"]["order_id"], 1);
+        assert_eq!(
+            record["span"]["extra"],
+            json!({"user_id": 7, "token": "abc"})
+        );
+    }
+}
@@ -0,0 +1,603 @@
+//! Upload of rotated log files to object storage
+//!
+//! With the `archive` feature and `LOG_ARCHIVE_URL` set (or an
+//! [`ArchiveConfig`] passed to
+//! [`Builder::with_archive`](crate::Builder::with_archive)), a background
+//! thread uploads every completed daily log file, i.e. one of an earlier UTC
+//! day, and deletes the local copy once the upload succeeded:
+//!
+//! - `LOG_ARCHIVE_URL`: `s3://bucket/prefix/`, `gs://bucket/prefix/` or `azblob://account/container/prefix/`
+//! - `LOG_ARCHIVE_ENDPOINT`: Endpoint override (e.g. "http://localhost:9000" for MinIO)
+//! - `LOG_ARCHIVE_AZURE_SAS`: SAS token with write access to the Azure container (required for `azblob://`)
+//! - `LOG_ARCHIVE_INTERVAL_SECS`: Time between scans of the log directory (default: 300)
+//! - `LOG_ARCHIVE_KEEP_LOCAL`: Set to "true" to keep uploaded files
+//! - `LOG_ARCHIVE_MANIFEST`: Manifest path (default: "archive-manifest.jsonl" in the log directory)
+//!
+//! S3 uploads are signed with SigV4 using the standard `AWS_*` credentials
+//! (or the ECS container credentials endpoint) and `AWS_REGION`. GCS is
+//! reached through its S3-compatible XML API, with an HMAC key in
+//! `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY`.
+//!
+//! Each upload is tried a few times with backoff; a file that still fails
+//! stays on disk and is retried on the next scan. Every archived file is
+//! recorded as one JSON line in the manifest:
+//!
+//! ```text
+//! {"file":"app.2025-01-01","key":"prod/app.2025-01-01","location":"s3://logs/prod/","bytes":1048576,"sha256":"…","archived_at_ms":1735779600000}
+//! ```
+//!
+//! Other destinations implement [`ArchiveStore`].
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::aws::{self, CredentialsCache};
+use crate::file::LogFile;
+
+/// Name of the manifest in the log directory unless configured
+pub const DEFAULT_MANIFEST: &str = "archive-manifest.jsonl";
+
+/// Destination of archived log files
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::archive::ArchiveStore;
+/// use std::path::Path;
+///
+/// struct NetworkShare;
+///
+/// impl ArchiveStore for NetworkShare {
+///     fn upload(&self, key: &str, path: &Path) -> Result<(), String> {
+///         std::fs::copy(path, Path::new("/mnt/archive").join(key))
+///             .map(drop)
+///             .map_err(|e| e.to_string())
+///     }
+///
+///     fn location(&self) -> String {
+///         "/mnt/archive".to_string()
+///     }
+/// }
+/// ```
+pub trait ArchiveStore: Send + Sync {
+    /// Upload the file at `path` as object `key`
+    ///
+    /// Called from the archive thread; may block.
+    fn upload(&self, key: &str, path: &Path) -> Result<(), String>;
+
+    /// Where objects are stored, for the manifest and reports
+    fn location(&self) -> String;
+}
+
+/// Amazon S3 or an S3-compatible store (GCS, MinIO, LocalStack)
+pub struct S3Store {
+    bucket: String,
+    region: String,
+    endpoint: Option<String>,
+    credentials: Mutex<CredentialsCache>,
+}
+
+impl S3Store {
+    /// Bucket `bucket` of Amazon S3 in `region`
+    pub fn new(bucket: impl Into<String>, region: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            region: region.into(),
+            endpoint: None,
+            credentials: Mutex::default(),
+        }
+    }
+
+    /// Bucket `bucket` of Google Cloud Storage, through its S3-compatible API
+    pub fn gcs(bucket: impl Into<String>) -> Self {
+        Self::new(bucket, "auto").endpoint("https://storage.googleapis.com")
+    }
+
+    /// Send requests to `endpoint` with path-style URLs instead of AWS
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Host and path of the object `key`
+    fn address(&self, key: &str) -> (String, String) {
+        match &self.endpoint {
+            Some(endpoint) => (
+                endpoint.clone(),
+                format!("/{}/{}", self.bucket, encode_path(key)),
+            ),
+            None => (
+                format!("https://{}.s3.{}.amazonaws.com", self.bucket, self.region),
+                format!("/{}", encode_path(key)),
+            ),
+        }
+    }
+}
+
+impl ArchiveStore for S3Store {
+    fn upload(&self, key: &str, path: &Path) -> Result<(), String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        let credentials = self
+            .credentials
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get()?;
+        let (base, object) = self.address(key);
+        let host = base.split("://").nth(1).unwrap_or(&base).to_string();
+        let request = aws::Request {
+            method: "PUT",
+            path: &object,
+            service: "s3",
+            region: &self.region,
+            headers: vec![
+                ("host", host),
+                ("x-amz-content-sha256", "UNSIGNED-PAYLOAD".to_string()),
+            ],
+            payload_hash: "UNSIGNED-PAYLOAD".to_string(),
+        };
+        let mut put = ureq::put(&format!("{}{}", base, object))
+            .timeout(Duration::from_secs(300))
+            .set("Content-Length", &len.to_string())
+            .set("x-amz-content-sha256", "UNSIGNED-PAYLOAD");
+        for (name, value) in aws::sign(&credentials, request, SystemTime::now()) {
+            put = put.set(&name, &value);
+        }
+        match put.send(file) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => {
+                if status == 400 || status == 403 {
+                    // Possibly expired container credentials
+                    self.credentials
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .invalidate();
+                }
+                let body = response.into_string().unwrap_or_default();
+                Err(format!("{} {}", status, body.trim()))
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn location(&self) -> String {
+        match self.endpoint.as_deref() {
+            Some("https://storage.googleapis.com") => format!("gs://{}", self.bucket),
+            Some(endpoint) => format!("{}/{}", endpoint, self.bucket),
+            None => format!("s3://{}", self.bucket),
+        }
+    }
+}
+
+/// Container of Azure Blob Storage, written with a SAS token
+pub struct AzureBlobStore {
+    account: String,
+    container: String,
+    sas: String,
+    endpoint: Option<String>,
+}
+
+impl AzureBlobStore {
+    /// Container `container` of storage account `account`, using the SAS token `sas`
+    pub fn new(
+        account: impl Into<String>,
+        container: impl Into<String>,
+        sas: impl Into<String>,
+    ) -> Self {
+        Self {
+            account: account.into(),
+            container: container.into(),
+            sas: sas.into().trim_start_matches('?').to_string(),
+            endpoint: None,
+        }
+    }
+
+    /// Send requests to `endpoint` (e.g. Azurite) instead of `<account>.blob.core.windows.net`
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    fn url(&self, key: &str) -> String {
+        let base = match &self.endpoint {
+            Some(endpoint) => format!("{}/{}", endpoint, self.account),
+            None => format!("https://{}.blob.core.windows.net", self.account),
+        };
+        format!(
+            "{}/{}/{}?{}",
+            base,
+            self.container,
+            encode_path(key),
+            self.sas
+        )
+    }
+}
+
+impl ArchiveStore for AzureBlobStore {
+    fn upload(&self, key: &str, path: &Path) -> Result<(), String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        let put = ureq::put(&self.url(key))
+            .timeout(Duration::from_secs(300))
+            .set("Content-Length", &len.to_string())
+            .set("x-ms-blob-type", "BlockBlob")
+            .set("x-ms-version", "2021-08-06");
+        match put.send(file) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                Err(format!("{} {}", status, body.trim()))
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn location(&self) -> String {
+        format!("azblob://{}/{}", self.account, self.container)
+    }
+}
+
+/// Archiving of rotated log files
+#[derive(Clone)]
+pub struct ArchiveConfig {
+    /// Destination of the files
+    pub store: Arc<dyn ArchiveStore>,
+    /// Prepended to file names to form object keys (e.g. "prod/web/")
+    pub key_prefix: String,
+    /// Time between scans of the log directory
+    pub interval: Duration,
+    /// Attempts per file and scan
+    pub max_retries: u32,
+    /// Keep files on disk after uploading them
+    pub keep_local: bool,
+    /// Manifest path, `archive-manifest.jsonl` in the log directory if `None`
+    pub manifest: Option<PathBuf>,
+}
+
+impl ArchiveConfig {
+    /// Archive to `store` every 5 minutes, deleting uploaded files
+    pub fn new(store: impl ArchiveStore + 'static) -> Self {
+        Self::with_store(Arc::new(store))
+    }
+
+    fn with_store(store: Arc<dyn ArchiveStore>) -> Self {
+        Self {
+            store,
+            key_prefix: String::new(),
+            interval: Duration::from_secs(300),
+            max_retries: 3,
+            keep_local: false,
+            manifest: None,
+        }
+    }
+
+    /// Read the configuration from the environment, if `LOG_ARCHIVE_URL` is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let url = match crate::config_file::var("LOG_ARCHIVE_URL") {
+            Ok(url) if !url.trim().is_empty() => url.trim().to_string(),
+            _ => return Ok(None),
+        };
+        let endpoint = crate::config_file::var("LOG_ARCHIVE_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.trim().is_empty());
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| format!("LOG_ARCHIVE_URL '{}' has no scheme", url))?;
+        let (bucket, key_prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(format!("LOG_ARCHIVE_URL '{}' has no bucket", url));
+        }
+        let (store, key_prefix): (Arc<dyn ArchiveStore>, &str) = match scheme {
+            "s3" => {
+                let region = std::env::var("AWS_REGION")
+                    .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+                    .map_err(|_| "LOG_ARCHIVE_URL is s3:// but AWS_REGION is not set")?;
+                let mut store = S3Store::new(bucket, region.trim());
+                if let Some(endpoint) = endpoint {
+                    store = store.endpoint(endpoint.trim());
+                }
+                (Arc::new(store), key_prefix)
+            }
+            "gs" => {
+                let mut store = S3Store::gcs(bucket);
+                if let Some(endpoint) = endpoint {
+                    store = store.endpoint(endpoint.trim());
+                }
+                (Arc::new(store), key_prefix)
+            }
+            "azblob" => {
+                let (container, key_prefix) =
+                    key_prefix.split_once('/').unwrap_or((key_prefix, ""));
+                if container.is_empty() {
+                    return Err(format!("LOG_ARCHIVE_URL '{}' has no container", url));
+                }
+                let sas = crate::config_file::var("LOG_ARCHIVE_AZURE_SAS").map_err(|_| {
+                    "LOG_ARCHIVE_URL is azblob:// but LOG_ARCHIVE_AZURE_SAS is not set"
+                })?;
+                let mut store = AzureBlobStore::new(bucket, container, sas.trim());
+                if let Some(endpoint) = endpoint {
+                    store = store.endpoint(endpoint.trim());
+                }
+                (Arc::new(store), key_prefix)
+            }
+            other => {
+                return Err(format!(
+                    "unsupported LOG_ARCHIVE_URL scheme '{}', expected s3, gs or azblob",
+                    other
+                ))
+            }
+        };
+
+        let mut config = Self::with_store(store);
+        config.key_prefix = normalize_prefix(key_prefix);
+        if let Ok(secs) = crate::config_file::var("LOG_ARCHIVE_INTERVAL_SECS") {
+            match secs.trim().parse::<u64>() {
+                Ok(secs) if secs > 0 => config.interval = Duration::from_secs(secs),
+                _ => {
+                    return Err(format!(
+                        "LOG_ARCHIVE_INTERVAL_SECS '{}' is not a positive number of seconds",
+                        secs
+                    ))
+                }
+            }
+        }
+        config.keep_local =
+            crate::config_file::var("LOG_ARCHIVE_KEEP_LOCAL").unwrap_or_default() == "true";
+        config.manifest = crate::config_file::var("LOG_ARCHIVE_MANIFEST")
+            .ok()
+            .filter(|path| !path.trim().is_empty())
+            .map(|path| PathBuf::from(path.trim()));
+        Ok(Some(config))
+    }
+
+    /// Where objects go, e.g. "s3://bucket/prefix/"
+    pub fn location(&self) -> String {
+        format!("{}/{}", self.store.location(), self.key_prefix)
+    }
+}
+
+/// A key prefix ending with `/`, unless empty
+fn normalize_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", prefix)
+    }
+}
+
+/// Percent-encode an object key for a request path, keeping `/`
+fn encode_path(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Uploads the completed files of one log file
+struct Archiver {
+    config: ArchiveConfig,
+    log_file: Arc<LogFile>,
+    manifest: PathBuf,
+    /// Files uploaded but kept on disk, so they are not uploaded again
+    archived: HashSet<String>,
+}
+
+impl Archiver {
+    fn new(config: ArchiveConfig, log_file: Arc<LogFile>) -> Self {
+        let manifest = config
+            .manifest
+            .clone()
+            .unwrap_or_else(|| log_file.dir().join(DEFAULT_MANIFEST));
+        // Uploads of earlier runs, when files are kept
+        let archived = std::fs::read_to_string(&manifest)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter_map(|entry| entry["file"].as_str().map(String::from))
+            .collect();
+        Self {
+            config,
+            log_file,
+            manifest,
+            archived,
+        }
+    }
+
+    /// Upload every completed file not archived yet
+    fn run_once(&mut self) {
+        let files = match self.log_file.completed_files() {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Cannot list log files to archive: {}", e);
+                return;
+            }
+        };
+        for path in files {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if self.archived.contains(name) {
+                continue;
+            }
+            let name = name.to_string();
+            match self.archive(&name, &path) {
+                Ok(()) => {
+                    self.archived.insert(name);
+                }
+                Err(e) => {
+                    eprintln!("Cannot archive log file '{}': {}", path.display(), e);
+                    crate::metrics::add_sink_failure();
+                }
+            }
+        }
+    }
+
+    /// Upload one file with retries, record it and delete it
+    fn archive(&self, name: &str, path: &Path) -> Result<(), String> {
+        let (bytes, sha256) = digest(path).map_err(|e| e.to_string())?;
+        let key = format!("{}{}", self.config.key_prefix, name);
+        let mut backoff = Duration::from_secs(1);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.config.store.upload(&key, path) {
+                Ok(()) => break,
+                Err(e) if attempt >= self.config.max_retries.max(1) => return Err(e),
+                Err(e) => {
+                    eprintln!(
+                        "Archive upload of '{}' attempt {} failed: {}",
+                        name, attempt, e
+                    );
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(Duration::from_secs(60));
+                }
+            }
+        }
+
+        let entry = json!({
+            "file": name,
+            "key": key,
+            "location": self.config.location(),
+            "bytes": bytes,
+            "sha256": sha256,
+            "archived_at_ms": crate::time_fields::unix_millis(SystemTime::now()),
+        });
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.manifest)
+            .map_err(|e| format!("cannot write manifest: {}", e))?;
+        manifest
+            .write_all(format!("{}\n", entry).as_bytes())
+            .map_err(|e| format!("cannot write manifest: {}", e))?;
+
+        if !self.config.keep_local {
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                // Archived and removed by another process sharing the file
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("uploaded but not deleted: {}", e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size and hex SHA-256 of a file
+fn digest(path: &Path) -> io::Result<(u64, String)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    let mut bytes = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        bytes += n as u64;
+    }
+    Ok((bytes, aws::hex(&hasher.finalize())))
+}
+
+/// Archive the completed files of `log_file` on a background thread
+pub(crate) fn spawn(config: ArchiveConfig, log_file: Arc<LogFile>) {
+    let interval = config.interval;
+    let mut archiver = Archiver::new(config, log_file);
+    let spawned = std::thread::Builder::new()
+        .name("log-archive".to_string())
+        .spawn(move || loop {
+            archiver.run_once();
+            std::thread::sleep(interval);
+        });
+    if let Err(e) = spawned {
+        eprintln!("Failed to start log archive thread: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileOptions;
+
+    /// Store copying files into a directory
+    struct DirStore(PathBuf, Mutex<Vec<String>>);
+
+    impl ArchiveStore for DirStore {
+        fn upload(&self, key: &str, path: &Path) -> Result<(), String> {
+            self.1.lock().unwrap().push(key.to_string());
+            std::fs::copy(path, self.0.join(key.replace('/', "_")))
+                .map(drop)
+                .map_err(|e| e.to_string())
+        }
+
+        fn location(&self) -> String {
+            self.0.display().to_string()
+        }
+    }
+
+    #[test]
+    fn test_completed_files_are_uploaded_and_removed() {
+        let root = std::env::temp_dir().join(format!("log-archive-{}", std::process::id()));
+        let (dir, remote) = (root.join("logs"), root.join("remote"));
+        std::fs::create_dir_all(&remote).unwrap();
+        let log_file = LogFile::open(dir.clone(), "app", FileOptions::default());
+        (&*log_file).write_all(b"today\n").unwrap();
+        std::fs::write(dir.join("app.2020-01-02"), b"old\n").unwrap();
+        std::fs::write(dir.join("other.2020-01-02"), b"not ours\n").unwrap();
+
+        let store = Arc::new(DirStore(remote.clone(), Mutex::default()));
+        let mut config = ArchiveConfig::with_store(store.clone());
+        config.key_prefix = normalize_prefix("/prod/");
+        let mut archiver = Archiver::new(config, log_file);
+        archiver.run_once();
+        archiver.run_once();
+
+        assert_eq!(*store.1.lock().unwrap(), ["prod/app.2020-01-02"]);
+        assert_eq!(
+            std::fs::read_to_string(remote.join("prod_app.2020-01-02")).unwrap(),
+            "old\n"
+        );
+        assert!(!dir.join("app.2020-01-02").exists());
+        assert!(dir.join("other.2020-01-02").exists());
+        let manifest = std::fs::read_to_string(dir.join(DEFAULT_MANIFEST)).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(manifest.trim()).unwrap();
+        assert_eq!(entry["key"], "prod/app.2020-01-02");
+        assert_eq!(entry["bytes"], 4);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_object_addresses() {
+        let s3 = S3Store::new("logs", "eu-west-1");
+        assert_eq!(
+            s3.address("prod/app 1"),
+            (
+                "https://logs.s3.eu-west-1.amazonaws.com".to_string(),
+                "/prod/app%201".to_string()
+            )
+        );
+        let gcs = S3Store::gcs("logs");
+        assert_eq!(gcs.address("a").1, "/logs/a");
+        assert_eq!(gcs.location(), "gs://logs");
+        let azure = AzureBlobStore::new("acct", "logs", "?sv=1&sig=x");
+        assert_eq!(
+            azure.url("prod/a"),
+            "https://acct.blob.core.windows.net/logs/prod/a?sv=1&sig=x"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Bridging to async writers
+//!
+//! [`AsyncWriter`] lets any `tokio::io::AsyncWrite` (pipes, TLS streams,
+//! custom transports) receive records without implementing a layer. The
+//! logging side stays synchronous: records are queued on a bounded channel
+//! and written by an [`AsyncWriterTask`] spawned on the application's
+//! runtime. A full queue drops records instead of blocking the caller.
+//!
+//! ```no_run
+//! # async fn run() {
+//! use custom_tracing_logger::async_writer::AsyncWriter;
+//!
+//! let (writer, task) = AsyncWriter::new(tokio::io::stderr());
+//! let task = tokio::spawn(task.run());
+//! custom_tracing_logger::builder()
+//!     .add_sink(Box::new(writer.clone()))
+//!     .init();
+//!
+//! // On shutdown: write what is queued, then close the stream
+//! writer.close();
+//! let _ = task.await;
+//! # }
+//! ```
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::sink::LogSink;
+
+/// Default number of records queued before new ones are dropped
+pub const DEFAULT_CAPACITY: usize = 8192;
+
+/// Largest batch handed to the writer in one call
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+enum Message {
+    Record(Vec<u8>),
+    Flush,
+    Close,
+}
+
+/// Synchronous handle queueing records for an async writer
+///
+/// Usable as a [`LogSink`] with [`Builder::add_sink`](crate::Builder::add_sink)
+/// or as a `MakeWriter` in a custom subscriber stack. Clones share the queue.
+#[derive(Clone)]
+pub struct AsyncWriter {
+    tx: mpsc::Sender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Drives an [`AsyncWriter`]'s queue into the underlying writer
+///
+/// Spawn [`run`](AsyncWriterTask::run) on the runtime; it completes after
+/// [`AsyncWriter::close`], or once every handle is dropped.
+pub struct AsyncWriterTask<W> {
+    writer: W,
+    rx: mpsc::Receiver<Message>,
+}
+
+impl AsyncWriter {
+    /// Bridge to `writer`, queueing up to [`DEFAULT_CAPACITY`] records
+    pub fn new<W>(writer: W) -> (Self, AsyncWriterTask<W>)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        Self::with_capacity(writer, DEFAULT_CAPACITY)
+    }
+
+    /// Bridge to `writer`, queueing up to `capacity` records
+    pub fn with_capacity<W>(writer: W, capacity: usize) -> (Self, AsyncWriterTask<W>)
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let handle = Self {
+            tx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (handle, AsyncWriterTask { writer, rx })
+    }
+
+    /// Records dropped because the queue was full or the task had finished
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Write everything queued so far, then flush and shut down the writer
+    ///
+    /// Records queued after this call are dropped.
+    pub fn close(&self) {
+        self.send(Message::Close);
+    }
+
+    fn send(&self, message: Message) {
+        if self.tx.try_send(message).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            crate::metrics::add_dropped(1);
+        }
+    }
+}
+
+impl LogSink for AsyncWriter {
+    fn write_event(&self, json: &[u8]) {
+        let mut record = Vec::with_capacity(json.len() + 1);
+        record.extend_from_slice(json);
+        record.push(b'\n');
+        self.send(Message::Record(record));
+    }
+
+    fn flush(&self) {
+        self.send(Message::Flush);
+    }
+}
+
+impl<'a> MakeWriter<'a> for AsyncWriter {
+    type Writer = AsyncRecord<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        AsyncRecord {
+            buf: Vec::new(),
+            target: self,
+        }
+    }
+}
+
+/// Buffers one formatted record and queues it when dropped
+pub struct AsyncRecord<'a> {
+    buf: Vec<u8>,
+    target: &'a AsyncWriter,
+}
+
+impl Write for AsyncRecord<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for AsyncRecord<'_> {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            self.target
+                .send(Message::Record(std::mem::take(&mut self.buf)));
+        }
+    }
+}
+
+impl<W> AsyncWriterTask<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Write queued records until closed, batching whatever is ready
+    ///
+    /// Returns the first error from the writer; records queued after that
+    /// are dropped.
+    pub async fn run(mut self) -> io::Result<()> {
+        let mut batch = Vec::new();
+        while let Some(message) = self.rx.recv().await {
+            let mut flush = false;
+            let mut close = false;
+            let mut next = Some(message);
+            while let Some(message) = next {
+                match message {
+                    Message::Record(record) => batch.extend_from_slice(&record),
+                    Message::Flush => flush = true,
+                    Message::Close => {
+                        close = true;
+                        break;
+                    }
+                }
+                next = if batch.len() < MAX_BATCH_BYTES {
+                    self.rx.try_recv().ok()
+                } else {
+                    None
+                };
+            }
+
+            if !batch.is_empty() {
+                self.writer.write_all(&batch).await?;
+                batch.clear();
+            }
+            if close {
+                break;
+            }
+            if flush {
+                self.writer.flush().await?;
+            }
+        }
+        self.writer.flush().await?;
+        self.writer.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Poll a future whose input is already queued to completion
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn test_records_written_until_close() {
+        let mut out = Vec::new();
+        let (writer, task) = AsyncWriter::new(&mut out);
+
+        writer.write_event(br#"{"n":1}"#);
+        let mut record = writer.make_writer();
+        record.write_all(b"{\"n\":2}\n").unwrap();
+        drop(record);
+        writer.close();
+        writer.write_event(br#"{"n":3}"#);
+
+        block_on(task.run()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"n\":1}\n{\"n\":2}\n");
+    }
+
+    #[test]
+    fn test_full_queue_drops_records() {
+        let (writer, _task) = AsyncWriter::with_capacity(Vec::new(), 2);
+        for _ in 0..5 {
+            writer.write_event(b"{}");
+        }
+        assert_eq!(writer.dropped(), 3);
+    }
+}
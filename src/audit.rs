@@ -0,0 +1,729 @@
+//! Audit records outside the log pipeline
+//!
+//! [`event`] writes one JSON line per security-relevant action to a
+//! dedicated append-only file (or a [`LogSink`]) with mandatory `actor`,
+//! `action` and `outcome` fields. Audit records do not go through the
+//! `tracing` subscriber, so `RUST_LOG`, rate limiting, sampling and output
+//! channels cannot silence them, and each record is flushed before
+//! [`event`] returns. Every record carries a per-process `seq`, so a
+//! removed line leaves a visible gap.
+//!
+//! With hash chaining (`LOG_AUDIT_HASH_CHAIN=true`) each record also
+//! carries `prev_hash`, the SHA-256 of the previous record's line, and
+//! [`verify_audit_log`] detects modified, removed or reordered records.
+//!
+//! The audit file is configured with `LOG_AUDIT_DIR` (plus
+//! `LOG_AUDIT_PREFIX`, `LOG_AUDIT_ROTATION` and `LOG_AUDIT_MAX_FILES`) or
+//! with [`install`], and gets the same mode and group as the log files
+//! (`LOG_FILE_MODE`, `LOG_FILE_GROUP`). Without either, audit records are
+//! written to stderr, where no filter can silence them either.
+//!
+//! ```
+//! use custom_tracing_logger::audit::{self, Outcome};
+//!
+//! audit::event("admin@example.com", "role.grant", Some("user:42"), Outcome::Success)?;
+//! // {"timestamp":"2025-01-01T12:00:00.000000Z","seq":1,"actor":"admin@example.com",
+//! //  "action":"role.grant","resource":"user:42","outcome":"success"}
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tracing_appender::rolling::Rotation;
+
+use crate::permissions::FilePermissions;
+use crate::sink::LogSink;
+use crate::time_fields::Rfc3339;
+
+/// Audit log used by [`event`]; `Err` when the configured one could not be opened
+static INSTALLED: OnceLock<Result<AuditLog, String>> = OnceLock::new();
+
+/// Sequence number of the last audit record
+static SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// `prev_hash` of the first record of a chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Result of an audited action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The action was carried out
+    Success,
+    /// The action was attempted and failed
+    Failure,
+    /// The action was refused, e.g. for missing permissions
+    Denied,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Denied => "denied",
+        })
+    }
+}
+
+/// Location and rotation of the audit file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditConfig {
+    /// Directory of the audit files
+    pub dir: PathBuf,
+    /// File name prefix (default: "audit")
+    pub prefix: String,
+    /// How often a new file is started (default: daily)
+    pub rotation: Rotation,
+    /// Rotated files kept; older ones are deleted (default: all are kept)
+    pub max_files: Option<usize>,
+    /// Add `prev_hash` to every record (default: off)
+    pub hash_chain: bool,
+    /// Mode and group of the audit files and of the directories created for them
+    pub permissions: FilePermissions,
+}
+
+impl AuditConfig {
+    /// Daily audit files named `audit.YYYY-MM-DD` in `dir`, all kept
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: "audit".to_string(),
+            rotation: Rotation::DAILY,
+            max_files: None,
+            hash_chain: false,
+            permissions: FilePermissions::default(),
+        }
+    }
+
+    /// Read the configuration from the environment
+    ///
+    /// - `LOG_AUDIT_DIR`: Directory of the audit files (required, no audit file without it)
+    /// - `LOG_AUDIT_PREFIX`: File name prefix (default: "audit")
+    /// - `LOG_AUDIT_ROTATION`: "daily" (default), "hourly" or "never"
+    /// - `LOG_AUDIT_MAX_FILES`: Rotated files kept (default: all)
+    /// - `LOG_AUDIT_HASH_CHAIN`: "true" to chain records by `prev_hash` (default: "false")
+    ///
+    /// Permissions come from `LOG_FILE_MODE` and `LOG_FILE_GROUP`, as for
+    /// the log files.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        Self::read_env(true)
+    }
+
+    /// [`from_env`](Self::from_env) without creating the directory
+    pub(crate) fn check_env() -> Result<Option<Self>, String> {
+        Self::read_env(false)
+    }
+
+    fn read_env(create: bool) -> Result<Option<Self>, String> {
+        let Ok(raw) = crate::config_file::var("LOG_AUDIT_DIR") else {
+            return Ok(None);
+        };
+        let permissions = FilePermissions::from_env()?;
+        let dir = if create {
+            crate::paths::resolve_log_dir_with(&raw, &permissions)
+        } else {
+            crate::paths::check_log_dir(&raw)
+        }
+        .map_err(|e| e.to_string())?;
+        let mut config = Self::new(dir);
+        config.permissions = permissions;
+        if let Ok(prefix) = crate::config_file::var("LOG_AUDIT_PREFIX") {
+            config.prefix = prefix.trim().to_string();
+        }
+        if let Ok(rotation) = crate::config_file::var("LOG_AUDIT_ROTATION") {
+            config.rotation = match rotation.trim().to_ascii_lowercase().as_str() {
+                "daily" => Rotation::DAILY,
+                "hourly" => Rotation::HOURLY,
+                "never" => Rotation::NEVER,
+                other => {
+                    return Err(format!(
+                        "unknown audit rotation '{}', expected daily, hourly or never",
+                        other
+                    ))
+                }
+            };
+        }
+        if let Ok(max) = crate::config_file::var("LOG_AUDIT_MAX_FILES") {
+            match max.trim().parse::<usize>() {
+                Ok(max) if max > 0 => config.max_files = Some(max),
+                _ => return Err(format!("'{}' is not a positive number of files", max)),
+            }
+        }
+        config.hash_chain =
+            crate::config_file::var("LOG_AUDIT_HASH_CHAIN").unwrap_or_default() == "true";
+        Ok(Some(config))
+    }
+}
+
+enum Destination {
+    File(AuditFile),
+    Sink(Box<dyn LogSink>),
+    Stderr,
+}
+
+/// Rotating audit file, opened with the configured permissions
+struct AuditFile {
+    config: AuditConfig,
+    /// Name and handle of the file being appended to
+    current: Option<(String, File)>,
+}
+
+impl AuditFile {
+    /// Open the file for records written now
+    fn open(config: &AuditConfig) -> io::Result<Self> {
+        let mut file = Self {
+            config: config.clone(),
+            current: None,
+        };
+        file.roll(SystemTime::now())?;
+        Ok(file)
+    }
+
+    /// Name of the file holding records written at `time`
+    ///
+    /// `prefix.YYYY-MM-DD` (daily), `prefix.YYYY-MM-DD-HH` (hourly),
+    /// `prefix.YYYY-MM-DD-HH-MM` (minutely) or just `prefix`, in UTC.
+    fn name_at(&self, time: SystemTime) -> String {
+        let rotation = &self.config.rotation;
+        let end = if *rotation == Rotation::MINUTELY {
+            16
+        } else if *rotation == Rotation::HOURLY {
+            13
+        } else if *rotation == Rotation::DAILY {
+            10
+        } else {
+            return self.config.prefix.clone();
+        };
+        let stamp = Rfc3339(time).to_string();
+        format!(
+            "{}.{}",
+            self.config.prefix,
+            stamp[..end].replace(['T', ':'], "-")
+        )
+    }
+
+    /// Switch to the file of `time` if it is not the open one
+    fn roll(&mut self, time: SystemTime) -> io::Result<&mut File> {
+        let name = self.name_at(time);
+        if self.current.as_ref().is_none_or(|(open, _)| *open != name) {
+            let permissions = &self.config.permissions;
+            permissions.create_dir_all(&self.config.dir)?;
+            let path = self.config.dir.join(&name);
+            let mut options = OpenOptions::new();
+            options.create(true).append(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(permissions.file_mode());
+            }
+            let file = options.open(&path)?;
+            permissions.apply(&file)?;
+            self.current = Some((name, file));
+            self.prune();
+        }
+        let (_, file) = self.current.as_mut().expect("audit file opened above");
+        Ok(file)
+    }
+
+    /// Delete the oldest files beyond [`max_files`](AuditConfig::max_files)
+    fn prune(&self) {
+        let Some(max) = self.config.max_files else {
+            return;
+        };
+        let files = match chain_files(&self.config.dir, &self.config.prefix) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Cannot list audit files: {}", e);
+                return;
+            }
+        };
+        for path in &files[..files.len().saturating_sub(max)] {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("Cannot remove audit file '{}': {}", path.display(), e);
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let file = self.roll(SystemTime::now())?;
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        file.flush()
+    }
+}
+
+struct State {
+    destination: Destination,
+    /// Hash of the last record written, when records are chained
+    prev_hash: Option<String>,
+}
+
+/// Destination of audit records, see [`install`]
+pub struct AuditLog {
+    state: Mutex<State>,
+}
+
+impl AuditLog {
+    /// Append audit records to rotating files
+    ///
+    /// With [`hash_chain`](AuditConfig::hash_chain), the chain continues
+    /// from the last record of the newest existing file.
+    pub fn file(config: &AuditConfig) -> io::Result<Self> {
+        let prev_hash = if config.hash_chain {
+            let newest = chain_files(&config.dir, &config.prefix)?.pop();
+            let last = match newest {
+                Some(path) => last_line(&path)?,
+                None => None,
+            };
+            Some(last.map_or_else(|| GENESIS_HASH.to_string(), |line| hash(&line)))
+        } else {
+            None
+        };
+        let file = AuditFile::open(config)
+            .map_err(|e| io::Error::other(format!("cannot open audit file: {}", e)))?;
+        Ok(Self::new(Destination::File(file), prev_hash))
+    }
+
+    /// Hand audit records to a sink, e.g. a write-once store
+    pub fn sink(sink: impl LogSink + 'static) -> Self {
+        Self::new(Destination::Sink(Box::new(sink)), None)
+    }
+
+    /// Chain the records of a sink by `prev_hash`, starting a new chain
+    pub fn with_hash_chain(self) -> Self {
+        let mut state = self.state.into_inner().unwrap_or_else(|e| e.into_inner());
+        state
+            .prev_hash
+            .get_or_insert_with(|| GENESIS_HASH.to_string());
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    fn new(destination: Destination, prev_hash: Option<String>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                destination,
+                prev_hash,
+            }),
+        }
+    }
+
+    fn write(&self, mut record: Value) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let (Some(prev_hash), Value::Object(fields)) = (&state.prev_hash, &mut record) {
+            fields.insert("prev_hash".to_string(), prev_hash.clone().into());
+        }
+        let line = record.to_string();
+        match &mut state.destination {
+            Destination::File(file) => file.write_line(&line)?,
+            Destination::Sink(sink) => {
+                sink.write_event(line.as_bytes());
+                sink.flush();
+            }
+            Destination::Stderr => {
+                let mut stderr = io::stderr().lock();
+                stderr.write_all(format!("{}\n", line).as_bytes())?;
+                stderr.flush()?;
+            }
+        }
+        if state.prev_hash.is_some() {
+            state.prev_hash = Some(hash(&line));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog").finish_non_exhaustive()
+    }
+}
+
+/// Use `log` for all audit records; returns `false` if one is already in use
+///
+/// Call it before the first [`event`], which otherwise settles on
+/// `LOG_AUDIT_DIR` (or stderr).
+pub fn install(log: AuditLog) -> bool {
+    INSTALLED.set(Ok(log)).is_ok()
+}
+
+fn installed() -> io::Result<&'static AuditLog> {
+    let installed = INSTALLED.get_or_init(|| match AuditConfig::from_env()? {
+        Some(config) => AuditLog::file(&config).map_err(|e| e.to_string()),
+        None => Ok(AuditLog::new(Destination::Stderr, None)),
+    });
+    match installed {
+        Ok(log) => Ok(log),
+        Err(e) => Err(io::Error::other(format!("audit log unavailable: {}", e))),
+    }
+}
+
+/// Record a security-relevant action
+///
+/// Fails without writing anything when `actor` or `action` is empty, and
+/// when the configured audit file cannot be opened or written, so callers
+/// can refuse an action that would go unaudited.
+pub fn event(
+    actor: &str,
+    action: &str,
+    resource: Option<&str>,
+    outcome: Outcome,
+) -> io::Result<()> {
+    let log = installed()?;
+    let record = record(actor, action, resource, outcome, SystemTime::now())?;
+    log.write(record)
+}
+
+fn record(
+    actor: &str,
+    action: &str,
+    resource: Option<&str>,
+    outcome: Outcome,
+    time: SystemTime,
+) -> io::Result<Value> {
+    for (name, value) in [("actor", actor), ("action", action)] {
+        if value.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("audit event without {}", name),
+            ));
+        }
+    }
+    Ok(json!({
+        "timestamp": Rfc3339(time).to_string(),
+        "seq": SEQ.fetch_add(1, Ordering::Relaxed) + 1,
+        "actor": actor,
+        "action": action,
+        "resource": resource,
+        "outcome": outcome.to_string(),
+    }))
+}
+
+/// Hex SHA-256 of a record's line, as stored in the next record's `prev_hash`
+fn hash(line: &str) -> String {
+    Sha256::digest(line.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Files of the chain `prefix` in `dir`, oldest first (dated names sort by date)
+fn chain_files(dir: &Path, prefix: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if (name == prefix || name.starts_with(&format!("{}.", prefix))) && entry.path().is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Last non-empty line of a file, reading only its end
+fn last_line(path: &Path) -> io::Result<Option<String>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut window = 64 * 1024;
+    loop {
+        let start = len.saturating_sub(window);
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)?;
+        let text = String::from_utf8_lossy(&tail);
+        let mut lines = text.lines().rev().filter(|line| !line.trim().is_empty());
+        if let Some(last) = lines.next() {
+            // The window may start inside the last line; widen it until a line break precedes it
+            if start == 0 || lines.next().is_some() {
+                return Ok(Some(last.to_string()));
+            }
+        } else if start == 0 {
+            return Ok(None);
+        }
+        window *= 4;
+    }
+}
+
+/// Result of a successful [`verify_audit_log`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainSummary {
+    /// Records checked
+    pub records: u64,
+    /// `prev_hash` of the first record: [`GENESIS_HASH`], or the hash of a record in an older file
+    pub first_prev_hash: String,
+    /// Hash of the last record
+    ///
+    /// Records removed from the end of the log leave no trace in the chain;
+    /// compare this with a copy kept elsewhere (e.g. published daily) to
+    /// detect truncation.
+    pub last_hash: String,
+}
+
+/// Why [`verify_audit_log`] rejected a log
+#[derive(Debug)]
+pub enum ChainError {
+    /// A file could not be read
+    Io(io::Error),
+    /// A record does not continue the chain
+    Broken {
+        /// File holding the record
+        file: PathBuf,
+        /// Line number of the record, from 1
+        line: usize,
+        /// What is wrong with it
+        reason: String,
+    },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Io(e) => write!(f, "cannot read audit log: {}", e),
+            ChainError::Broken { file, line, reason } => {
+                write!(f, "{}:{}: {}", file.display(), line, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+impl From<io::Error> for ChainError {
+    fn from(e: io::Error) -> Self {
+        ChainError::Io(e)
+    }
+}
+
+/// Check the hash chain of an audit file, or of every file of an audit directory
+///
+/// A directory is checked as one chain across its rotated files, oldest
+/// first; pass `prefix` files only (e.g. a directory holding nothing but
+/// audit files). Every record must carry the hash of the record before it,
+/// so a modified, removed, inserted or reordered record is reported with
+/// its position. The first record may continue a chain from a file that
+/// has since been deleted by retention; its `prev_hash` is returned.
+///
+/// ```no_run
+/// use custom_tracing_logger::audit;
+///
+/// match audit::verify_audit_log("/var/log/myapp/audit") {
+///     Ok(summary) => println!("{} records intact, head {}", summary.records, summary.last_hash),
+///     Err(e) => eprintln!("audit log tampered with: {}", e),
+/// }
+/// ```
+pub fn verify_audit_log(path: impl AsRef<Path>) -> Result<ChainSummary, ChainError> {
+    let path = path.as_ref();
+    let files = if path.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?;
+        files.retain(|file| file.is_file());
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut summary: Option<ChainSummary> = None;
+    for file in files {
+        let reader = BufReader::new(File::open(&file)?);
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let broken = |reason: String| ChainError::Broken {
+                file: file.clone(),
+                line: index + 1,
+                reason,
+            };
+            let record: Value = serde_json::from_str(&line)
+                .map_err(|e| broken(format!("not a JSON record: {}", e)))?;
+            let prev_hash = record
+                .get("prev_hash")
+                .and_then(Value::as_str)
+                .ok_or_else(|| broken("record has no prev_hash".to_string()))?;
+            match &mut summary {
+                Some(summary) => {
+                    if prev_hash != summary.last_hash {
+                        return Err(broken(
+                            "prev_hash does not match the previous record".to_string(),
+                        ));
+                    }
+                    summary.records += 1;
+                    summary.last_hash = hash(&line);
+                }
+                None => {
+                    summary = Some(ChainSummary {
+                        records: 1,
+                        first_prev_hash: prev_hash.to_string(),
+                        last_hash: hash(&line),
+                    })
+                }
+            }
+        }
+    }
+    Ok(summary.unwrap_or_else(|| ChainSummary {
+        records: 0,
+        first_prev_hash: GENESIS_HASH.to_string(),
+        last_hash: GENESIS_HASH.to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_record_requires_actor_and_action() {
+        let error = record("", "login", None, Outcome::Denied, SystemTime::now()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(record("bob", " ", None, Outcome::Denied, SystemTime::now()).is_err());
+
+        let first = record("bob", "login", None, Outcome::Denied, SystemTime::now()).unwrap();
+        let second = record("bob", "login", None, Outcome::Denied, SystemTime::now()).unwrap();
+        assert_eq!(first["outcome"], "denied");
+        assert_eq!(first["resource"], Value::Null);
+        assert!(second["seq"].as_u64() > first["seq"].as_u64());
+    }
+
+    #[test]
+    fn test_file_appends_flushed_lines() {
+        let dir = std::env::temp_dir().join(format!("audit_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log = AuditLog::file(&AuditConfig::new(&dir)).unwrap();
+        let entry = record(
+            "admin",
+            "role.grant",
+            Some("user:42"),
+            Outcome::Success,
+            SystemTime::now(),
+        );
+        log.write(entry.unwrap()).unwrap();
+
+        let file = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        let line: Value = serde_json::from_str(content.trim_end()).unwrap();
+        assert_eq!(line["action"], "role.grant");
+        assert_eq!(line["resource"], "user:42");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_files_rotate_with_permissions() {
+        let dir = std::env::temp_dir().join(format!("audit_perm_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config = AuditConfig::new(&dir);
+        config.rotation = Rotation::HOURLY;
+        config.max_files = Some(2);
+        config.permissions = FilePermissions::mode(0o600);
+        let mut file = AuditFile::open(&config).unwrap();
+
+        let hour = 1_700_000_000;
+        for offset in 0..3 {
+            let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(hour + offset * 3600);
+            file.roll(time).unwrap();
+        }
+        assert_eq!(
+            file.name_at(std::time::UNIX_EPOCH + std::time::Duration::from_secs(hour)),
+            "audit.2023-11-14-22"
+        );
+        let files = chain_files(&dir, "audit").unwrap();
+        assert_eq!(files.len(), 2);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&files[0]).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sink_receives_records() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let log = AuditLog::sink(move |json: &[u8]| sink.lock().unwrap().push(json.to_vec()));
+        let entry = record(
+            "svc",
+            "key.rotate",
+            None,
+            Outcome::Failure,
+            SystemTime::now(),
+        );
+        log.write(entry.unwrap()).unwrap();
+
+        let received = received.lock().unwrap();
+        let line: Value = serde_json::from_slice(&received[0]).unwrap();
+        assert_eq!(line["outcome"], "failure");
+    }
+
+    #[test]
+    fn test_hash_chain_survives_restart_and_detects_tampering() {
+        let dir = std::env::temp_dir().join(format!("audit_chain_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config = AuditConfig::new(&dir);
+        config.hash_chain = true;
+        let write = |log: &AuditLog, action: &str| {
+            let entry = record("admin", action, None, Outcome::Success, SystemTime::now());
+            log.write(entry.unwrap()).unwrap();
+        };
+
+        let log = AuditLog::file(&config).unwrap();
+        write(&log, "user.create");
+        write(&log, "role.grant");
+        drop(log);
+        // A new process continues the chain from the last record on disk
+        write(&AuditLog::file(&config).unwrap(), "user.delete");
+
+        let summary = verify_audit_log(&dir).unwrap();
+        assert_eq!(summary.records, 3);
+        assert_eq!(summary.first_prev_hash, GENESIS_HASH);
+
+        let file = chain_files(&dir, "audit").unwrap().pop().unwrap();
+        let content = std::fs::read_to_string(&file).unwrap();
+        std::fs::write(&file, content.replace("role.grant", "role.revoke")).unwrap();
+        match verify_audit_log(&file) {
+            Err(ChainError::Broken { line, .. }) => assert_eq!(line, 3),
+            other => panic!("tampering not detected: {:?}", other),
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sink_chain_links_records() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let log = AuditLog::sink(move |json: &[u8]| {
+            sink.lock()
+                .unwrap()
+                .push(String::from_utf8(json.to_vec()).unwrap())
+        })
+        .with_hash_chain();
+        for action in ["login", "logout"] {
+            let entry = record("bob", action, None, Outcome::Success, SystemTime::now());
+            log.write(entry.unwrap()).unwrap();
+        }
+
+        let received = received.lock().unwrap();
+        let first: Value = serde_json::from_str(&received[0]).unwrap();
+        let second: Value = serde_json::from_str(&received[1]).unwrap();
+        assert_eq!(first["prev_hash"], GENESIS_HASH);
+        assert_eq!(second["prev_hash"], hash(&received[0]));
+    }
+}
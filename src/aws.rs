@@ -0,0 +1,212 @@
+//! AWS credentials and Signature Version 4 signing
+//!
+//! Shared by the CloudWatch sink and the S3 archive store. Credentials come
+//! from the standard `AWS_*` environment variables or the ECS container
+//! credentials endpoint.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Container credentials are refreshed well before they expire
+const CREDENTIALS_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone)]
+pub(crate) struct Credentials {
+    pub(crate) access_key_id: String,
+    pub(crate) secret_access_key: String,
+    pub(crate) session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Static credentials from the environment, as provided by Lambda
+    fn from_env() -> Option<Self> {
+        Some(Self {
+            access_key_id: std::env::var("AWS_ACCESS_KEY_ID").ok()?,
+            secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+
+    /// Task role credentials from the ECS container credentials endpoint
+    fn from_container() -> Result<Self, String> {
+        let url = match (
+            std::env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI"),
+            std::env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI"),
+        ) {
+            (Ok(relative), _) => format!("http://169.254.170.2{}", relative),
+            (_, Ok(full)) => full,
+            _ => return Err("no AWS credentials found in the environment".to_string()),
+        };
+        let mut request = ureq::get(&url).timeout(Duration::from_secs(2));
+        if let Ok(token) = std::env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+            request = request.set("Authorization", &token);
+        }
+        let body = request
+            .call()
+            .map_err(|e| format!("container credentials request failed: {}", e))?
+            .into_string()
+            .map_err(|e| format!("container credentials response unreadable: {}", e))?;
+        let body: Value = serde_json::from_str(&body)
+            .map_err(|e| format!("container credentials response invalid: {}", e))?;
+        let field = |name: &str| body.get(name).and_then(Value::as_str).map(String::from);
+
+        Ok(Self {
+            access_key_id: field("AccessKeyId").ok_or("missing AccessKeyId")?,
+            secret_access_key: field("SecretAccessKey").ok_or("missing SecretAccessKey")?,
+            session_token: field("Token"),
+        })
+    }
+}
+
+/// Credentials fetched on first use and refreshed periodically
+#[derive(Debug, Default)]
+pub(crate) struct CredentialsCache {
+    cached: Option<(Credentials, Instant)>,
+}
+
+impl CredentialsCache {
+    pub(crate) fn get(&mut self) -> Result<Credentials, String> {
+        if let Some((credentials, fetched)) = &self.cached {
+            if fetched.elapsed() < CREDENTIALS_TTL {
+                return Ok(credentials.clone());
+            }
+        }
+        let credentials = match Credentials::from_env() {
+            Some(credentials) => credentials,
+            None => Credentials::from_container()?,
+        };
+        self.cached = Some((credentials.clone(), Instant::now()));
+        Ok(credentials)
+    }
+
+    /// Fetch fresh credentials on the next call, e.g. after an expired token
+    pub(crate) fn invalidate(&mut self) {
+        self.cached = None;
+    }
+}
+
+/// Request to sign with [`sign`]
+pub(crate) struct Request<'a> {
+    pub(crate) method: &'a str,
+    /// URI-encoded path
+    pub(crate) path: &'a str,
+    pub(crate) service: &'a str,
+    pub(crate) region: &'a str,
+    /// Headers to sign besides `x-amz-date`, with lowercase names
+    pub(crate) headers: Vec<(&'a str, String)>,
+    /// Hex SHA-256 of the body, or "UNSIGNED-PAYLOAD"
+    pub(crate) payload_hash: String,
+}
+
+/// Sign `request` with AWS Signature Version 4
+///
+/// Returns the headers to add to the request besides those it signs.
+pub(crate) fn sign(
+    credentials: &Credentials,
+    request: Request<'_>,
+    now: SystemTime,
+) -> Vec<(String, String)> {
+    let amz_date = amz_datetime(now);
+    let date = &amz_date[..8];
+    let scope = format!(
+        "{}/{}/{}/aws4_request",
+        date, request.region, request.service
+    );
+
+    let mut headers = request.headers;
+    headers.push(("x-amz-date", amz_date.clone()));
+    headers.sort_by(|a, b| a.0.cmp(b.0));
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        request.method, request.path, canonical_headers, signed_headers, request.payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let secret = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac_sha256(secret.as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, request.region.as_bytes());
+    let key = hmac_sha256(&key, request.service.as_bytes());
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    let mut headers = vec![
+        ("X-Amz-Date".to_string(), amz_date.clone()),
+        (
+            "Authorization".to_string(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                credentials.access_key_id, scope, signed_headers, signature
+            ),
+        ),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("X-Amz-Security-Token".to_string(), token.clone()));
+    }
+    headers
+}
+
+/// Hex SHA-256 of `data`
+#[cfg(feature = "cloudwatch")]
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format a time as SigV4's basic ISO 8601 form ("20150830T123600Z")
+fn amz_datetime(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = crate::time_fields::civil_from_days(days as i64);
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amz_datetime() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+        assert_eq!(amz_datetime(time), "20150830T123600Z");
+        assert_eq!(amz_datetime(UNIX_EPOCH), "19700101T000000Z");
+    }
+}
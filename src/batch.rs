@@ -0,0 +1,286 @@
+//! Background batching shared by the push sinks
+//!
+//! Formatted records are handed to a bounded channel and collected on a
+//! dedicated thread into batches, which are flushed when they reach a size
+//! limit or after a fixed interval. Records are dropped rather than blocking
+//! the application when the push thread falls behind. [`flush_all`] pushes
+//! what every thread holds, so panics and frozen Lambda environments don't
+//! lose the last records.
+
+use std::cell::Cell;
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+#[cfg(any(feature = "loki", feature = "cloudwatch"))]
+use std::time::SystemTime;
+use std::time::{Duration, Instant};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Records buffered between the application and the push thread
+const CHANNEL_CAPACITY: usize = 10_000;
+
+/// Longest time [`flush_all`] waits for the push threads
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A single formatted record waiting to be pushed
+pub struct Entry {
+    /// Time the record was written
+    #[cfg(any(feature = "loki", feature = "cloudwatch"))]
+    pub timestamp: SystemTime,
+    /// Formatted record without the trailing newline
+    pub line: String,
+}
+
+impl Entry {
+    /// Record written now
+    pub fn new(line: String) -> Self {
+        Self {
+            #[cfg(any(feature = "loki", feature = "cloudwatch"))]
+            timestamp: SystemTime::now(),
+            line,
+        }
+    }
+}
+
+/// What the application hands to a push thread
+enum Message {
+    /// A record to batch
+    Entry(Entry),
+    /// Push the current batch now, then acknowledge
+    Flush(SyncSender<()>),
+}
+
+/// Senders of the live batch writers, for [`flush_all`]
+static REGISTERED: Mutex<Vec<Weak<SyncSender<Message>>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Set on push threads, which must not wait for their own flush
+    static PUSH_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// When a batch is flushed
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BatchPolicy {
+    /// Maximum number of records per batch
+    pub(crate) max_events: usize,
+    /// Maximum accumulated size of a batch in bytes
+    pub(crate) max_bytes: usize,
+    /// Bytes counted per record on top of its length
+    pub(crate) overhead: usize,
+    /// Maximum time a record waits before its batch is flushed
+    pub(crate) interval: Duration,
+}
+
+/// `MakeWriter` feeding a batching push thread
+pub struct BatchWriter {
+    sender: Arc<SyncSender<Message>>,
+}
+
+impl BatchWriter {
+    /// Spawn a thread named `name` that calls `push` with each completed batch
+    pub(crate) fn spawn<F>(name: &str, policy: BatchPolicy, push: F) -> io::Result<Self>
+    where
+        F: FnMut(Vec<Entry>) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || run_batches(policy, receiver, push))?;
+        let sender = Arc::new(sender);
+        let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+        registered.retain(|sender| sender.strong_count() > 0);
+        registered.push(Arc::downgrade(&sender));
+        Ok(Self { sender })
+    }
+}
+
+/// Ask a push thread to push its batch, returning where it acknowledges
+fn request_flush(sender: &SyncSender<Message>) -> Option<Receiver<()>> {
+    let (ack, done) = mpsc::sync_channel(1);
+    sender.try_send(Message::Flush(ack)).ok()?;
+    Some(done)
+}
+
+/// Push what every batch writer has queued, waiting at most 5 seconds
+///
+/// Does nothing on a push thread, which can't wait for itself.
+pub(crate) fn flush_all() {
+    if PUSH_THREAD.with(Cell::get) {
+        return;
+    }
+    let senders: Vec<_> = REGISTERED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    let pending: Vec<_> = senders
+        .iter()
+        .filter_map(|sender| request_flush(sender))
+        .collect();
+    drop(senders);
+    let deadline = Instant::now() + FLUSH_TIMEOUT;
+    for done in pending {
+        let _ = done.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+impl<'a> MakeWriter<'a> for BatchWriter {
+    type Writer = BatchEventWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        BatchEventWriter {
+            buf: Vec::new(),
+            sender: &self.sender,
+        }
+    }
+}
+
+/// Buffers one formatted record and queues it when dropped
+pub struct BatchEventWriter<'a> {
+    buf: Vec<u8>,
+    sender: &'a SyncSender<Message>,
+}
+
+impl io::Write for BatchEventWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for BatchEventWriter<'_> {
+    fn drop(&mut self) {
+        let line = String::from_utf8_lossy(&self.buf).trim_end().to_string();
+        if line.is_empty() {
+            return;
+        }
+        match self.sender.try_send(Message::Entry(Entry::new(line))) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => crate::metrics::add_dropped(1),
+            Err(TrySendError::Disconnected(_)) => {
+                crate::metrics::add_dropped(1);
+                eprintln!("Log push thread is no longer running, dropping event");
+            }
+        }
+    }
+}
+
+fn run_batches<F>(policy: BatchPolicy, receiver: Receiver<Message>, mut push: F)
+where
+    F: FnMut(Vec<Entry>),
+{
+    // Events raised while pushing (e.g. by the HTTP client) must not be batched again
+    let _entered = crate::reentrant::enter();
+    PUSH_THREAD.with(|push_thread| push_thread.set(true));
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+    let mut deadline = Instant::now() + policy.interval;
+
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let mut flushed = None;
+        let disconnected = match receiver.recv_timeout(timeout) {
+            Ok(Message::Entry(entry)) => {
+                let size = entry.line.len() + policy.overhead;
+                if !batch.is_empty() && batch_bytes + size > policy.max_bytes {
+                    push(std::mem::take(&mut batch));
+                    batch_bytes = 0;
+                }
+                batch_bytes += size;
+                batch.push(entry);
+                false
+            }
+            Ok(Message::Flush(ack)) => {
+                flushed = Some(ack);
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+
+        if batch.len() >= policy.max_events
+            || Instant::now() >= deadline
+            || disconnected
+            || flushed.is_some()
+        {
+            if !batch.is_empty() {
+                push(std::mem::take(&mut batch));
+                batch_bytes = 0;
+            }
+            deadline = Instant::now() + policy.interval;
+        }
+        if let Some(ack) = flushed {
+            let _ = ack.send(());
+        }
+
+        if disconnected {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_batches_respect_byte_limit() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&batches);
+        let policy = BatchPolicy {
+            max_events: 100,
+            max_bytes: 10,
+            overhead: 2,
+            interval: Duration::from_secs(60),
+        };
+
+        let writer = BatchWriter::spawn("test-batch", policy, move |batch| {
+            sink.lock().unwrap().push(batch.len());
+        })
+        .unwrap();
+        for _ in 0..3 {
+            writer.make_writer().write_all(b"abc\n").unwrap();
+        }
+        drop(writer);
+
+        // The push thread flushes the remainder once the writer is gone
+        for _ in 0..100 {
+            if batches.lock().unwrap().iter().sum::<usize>() == 3 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*batches.lock().unwrap(), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_flush_pushes_before_the_interval() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&batches);
+        let policy = BatchPolicy {
+            max_events: 100,
+            max_bytes: usize::MAX,
+            overhead: 0,
+            interval: Duration::from_secs(60),
+        };
+
+        let writer = BatchWriter::spawn("test-flush", policy, move |batch| {
+            sink.lock().unwrap().push(batch.len());
+        })
+        .unwrap();
+        for _ in 0..2 {
+            writer.make_writer().write_all(b"abc\n").unwrap();
+        }
+        flush_all();
+        assert_eq!(*batches.lock().unwrap(), vec![2]);
+    }
+}
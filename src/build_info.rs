@@ -0,0 +1,107 @@
+//! Build metadata on every record
+//!
+//! [`build_info!`](crate::build_info!) captures the version, git commit,
+//! compiler version and build time of the application at compile time, and
+//! [`Builder::with_build_info`](crate::Builder::with_build_info) stamps them
+//! on every record under `build`, so an error spike can be tied to a deploy
+//! straight from the logs.
+
+use serde_json::{Map, Value};
+
+/// Key of the build metadata object in records
+pub const BUILD: &str = "build";
+
+/// Build metadata of the application
+///
+/// # Examples
+/// ```no_run
+/// // GIT_SHA, RUSTC_VERSION and BUILD_TIMESTAMP are read at compile time when set,
+/// // e.g. by a build script emitting `cargo:rustc-env=GIT_SHA=...`
+/// custom_tracing_logger::builder()
+///     .with_build_info(custom_tracing_logger::build_info!())
+///     .init();
+///
+/// tracing::info!("Started"); // "build":{"version":"1.4.2","git_sha":"3f2c1ab",...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildInfo {
+    fields: Map<String, Value>,
+}
+
+impl BuildInfo {
+    /// Build metadata with the application `version`
+    pub fn new(version: impl Into<String>) -> Self {
+        Self::default().field("version", version.into())
+    }
+
+    /// Add the commit the application was built from
+    pub fn git_sha(self, sha: impl Into<String>) -> Self {
+        self.field("git_sha", sha.into())
+    }
+
+    /// Add the compiler version
+    pub fn rustc(self, version: impl Into<String>) -> Self {
+        self.field("rustc", version.into())
+    }
+
+    /// Add the build time, e.g. as an RFC 3339 timestamp
+    pub fn built_at(self, timestamp: impl Into<String>) -> Self {
+        self.field("built_at", timestamp.into())
+    }
+
+    /// Add any other build field, replacing one with the same name
+    pub fn field(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.insert(name.into(), value.into());
+        self
+    }
+
+    /// Metadata read from the compile-time environment by [`build_info!`](crate::build_info!)
+    #[doc(hidden)]
+    pub fn from_compile_env(
+        version: &str,
+        git_sha: Option<&str>,
+        rustc: Option<&str>,
+        built_at: Option<&str>,
+    ) -> Self {
+        let mut info = Self::new(version);
+        for (name, value) in [
+            ("git_sha", git_sha),
+            ("rustc", rustc),
+            ("built_at", built_at),
+        ] {
+            if let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) {
+                info = info.field(name, value);
+            }
+        }
+        info
+    }
+}
+
+impl From<BuildInfo> for Value {
+    fn from(info: BuildInfo) -> Self {
+        Value::Object(info.fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_compile_env_skips_missing() {
+        let info = BuildInfo::from_compile_env("1.4.2", Some("3f2c1ab"), None, Some(" "));
+        assert_eq!(
+            Value::from(info),
+            serde_json::json!({"version": "1.4.2", "git_sha": "3f2c1ab"})
+        );
+    }
+
+    #[test]
+    fn test_macro_captures_caller_version() {
+        let info = crate::build_info!(profile = "release", git_sha = "abc");
+        let value = Value::from(info);
+        assert_eq!(value["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(value["profile"], "release");
+        assert_eq!(value["git_sha"], "abc");
+    }
+}
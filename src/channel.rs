@@ -0,0 +1,166 @@
+//! Record channels
+//!
+//! Every record belongs to a [`Channel`]: operational records are
+//! `technical`, product analytics events `business` and security-relevant
+//! actions `audit`. A record picks its channel with a `channel` field
+//! (records without one are technical), and each output can be restricted
+//! to some channels with
+//! [`Builder::with_output_channels`](crate::Builder::with_output_channels),
+//! so analytics events are split from operational noise at the source.
+//!
+//! ```
+//! use custom_tracing_logger::channel::Channel;
+//!
+//! tracing::info!(channel = %Channel::Business, plan = "gold", "Subscription upgraded");
+//! tracing::info!(channel = "audit", actor = "admin", "Role granted");
+//! ```
+
+use std::fmt;
+
+use tracing::field::{Field, Visit};
+use tracing::Event;
+
+/// Name of the field selecting a record's channel
+pub const FIELD: &str = "channel";
+
+/// Kind of a record, for routing it to outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Operational records (default)
+    Technical,
+    /// Product and analytics events
+    Business,
+    /// Security-relevant actions
+    Audit,
+}
+
+impl Channel {
+    /// Every channel
+    pub const ALL: [Channel; 3] = [Channel::Technical, Channel::Business, Channel::Audit];
+
+    /// Name used in the `channel` field
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Channel::Technical => "technical",
+            Channel::Business => "business",
+            Channel::Audit => "audit",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Channel::ALL
+            .into_iter()
+            .find(|channel| channel.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "unknown channel '{}', expected technical, business or audit",
+                    s.trim()
+                )
+            })
+    }
+}
+
+/// Channels an output accepts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ChannelSet(u8);
+
+impl ChannelSet {
+    pub(crate) fn contains(self, channel: Channel) -> bool {
+        self.0 & channel.bit() != 0
+    }
+
+    /// Parse a comma-separated list of channel names
+    pub(crate) fn parse(list: &str) -> Result<Self, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    /// Read the channels an output accepts from `var`, if set
+    pub(crate) fn from_env_var(var: &str) -> Result<Option<Self>, String> {
+        match crate::config_file::var(var) {
+            Ok(list) => Self::parse(&list)
+                .map(Some)
+                .map_err(|e| format!("Invalid {}: {}", var, e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Channel names, for reports
+    pub(crate) fn names(self) -> Vec<&'static str> {
+        Channel::ALL
+            .into_iter()
+            .filter(|channel| self.contains(*channel))
+            .map(Channel::as_str)
+            .collect()
+    }
+}
+
+impl FromIterator<Channel> for ChannelSet {
+    fn from_iter<I: IntoIterator<Item = Channel>>(channels: I) -> Self {
+        Self(
+            channels
+                .into_iter()
+                .fold(0, |bits, channel| bits | channel.bit()),
+        )
+    }
+}
+
+/// Channel of an event, from its `channel` field
+///
+/// Records without the field, or with an unknown channel name, are technical.
+pub(crate) fn of(event: &Event<'_>) -> Channel {
+    struct Visitor(Option<Channel>);
+
+    impl Visit for Visitor {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == FIELD {
+                self.0 = value.parse().ok();
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == FIELD {
+                self.0 = crate::json_field::debug_text(value)
+                    .trim_matches('"')
+                    .parse()
+                    .ok();
+            }
+        }
+    }
+
+    let mut visitor = Visitor(None);
+    event.record(&mut visitor);
+    visitor.0.unwrap_or(Channel::Technical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_channel_set() {
+        let set = ChannelSet::parse("business, AUDIT").unwrap();
+        assert!(set.contains(Channel::Business));
+        assert!(set.contains(Channel::Audit));
+        assert!(!set.contains(Channel::Technical));
+        assert_eq!(set.names(), vec!["business", "audit"]);
+        assert!(ChannelSet::parse("marketing").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+//! Pluggable clocks for the monotonic `uptime_ns` record field
+//!
+//! Wall-clock timestamps can jump when NTP steps the system time, which
+//! scrambles ordering in log files. Records can additionally carry an
+//! `uptime_ns` value taken from a monotonic [`Clock`] to order them reliably.
+
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Monotonic time source for the `uptime_ns` field
+pub trait Clock: Send + Sync {
+    /// Nanoseconds elapsed since a fixed origin; must never go backwards
+    fn uptime_ns(&self) -> u64;
+}
+
+/// Default clock backed by `std::time::Instant`, counting from its first use
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn uptime_ns(&self) -> u64 {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> u64 + Send + Sync,
+{
+    fn uptime_ns(&self) -> u64 {
+        self()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic_clock_never_goes_backwards() {
+        let clock = MonotonicClock;
+        let first = clock.uptime_ns();
+        let second = clock.uptime_ns();
+        assert!(second >= first);
+    }
+}
@@ -0,0 +1,385 @@
+//! AWS CloudWatch Logs sink
+//!
+//! Batches JSON events on a background thread and ships them with
+//! `PutLogEvents`, so Lambda and ECS workloads don't need the CloudWatch
+//! agent. Requests are signed with SigV4 using credentials from the standard
+//! `AWS_*` environment variables or the ECS container credentials endpoint.
+//! Enabled with the `cloudwatch` feature and configured through
+//! `LOG_CLOUDWATCH_*` environment variables.
+
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::aws::{self, Credentials, CredentialsCache};
+use crate::batch::{BatchEventWriter, BatchPolicy, BatchWriter, Entry};
+
+/// `PutLogEvents` limits: 10,000 events and 1 MiB per batch, counting 26
+/// bytes of overhead per event, and 256 KiB per event
+const MAX_BATCH_EVENTS: usize = 10_000;
+const MAX_BATCH_BYTES: usize = 1_048_576;
+const EVENT_OVERHEAD: usize = 26;
+const MAX_EVENT_BYTES: usize = 262_144 - EVENT_OVERHEAD;
+
+/// CloudWatch Logs sink configuration
+#[derive(Debug, Clone)]
+pub struct CloudWatchConfig {
+    /// AWS region (e.g. "eu-west-1")
+    pub region: String,
+    /// Log group the stream belongs to (must already exist)
+    pub log_group: String,
+    /// Log stream to write to, created on first use if missing
+    pub log_stream: String,
+    /// Endpoint override (e.g. "http://localhost:4566" for LocalStack)
+    pub endpoint: Option<String>,
+    /// Maximum time an event waits before being pushed
+    pub batch_interval: Duration,
+    /// Attempts per batch before it is dropped
+    pub max_retries: u32,
+}
+
+impl CloudWatchConfig {
+    /// Create a configuration for `log_group`/`log_stream` in `region`
+    pub fn new(
+        region: impl Into<String>,
+        log_group: impl Into<String>,
+        log_stream: impl Into<String>,
+    ) -> Self {
+        Self {
+            region: region.into(),
+            log_group: log_group.into(),
+            log_stream: log_stream.into(),
+            endpoint: None,
+            batch_interval: Duration::from_millis(1000),
+            max_retries: 5,
+        }
+    }
+
+    /// Read the configuration from the environment
+    ///
+    /// - `LOG_CLOUDWATCH_GROUP`: Log group (required, sink is disabled without it)
+    /// - `LOG_CLOUDWATCH_STREAM`: Log stream (default: "$HOSTNAME-$PID")
+    /// - `AWS_REGION` / `AWS_DEFAULT_REGION`: Region (required when the group is set)
+    /// - `LOG_CLOUDWATCH_ENDPOINT`: Endpoint override
+    /// - `LOG_CLOUDWATCH_BATCH_INTERVAL_MS`: Maximum push delay (default: 1000)
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let log_group = match crate::config_file::var("LOG_CLOUDWATCH_GROUP") {
+            Ok(group) => group.trim().to_string(),
+            Err(_) => return Ok(None),
+        };
+        let region = std::env::var("AWS_REGION")
+            .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+            .map_err(|_| "LOG_CLOUDWATCH_GROUP is set but AWS_REGION is not".to_string())?;
+        let log_stream = crate::config_file::var("LOG_CLOUDWATCH_STREAM").unwrap_or_else(|_| {
+            let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "instance".to_string());
+            format!("{}-{}", host, std::process::id())
+        });
+
+        let mut config = Self::new(region.trim(), log_group, log_stream.trim());
+        config.endpoint = crate::config_file::var("LOG_CLOUDWATCH_ENDPOINT").ok();
+        if let Some(ms) = crate::config_file::var("LOG_CLOUDWATCH_BATCH_INTERVAL_MS")
+            .ok()
+            .and_then(|ms| ms.trim().parse().ok())
+        {
+            config.batch_interval = Duration::from_millis(ms);
+        }
+        Ok(Some(config))
+    }
+
+    pub(crate) fn endpoint_url(&self) -> String {
+        match &self.endpoint {
+            Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+            None => format!("https://logs.{}.amazonaws.com", self.region),
+        }
+    }
+}
+
+/// `MakeWriter` that hands formatted events to the CloudWatch push thread
+pub struct CloudWatchWriter {
+    inner: BatchWriter,
+}
+
+impl CloudWatchWriter {
+    /// Spawn the push thread and return a writer feeding it
+    ///
+    /// Fails only when the thread can't be spawned.
+    pub fn new(config: CloudWatchConfig) -> io::Result<Self> {
+        let policy = BatchPolicy {
+            max_events: MAX_BATCH_EVENTS,
+            max_bytes: MAX_BATCH_BYTES,
+            overhead: EVENT_OVERHEAD,
+            interval: config.batch_interval,
+        };
+        let mut pusher = Pusher::new(config);
+        let inner = BatchWriter::spawn("cloudwatch-push", policy, move |batch| {
+            pusher.push(batch);
+        })?;
+        Ok(Self { inner })
+    }
+}
+
+impl<'a> MakeWriter<'a> for CloudWatchWriter {
+    type Writer = BatchEventWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.inner.make_writer()
+    }
+}
+
+/// Outcome of a single API call
+enum CallError {
+    /// Throttling, 5xx and transport errors, worth retrying after a pause
+    Retryable(String),
+    /// A CloudWatch error response (`__type`) with its parsed body
+    Api(String, Value),
+}
+
+/// State kept by the push thread between batches
+struct Pusher {
+    config: CloudWatchConfig,
+    endpoint: String,
+    host: String,
+    sequence_token: Option<String>,
+    credentials: CredentialsCache,
+}
+
+impl Pusher {
+    fn new(config: CloudWatchConfig) -> Self {
+        let endpoint = config.endpoint_url();
+        let host = endpoint
+            .split("://")
+            .nth(1)
+            .unwrap_or(&endpoint)
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            config,
+            endpoint,
+            host,
+            sequence_token: None,
+            credentials: CredentialsCache::default(),
+        }
+    }
+
+    fn push(&mut self, mut batch: Vec<Entry>) {
+        // CloudWatch rejects batches that aren't in chronological order
+        batch.sort_by_key(|entry| entry.timestamp);
+        let events: Vec<Value> = batch
+            .iter()
+            .map(|entry| {
+                json!({
+                    "timestamp": timestamp_ms(entry.timestamp),
+                    "message": truncate(&entry.line, MAX_EVENT_BYTES),
+                })
+            })
+            .collect();
+
+        let mut backoff = Duration::from_millis(100);
+        let mut attempt = 0;
+        while attempt < self.config.max_retries {
+            attempt += 1;
+            let mut body = json!({
+                "logGroupName": self.config.log_group,
+                "logStreamName": self.config.log_stream,
+                "logEvents": events,
+            });
+            if let Some(token) = &self.sequence_token {
+                body["sequenceToken"] = json!(token);
+            }
+
+            match self.call("PutLogEvents", &body) {
+                Ok(response) => {
+                    self.update_sequence_token(&response);
+                    return;
+                }
+                Err(CallError::Api(kind, response)) => match kind.as_str() {
+                    "InvalidSequenceTokenException" => {
+                        self.sequence_token = response
+                            .get("expectedSequenceToken")
+                            .and_then(Value::as_str)
+                            .map(String::from);
+                    }
+                    "DataAlreadyAcceptedException" => {
+                        self.update_sequence_token(&response);
+                        return;
+                    }
+                    "ResourceNotFoundException" => {
+                        let stream = json!({
+                            "logGroupName": self.config.log_group,
+                            "logStreamName": self.config.log_stream,
+                        });
+                        if let Err(CallError::Api(kind, _)) = self.call("CreateLogStream", &stream)
+                        {
+                            if kind != "ResourceAlreadyExistsException" {
+                                eprintln!("CloudWatch CreateLogStream failed: {}", kind);
+                                break;
+                            }
+                        }
+                        self.sequence_token = None;
+                    }
+                    "ExpiredTokenException" | "UnrecognizedClientException" => {
+                        self.credentials.invalidate();
+                    }
+                    _ => {
+                        eprintln!("CloudWatch PutLogEvents rejected: {} {}", kind, response);
+                        break;
+                    }
+                },
+                Err(CallError::Retryable(reason)) => {
+                    eprintln!(
+                        "CloudWatch PutLogEvents attempt {} failed: {}",
+                        attempt, reason
+                    );
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(Duration::from_secs(10));
+                }
+            }
+        }
+        eprintln!("CloudWatch dropped a batch of {} events", batch.len());
+        crate::metrics::add_sink_failure();
+        crate::metrics::add_dropped(batch.len());
+    }
+
+    fn update_sequence_token(&mut self, response: &Value) {
+        if let Some(token) = response.get("nextSequenceToken").and_then(Value::as_str) {
+            self.sequence_token = Some(token.to_string());
+        }
+    }
+
+    fn call(&mut self, action: &str, body: &Value) -> Result<Value, CallError> {
+        let credentials = self.credentials.get().map_err(CallError::Retryable)?;
+        let body = body.to_string();
+        let target = format!("Logs_20140328.{}", action);
+        let headers = sign(
+            &credentials,
+            &self.config.region,
+            &self.host,
+            &target,
+            &body,
+            SystemTime::now(),
+        );
+
+        let mut request = ureq::post(&format!("{}/", self.endpoint))
+            .timeout(Duration::from_secs(10))
+            .set("Content-Type", "application/x-amz-json-1.1");
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+
+        match request.send_string(&body) {
+            Ok(response) => Ok(response
+                .into_string()
+                .ok()
+                .and_then(|body| serde_json::from_str(&body).ok())
+                .unwrap_or(Value::Null)),
+            Err(ureq::Error::Status(status, response)) => {
+                let body: Value = response
+                    .into_string()
+                    .ok()
+                    .and_then(|body| serde_json::from_str(&body).ok())
+                    .unwrap_or(Value::Null);
+                let kind = body
+                    .get("__type")
+                    .and_then(Value::as_str)
+                    .map(|kind| kind.rsplit('#').next().unwrap_or(kind).to_string())
+                    .unwrap_or_default();
+                if status >= 500 || kind == "ThrottlingException" {
+                    Err(CallError::Retryable(format!("{} {}", status, kind)))
+                } else {
+                    Err(CallError::Api(kind, body))
+                }
+            }
+            Err(e) => Err(CallError::Retryable(e.to_string())),
+        }
+    }
+}
+
+/// Sign a Logs JSON API request with AWS Signature Version 4
+///
+/// Returns the headers to add to the request besides `Content-Type`.
+fn sign(
+    credentials: &Credentials,
+    region: &str,
+    host: &str,
+    target: &str,
+    body: &str,
+    now: SystemTime,
+) -> Vec<(String, String)> {
+    let request = aws::Request {
+        method: "POST",
+        path: "/",
+        service: "logs",
+        region,
+        headers: vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host.to_string()),
+            ("x-amz-target", target.to_string()),
+        ],
+        payload_hash: aws::sha256_hex(body.as_bytes()),
+    };
+    let mut headers = aws::sign(credentials, request, now);
+    headers.push(("X-Amz-Target".to_string(), target.to_string()));
+    headers
+}
+
+fn timestamp_ms(timestamp: SystemTime) -> u64 {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Cut `line` to at most `max` bytes on a character boundary
+fn truncate(line: &str, max: usize) -> &str {
+    if line.len() <= max {
+        return line;
+    }
+    let mut end = max;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigv4_signature() {
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let headers = sign(
+            &credentials,
+            "us-east-1",
+            "logs.us-east-1.amazonaws.com",
+            "Logs_20140328.PutLogEvents",
+            "{}",
+            UNIX_EPOCH + Duration::from_secs(1_440_938_160),
+        );
+        let authorization = &headers
+            .iter()
+            .find(|(name, _)| name == "Authorization")
+            .unwrap()
+            .1;
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/logs/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date;x-amz-target, \
+             Signature=d53271f0aa89869c922e1c29c5bafd7769951a9bbc52b1ae8dc712b05258d319"
+        );
+    }
+
+    #[test]
+    fn test_truncate_on_char_boundary() {
+        assert_eq!(truncate("héllo", 2), "h");
+        assert_eq!(truncate("hello", 10), "hello");
+    }
+}
@@ -0,0 +1,408 @@
+//! Declarative configuration files
+//!
+//! Setting `LOG_CONFIG_FILE=logger.toml` (or a `.yaml` file with the `yaml`
+//! feature) keeps the filter, outputs, redaction, static fields and sinks in
+//! one reviewed file instead of a long list of environment variables:
+//!
+//! ```toml
+//! filter = "info,hyper=warn"
+//! format = "json"
+//!
+//! [file]
+//! dir = "/var/log/payments"
+//! prefix = "payments"
+//! channels = ["technical"]
+//!
+//! [redaction]
+//! fields = ["password", "ssn"]
+//! patterns = ["credit_card"]
+//!
+//! [static_fields]
+//! service = "payments"
+//! env = "prod"
+//!
+//! [sinks]
+//! net_addr = "tcp://collector:5000"
+//!
+//! # Any other option, by its variable name
+//! [env]
+//! LOG_SEQ = "true"
+//! ```
+//!
+//! Each setting stands for the environment variable documented on
+//! [`init`](crate::init). Variables that are set in the environment win over
+//! the file, so a deployment can still override single options, and builder
+//! options win over both. The file is only read; the process environment is
+//! never modified.
+//!
+//! `[static_fields]` is a table, so its values may be numbers, booleans or
+//! nested tables. There is no rotation key: the log file always rotates
+//! daily. Options without a key of their own, such as `LOG_AUDIT_ROTATION`,
+//! go under `[env]`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Contents of a configuration file
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::config_file::LoggerConfig;
+///
+/// let config = LoggerConfig::from_toml_str("filter = \"debug\"\n[file]\ndir = \"./logs\"").unwrap();
+/// assert_eq!(config.filter.as_deref(), Some("debug"));
+/// assert_eq!(config.vars().unwrap()["LOG_FILE_DIR"], "./logs");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggerConfig {
+    /// Filter directives (`RUST_LOG`)
+    pub filter: Option<String>,
+    /// Record layout of every output (`LOG_FORMAT`)
+    pub format: Option<String>,
+    /// Console output
+    pub console: OutputConfig,
+    /// Log file output
+    pub file: FileConfig,
+    /// Redaction of sensitive values
+    pub redaction: RedactionConfig,
+    /// Constant fields added to every record (`LOG_STATIC_FIELDS`)
+    pub static_fields: BTreeMap<String, Value>,
+    /// Additional destinations
+    pub sinks: SinksConfig,
+    /// Any other option, keyed by its environment variable name
+    pub env: BTreeMap<String, String>,
+}
+
+/// Settings of the console output
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Record layout overriding the global one (`LOG_CONSOLE_FORMAT`)
+    pub format: Option<String>,
+    /// Channels written to the console (`LOG_CONSOLE_CHANNELS`)
+    pub channels: Option<Vec<String>>,
+}
+
+/// Settings of the log file output
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    /// Directory of the daily files (`LOG_FILE_DIR`)
+    pub dir: Option<String>,
+    /// File name prefix (`LOG_FILE_PREFIX`)
+    pub prefix: Option<String>,
+    /// Disable console output (`LOG_FILE_ONLY`)
+    pub only: Option<bool>,
+    /// Record layout overriding the global one (`LOG_FILE_FORMAT`)
+    pub format: Option<String>,
+    /// Channels written to the file (`LOG_FILE_CHANNELS`)
+    pub channels: Option<Vec<String>>,
+}
+
+/// Settings of value redaction
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactionConfig {
+    /// Field names whose values are redacted (`LOG_REDACT_FIELDS`)
+    pub fields: Vec<String>,
+    /// Built-in value patterns to redact (`LOG_REDACT_PATTERNS`)
+    pub patterns: Vec<String>,
+    /// Key for HMAC digests of redacted values (`LOG_REDACTION_KEY`)
+    pub key: Option<String>,
+}
+
+/// Destinations besides the console and file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinksConfig {
+    /// Local Datadog agent (`LOG_DATADOG_AGENT_ADDR`)
+    pub datadog_agent_addr: Option<String>,
+    /// NDJSON collector, "tcp://..." or "udp://..." (`LOG_NET_ADDR`)
+    pub net_addr: Option<String>,
+    /// Unix domain socket (`LOG_SOCKET_PATH`)
+    pub socket_path: Option<String>,
+    /// Grafana Loki push URL (`LOG_LOKI_URL`)
+    pub loki_url: Option<String>,
+    /// AWS CloudWatch Logs group (`LOG_CLOUDWATCH_GROUP`)
+    pub cloudwatch_group: Option<String>,
+}
+
+impl LoggerConfig {
+    /// Read a configuration file, picking the format from its extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml_str(&text),
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => Self::from_yaml_str(&text),
+            #[cfg(not(feature = "yaml"))]
+            Some("yaml" | "yml") => Err("YAML config files require the `yaml` feature".to_string()),
+            _ => Err(format!("{} is not a .toml or .yaml file", path.display())),
+        }
+    }
+
+    /// Parse a TOML configuration
+    pub fn from_toml_str(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Parse a YAML configuration
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(text: &str) -> Result<Self, String> {
+        serde_yaml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// The environment variables this configuration stands for
+    ///
+    /// `[static_fields]` is not among them; it is kept as a map so values
+    /// may be nested.
+    pub fn vars(&self) -> Result<BTreeMap<String, String>, String> {
+        let mut vars = BTreeMap::new();
+        let mut set = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                vars.insert(name.to_string(), value);
+            }
+        };
+        let list = |items: &[String]| (!items.is_empty()).then(|| items.join(","));
+
+        set("RUST_LOG", self.filter.clone());
+        set("LOG_FORMAT", self.format.clone());
+        set("LOG_CONSOLE_FORMAT", self.console.format.clone());
+        set(
+            "LOG_CONSOLE_CHANNELS",
+            self.console.channels.as_ref().map(|c| c.join(",")),
+        );
+        set("LOG_FILE_DIR", self.file.dir.clone());
+        set("LOG_FILE_PREFIX", self.file.prefix.clone());
+        set("LOG_FILE_ONLY", self.file.only.map(|only| only.to_string()));
+        set("LOG_FILE_FORMAT", self.file.format.clone());
+        set(
+            "LOG_FILE_CHANNELS",
+            self.file.channels.as_ref().map(|c| c.join(",")),
+        );
+        set("LOG_REDACT_FIELDS", list(&self.redaction.fields));
+        set("LOG_REDACT_PATTERNS", list(&self.redaction.patterns));
+        set("LOG_REDACTION_KEY", self.redaction.key.clone());
+        set(
+            "LOG_DATADOG_AGENT_ADDR",
+            self.sinks.datadog_agent_addr.clone(),
+        );
+        set("LOG_NET_ADDR", self.sinks.net_addr.clone());
+        set("LOG_SOCKET_PATH", self.sinks.socket_path.clone());
+        set("LOG_LOKI_URL", self.sinks.loki_url.clone());
+        set("LOG_CLOUDWATCH_GROUP", self.sinks.cloudwatch_group.clone());
+
+        for (name, value) in &self.env {
+            if !name.starts_with("LOG_") || name == "LOG_CONFIG_FILE" {
+                return Err(format!("'{}' is not a logger option", name));
+            }
+            let static_fields = name == "LOG_STATIC_FIELDS" && !self.static_fields.is_empty();
+            if vars.contains_key(name) || static_fields {
+                return Err(format!("{} is also set outside [env]", name));
+            }
+            vars.insert(name.clone(), value.clone());
+        }
+        Ok(vars)
+    }
+}
+
+/// Options read from `LOG_CONFIG_FILE`
+#[derive(Debug, Default)]
+struct Loaded {
+    vars: BTreeMap<String, String>,
+    static_fields: Map<String, Value>,
+}
+
+/// The configuration file last read by [`load`]
+static LOADED: Mutex<Option<Loaded>> = Mutex::new(None);
+
+thread_local! {
+    /// The configuration file read by [`preview`], shadowing [`LOADED`]
+    static PREVIEW: RefCell<Option<Loaded>> = const { RefCell::new(None) };
+}
+
+/// Parse the configuration file at `path`
+fn read(path: &str) -> Result<Loaded, String> {
+    let config = LoggerConfig::from_file(path)?;
+    Ok(Loaded {
+        vars: config.vars()?,
+        static_fields: config.static_fields.into_iter().collect(),
+    })
+}
+
+/// Read `LOG_CONFIG_FILE`, if set, for [`var`] to fall back on
+///
+/// The process environment is never modified. Returns the file that was
+/// read.
+pub(crate) fn load() -> Result<Option<String>, String> {
+    let mut loaded = LOADED.lock().unwrap_or_else(|e| e.into_inner());
+    *loaded = None;
+    let Ok(path) = std::env::var("LOG_CONFIG_FILE") else {
+        return Ok(None);
+    };
+    *loaded = Some(read(&path)?);
+    Ok(Some(path))
+}
+
+/// Run `f` on this thread as if `LOG_CONFIG_FILE` had been loaded
+///
+/// The configuration the logger runs with is left alone. `f` is given the
+/// file that was read.
+pub(crate) fn preview<R>(f: impl FnOnce(Option<String>) -> R) -> Result<R, String> {
+    preview_file(std::env::var("LOG_CONFIG_FILE").ok(), f)
+}
+
+/// Run `f` on this thread as if the file at `path` had been loaded
+fn preview_file<R>(path: Option<String>, f: impl FnOnce(Option<String>) -> R) -> Result<R, String> {
+    let config = path.as_deref().map(read).transpose()?;
+    PREVIEW.with(|preview| *preview.borrow_mut() = Some(config.unwrap_or_default()));
+    let _guard = PreviewGuard;
+    Ok(f(path))
+}
+
+/// Ends a [`preview`] when dropped, even if the previewed function panicked
+struct PreviewGuard;
+
+impl Drop for PreviewGuard {
+    fn drop(&mut self) {
+        PREVIEW.with(|preview| preview.borrow_mut().take());
+    }
+}
+
+/// Run `f` on the configuration file in effect on this thread
+fn with_loaded<R>(f: impl FnOnce(Option<&Loaded>) -> R) -> R {
+    PREVIEW.with(|preview| match preview.borrow().as_ref() {
+        Some(loaded) => f(Some(loaded)),
+        None => f(LOADED.lock().unwrap_or_else(|e| e.into_inner()).as_ref()),
+    })
+}
+
+/// Value of option `name`: the environment variable, else the file's value
+pub(crate) fn var(name: &str) -> Result<String, std::env::VarError> {
+    match std::env::var(name) {
+        Err(std::env::VarError::NotPresent) => with_loaded(|loaded| {
+            loaded
+                .and_then(|loaded| loaded.vars.get(name).cloned())
+                .ok_or(std::env::VarError::NotPresent)
+        }),
+        result => result,
+    }
+}
+
+/// Value the configuration file gives option `name`, if any
+///
+/// `[static_fields]` reads as `LOG_STATIC_FIELDS`, in JSON.
+pub(crate) fn file_value(name: &str) -> Option<String> {
+    with_loaded(|loaded| {
+        let loaded = loaded?;
+        match loaded.vars.get(name) {
+            Some(value) => Some(value.clone()),
+            None if name == "LOG_STATIC_FIELDS" && !loaded.static_fields.is_empty() => {
+                Some(Value::Object(loaded.static_fields.clone()).to_string())
+            }
+            None => None,
+        }
+    })
+}
+
+/// The `[static_fields]` of the configuration file, if it has any
+pub(crate) fn static_fields() -> Option<Map<String, Value>> {
+    with_loaded(|loaded| {
+        loaded
+            .map(|loaded| loaded.static_fields.clone())
+            .filter(|fields| !fields.is_empty())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_config_maps_to_env_vars() {
+        let config = LoggerConfig::from_toml_str(
+            r#"
+filter = "info,hyper=warn"
+
+[file]
+dir = "./logs"
+only = true
+channels = ["business", "audit"]
+
+[redaction]
+fields = ["password", "ssn"]
+
+[static_fields]
+service = "payments"
+build = 42
+region = { name = "eu-west-1", zone = "b" }
+
+[env]
+LOG_SEQ = "true"
+"#,
+        )
+        .unwrap();
+        let vars = config.vars().unwrap();
+        let expected: BTreeMap<String, String> = [
+            ("LOG_FILE_CHANNELS", "business,audit"),
+            ("LOG_FILE_DIR", "./logs"),
+            ("LOG_FILE_ONLY", "true"),
+            ("LOG_REDACT_FIELDS", "password,ssn"),
+            ("LOG_SEQ", "true"),
+            ("RUST_LOG", "info,hyper=warn"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        assert_eq!(vars, expected);
+        assert_eq!(config.static_fields["build"], 42);
+        assert_eq!(config.static_fields["region"]["zone"], "b");
+    }
+
+    #[test]
+    fn test_rejects_unknown_keys_and_options() {
+        assert!(LoggerConfig::from_toml_str("[file]\ndirectory = \"./logs\"").is_err());
+        let config = LoggerConfig::from_toml_str("[env]\nPATH = \"/bin\"").unwrap();
+        assert!(config.vars().is_err());
+        let config =
+            LoggerConfig::from_toml_str("filter = \"info\"\n[env]\nLOG_FORMAT = \"ecs\"").unwrap();
+        assert!(config.vars().is_ok());
+        let config = LoggerConfig::from_toml_str(
+            "[static_fields]\nservice = \"a\"\n[env]\nLOG_STATIC_FIELDS = \"service=b\"",
+        )
+        .unwrap();
+        assert!(config.vars().is_err());
+    }
+
+    #[test]
+    fn test_preview_leaves_environment_alone() {
+        let path = std::env::temp_dir().join(format!("preview-{}.toml", std::process::id()));
+        std::fs::write(&path, "[env]\nLOG_PREVIEW_TEST = \"1\"\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let value = preview_file(Some(path.clone()), |file| {
+            assert_eq!(file.as_deref(), Some(path.as_str()));
+            var("LOG_PREVIEW_TEST")
+        });
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(value.unwrap().as_deref(), Ok("1"));
+        assert!(std::env::var_os("LOG_PREVIEW_TEST").is_none());
+        assert!(PREVIEW.with(|preview| preview.borrow().is_none()));
+    }
+
+    #[test]
+    fn test_preview_ends_when_the_check_panics() {
+        let panicked = std::panic::catch_unwind(|| preview_file(None, |_| panic!("check failed")));
+        assert!(panicked.is_err());
+        assert!(PREVIEW.with(|preview| preview.borrow().is_none()));
+    }
+}
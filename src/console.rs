@@ -0,0 +1,80 @@
+//! Severity split of console output
+//!
+//! Container platforms and shells treat stderr as the error stream, so
+//! records that all go to stdout are classified as informational. With
+//! `LOG_STDERR_LEVEL=warn` console records at WARN and ERROR go to stderr
+//! and less severe ones to stdout. Only the standard console is split: a
+//! writer from [`Builder::with_writer`](crate::Builder::with_writer) and
+//! ordered outputs still receive every record.
+
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Read `LOG_STDERR_LEVEL`, the least severe level written to stderr; `None` when unset or "off"
+pub(crate) fn stderr_level_from_env() -> Result<Option<Level>, String> {
+    match crate::config_file::var("LOG_STDERR_LEVEL") {
+        Ok(raw) if !raw.trim().is_empty() => raw
+            .trim()
+            .parse::<LevelFilter>()
+            .map(LevelFilter::into_level)
+            .map_err(|_| {
+                format!(
+                    "'{}', expected error, warn, info, debug, trace or off",
+                    raw.trim()
+                )
+            }),
+        _ => Ok(None),
+    }
+}
+
+/// Writer sending records at `stderr_level` or more severe to `stderr`, the rest to `stdout`
+pub(crate) fn split<O, E>(
+    stderr_level: Level,
+    stdout: O,
+    stderr: E,
+) -> impl for<'writer> MakeWriter<'writer> + Send + Sync + 'static
+where
+    O: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+    E: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    stderr.with_max_level(stderr_level).or_else(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use crate::format::EventFormat;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_severe_records_go_to_stderr() {
+        let stdout = Capture::default();
+        let stderr = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .event_format(EventFormat::new(false))
+                .with_writer(split(Level::WARN, stdout.clone(), stderr.clone())),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("cache miss");
+            tracing::info!("request served");
+            tracing::warn!("retrying");
+            tracing::error!("payment failed");
+        });
+
+        let levels = |capture: &Capture| -> Vec<String> {
+            capture
+                .records()
+                .iter()
+                .map(|record| record["level"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(levels(&stdout), ["DEBUG", "INFO"]);
+        assert_eq!(levels(&stderr), ["WARN", "ERROR"]);
+    }
+}
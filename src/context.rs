@@ -0,0 +1,340 @@
+//! Scoped context fields
+//!
+//! Fields set with [`scope`] or [`scope_sync`] are added to the `fields` of
+//! every record emitted inside the scope, flattened next to the event's own
+//! fields, including across `.await` points. Unlike span fields they need
+//! no declaration up front and show up on each record, which is what log
+//! pipelines indexing `fields.tenant_id` need.
+//!
+//! The context is tied to the future or closure, not to the thread: tasks
+//! spawned inside a scope start without it, so hand it over with
+//! [`current`] and [`LogContext::scope`].
+//!
+//! A scope can also log at DEBUG whatever `RUST_LOG` says, for investigating
+//! one request without raising verbosity for all of them: run it with
+//! [`debug`], or call [`enable_debug`] inside it, e.g. after checking who
+//! the caller is. The request middleware does this for requests carrying
+//! the `LOG_REQUEST_DEBUG_TOKEN` header. Either needs request debugging
+//! enabled (`LOG_REQUEST_DEBUG=true` or
+//! [`Builder::with_request_debug`](crate::Builder::with_request_debug)), as
+//! every disabled DEBUG callsite is then checked at runtime.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use serde_json::{Map, Value};
+
+thread_local! {
+    /// Scopes active on this thread, innermost last
+    static STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An active scope
+struct Frame {
+    /// Fields of the scope merged with those of the enclosing scopes
+    fields: Arc<Map<String, Value>>,
+    /// Debug flag of the scope, shared with its [`LogContext`]
+    debug: Arc<AtomicBool>,
+}
+
+/// Set of context fields
+#[derive(Debug, Clone, Default)]
+pub struct LogContext {
+    fields: Arc<Map<String, Value>>,
+    debug: Arc<AtomicBool>,
+}
+
+impl LogContext {
+    /// Create a context from field name and value pairs
+    pub fn new<K, V>(fields: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        let fields = fields
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        Self {
+            fields: Arc::new(fields),
+            debug: Arc::default(),
+        }
+    }
+
+    /// Log records at DEBUG and above inside these scopes, whatever `RUST_LOG` says
+    pub fn with_debug(mut self) -> Self {
+        self.debug = Arc::new(AtomicBool::new(true));
+        self
+    }
+
+    /// Value of a context field
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    /// Run `future` with these fields added to every record it emits
+    pub fn scope<F: Future>(self, future: F) -> Scoped<F> {
+        Scoped {
+            context: self,
+            inner: future,
+        }
+    }
+
+    /// Run `f` with these fields added to every record it emits
+    pub fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _entered = self.enter();
+        f()
+    }
+
+    /// Make these fields, on top of the active ones, current on this thread until dropped
+    fn enter(&self) -> Entered {
+        STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let merged = match stack.last() {
+                Some(outer) => {
+                    let mut merged = Map::clone(&outer.fields);
+                    merged.extend(Map::clone(&self.fields));
+                    Arc::new(merged)
+                }
+                None => Arc::clone(&self.fields),
+            };
+            stack.push(Frame {
+                fields: merged,
+                debug: Arc::clone(&self.debug),
+            });
+        });
+        Entered(())
+    }
+}
+
+/// Pops the innermost scope when dropped
+struct Entered(());
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        STACK.with(|stack| stack.borrow_mut().pop());
+    }
+}
+
+/// Future running with context fields, see [`scope`]
+#[derive(Debug)]
+pub struct Scoped<F> {
+    context: LogContext,
+    inner: F,
+}
+
+impl<F: Future> Future for Scoped<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `inner` is never moved out of `self`, so pinning it is sound
+        let this = unsafe { self.get_unchecked_mut() };
+        let _entered = this.context.enter();
+        unsafe { Pin::new_unchecked(&mut this.inner) }.poll(cx)
+    }
+}
+
+/// Run `future` with `fields` added to every record it emits, across `.await` points
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::context;
+///
+/// # async fn handle() {}
+/// # async fn run() {
+/// context::scope([("tenant_id", "acme"), ("plan", "gold")], async {
+///     tracing::info!("Handling order"); // "fields":{"message":"Handling order","tenant_id":"acme","plan":"gold"}
+///     handle().await;
+/// })
+/// .await;
+/// # }
+/// ```
+pub fn scope<K, V, F>(fields: impl IntoIterator<Item = (K, V)>, future: F) -> Scoped<F>
+where
+    K: Into<String>,
+    V: Into<Value>,
+    F: Future,
+{
+    LogContext::new(fields).scope(future)
+}
+
+/// Run `f` with `fields` added to every record it emits
+pub fn scope_sync<K, V, R>(fields: impl IntoIterator<Item = (K, V)>, f: impl FnOnce() -> R) -> R
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    LogContext::new(fields).in_scope(f)
+}
+
+/// Run `future` logging at DEBUG and above, across `.await` points
+///
+/// Needs request debugging enabled, see the [module docs](self).
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::context;
+///
+/// # async fn run() {
+/// context::debug(async {
+///     tracing::debug!("Cache miss"); // logged even at RUST_LOG=info
+/// })
+/// .await;
+/// # }
+/// ```
+pub fn debug<F: Future>(future: F) -> Scoped<F> {
+    LogContext::default().with_debug().scope(future)
+}
+
+/// Log at DEBUG and above for the rest of the innermost active scope
+///
+/// The scope is usually a request, so its remaining records, and those of
+/// tasks handed its [`current`] context, are logged in full. Returns
+/// `false` when no scope is active. Needs request debugging enabled, see
+/// the [module docs](self).
+pub fn enable_debug() -> bool {
+    STACK.with(|stack| match stack.borrow().last() {
+        Some(frame) => {
+            frame.debug.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Fields active on this thread, e.g. to hand over to a spawned task
+pub fn current() -> LogContext {
+    STACK.with(|stack| {
+        let stack = stack.borrow();
+        LogContext {
+            fields: stack
+                .last()
+                .map(|frame| Arc::clone(&frame.fields))
+                .unwrap_or_default(),
+            debug: Arc::new(AtomicBool::new(is_debug_in(&stack))),
+        }
+    })
+}
+
+/// Whether a scope active on this thread logs at DEBUG
+pub(crate) fn is_debug() -> bool {
+    STACK.with(|stack| is_debug_in(&stack.borrow()))
+}
+
+fn is_debug_in(stack: &[Frame]) -> bool {
+    stack
+        .iter()
+        .any(|frame| frame.debug.load(Ordering::Relaxed))
+}
+
+/// Read `LOG_REQUEST_DEBUG`, which defaults to whether a debug header token is configured
+pub(crate) fn request_debug_from_env() -> bool {
+    match crate::config_file::var("LOG_REQUEST_DEBUG") {
+        Ok(enabled) => enabled == "true",
+        Err(_) => crate::http::DebugHeader::from_env().is_some(),
+    }
+}
+
+/// Whether any scope is active on this thread
+pub(crate) fn is_active() -> bool {
+    STACK.with(|stack| !stack.borrow().is_empty())
+}
+
+/// Add the active fields to a formatted record, never replacing the event's own fields
+pub(crate) fn apply(record: &mut Map<String, Value>) {
+    STACK.with(|stack| {
+        let stack = stack.borrow();
+        let Some(frame) = stack.last() else {
+            return;
+        };
+        if let Some(Value::Object(fields)) = record.get_mut("fields") {
+            for (key, value) in frame.fields.iter() {
+                if !fields.contains_key(key) {
+                    fields.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::per_test_subscriber;
+    use std::pin::pin;
+    use std::task::{Wake, Waker};
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Future pending on its first poll, like an await on I/O
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if std::mem::replace(&mut self.0, true) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            // Nothing may leak out of the scope between polls
+            assert!(!is_active());
+        }
+    }
+
+    #[test]
+    fn test_nested_sync_scopes() {
+        let logs = per_test_subscriber();
+        scope_sync([("tenant_id", "acme")], || {
+            scope_sync([("request_id", "r1")], || {
+                tracing::info!(request_id = "own", "inner");
+                assert_eq!(current().get("tenant_id"), Some(&"acme".into()));
+            });
+            tracing::info!("outer");
+        });
+        tracing::info!("outside");
+
+        let records = logs.records();
+        assert_eq!(records[0]["fields"]["tenant_id"], "acme");
+        assert_eq!(records[0]["fields"]["request_id"], "own");
+        assert_eq!(records[1]["fields"]["tenant_id"], "acme");
+        assert!(records[1]["fields"].get("request_id").is_none());
+        assert!(records[2]["fields"].get("tenant_id").is_none());
+    }
+
+    #[test]
+    fn test_async_scope_survives_await() {
+        let logs = per_test_subscriber();
+        block_on(scope([("tenant_id", "acme"), ("attempt", "1")], async {
+            tracing::info!("before");
+            YieldOnce(false).await;
+            tracing::info!("after");
+        }));
+
+        let records = logs.records();
+        assert_eq!(records.len(), 2);
+        assert!(records
+            .iter()
+            .all(|record| record["fields"]["tenant_id"] == "acme"));
+    }
+}
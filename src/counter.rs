@@ -0,0 +1,138 @@
+//! Pre-aggregated counting events
+//!
+//! [`count!`](crate::count) increments an in-process counter instead of
+//! emitting a record per occurrence. A background thread periodically emits
+//! one aggregate record per counter and key combination, so ultra-high
+//! frequency events (cache misses, retries) stay visible without flooding
+//! the pipeline.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default time between aggregate records
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Counter name with its key fields, in call order
+type CounterKey = (&'static str, Vec<(&'static str, String)>);
+
+struct Counters {
+    counts: HashMap<CounterKey, u64>,
+    since: Instant,
+}
+
+static COUNTERS: OnceLock<Mutex<Counters>> = OnceLock::new();
+
+fn counters() -> MutexGuard<'static, Counters> {
+    let counters = COUNTERS.get_or_init(|| {
+        spawn_flusher();
+        Mutex::new(Counters {
+            counts: HashMap::new(),
+            since: Instant::now(),
+        })
+    });
+    counters.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Flush interval from `LOG_COUNT_INTERVAL_MS` (default: 10000)
+fn interval() -> Duration {
+    crate::config_file::var("LOG_COUNT_INTERVAL_MS")
+        .ok()
+        .and_then(|ms| ms.trim().parse().ok())
+        .filter(|&ms| ms > 0)
+        .map_or(DEFAULT_INTERVAL, Duration::from_millis)
+}
+
+/// Drop pending counts without emitting them
+pub(crate) fn reset() {
+    if let Some(counters) = COUNTERS.get() {
+        let mut counters = counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.counts.clear();
+        counters.since = Instant::now();
+    }
+}
+
+fn spawn_flusher() {
+    let interval = interval();
+    let spawned = thread::Builder::new()
+        .name("log-counters".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            flush();
+        });
+    if let Err(e) = spawned {
+        eprintln!("Failed to spawn counter flush thread: {}", e);
+    }
+}
+
+/// Add `by` to the counter `name` for the given key fields
+///
+/// Called by [`count!`](crate::count); prefer the macro.
+pub fn increment(name: &'static str, by: u64, keys: &[(&'static str, &dyn Display)]) {
+    let keys = keys
+        .iter()
+        .map(|(key, value)| (*key, value.to_string()))
+        .collect();
+    *counters().counts.entry((name, keys)).or_insert(0) += by;
+}
+
+/// Take the accumulated counts and the time they were collected over
+fn drain() -> (Vec<(CounterKey, u64)>, Duration) {
+    let mut counters = counters();
+    let elapsed = counters.since.elapsed();
+    counters.since = Instant::now();
+    let counts = counters.counts.drain().collect();
+    (counts, elapsed)
+}
+
+/// Emit one aggregate record per counter now and reset the counts
+///
+/// Runs periodically in the background; call it before shutdown so the
+/// last interval is not lost.
+pub fn flush() {
+    let (counts, elapsed) = drain();
+    let interval_ms = elapsed.as_millis() as u64;
+    for ((name, keys), count) in counts {
+        let keys = keys
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        tracing::info!(
+            target: "count",
+            counter = name,
+            count,
+            interval_ms,
+            keys = keys.as_str(),
+            "Counter aggregate"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_are_aggregated_per_key() {
+        for _ in 0..3 {
+            crate::count!("test_cache_miss", region = "eu");
+        }
+        crate::count!("test_cache_miss", by = 5, region = "us");
+        crate::count!("test_cache_miss", by = 2, region = "eu");
+
+        let (counts, _) = drain();
+        let count = |region: &str| {
+            counts
+                .iter()
+                .find(|((name, keys), _)| {
+                    *name == "test_cache_miss" && keys == &[("region", region.to_string())]
+                })
+                .map(|(_, count)| *count)
+        };
+        assert_eq!(count("eu"), Some(5));
+        assert_eq!(count("us"), Some(5));
+    }
+}
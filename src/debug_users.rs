@@ -0,0 +1,313 @@
+//! Full logging for selected users
+//!
+//! Spans whose designated field (default `user_id`) matches an allowlist
+//! (`LOG_DEBUG_USERS=42,1337`) keep every record inside them, at all
+//! levels, while `RUST_LOG` still applies everywhere else. Support can turn
+//! on deep logging for a single complaining customer without raising global
+//! verbosity.
+//!
+//! The same filter keeps DEBUG records inside
+//! [`context`](crate::context) scopes with debug enabled, when request
+//! debugging is on.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Filter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Span field identifying the user by default
+const DEFAULT_FIELD: &str = "user_id";
+
+/// Users whose spans are logged at every level
+#[derive(Debug, Clone)]
+pub(crate) struct DebugUsers {
+    field: String,
+    values: HashSet<String>,
+}
+
+impl DebugUsers {
+    pub(crate) fn new(
+        field: impl Into<String>,
+        values: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Read the users from the environment, if any are configured
+    ///
+    /// - `LOG_DEBUG_USERS`: Field values to match (e.g. "42,1337")
+    /// - `LOG_DEBUG_USER_FIELD`: Span field to match on (default: "user_id")
+    pub(crate) fn from_env() -> Option<Self> {
+        let values = crate::config_file::var("LOG_DEBUG_USERS").ok()?;
+        let field = crate::config_file::var("LOG_DEBUG_USER_FIELD")
+            .map(|field| field.trim().to_string())
+            .unwrap_or_else(|_| DEFAULT_FIELD.to_string());
+        let users = Self::new(
+            field,
+            values
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty()),
+        );
+        (!users.values.is_empty()).then_some(users)
+    }
+
+    /// Short description for configuration reports, e.g. "user_id in [42, 1337]"
+    pub(crate) fn describe(&self) -> String {
+        let mut values: Vec<_> = self.values.iter().map(String::as_str).collect();
+        values.sort_unstable();
+        format!("{} in [{}]", self.field, values.join(", "))
+    }
+}
+
+/// Marks a span opened for a debug user
+struct DebugScope;
+
+/// `RUST_LOG` output filter that lets everything through inside debug-user spans
+pub(crate) struct DebugUserFilter {
+    inner: EnvFilter,
+    users: Option<Arc<DebugUsers>>,
+    request_debug: bool,
+}
+
+impl DebugUserFilter {
+    pub(crate) fn new(inner: EnvFilter, users: Option<DebugUsers>) -> Self {
+        Self {
+            inner,
+            users: users.map(Arc::new),
+            request_debug: false,
+        }
+    }
+
+    /// Also let DEBUG records through inside context scopes with debug enabled
+    pub(crate) fn with_request_debug(mut self, enabled: bool) -> Self {
+        self.request_debug = enabled;
+        self
+    }
+
+    /// Whether `metadata` may be enabled by a debug scope, so must be checked at runtime
+    fn is_dynamic(&self, metadata: &Metadata<'_>) -> bool {
+        self.users.is_some() || (self.request_debug && *metadata.level() <= Level::DEBUG)
+    }
+
+    pub(crate) fn env_filter(&self) -> &EnvFilter {
+        &self.inner
+    }
+
+    /// Replace the `RUST_LOG` filter, keeping the debug users
+    pub(crate) fn set_env_filter(&mut self, filter: EnvFilter) {
+        self.inner = filter;
+    }
+
+    fn in_debug_scope<S>(ctx: &Context<'_, S>) -> bool
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        ctx.lookup_current().is_some_and(|span| {
+            span.scope()
+                .any(|span| span.extensions().get::<DebugScope>().is_some())
+        })
+    }
+
+    /// Mark `id` when its recorded values identify a debug user
+    fn mark<S>(&self, id: &Id, ctx: &Context<'_, S>, record: impl FnOnce(&mut dyn Visit))
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let Some(users) = &self.users else {
+            return;
+        };
+        let mut matcher = Matcher {
+            users,
+            matched: false,
+        };
+        record(&mut matcher);
+        if matcher.matched {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().replace(DebugScope);
+            }
+        }
+    }
+}
+
+impl<S> Filter<S> for DebugUserFilter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+        Filter::<S>::enabled(&self.inner, metadata, ctx)
+            || (self.request_debug
+                && *metadata.level() <= Level::DEBUG
+                && crate::context::is_debug())
+            || (self.users.is_some() && Self::in_debug_scope(ctx))
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let interest = Filter::<S>::callsite_enabled(&self.inner, metadata);
+        if self.is_dynamic(metadata) && !interest.is_always() {
+            // Disabled callsites must be re-checked against the current span
+            Interest::sometimes()
+        } else {
+            interest
+        }
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        let hint = Filter::<S>::max_level_hint(&self.inner);
+        if self.users.is_some() {
+            Some(LevelFilter::TRACE)
+        } else if self.request_debug {
+            hint.map(|hint| hint.max(LevelFilter::DEBUG))
+        } else {
+            hint
+        }
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.mark(id, &ctx, |visitor| attrs.record(visitor));
+        Filter::<S>::on_new_span(&self.inner, attrs, id, ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.mark(id, &ctx, |visitor| values.record(visitor));
+        Filter::<S>::on_record(&self.inner, id, values, ctx);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        Filter::<S>::on_enter(&self.inner, id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        Filter::<S>::on_exit(&self.inner, id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        Filter::<S>::on_close(&self.inner, id, ctx);
+    }
+}
+
+/// Checks recorded values against the debug users
+struct Matcher<'a> {
+    users: &'a DebugUsers,
+    matched: bool,
+}
+
+impl Matcher<'_> {
+    fn check(&mut self, field: &Field, value: &str) {
+        if field.name() == self.users.field && self.users.values.contains(value) {
+            self.matched = true;
+        }
+    }
+}
+
+impl Visit for Matcher<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.check(field, &value.to_string());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.check(field, &value.to_string());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.check(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let value = crate::json_field::debug_text(value);
+        self.check(field, value.trim_matches('"'));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use tracing_subscriber::layer::{Layer, SubscriberExt};
+
+    #[test]
+    fn test_debug_user_spans_bypass_level_filter() {
+        let capture = Capture::default();
+        let users = DebugUsers::new("user_id", ["42"]);
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(capture.clone())
+                .with_filter(DebugUserFilter::new(EnvFilter::new("info"), Some(users))),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", user_id = 42).in_scope(|| {
+                tracing::debug!("debug for 42");
+                tracing::trace_span!("db").in_scope(|| tracing::trace!("trace for 42"));
+            });
+            tracing::info_span!("request", user_id = 7).in_scope(|| {
+                tracing::debug!("debug for 7");
+                tracing::info!("info for 7");
+            });
+            let late = tracing::info_span!("request", user_id = tracing::field::Empty);
+            late.record("user_id", "42");
+            late.in_scope(|| tracing::debug!("debug for late 42"));
+        });
+
+        let messages: Vec<_> = capture
+            .records()
+            .iter()
+            .map(|r| r["fields"]["message"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "debug for 42",
+                "trace for 42",
+                "info for 7",
+                "debug for late 42"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debug_scopes_log_debug_records() {
+        let capture = Capture::default();
+        let filter = DebugUserFilter::new(EnvFilter::new("info"), None).with_request_debug(true);
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(capture.clone())
+                .with_filter(filter),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("outside");
+            crate::context::scope_sync([("request_id", "r1")], || {
+                tracing::debug!("before enabling");
+                assert!(crate::context::enable_debug());
+                tracing::debug!("after enabling");
+                tracing::trace!("trace stays off");
+            });
+            crate::context::LogContext::default()
+                .with_debug()
+                .in_scope(|| tracing::debug!("debug scope"));
+            tracing::debug!("outside again");
+            assert!(!crate::context::enable_debug());
+        });
+
+        let messages: Vec<_> = capture
+            .records()
+            .iter()
+            .map(|r| r["fields"]["message"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(messages, vec!["after enabling", "debug scope"]);
+    }
+}
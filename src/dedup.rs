@@ -0,0 +1,255 @@
+//! Collapsing of repeated records
+//!
+//! Consecutive identical records (ignoring `timestamp`, `seq` and
+//! `uptime_ns`) written to one output are held back and replaced by a single
+//! summary record carrying `repeat_count`, the number of records it stands
+//! for. The summary is written when a different record arrives, or once the
+//! timeout has passed, so a tight loop logging the same error produces one
+//! record per timeout instead of thousands.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::format::EventFormat;
+
+/// Field holding the number of collapsed records
+pub(crate) const REPEAT_COUNT: &str = "repeat_count";
+
+/// Default time a summary may be held back
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fields that differ between otherwise identical records
+const VOLATILE: [&str; 4] = ["timestamp", "seq", "event_id", "uptime_ns"];
+
+/// Read `LOG_DEDUP` and `LOG_DEDUP_TIMEOUT_MS`, the timeout when enabled
+pub(crate) fn timeout_from_env() -> Option<Duration> {
+    if crate::config_file::var("LOG_DEDUP").unwrap_or_default() != "true" {
+        return None;
+    }
+    let timeout = crate::config_file::var("LOG_DEDUP_TIMEOUT_MS")
+        .ok()
+        .and_then(|ms| ms.trim().parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_TIMEOUT);
+    Some(timeout)
+}
+
+type Emit = Box<dyn Fn(Map<String, Value>) + Send + Sync>;
+
+/// Repeat state of one output
+pub(crate) struct Dedup {
+    timeout: Duration,
+    state: Mutex<State>,
+    emit: Emit,
+}
+
+#[derive(Default)]
+struct State {
+    key: Option<String>,
+    /// Latest held-back repeat of `key`
+    pending: Option<Map<String, Value>>,
+    repeats: u64,
+    since: Option<Instant>,
+}
+
+impl State {
+    /// Turn the held-back repeats into a summary record
+    fn take_summary(&mut self, now: Instant) -> Option<Map<String, Value>> {
+        let mut summary = self.pending.take()?;
+        summary.insert(REPEAT_COUNT.to_string(), self.repeats.into());
+        self.repeats = 0;
+        self.since = Some(now);
+        Some(summary)
+    }
+
+    fn expired(&self, timeout: Duration, now: Instant) -> bool {
+        self.since
+            .is_some_and(|since| now.saturating_duration_since(since) >= timeout)
+    }
+}
+
+impl Dedup {
+    fn new(timeout: Duration, emit: Emit) -> Self {
+        Self {
+            timeout,
+            state: Mutex::new(State::default()),
+            emit,
+        }
+    }
+
+    /// Dedup state writing summaries through `writer` as rendered by `format`
+    ///
+    /// A background thread writes summaries whose timeout has passed; it
+    /// exits once the returned state is dropped.
+    pub(crate) fn start<W>(timeout: Duration, format: &EventFormat, writer: Arc<W>) -> Arc<Self>
+    where
+        W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+    {
+        let format = format.clone();
+        let emit: Emit = Box::new(move |summary| {
+            if let Ok(line) = format.render(summary) {
+                let _ = writeln!(writer.make_writer(), "{}", line);
+            }
+        });
+        let dedup = Arc::new(Self::new(timeout, emit));
+
+        let weak = Arc::downgrade(&dedup);
+        let _ = thread::Builder::new()
+            .name("log-dedup".to_string())
+            .spawn(move || flush_loop(weak, timeout));
+        dedup
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record to write now, or `None` when it repeats the previous one
+    ///
+    /// A pending summary is written first whenever the record changes.
+    pub(crate) fn observe(&self, record: Map<String, Value>) -> Option<Map<String, Value>> {
+        let key = key_of(&record);
+        let now = Instant::now();
+        let mut state = self.lock();
+        let (summary, record) = if state.key.as_deref() == Some(key.as_str()) {
+            state.repeats += 1;
+            state.pending = Some(record);
+            let summary = state
+                .expired(self.timeout, now)
+                .then(|| state.take_summary(now))
+                .flatten();
+            (summary, None)
+        } else {
+            let summary = state.take_summary(now);
+            state.key = Some(key);
+            state.since = Some(now);
+            (summary, Some(record))
+        };
+        drop(state);
+
+        if let Some(summary) = summary {
+            (self.emit)(summary);
+        }
+        record
+    }
+
+    /// Write the pending summary if it has been held back for the timeout
+    fn flush_expired(&self) {
+        let now = Instant::now();
+        let summary = {
+            let mut state = self.lock();
+            if state.expired(self.timeout, now) {
+                state.take_summary(now)
+            } else {
+                None
+            }
+        };
+        if let Some(summary) = summary {
+            (self.emit)(summary);
+        }
+    }
+}
+
+fn flush_loop(dedup: Weak<Dedup>, timeout: Duration) {
+    let _entered = crate::reentrant::enter();
+    let tick = (timeout / 2).max(Duration::from_millis(10));
+    loop {
+        thread::sleep(tick);
+        match dedup.upgrade() {
+            Some(dedup) => dedup.flush_expired(),
+            None => return,
+        }
+    }
+}
+
+/// Identity of a record, everything but its volatile fields
+fn key_of(record: &Map<String, Value>) -> String {
+    let stable: Map<String, Value> = record
+        .iter()
+        .filter(|(key, _)| !VOLATILE.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    Value::Object(stable).to_string()
+}
+
+/// `MakeWriter` shared between an output layer and its dedup state
+pub(crate) struct SharedWriter<W>(pub(crate) Arc<W>);
+
+impl<'a, W> MakeWriter<'a> for SharedWriter<W>
+where
+    W: MakeWriter<'a>,
+{
+    type Writer = W::Writer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.0.make_writer()
+    }
+
+    fn make_writer_for(&'a self, meta: &tracing::Metadata<'_>) -> Self::Writer {
+        self.0.make_writer_for(meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    type Emitted = Arc<Mutex<Vec<Map<String, Value>>>>;
+
+    fn record(message: &str, timestamp: &str) -> Map<String, Value> {
+        let record =
+            json!({ "timestamp": timestamp, "level": "ERROR", "fields": { "message": message } });
+        record.as_object().unwrap().clone()
+    }
+
+    fn collecting(timeout: Duration) -> (Dedup, Emitted) {
+        let emitted = Emitted::default();
+        let sink = Arc::clone(&emitted);
+        let emit: Emit = Box::new(move |summary| sink.lock().unwrap().push(summary));
+        (Dedup::new(timeout, emit), emitted)
+    }
+
+    #[test]
+    fn test_repeats_collapse_until_change() {
+        let (dedup, emitted) = collecting(Duration::from_secs(60));
+
+        assert!(dedup.observe(record("db down", "t1")).is_some());
+        assert!(dedup.observe(record("db down", "t2")).is_none());
+        assert!(dedup.observe(record("db down", "t3")).is_none());
+        assert!(emitted.lock().unwrap().is_empty());
+
+        assert!(dedup.observe(record("recovered", "t4")).is_some());
+        let emitted = emitted.lock().unwrap();
+        assert_eq!(emitted.len(), 1);
+        assert_eq!(emitted[0][REPEAT_COUNT], 2);
+        assert_eq!(emitted[0]["timestamp"], "t3");
+        assert_eq!(emitted[0]["fields"]["message"], "db down");
+    }
+
+    #[test]
+    fn test_timeout_flushes_pending_summary() {
+        let (dedup, emitted) = collecting(Duration::ZERO);
+
+        dedup.observe(record("db down", "t1"));
+        dedup.flush_expired();
+        assert!(emitted.lock().unwrap().is_empty());
+
+        // With a zero timeout every repeat is summarized right away
+        assert!(dedup.observe(record("db down", "t2")).is_none());
+        dedup.observe(record("db down", "t3"));
+        dedup.flush_expired();
+        let counts: Vec<_> = emitted
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|summary| summary[REPEAT_COUNT].clone())
+            .collect();
+        assert_eq!(counts, vec![json!(1), json!(1)]);
+    }
+}
@@ -0,0 +1,141 @@
+//! Structured diffs of serializable values
+//!
+//! Backs [`structured::diff`](crate::structured::diff): both values are
+//! serialized to JSON and walked side by side, keeping only the paths whose
+//! values differ, so a config reload logs what changed instead of two full
+//! blobs.
+
+use serde_json::{Map, Value};
+
+/// Most changes listed in one record; the rest are only counted
+pub(crate) const MAX_CHANGES: usize = 32;
+
+/// Longest serialized old or new value kept; longer ones are cut
+pub(crate) const MAX_VALUE_LEN: usize = 256;
+
+/// Changed paths between two values
+#[derive(Debug, Default)]
+pub(crate) struct Changes {
+    /// `{"path", "old", "new"}` objects; `old` or `new` is absent for added or removed keys
+    pub(crate) listed: Vec<Value>,
+    /// Number of changes, including those not listed
+    pub(crate) total: usize,
+}
+
+/// Compare `old` with `new`, descending into objects; arrays and scalars are compared whole
+pub(crate) fn changes(old: &Value, new: &Value) -> Changes {
+    let mut changes = Changes::default();
+    walk(&mut String::new(), Some(old), Some(new), &mut changes);
+    changes
+}
+
+fn walk(path: &mut String, old: Option<&Value>, new: Option<&Value>, changes: &mut Changes) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let removed = old.keys().filter(|key| !new.contains_key(*key));
+            for key in new.keys().chain(removed) {
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                walk(path, old.get(key), new.get(key), changes);
+                path.truncate(len);
+            }
+        }
+        (old, new) if old == new => {}
+        (old, new) => {
+            changes.total += 1;
+            if changes.listed.len() < MAX_CHANGES {
+                let mut change = Map::new();
+                let path = if path.is_empty() { "." } else { path.as_str() };
+                change.insert("path".to_string(), path.into());
+                if let Some(old) = old {
+                    change.insert("old".to_string(), capped(old));
+                }
+                if let Some(new) = new {
+                    change.insert("new".to_string(), capped(new));
+                }
+                changes.listed.push(Value::Object(change));
+            }
+        }
+    }
+}
+
+/// The value itself, or its serialized form cut to [`MAX_VALUE_LEN`] bytes
+fn capped(value: &Value) -> Value {
+    let serialized = value.to_string();
+    if serialized.len() <= MAX_VALUE_LEN {
+        return value.clone();
+    }
+    let mut end = MAX_VALUE_LEN;
+    while !serialized.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &serialized[..end]).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_changes_lists_paths() {
+        let old = json!({"pool": {"max": 10, "min": 1}, "hosts": ["a"], "debug": true});
+        let new = json!({"pool": {"max": 20, "min": 1}, "hosts": ["a", "b"], "region": "eu"});
+        let mut changes = changes(&old, &new);
+        changes
+            .listed
+            .sort_by_key(|change| change["path"].to_string());
+
+        assert_eq!(changes.total, 4);
+        assert_eq!(
+            changes.listed,
+            vec![
+                json!({"path": "debug", "old": true}),
+                json!({"path": "hosts", "old": ["a"], "new": ["a", "b"]}),
+                json!({"path": "pool.max", "old": 10, "new": 20}),
+                json!({"path": "region", "new": "eu"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_logs_one_record() {
+        let logs = crate::testing::per_test_subscriber();
+        crate::structured::diff(
+            "config",
+            &json!({"level": "info"}),
+            &json!({"level": "debug"}),
+        );
+        crate::structured::diff(
+            "config",
+            &json!({"level": "info"}),
+            &json!({"level": "info"}),
+        );
+
+        let records = logs.records();
+        assert_eq!(records.len(), 1);
+        let fields = &records[0]["fields"];
+        assert_eq!(fields["label"], "config");
+        assert_eq!(fields["changed"], 1);
+        assert_eq!(
+            fields["changes"],
+            json!([{"path": "level", "old": "info", "new": "debug"}])
+        );
+    }
+
+    #[test]
+    fn test_changes_are_capped() {
+        let old: Map<String, Value> = (0..40).map(|i| (i.to_string(), 0.into())).collect();
+        let new = json!({"0": "x".repeat(1000)});
+        let changes = changes(&Value::Object(old), &new);
+
+        assert_eq!(changes.total, 40);
+        assert_eq!(changes.listed.len(), MAX_CHANGES);
+        let capped = changes.listed[0]["new"].as_str().unwrap();
+        assert_eq!(capped.len(), MAX_VALUE_LEN + '…'.len_utf8());
+        assert_eq!(super::changes(&new, &new).total, 0);
+    }
+}
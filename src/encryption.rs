@@ -0,0 +1,212 @@
+//! Log file encryption at rest
+//!
+//! With the `encryption` feature and `LOG_ENCRYPTION_KEY` set (or a key
+//! from [`Builder::with_file_encryption`](crate::Builder::with_file_encryption),
+//! e.g. fetched from a KMS), every record written to the log file is
+//! encrypted with AES-256-GCM under a fresh random nonce. Console and
+//! network outputs are unchanged.
+//!
+//! Each record becomes one self-contained frame, so files can be rotated,
+//! reopened and appended to by later runs, and a torn final frame only
+//! loses that record:
+//!
+//! ```text
+//! [version: 1 byte][length: u32 big-endian][nonce: 12 bytes][ciphertext + tag: length - 12 bytes]
+//! ```
+//!
+//! [`decrypt_file`] and [`decrypt`] turn a file back into NDJSON:
+//!
+//! ```no_run
+//! use custom_tracing_logger::encryption::{self, EncryptionKey};
+//!
+//! let key = EncryptionKey::from_env()?.expect("LOG_ENCRYPTION_KEY is set");
+//! let records = encryption::decrypt_file("logs/app.2025-01-01", &key)?;
+//! for line in records.lines() {
+//!     println!("{}", line);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+
+/// Format version of a frame
+const VERSION: u8 = 1;
+
+/// Bytes of a GCM nonce
+const NONCE_LEN: usize = 12;
+
+/// Longest accepted frame, guarding [`decrypt`] against corrupted lengths
+const MAX_FRAME: usize = 64 * 1024 * 1024;
+
+/// Provides the file encryption key when the logger is built
+pub(crate) type KeyProvider = Box<dyn FnOnce() -> Result<EncryptionKey, String> + Send>;
+
+/// 256-bit AES key
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Use 32 raw key bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parse 64 hex digits
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err("expected 64 hex digits (a 256-bit key)".to_string());
+        }
+        let mut bytes = [0u8; 32];
+        for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).unwrap_or_default();
+            *byte = u8::from_str_radix(pair, 16)
+                .map_err(|_| format!("'{}' is not a hex byte", pair))?;
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Read the key from `LOG_ENCRYPTION_KEY`, if set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match crate::config_file::var("LOG_ENCRYPTION_KEY") {
+            Ok(hex) if !hex.trim().is_empty() => Self::from_hex(&hex).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(***)")
+    }
+}
+
+/// Encrypts records into frames
+pub(crate) struct Encryptor {
+    cipher: Aes256Gcm,
+}
+
+impl Encryptor {
+    pub(crate) fn new(key: &EncryptionKey) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(&key.0.into()),
+        }
+    }
+
+    /// The frame holding `record`
+    pub(crate) fn seal(&self, record: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, record)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record too large"))?;
+        let len = u32::try_from(NONCE_LEN + ciphertext.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record too large"))?;
+        let mut frame = Vec::with_capacity(5 + len as usize);
+        frame.push(VERSION);
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&nonce);
+        frame.extend_from_slice(&ciphertext);
+        Ok(frame)
+    }
+}
+
+/// Decrypt the frames read from `input` and write the records to `output`
+///
+/// Returns the number of records. Fails with [`io::ErrorKind::InvalidData`]
+/// on a wrong key or a modified frame, and with
+/// [`io::ErrorKind::UnexpectedEof`] on a truncated last frame, after
+/// writing every record before it.
+pub fn decrypt<R: Read, W: Write>(input: R, mut output: W, key: &EncryptionKey) -> io::Result<u64> {
+    let cipher = Aes256Gcm::new(&key.0.into());
+    let mut input = BufReader::new(input);
+    let mut records = 0;
+    loop {
+        let mut header = [0u8; 5];
+        match input.read(&mut header[..1])? {
+            0 => return Ok(records),
+            _ => input.read_exact(&mut header[1..])?,
+        }
+        if header[0] != VERSION {
+            return Err(invalid(format!(
+                "unknown frame version {} after {} records",
+                header[0], records
+            )));
+        }
+        let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if !(NONCE_LEN..=MAX_FRAME).contains(&len) {
+            return Err(invalid(format!(
+                "invalid frame length {} after {} records",
+                len, records
+            )));
+        }
+        let mut frame = vec![0u8; len];
+        input.read_exact(&mut frame)?;
+        let (nonce, ciphertext) = frame.split_at(NONCE_LEN);
+        let record = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                invalid(format!(
+                    "record {} failed authentication (wrong key or modified file)",
+                    records + 1
+                ))
+            })?;
+        output.write_all(&record)?;
+        records += 1;
+    }
+}
+
+/// Decrypt an encrypted log file into its NDJSON records
+pub fn decrypt_file(path: impl AsRef<Path>, key: &EncryptionKey) -> io::Result<String> {
+    let mut records = Vec::new();
+    decrypt(File::open(path)?, &mut records, key)?;
+    String::from_utf8(records).map_err(|e| invalid(e.to_string()))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> EncryptionKey {
+        EncryptionKey::from_bytes([byte; 32])
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let encryptor = Encryptor::new(&key(7));
+        let mut file = Vec::new();
+        file.extend(encryptor.seal(b"{\"n\":1}\n").unwrap());
+        file.extend(encryptor.seal(b"{\"n\":2}\n").unwrap());
+        assert!(!file.windows(5).any(|window| window == b"\"n\":1"));
+
+        let mut records = Vec::new();
+        assert_eq!(decrypt(&file[..], &mut records, &key(7)).unwrap(), 2);
+        assert_eq!(records, b"{\"n\":1}\n{\"n\":2}\n");
+
+        let err = decrypt(&file[..], io::sink(), &key(8)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = decrypt(&file[..file.len() - 3], io::sink(), &key(7)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_key_from_hex() {
+        let hex = "00".repeat(31) + "ff";
+        let mut bytes = [0u8; 32];
+        bytes[31] = 0xff;
+        assert_eq!(EncryptionKey::from_hex(&hex).unwrap(), EncryptionKey(bytes));
+        assert!(EncryptionKey::from_hex("abcd").is_err());
+        assert!(EncryptionKey::from_hex(&"zz".repeat(32)).is_err());
+        assert_eq!(format!("{:?}", key(1)), "EncryptionKey(***)");
+    }
+}
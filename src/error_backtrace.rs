@@ -0,0 +1,80 @@
+//! Backtraces on ERROR records
+//!
+//! With `LOG_ERROR_BACKTRACE=true`, or `RUST_BACKTRACE` set to anything but
+//! "0", every ERROR event gets a `backtrace` field showing where it was
+//! logged. The backtrace is captured once, before any output formats the
+//! event, so each output writes the same one.
+
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Whether `LOG_ERROR_BACKTRACE`, or `RUST_BACKTRACE` when it is unset, asks for backtraces
+pub(crate) fn enabled_from_env() -> bool {
+    match crate::config_file::var("LOG_ERROR_BACKTRACE") {
+        Ok(value) => value == "true",
+        Err(_) => std::env::var("RUST_BACKTRACE").is_ok_and(|value| value != "0"),
+    }
+}
+
+/// Whether an event gets a captured backtrace
+pub(crate) fn applies_to(event: &Event<'_>) -> bool {
+    let metadata = event.metadata();
+    *metadata.level() == Level::ERROR
+        && !metadata.is_span()
+        // Panic records carry the backtrace of the panic itself
+        && metadata.fields().field("backtrace").is_none()
+}
+
+/// Backtrace captured for the ERROR event currently being formatted on this thread
+pub(crate) fn current() -> Option<Arc<str>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Layer capturing backtraces; must run before the output layers
+pub(crate) struct BacktraceLayer;
+
+impl<S: Subscriber> Layer<S> for BacktraceLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let backtrace = applies_to(event).then(|| Backtrace::force_capture().to_string().into());
+        CURRENT.with(|current| *current.borrow_mut() = backtrace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use crate::format::EventFormat;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_error_records_carry_backtrace() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(BacktraceLayer).with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .event_format(EventFormat::new(false).with_error_backtrace(true))
+                .with_writer(capture.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("slow");
+            tracing::error!("failed");
+            tracing::error!(backtrace = "from panic", "panicked");
+        });
+
+        let records = capture.records();
+        assert!(records[0]["fields"].get("backtrace").is_none());
+        let backtrace = records[1]["fields"]["backtrace"].as_str().unwrap();
+        assert!(backtrace.contains("test_error_records_carry_backtrace"));
+        assert_eq!(records[2]["fields"]["backtrace"], "from panic");
+    }
+}
@@ -0,0 +1,120 @@
+//! Structured error records
+//!
+//! [`structured::error_with_context`](crate::structured::error_with_context)
+//! takes any [`ErrorMessage`]: a plain message, or with the `anyhow` feature
+//! an `anyhow::Error`, whose context chain and backtrace become fields
+//! instead of being flattened into one string:
+//!
+//! ```json
+//! {"level":"ERROR","fields":{"message":"loading config","error_code":"CONFIG",
+//!  "error_chain":"[\"loading config\",\"reading app.toml\",\"No such file or directory (os error 2)\"]",
+//!  "backtrace":"..."}}
+//! ```
+//!
+//! `error_chain` is a JSON array, outermost context first; `backtrace` is only
+//! present when anyhow captured one (`RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`).
+//! [`log_error!`](crate::log_error) accepts the same errors with `error = err`.
+
+/// Something [`error_with_context`](crate::structured::error_with_context) can log
+pub trait ErrorMessage {
+    /// Write an ERROR record carrying `error_code`
+    fn log_with_code(&self, error_code: &str);
+}
+
+impl ErrorMessage for str {
+    fn log_with_code(&self, error_code: &str) {
+        tracing::error!(error_code = error_code, "{}" = self);
+    }
+}
+
+impl ErrorMessage for String {
+    fn log_with_code(&self, error_code: &str) {
+        self.as_str().log_with_code(error_code);
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl ErrorMessage for anyhow::Error {
+    fn log_with_code(&self, error_code: &str) {
+        crate::log_error!(error_code, error = self);
+    }
+}
+
+/// Fields of an `anyhow::Error`, as written by [`log_error!`](crate::log_error)
+#[cfg(feature = "anyhow")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// Outermost message
+    pub message: String,
+    /// Every message from the outermost context to the root cause
+    pub chain: Vec<String>,
+    /// Backtrace anyhow captured, if it captured one
+    pub backtrace: Option<String>,
+}
+
+#[cfg(feature = "anyhow")]
+impl ErrorReport {
+    /// Take the message, chain and backtrace of `error`
+    pub fn new(error: &anyhow::Error) -> Self {
+        use std::backtrace::BacktraceStatus;
+
+        let backtrace = error.backtrace();
+        Self {
+            message: error.to_string(),
+            chain: error.chain().map(ToString::to_string).collect(),
+            backtrace: (backtrace.status() == BacktraceStatus::Captured)
+                .then(|| backtrace.to_string()),
+        }
+    }
+
+    /// The chain as a JSON array, the form it takes in records
+    pub fn chain_json(&self) -> String {
+        serde_json::Value::from(self.chain.clone()).to_string()
+    }
+}
+
+#[cfg(all(test, feature = "anyhow"))]
+mod tests {
+    use crate::format::tests::Capture;
+    use anyhow::Context;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn failing() -> anyhow::Result<()> {
+        let missing: Result<(), std::io::Error> = Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no such file",
+        ));
+        missing
+            .context("reading app.toml")
+            .context("loading config")
+    }
+
+    #[test]
+    fn test_anyhow_chain_becomes_fields() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(capture.clone()),
+        );
+        let error = failing().unwrap_err();
+
+        tracing::subscriber::with_default(subscriber, || {
+            crate::structured::error_with_context("CONFIG", &error);
+            crate::log_error!("CONFIG", error = error, attempt = 2);
+        });
+
+        for record in capture.records() {
+            let fields = &record["fields"];
+            assert_eq!(fields["message"], "loading config");
+            assert_eq!(fields["error_code"], "CONFIG");
+            let chain: Vec<String> =
+                serde_json::from_str(fields["error_chain"].as_str().unwrap()).unwrap();
+            assert_eq!(
+                chain,
+                ["loading config", "reading app.toml", "no such file"]
+            );
+        }
+        assert_eq!(capture.records()[1]["fields"]["attempt"], 2);
+    }
+}
@@ -0,0 +1,146 @@
+//! Dropping events by their field values
+//!
+//! `RUST_LOG` selects events by target and level only, which can't express
+//! "drop successful health checks". A predicate registered with
+//! [`Builder::with_event_filter`](crate::Builder::with_event_filter) sees
+//! each event's target, level and fields as an [`EventView`] and returns
+//! `false` to drop the event from every output. It runs before sampling and
+//! rate limiting, so dropped events use up no tokens, and only for events
+//! `RUST_LOG` enables.
+//!
+//! A predicate that panics keeps the event; the panic is reported once on
+//! stderr and counted as a sink failure in [`stats`](crate::stats).
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::{Map, Value};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::reentrant::FieldMap;
+
+/// Predicate registered with [`Builder::with_event_filter`](crate::Builder::with_event_filter)
+pub type EventPredicate = Box<dyn Fn(&EventView<'_>) -> bool + Send + Sync>;
+
+/// An event as seen by the predicate
+#[derive(Debug)]
+pub struct EventView<'a> {
+    target: &'a str,
+    level: &'a Level,
+    fields: Map<String, Value>,
+}
+
+impl<'a> EventView<'a> {
+    fn new(event: &'a Event<'a>) -> Self {
+        let mut fields = FieldMap(Map::new());
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        Self {
+            target: metadata.target(),
+            level: metadata.level(),
+            fields: fields.0,
+        }
+    }
+
+    /// Target of the event, usually its module path
+    pub fn target(&self) -> &str {
+        self.target
+    }
+
+    /// Level of the event
+    pub fn level(&self) -> &Level {
+        self.level
+    }
+
+    /// The event's message, if it has one
+    pub fn message(&self) -> Option<&str> {
+        self.fields.get("message").and_then(Value::as_str)
+    }
+
+    /// Value of the field `name`, with integers, floats and booleans kept as such
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.get(name)
+    }
+
+    /// Every field of the event, including `message`
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+}
+
+/// Layer dropping the events the predicate rejects before any output sees them
+pub(crate) struct EventFilterLayer {
+    predicate: EventPredicate,
+    failed: AtomicBool,
+}
+
+impl EventFilterLayer {
+    pub(crate) fn new(predicate: EventPredicate) -> Self {
+        Self {
+            predicate,
+            failed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EventFilterLayer {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        let view = EventView::new(event);
+        match panic::catch_unwind(AssertUnwindSafe(|| (self.predicate)(&view))) {
+            Ok(keep) => keep,
+            Err(_) => {
+                crate::metrics::add_sink_failure();
+                if !self.failed.swap(true, Ordering::Relaxed) {
+                    eprintln!("Event filter panicked; events it fails on are kept");
+                }
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_rejected_events_are_dropped() {
+        let capture = Capture::default();
+        let layer = EventFilterLayer::new(Box::new(|event: &EventView<'_>| {
+            if event.target() == "panics" {
+                panic!("broken filter");
+            }
+            !(event.field("status") == Some(&Value::from(200))
+                && event.field("path").and_then(Value::as_str) == Some("/health"))
+        }));
+        let subscriber = tracing_subscriber::registry().with(layer).with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(capture.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(path = "/health", status = 200, "HTTP request completed");
+            tracing::info!(path = "/health", status = 503, "HTTP request completed");
+            tracing::info!(path = "/orders", status = 200, "HTTP request completed");
+            tracing::info!(target: "panics", "Kept");
+        });
+
+        let records = capture.records();
+        let kept: Vec<_> = records
+            .iter()
+            .map(|r| (r["fields"]["path"].clone(), r["fields"]["status"].clone()))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                (Value::from("/health"), Value::from(503)),
+                (Value::from("/orders"), Value::from(200)),
+                (Value::Null, Value::Null),
+            ]
+        );
+    }
+}
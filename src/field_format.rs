@@ -0,0 +1,185 @@
+//! Per-field value formatters
+//!
+//! Applications register a function per field name once, e.g. rendering
+//! `amount_cents` as a decimal amount or canonicalizing `client_ip`, and
+//! every record is rewritten in the formatter stage, so presentation
+//! conventions live in one place instead of at each call site.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+
+/// Formatting function: the new value, or `None` to keep the original
+type FormatFn = dyn Fn(&Value) -> Option<Value> + Send + Sync;
+
+/// Formatting functions by field name
+///
+/// # Examples
+/// ```no_run
+/// use custom_tracing_logger::field_format::{self, FieldFormatters};
+///
+/// let formatters = FieldFormatters::new()
+///     .field("amount_cents", field_format::cents_as_decimal)
+///     .field("client_ip", field_format::canonical_ip)
+///     .field("sku", |v| v.as_str().map(|s| s.to_uppercase().into()));
+/// custom_tracing_logger::builder()
+///     .with_field_formatters(formatters)
+///     .init();
+///
+/// tracing::info!(amount_cents = 1999, "Charged"); // "amount_cents":"19.99"
+/// ```
+#[derive(Clone, Default)]
+pub struct FieldFormatters {
+    formatters: HashMap<String, Arc<FormatFn>>,
+}
+
+impl fmt::Debug for FieldFormatters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.formatters.keys()).finish()
+    }
+}
+
+impl FieldFormatters {
+    /// Create a registry without any formatters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Format the values of fields named `name` with `format`, replacing any previous one
+    ///
+    /// `format` returns `None` to leave a value as it is, e.g. when it has
+    /// an unexpected type.
+    pub fn field(
+        mut self,
+        name: impl Into<String>,
+        format: impl Fn(&Value) -> Option<Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.formatters.insert(name.into(), Arc::new(format));
+        self
+    }
+
+    /// Whether no formatters are registered
+    pub fn is_empty(&self) -> bool {
+        self.formatters.is_empty()
+    }
+
+    /// Format the event and span fields of a formatted record in place
+    pub(crate) fn apply(&self, record: &mut Map<String, Value>) {
+        for key in ["fields", "span"] {
+            if let Some(Value::Object(fields)) = record.get_mut(key) {
+                self.format(fields);
+            }
+        }
+        if let Some(Value::Array(spans)) = record.get_mut("spans") {
+            for span in spans {
+                if let Value::Object(fields) = span {
+                    self.format(fields);
+                }
+            }
+        }
+    }
+
+    fn format(&self, fields: &mut Map<String, Value>) {
+        for (key, value) in fields.iter_mut() {
+            if let Some(formatted) = self.formatters.get(key).and_then(|format| format(value)) {
+                *value = formatted;
+            }
+        }
+    }
+}
+
+/// Render an integer amount of cents as a decimal string, e.g. 1999 as "19.99"
+pub fn cents_as_decimal(value: &Value) -> Option<Value> {
+    let cents = value.as_i64()?;
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    Some(format!("{}{}.{:02}", sign, cents / 100, cents % 100).into())
+}
+
+/// Render an IP address in canonical form
+///
+/// IPv6 is compressed and lowercased, and IPv4-mapped IPv6 addresses are
+/// shown as plain IPv4, so the same client always has the same value.
+pub fn canonical_ip(value: &Value) -> Option<Value> {
+    let ip: IpAddr = value.as_str()?.trim().parse().ok()?;
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    };
+    Some(ip.to_string().into())
+}
+
+/// Render a Debug-formatted `Duration` ("1.234567s", "12ms", "3µs") as a number of milliseconds
+///
+/// For fields recorded as `?elapsed`; new call sites can use
+/// [`time_fields::millis`](crate::time_fields::millis) directly.
+pub fn debug_duration_as_millis(value: &Value) -> Option<Value> {
+    const UNITS: [(&str, f64); 4] = [("ns", 1e-6), ("µs", 1e-3), ("ms", 1.0), ("s", 1e3)];
+    let text = value.as_str()?.trim();
+    let (number, scale) = UNITS
+        .iter()
+        .find_map(|(suffix, scale)| Some((text.strip_suffix(suffix)?, scale)))?;
+    let millis = number.parse::<f64>().ok()? * scale;
+    serde_json::Number::from_f64(millis).map(Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_formats_event_and_span_fields() {
+        let formatters = FieldFormatters::new()
+            .field("amount_cents", cents_as_decimal)
+            .field("client_ip", canonical_ip);
+        let mut record = json!({
+            "fields": {"message": "Charged", "amount_cents": 1999, "client_ip": "::FFFF:10.0.0.1"},
+            "span": {"name": "checkout", "amount_cents": -5},
+            "spans": [{"name": "request", "client_ip": "2001:DB8:0:0:0:0:0:1"}],
+            "target": "amount_cents"
+        });
+        formatters.apply(record.as_object_mut().unwrap());
+
+        assert_eq!(record["fields"]["amount_cents"], "19.99");
+        assert_eq!(record["fields"]["client_ip"], "10.0.0.1");
+        assert_eq!(record["span"]["amount_cents"], "-0.05");
+        assert_eq!(record["spans"][0]["client_ip"], "2001:db8::1");
+        assert_eq!(record["target"], "amount_cents");
+    }
+
+    #[test]
+    fn test_debug_duration_as_millis() {
+        let millis = |d: std::time::Duration| debug_duration_as_millis(&format!("{:?}", d).into());
+        assert_eq!(
+            millis(std::time::Duration::from_micros(1_234_567)),
+            Some(1234.567.into())
+        );
+        assert_eq!(
+            millis(std::time::Duration::from_millis(12)),
+            Some(12.0.into())
+        );
+        assert_eq!(
+            millis(std::time::Duration::from_nanos(3_500)),
+            Some(3.5e-3.into())
+        );
+        assert_eq!(debug_duration_as_millis(&"soon".into()), None);
+    }
+
+    #[test]
+    fn test_unformattable_values_are_kept() {
+        let formatters = FieldFormatters::new()
+            .field("amount_cents", cents_as_decimal)
+            .field("client_ip", canonical_ip);
+        let mut record = json!({
+            "fields": {"amount_cents": "n/a", "client_ip": "localhost"}
+        });
+        formatters.apply(record.as_object_mut().unwrap());
+
+        assert_eq!(record["fields"]["amount_cents"], "n/a");
+        assert_eq!(record["fields"]["client_ip"], "localhost");
+    }
+}
@@ -0,0 +1,761 @@
+//! Reopenable log file output
+//!
+//! The daily rotating file is wrapped so it can be reopened at its path
+//! after an external tool such as logrotate has moved it away; otherwise
+//! records keep going to the renamed (or deleted) file. Files are shared
+//! per path, so installing the logger again in the same process (test
+//! harnesses, dev servers) never opens a second handle to the same file.
+//!
+//! When a write fails (a full disk, a revoked mount), records are kept in a
+//! bounded in-memory buffer and written in order once the file accepts
+//! them again, retried at most every `LOG_FILE_RETRY_MS`. When the buffer
+//! (`LOG_FILE_BUFFER_BYTES`) is full the oldest records are dropped and
+//! counted in [`stats`](crate::stats) as `dropped_events` and
+//! `dropped_bytes`.
+//!
+//! Several processes can log to the same file (a preforked worker pool,
+//! multiple binaries): each record is appended with one `O_APPEND` write,
+//! and with `LOG_FILE_SHARED=true` under an exclusive advisory lock, so
+//! records of concurrent writers never interleave. Rotation needs no
+//! coordination, as every process derives the file name from the UTC date.
+//!
+//! Files and the directories created for them get the mode and group of
+//! [`permissions`](crate::permissions). With the `encryption` feature,
+//! records can be encrypted before they reach the file, see
+//! [`encryption`](crate::encryption).
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::flusher::{Pending, Target};
+use crate::naming::FileNaming;
+use crate::permissions::FilePermissions;
+
+/// File outputs installed by the global logger, kept for [`reopen_log_files`]
+static INSTALLED: OnceLock<Vec<Arc<LogFile>>> = OnceLock::new();
+
+/// Every live log file by path, so each path is opened once
+static OPEN: Mutex<Option<HashMap<PathBuf, Weak<LogFile>>>> = Mutex::new(None);
+
+/// Reopen the log files at their configured paths
+///
+/// Call this after the files were rotated externally (e.g. from logrotate's
+/// `postrotate`, or your own signal handling). Does nothing when file
+/// logging is not enabled.
+pub fn reopen_log_files() {
+    for file in INSTALLED.get().into_iter().flatten() {
+        if let Err(e) = file.reopen() {
+            eprintln!("Cannot reopen log file: {}", e);
+        }
+    }
+}
+
+/// Flush the installed log files, if any
+pub(crate) fn flush_installed() -> io::Result<()> {
+    let mut result = Ok(());
+    for file in INSTALLED.get().into_iter().flatten() {
+        if let Err(e) = (&**file).flush() {
+            result = Err(e);
+        }
+    }
+    result
+}
+
+/// Flush and close every open log file; each reopens on its next write
+pub(crate) fn close_all() {
+    let mut open = OPEN.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(open) = open.as_mut() {
+        open.retain(|_, file| match file.upgrade() {
+            Some(file) => {
+                file.close();
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+pub(crate) fn install(files: Vec<Arc<LogFile>>) {
+    let _ = INSTALLED.set(files);
+}
+
+/// Bytes of records buffered while the file can't be written, unless configured
+pub(crate) const DEFAULT_BUFFER_BYTES: usize = 1024 * 1024;
+
+/// Time between attempts to write buffered records, unless configured
+pub(crate) const DEFAULT_RETRY: Duration = Duration::from_secs(1);
+
+/// How records are kept while the file can't be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WriteBuffer {
+    /// Most bytes of records kept; 0 drops records right away
+    pub(crate) capacity: usize,
+    /// Shortest time between two attempts to write them
+    pub(crate) retry: Duration,
+}
+
+impl Default for WriteBuffer {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_BUFFER_BYTES,
+            retry: DEFAULT_RETRY,
+        }
+    }
+}
+
+impl WriteBuffer {
+    /// Read `LOG_FILE_BUFFER_BYTES` and `LOG_FILE_RETRY_MS`
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let mut buffer = Self::default();
+        if let Ok(raw) = crate::config_file::var("LOG_FILE_BUFFER_BYTES") {
+            buffer.capacity = raw
+                .trim()
+                .parse()
+                .map_err(|_| format!("LOG_FILE_BUFFER_BYTES='{}' is not a number of bytes", raw))?;
+        }
+        if let Ok(raw) = crate::config_file::var("LOG_FILE_RETRY_MS") {
+            match raw.trim().parse::<u64>() {
+                Ok(ms) if ms > 0 => buffer.retry = Duration::from_millis(ms),
+                _ => {
+                    return Err(format!(
+                        "LOG_FILE_RETRY_MS='{}' is not a positive number of milliseconds",
+                        raw
+                    ))
+                }
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Read the environment, reporting invalid values on stderr and using the defaults
+    pub(crate) fn from_env_lenient() -> Self {
+        Self::from_env().unwrap_or_else(|e| {
+            eprintln!("Invalid log file buffer: {}", e);
+            Self::default()
+        })
+    }
+}
+
+/// Records waiting for the file to become writable again
+#[derive(Default)]
+struct Backlog {
+    records: VecDeque<Vec<u8>>,
+    bytes: usize,
+    /// Time of the last failed write, `None` while the file is writable
+    failed_at: Option<Instant>,
+}
+
+impl Backlog {
+    /// Keep `record`, dropping the oldest records to stay within `capacity`
+    fn push(&mut self, record: &[u8], capacity: usize) {
+        if record.len() > capacity {
+            crate::metrics::add_dropped_file_record(record.len());
+            return;
+        }
+        while self.bytes + record.len() > capacity {
+            if let Some(oldest) = self.records.pop_front() {
+                self.bytes -= oldest.len();
+                crate::metrics::add_dropped_file_record(oldest.len());
+            }
+        }
+        self.bytes += record.len();
+        self.records.push_back(record.to_vec());
+    }
+
+    /// Whether the failed file is due for another attempt
+    fn retry_due(&self, retry: Duration) -> bool {
+        self.failed_at.is_some_and(|at| at.elapsed() >= retry)
+    }
+}
+
+/// Options of a log file, fixed when it is first opened
+#[derive(Clone, Default)]
+pub(crate) struct FileOptions {
+    /// Mode and group of the files and created directories
+    pub(crate) permissions: FilePermissions,
+    /// Names of the daily files and the symlink to the active one
+    pub(crate) naming: FileNaming,
+    /// Buffering of records while the file can't be written
+    pub(crate) buffer: WriteBuffer,
+    /// Lock the file around each write, for files shared with other processes
+    pub(crate) shared: bool,
+    /// Encrypts each record before it is written
+    #[cfg(feature = "encryption")]
+    pub(crate) encryption_key: Option<crate::encryption::EncryptionKey>,
+}
+
+/// File of the current UTC day
+struct DailyFile {
+    file: File,
+    day: i64,
+}
+
+/// Daily rotating file that can be reopened in place
+///
+/// Files are named after the UTC date, `<prefix>.<YYYY-MM-DD>` unless
+/// [`FileNaming`] says otherwise.
+pub(crate) struct LogFile {
+    dir: PathBuf,
+    prefix: String,
+    permissions: FilePermissions,
+    naming: FileNaming,
+    /// Open file, `None` after [`close`](LogFile::close) until the next write
+    current: Mutex<Option<DailyFile>>,
+    /// Records written since the last timed flush
+    pending: Arc<Pending>,
+    buffer: WriteBuffer,
+    /// Whether writes take an exclusive advisory lock
+    shared: bool,
+    /// Records not yet written because the file failed
+    backlog: Mutex<Backlog>,
+    /// Encrypts each record before it is written
+    #[cfg(feature = "encryption")]
+    encryptor: Option<crate::encryption::Encryptor>,
+}
+
+impl LogFile {
+    /// The log file for `dir` and `prefix`, shared with any other output already using it
+    ///
+    /// A file already open at the same path keeps its own options.
+    pub(crate) fn open(dir: PathBuf, prefix: &str, options: FileOptions) -> Arc<Self> {
+        let mut open = OPEN.lock().unwrap_or_else(|e| e.into_inner());
+        let open = open.get_or_insert_with(HashMap::new);
+        let path = dir.join(prefix);
+        if let Some(file) = open.get(&path).and_then(Weak::upgrade) {
+            return file;
+        }
+        open.retain(|_, file| file.strong_count() > 0);
+        let file = Arc::new(Self::new(dir, prefix, options));
+        // Create the file right away; a failure is retried on the first write
+        let _ = file.reopen();
+        open.insert(path, Arc::downgrade(&file));
+        file
+    }
+
+    /// A log file with its own handle, not shared with other outputs
+    fn new(dir: PathBuf, prefix: &str, options: FileOptions) -> Self {
+        Self {
+            dir,
+            prefix: prefix.to_string(),
+            permissions: options.permissions,
+            naming: options.naming,
+            current: Mutex::new(None),
+            pending: Arc::default(),
+            buffer: options.buffer,
+            shared: options.shared,
+            backlog: Mutex::default(),
+            #[cfg(feature = "encryption")]
+            encryptor: options
+                .encryption_key
+                .as_ref()
+                .map(crate::encryption::Encryptor::new),
+        }
+    }
+
+    /// Open the file unless it is open already, reporting why it can't be
+    pub(crate) fn ensure_open(&self) -> io::Result<()> {
+        let mut current = self.lock();
+        if current.is_none() {
+            *current = Some(self.open_day(today())?);
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<DailyFile>> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Path of the file of `day` (days since the Unix epoch)
+    fn path_for(&self, day: i64) -> PathBuf {
+        let (year, month, date) = crate::time_fields::civil_from_days(day);
+        self.dir
+            .join(self.naming.file_name(&self.prefix, year, month, date))
+    }
+
+    /// Open (or create) the file of `day` with the configured permissions
+    fn open_day(&self, day: i64) -> io::Result<DailyFile> {
+        self.permissions.create_dir_all(&self.dir)?;
+        let path = self.path_for(day);
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(self.permissions.file_mode());
+        }
+        let file = options.open(&path)?;
+        if let Err(e) = self.permissions.apply(&file) {
+            eprintln!(
+                "Cannot set permissions of log file '{}': {}",
+                path.display(),
+                e
+            );
+        }
+        if let Some(name) = self.naming.symlink_name() {
+            if let Err(e) = self.link_current(name, &path) {
+                eprintln!("Cannot update log file symlink '{}': {}", name, e);
+            }
+        }
+        Ok(DailyFile { file, day })
+    }
+
+    /// Point the symlink `name` at `path`, replacing it atomically
+    #[cfg(unix)]
+    fn link_current(&self, name: &str, path: &Path) -> io::Result<()> {
+        let link = self.dir.join(name);
+        // Relative, so the directory can be moved or mounted elsewhere
+        let target = path.file_name().unwrap_or(path.as_os_str());
+        if std::fs::read_link(&link).is_ok_and(|current| current == target) {
+            return Ok(());
+        }
+        let staged = self
+            .dir
+            .join(format!(".{}.{}.tmp", name, std::process::id()));
+        let _ = std::fs::remove_file(&staged);
+        std::os::unix::fs::symlink(target, &staged)?;
+        std::fs::rename(&staged, &link)
+    }
+
+    #[cfg(not(unix))]
+    fn link_current(&self, _name: &str, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Replace the open file handle with a fresh one at the same path
+    ///
+    /// On failure the file stays closed and the next write tries again.
+    pub(crate) fn reopen(&self) -> io::Result<()> {
+        let reopened = self.open_day(today());
+        let mut current = self.lock();
+        match reopened {
+            Ok(file) => {
+                *current = Some(file);
+                Ok(())
+            }
+            Err(e) => {
+                *current = None;
+                Err(e)
+            }
+        }
+    }
+
+    /// Append `bytes` to the file of the current day
+    fn append(&self, bytes: &[u8]) -> io::Result<()> {
+        let day = today();
+        let mut current = self.lock();
+        let file = match current.as_mut() {
+            Some(current) if current.day == day => &mut current.file,
+            _ => &mut current.insert(self.open_day(day)?).file,
+        };
+        if !self.shared {
+            return file.write_all(bytes);
+        }
+        // Other processes wait until the whole record is written
+        file.lock()?;
+        let written = file.write_all(bytes);
+        let _ = file.unlock();
+        written
+    }
+
+    /// Append `record`, buffering it while the file can't be written
+    fn write_record(&self, record: &[u8]) {
+        let mut backlog = self.lock_backlog();
+        if backlog.retry_due(self.buffer.retry) {
+            self.drain(&mut backlog);
+        }
+        if backlog.failed_at.is_none() {
+            match self.append(record) {
+                Ok(()) => return,
+                Err(e) => {
+                    crate::metrics::add_write_error();
+                    eprintln!("Cannot write log file, buffering records: {}", e);
+                    backlog.failed_at = Some(Instant::now());
+                }
+            }
+        }
+        backlog.push(record, self.buffer.capacity);
+    }
+
+    /// Write the buffered records in order, stopping at the first failure
+    fn drain(&self, backlog: &mut Backlog) {
+        let failed = backlog.failed_at.is_some();
+        while let Some(record) = backlog.records.front() {
+            if self.append(record).is_err() {
+                crate::metrics::add_write_error();
+                backlog.failed_at = Some(Instant::now());
+                return;
+            }
+            backlog.bytes -= record.len();
+            backlog.records.pop_front();
+        }
+        backlog.failed_at = None;
+        if failed {
+            eprintln!("Log file writable again");
+        }
+    }
+
+    fn lock_backlog(&self) -> MutexGuard<'_, Backlog> {
+        self.backlog.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Directory of the daily files
+    #[cfg(feature = "archive")]
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Files of earlier days still in the directory, oldest first
+    #[cfg(feature = "archive")]
+    pub(crate) fn completed_files(&self) -> io::Result<Vec<PathBuf>> {
+        let today = crate::time_fields::civil_from_days(today());
+        let mut files: Vec<_> = std::fs::read_dir(&self.dir)?
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                let date = self.naming.date_of(&self.prefix, name.to_str()?)?;
+                (date < today).then(|| (date, entry.path()))
+            })
+            .collect();
+        files.sort();
+        Ok(files.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// This file as a target of the flusher thread
+    pub(crate) fn flush_target(self: &Arc<Self>) -> Target {
+        let file = Arc::clone(self);
+        Target {
+            pending: Arc::clone(&self.pending),
+            interval: None,
+            flush: Box::new(move || {
+                let _ = (&*file).flush();
+            }),
+        }
+    }
+
+    /// Flush and release the file handle
+    pub(crate) fn close(&self) {
+        if let Some(mut current) = self.lock().take() {
+            let _ = current.file.flush();
+        }
+    }
+}
+
+/// Days since the Unix epoch, in UTC
+fn today() -> i64 {
+    crate::time_fields::unix_millis(SystemTime::now()).div_euclid(86_400_000)
+}
+
+impl Write for &LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.mark();
+        // One self-contained frame per record
+        #[cfg(feature = "encryption")]
+        let sealed = match &self.encryptor {
+            Some(encryptor) => Some(encryptor.seal(buf)?),
+            None => None,
+        };
+        #[cfg(feature = "encryption")]
+        let record = sealed.as_deref().unwrap_or(buf);
+        #[cfg(not(feature = "encryption"))]
+        let record = buf;
+        self.write_record(record);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut backlog = self.lock_backlog();
+        if backlog.retry_due(self.buffer.retry) {
+            self.drain(&mut backlog);
+        }
+        if backlog.failed_at.is_some() {
+            // Keep the flusher thread coming back to retry
+            self.pending.mark();
+        }
+        drop(backlog);
+        match self.lock().as_mut() {
+            Some(current) => current.file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Shared handle to a [`LogFile`], writable from any output
+#[derive(Clone)]
+pub(crate) struct SharedLogFile(pub(crate) Arc<LogFile>);
+
+impl Write for SharedLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self.0).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self.0).flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for SharedLogFile {
+    type Writer = &'a LogFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reopen_recreates_moved_file() {
+        let dir = std::env::temp_dir().join(format!("log-reopen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut writer = SharedLogFile(LogFile::open(dir.clone(), "app", FileOptions::default()));
+
+        writer.write_all(b"before\n").unwrap();
+        let written: Vec<_> = std::fs::read_dir(&dir).unwrap().flatten().collect();
+        assert_eq!(written.len(), 1);
+        let current = written[0].path();
+        std::fs::rename(&current, dir.join("rotated")).unwrap();
+
+        writer.0.reopen().unwrap();
+        writer.write_all(b"after\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&current).unwrap(), "after\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("rotated")).unwrap(),
+            "before\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unusable_dir_is_reported() {
+        // A regular file where the directory should be
+        let dir = std::env::temp_dir().join(format!("log-blocked-{}", std::process::id()));
+        std::fs::write(&dir, b"").unwrap();
+        let file = LogFile::open(dir.clone(), "app", FileOptions::default());
+        assert!(file.ensure_open().is_err());
+        assert!(file.reopen().is_err());
+
+        let _ = std::fs::remove_file(&dir);
+    }
+
+    #[test]
+    fn test_failed_writes_are_buffered_and_retried() {
+        let dir = std::env::temp_dir().join(format!("log-retry-{}", std::process::id()));
+        std::fs::write(&dir, b"").unwrap();
+        let options = FileOptions {
+            buffer: WriteBuffer {
+                capacity: 64,
+                retry: Duration::from_millis(1),
+            },
+            ..FileOptions::default()
+        };
+        let mut writer = SharedLogFile(LogFile::open(dir.clone(), "app", options));
+        let before = crate::metrics::stats();
+        writer.write_all(b"first\n").unwrap();
+        writer.write_all(&[b'x'; 100]).unwrap();
+        writer.write_all(b"second\n").unwrap();
+        let after = crate::metrics::stats();
+        assert!(after.write_errors > before.write_errors);
+        assert!(after.dropped_bytes >= before.dropped_bytes + 100);
+
+        // The file becomes writable again
+        std::fs::remove_file(&dir).unwrap();
+        std::fs::create_dir(&dir).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        writer.write_all(b"third\n").unwrap();
+        let written = std::fs::read_dir(&dir).unwrap().flatten().next().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(written.path()).unwrap(),
+            "first\nsecond\nthird\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_full_backlog_drops_oldest() {
+        let mut backlog = Backlog::default();
+        backlog.push(b"one\n", 10);
+        backlog.push(b"two\n", 10);
+        backlog.push(b"three\n", 10);
+        assert_eq!(backlog.records, [b"two\n".to_vec(), b"three\n".to_vec()]);
+        assert_eq!(backlog.bytes, 10);
+        backlog.push(&[b'x'; 11], 10);
+        assert_eq!(backlog.records.len(), 2);
+    }
+
+    #[test]
+    fn test_shared_file_keeps_records_whole() {
+        let dir = std::env::temp_dir().join(format!("log-shared-lock-{}", std::process::id()));
+        // Separate handles, as separate processes would have
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let options = FileOptions {
+                    shared: true,
+                    ..FileOptions::default()
+                };
+                Arc::new(LogFile::new(dir.clone(), "app", options))
+            })
+            .collect();
+        let threads: Vec<_> = writers
+            .into_iter()
+            .enumerate()
+            .map(|(writer, file)| {
+                std::thread::spawn(move || {
+                    let record = format!(
+                        "{{\"writer\":{},\"pad\":\"{}\"}}\n",
+                        writer,
+                        "x".repeat(8192)
+                    );
+                    for _ in 0..100 {
+                        (&*file).write_all(record.as_bytes()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let written = std::fs::read_dir(&dir).unwrap().flatten().next().unwrap();
+        let contents = std::fs::read_to_string(written.path()).unwrap();
+        assert_eq!(contents.lines().count(), 400);
+        for line in contents.lines() {
+            serde_json::from_str::<serde_json::Value>(line).unwrap();
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_custom_names_and_current_symlink() {
+        let dir = std::env::temp_dir().join(format!("log-naming-{}", std::process::id()));
+        let naming = FileNaming::pattern("{prefix}-{date}.{ext}")
+            .unwrap()
+            .date_format("%Y%m%d")
+            .unwrap()
+            .extension("log")
+            .symlink("current");
+        let options = FileOptions {
+            naming,
+            ..FileOptions::default()
+        };
+        let file = LogFile::new(dir.clone(), "app", options);
+        (&file).write_all(b"one\n").unwrap();
+
+        let (year, month, day) = crate::time_fields::civil_from_days(today());
+        let name = format!("app-{:04}{:02}{:02}.log", year, month, day);
+        assert_eq!(std::fs::read_to_string(dir.join(&name)).unwrap(), "one\n");
+        assert_eq!(
+            std::fs::read_link(dir.join("current")).unwrap(),
+            Path::new(&name)
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("current")).unwrap(),
+            "one\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_same_path_shares_one_file() {
+        let dir = std::env::temp_dir().join(format!("log-shared-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = LogFile::open(dir.clone(), "app", FileOptions::default());
+        let second = LogFile::open(dir.clone(), "app", FileOptions::default());
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(
+            &first,
+            &LogFile::open(dir.clone(), "other", FileOptions::default())
+        ));
+
+        (&*first).write_all(b"one\n").unwrap();
+        close_all();
+        assert!(first.lock().is_none());
+        (&*second).write_all(b"two\n").unwrap();
+        let written: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("app")
+            })
+            .collect();
+        assert_eq!(written.len(), 1);
+        assert_eq!(std::fs::read_to_string(&written[0]).unwrap(), "one\ntwo\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_new_files_get_configured_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("log-mode-{}", std::process::id()));
+        let options = FileOptions {
+            permissions: FilePermissions::mode(0o600),
+            ..FileOptions::default()
+        };
+        let file = LogFile::open(dir.join("logs"), "app", options);
+        (&*file).write_all(b"one\n").unwrap();
+
+        let path = file.path_for(today());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let mode = std::fs::metadata(dir.join("logs"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_file_decrypts() {
+        use crate::encryption::{decrypt_file, EncryptionKey};
+
+        let dir = std::env::temp_dir().join(format!("log-encrypted-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = EncryptionKey::from_bytes([3; 32]);
+        let options = FileOptions {
+            encryption_key: Some(key.clone()),
+            ..FileOptions::default()
+        };
+        let mut writer = SharedLogFile(LogFile::open(dir.clone(), "app", options));
+        writer.write_all(b"{\"card\":\"4111\"}\n").unwrap();
+        writer.write_all(b"{\"n\":2}\n").unwrap();
+        writer.flush().unwrap();
+
+        let path = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .next()
+            .unwrap()
+            .path();
+        assert!(!String::from_utf8_lossy(&std::fs::read(&path).unwrap()).contains("4111"));
+        assert_eq!(
+            decrypt_file(&path, &key).unwrap(),
+            "{\"card\":\"4111\"}\n{\"n\":2}\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
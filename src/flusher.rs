@@ -0,0 +1,202 @@
+//! Time-based flushing of buffered outputs
+//!
+//! Registered sinks that buffer records (and the log file) are otherwise
+//! only flushed when their buffer fills, so tailing a quiet service shows
+//! nothing. A background thread flushes every output holding unflushed
+//! records once its interval has passed (`LOG_FLUSH_INTERVAL_MS`, or the
+//! sink's own [`LogSink::flush_interval`](crate::LogSink::flush_interval)),
+//! and with `LOG_IDLE_FLUSH_MS` as soon as its oldest unflushed record is
+//! that old, whatever the interval.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::clock::{Clock, MonotonicClock};
+
+/// Shortest time between two checks of the flusher thread
+const MIN_TICK: Duration = Duration::from_millis(1);
+
+/// When outputs are flushed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct FlushPolicy {
+    /// Default time between flushes of an output with unflushed records
+    pub(crate) interval: Option<Duration>,
+    /// Longest time a record stays unflushed
+    pub(crate) idle: Option<Duration>,
+}
+
+impl FlushPolicy {
+    /// Read `LOG_FLUSH_INTERVAL_MS` and `LOG_IDLE_FLUSH_MS`
+    pub(crate) fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            interval: millis_from_env("LOG_FLUSH_INTERVAL_MS")?,
+            idle: millis_from_env("LOG_IDLE_FLUSH_MS")?,
+        })
+    }
+}
+
+fn millis_from_env(name: &str) -> Result<Option<Duration>, String> {
+    let Ok(raw) = crate::config_file::var(name) else {
+        return Ok(None);
+    };
+    match raw.trim().parse::<u64>() {
+        Ok(ms) if ms > 0 => Ok(Some(Duration::from_millis(ms))),
+        _ => Err(format!(
+            "{}='{}' is not a positive number of milliseconds",
+            name, raw
+        )),
+    }
+}
+
+/// Unflushed records of one output, marked on write and cleared by the flusher
+#[derive(Debug, Default)]
+pub(crate) struct Pending {
+    /// Clock time of the oldest unflushed record plus one, 0 when all are flushed
+    since: AtomicU64,
+    /// Clock time of the last flush
+    last_flush: AtomicU64,
+}
+
+impl Pending {
+    /// Note that a record was written
+    pub(crate) fn mark(&self) {
+        let now = now_ns().max(1);
+        let _ = self
+            .since
+            .compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// Whether the output must be flushed at `now`, clearing the mark if so
+    ///
+    /// The mark is cleared before flushing, so a record written meanwhile
+    /// is flushed on a later round rather than lost.
+    fn take_due(&self, now: u64, interval: Option<Duration>, idle: Option<Duration>) -> bool {
+        let since = self.since.load(Ordering::Relaxed);
+        if since == 0 {
+            return false;
+        }
+        let elapsed = |from: u64, limit: Duration| now.saturating_sub(from) >= nanos(limit);
+        let due = interval
+            .is_some_and(|interval| elapsed(self.last_flush.load(Ordering::Relaxed), interval))
+            || idle.is_some_and(|idle| elapsed(since, idle));
+        if due {
+            self.since.store(0, Ordering::Relaxed);
+            self.last_flush.store(now, Ordering::Relaxed);
+        }
+        due
+    }
+}
+
+fn now_ns() -> u64 {
+    MonotonicClock.uptime_ns()
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// An output the flusher thread looks after
+pub(crate) struct Target {
+    pub(crate) pending: Arc<Pending>,
+    /// Interval of this output, falling back to the policy's
+    pub(crate) interval: Option<Duration>,
+    pub(crate) flush: Box<dyn Fn() + Send>,
+}
+
+/// Start flushing `targets` by `policy`, unless nothing would ever be due
+pub(crate) fn spawn(policy: FlushPolicy, targets: Vec<Target>) {
+    let limits = targets
+        .iter()
+        .flat_map(|target| [target.interval.or(policy.interval), policy.idle])
+        .flatten();
+    let Some(shortest) = limits.min() else {
+        return;
+    };
+    // Checking a few times per limit keeps the overshoot small
+    let tick = (shortest / 4).max(MIN_TICK);
+    let spawned = thread::Builder::new()
+        .name("log-flush".to_string())
+        .spawn(move || {
+            // Records raised by a flushing sink must not mark it again
+            let _entered = crate::reentrant::enter();
+            loop {
+                thread::sleep(tick);
+                flush_due(&policy, &targets, now_ns());
+            }
+        });
+    if let Err(e) = spawned {
+        eprintln!("Cannot start log flush thread: {}", e);
+    }
+}
+
+/// Flush every target due at `now`
+fn flush_due(policy: &FlushPolicy, targets: &[Target], now: u64) {
+    for target in targets {
+        let interval = target.interval.or(policy.interval);
+        if target.pending.take_due(now, interval, policy.idle) {
+            (target.flush)();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    const MS: u64 = 1_000_000;
+
+    fn counting_target(interval: Option<Duration>) -> (Target, Arc<AtomicUsize>) {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&flushes);
+        let target = Target {
+            pending: Arc::new(Pending::default()),
+            interval,
+            flush: Box::new(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }),
+        };
+        (target, flushes)
+    }
+
+    #[test]
+    fn test_only_outputs_with_records_are_flushed() {
+        let policy = FlushPolicy {
+            interval: Some(Duration::from_millis(100)),
+            idle: None,
+        };
+        let (quiet, quiet_flushes) = counting_target(None);
+        let (busy, busy_flushes) = counting_target(Some(Duration::from_millis(10)));
+        busy.pending.since.store(5 * MS, Ordering::Relaxed);
+        let targets = [quiet, busy];
+
+        flush_due(&policy, &targets, 5 * MS);
+        assert_eq!(busy_flushes.load(Ordering::Relaxed), 0);
+        flush_due(&policy, &targets, 10 * MS);
+        flush_due(&policy, &targets, 500 * MS);
+        assert_eq!(busy_flushes.load(Ordering::Relaxed), 1);
+        assert_eq!(quiet_flushes.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_idle_flush_bounds_record_age() {
+        let policy = FlushPolicy {
+            interval: Some(Duration::from_secs(60)),
+            idle: Some(Duration::from_millis(50)),
+        };
+        let (target, flushes) = counting_target(None);
+        target
+            .pending
+            .last_flush
+            .store(1_000 * MS, Ordering::Relaxed);
+        target.pending.since.store(1_010 * MS, Ordering::Relaxed);
+        let targets = [target];
+
+        flush_due(&policy, &targets, 1_059 * MS);
+        assert_eq!(flushes.load(Ordering::Relaxed), 0);
+        flush_due(&policy, &targets, 1_060 * MS);
+        assert_eq!(flushes.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,992 @@
+//! Event formatting
+//!
+//! Wraps tracing-subscriber's JSON formatter so records can be enriched
+//! with extra top-level fields and reshaped for a specific backend before
+//! they are written. Shared enrichment always runs before shaping, so every
+//! output carries the same data regardless of its layout. When no enrichment
+//! is configured events are passed straight through to the inner formatter.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{Format, Json, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::allowlist::Allowlist;
+use crate::channel::ChannelSet;
+use crate::clock::Clock;
+use crate::dedup::Dedup;
+use crate::field_format::FieldFormatters;
+use crate::formatter::RecordFormatter;
+use crate::key_map::KeyMap;
+use crate::limits::Limits;
+use crate::normalize::Normalize;
+use crate::redact::Redactor;
+
+/// Shape of the emitted records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LogFormat {
+    /// tracing-subscriber's JSON layout (`timestamp`, `level`, `fields`, `target`)
+    #[default]
+    Json,
+    /// Datadog reserved attributes (`status`, `message`, `dd.trace_id`, `dd.span_id`)
+    Datadog,
+    /// Elastic Common Schema (`@timestamp`, `log.level`, `message`, `ecs.version`)
+    Ecs,
+    /// `key=value` pairs, for syslog and other line-oriented consumers
+    Logfmt,
+    /// Google Cloud Logging (`severity`, `time`, `logging.googleapis.com/trace`)
+    Gcp,
+}
+
+impl LogFormat {
+    /// Name used in `LOG_FORMAT`
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            LogFormat::Json => "json",
+            LogFormat::Datadog => "datadog",
+            LogFormat::Ecs => "ecs",
+            LogFormat::Logfmt => "logfmt",
+            LogFormat::Gcp => "gcp",
+        }
+    }
+
+    /// Read `LOG_FORMAT` ("json", "datadog", "ecs", "logfmt" or "gcp"), defaulting to JSON
+    pub fn from_env() -> Self {
+        Self::from_env_var("LOG_FORMAT").unwrap_or_default()
+    }
+
+    /// Read a format from an arbitrary variable, `None` when unset or invalid
+    pub(crate) fn from_env_var(name: &str) -> Option<Self> {
+        crate::config_file::var(name)
+            .ok()
+            .and_then(|val| val.parse().ok())
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "datadog" => Ok(LogFormat::Datadog),
+            "ecs" => Ok(LogFormat::Ecs),
+            "logfmt" => Ok(LogFormat::Logfmt),
+            "gcp" => Ok(LogFormat::Gcp),
+            other => Err(format!("Unknown log format '{}'", other)),
+        }
+    }
+}
+
+/// Event formatter shared by every output layer
+#[derive(Clone)]
+pub(crate) struct EventFormat {
+    inner: Format<Json>,
+    style: LogFormat,
+    clock: Option<Arc<dyn Clock>>,
+    sequence: bool,
+    event_id: bool,
+    partition: bool,
+    error_backtrace: bool,
+    process_info: bool,
+    source_location: bool,
+    flatten_span: bool,
+    allowlist: Option<Arc<Allowlist>>,
+    formatters: Option<Arc<FieldFormatters>>,
+    static_fields: Option<Arc<Map<String, Value>>>,
+    redactor: Option<Arc<Redactor>>,
+    limits: Option<Limits>,
+    normalize: Option<Normalize>,
+    dedup_timeout: Option<Duration>,
+    dedup: Option<Arc<Dedup>>,
+    gcp_project: Option<Arc<str>>,
+    channels: Option<ChannelSet>,
+    key_map: Option<Arc<KeyMap>>,
+    record_formatter: Option<Arc<dyn RecordFormatter>>,
+}
+
+impl EventFormat {
+    pub(crate) fn new(enable_spans: bool) -> Self {
+        Self {
+            inner: Format::default()
+                .json()
+                .with_current_span(enable_spans)
+                .with_span_list(false),
+            style: LogFormat::Json,
+            clock: None,
+            sequence: false,
+            event_id: false,
+            partition: false,
+            error_backtrace: false,
+            process_info: false,
+            source_location: false,
+            flatten_span: false,
+            allowlist: None,
+            formatters: None,
+            static_fields: None,
+            redactor: None,
+            limits: None,
+            normalize: None,
+            dedup_timeout: None,
+            dedup: None,
+            gcp_project: std::env::var("GOOGLE_CLOUD_PROJECT").ok().map(Into::into),
+            channels: None,
+            key_map: None,
+            record_formatter: None,
+        }
+    }
+
+    /// Reshape records for a specific backend
+    pub(crate) fn with_style(mut self, style: LogFormat) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Serialize records with a custom formatter instead of the style
+    pub(crate) fn with_record_formatter(
+        mut self,
+        formatter: Option<Arc<dyn RecordFormatter>>,
+    ) -> Self {
+        self.record_formatter = formatter;
+        self
+    }
+
+    /// Rename record keys after reshaping
+    pub(crate) fn with_key_map(mut self, key_map: Option<Arc<KeyMap>>) -> Self {
+        self.key_map = key_map;
+        self
+    }
+
+    /// Write only records of these channels, or all when `None`
+    pub(crate) fn with_channels(mut self, channels: Option<ChannelSet>) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Add a monotonic `uptime_ns` field taken from `clock`
+    pub(crate) fn with_clock(mut self, clock: Option<Arc<dyn Clock>>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Add the per-process `seq` number assigned by the sequence layer
+    pub(crate) fn with_sequence(mut self, sequence: bool) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Add the `event_id` assigned by the sequence layer
+    pub(crate) fn with_event_id(mut self, event_id: bool) -> Self {
+        self.event_id = event_id;
+        self
+    }
+
+    /// Add the `partition` found by the partition layer
+    pub(crate) fn with_partition(mut self, partition: bool) -> Self {
+        self.partition = partition;
+        self
+    }
+
+    /// Add the backtrace captured for ERROR events as a `backtrace` field
+    pub(crate) fn with_error_backtrace(mut self, error_backtrace: bool) -> Self {
+        self.error_backtrace = error_backtrace;
+        self
+    }
+
+    /// Add `hostname`, `pid`, `thread_id` and `thread_name`
+    pub(crate) fn with_process_info(mut self, process_info: bool) -> Self {
+        self.process_info = process_info;
+        self
+    }
+
+    /// Add `file`, `line` and `module_path` of each record's call site
+    pub(crate) fn with_source_location(mut self, source_location: bool) -> Self {
+        self.source_location = source_location;
+        self
+    }
+
+    /// Move the current span's fields to the top level, with its name as `span_name`
+    pub(crate) fn with_flattened_span(mut self, flatten: bool) -> Self {
+        self.flatten_span = flatten;
+        self
+    }
+
+    /// Keep only allowlisted event and span fields
+    pub(crate) fn with_allowlist(mut self, allowlist: Option<Arc<Allowlist>>) -> Self {
+        self.allowlist = allowlist;
+        self
+    }
+
+    /// Render registered fields with their formatting functions
+    pub(crate) fn with_formatters(mut self, formatters: Option<Arc<FieldFormatters>>) -> Self {
+        self.formatters = formatters;
+        self
+    }
+
+    /// Add constant top-level fields to every record
+    pub(crate) fn with_static_fields(mut self, fields: Option<Arc<Map<String, Value>>>) -> Self {
+        self.static_fields = fields;
+        self
+    }
+
+    /// Rewrite sensitive fields before the record is rendered
+    pub(crate) fn with_redactor(mut self, redactor: Option<Arc<Redactor>>) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Truncate long strings and replace oversized records
+    pub(crate) fn with_limits(mut self, limits: Option<Limits>) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Rewrite line breaks and control characters in string values
+    pub(crate) fn with_normalize(mut self, normalize: Option<Normalize>) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Collapse consecutive identical records, holding summaries back for at most `timeout`
+    pub(crate) fn with_dedup(mut self, timeout: Option<Duration>) -> Self {
+        self.dedup_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn dedup_timeout(&self) -> Option<Duration> {
+        self.dedup_timeout
+    }
+
+    /// Attach the repeat state of the output this formatter writes to
+    pub(crate) fn with_dedup_state(mut self, dedup: Arc<Dedup>) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    fn is_passthrough(&self) -> bool {
+        self.style == LogFormat::Json
+            && self.clock.is_none()
+            && !self.sequence
+            && !self.event_id
+            && !self.partition
+            && !self.process_info
+            && !self.source_location
+            && !self.flatten_span
+            && self.allowlist.is_none()
+            && self.formatters.is_none()
+            && self.static_fields.is_none()
+            && self.redactor.is_none()
+            && self.limits.is_none()
+            && self.normalize.is_none()
+            && self.dedup.is_none()
+            && self.key_map.is_none()
+            && self.record_formatter.is_none()
+            && crate::http::field_style() == crate::http::FieldStyle::Flat
+    }
+
+    /// Add the fields shared by every output
+    fn enrich(&self, record: &mut Map<String, Value>) {
+        crate::record::stamp(record);
+        crate::context::apply(record);
+        if crate::http::field_style() == crate::http::FieldStyle::Otel {
+            crate::http::nest_fields(record);
+        }
+        if self.sequence {
+            if let Some(seq) = crate::sequence::current() {
+                record.insert("seq".to_string(), seq.into());
+            }
+        }
+        if self.event_id {
+            if let Some(event_id) = crate::sequence::current_event_id() {
+                record.insert("event_id".to_string(), event_id.into());
+            }
+        }
+        if self.partition {
+            if let Some(partition) = crate::partition::current() {
+                record.insert("partition".to_string(), Value::from(&*partition));
+            }
+        }
+        if let Some(clock) = &self.clock {
+            record.insert("uptime_ns".to_string(), clock.uptime_ns().into());
+        }
+        if self.process_info {
+            crate::process_info::apply(record);
+        }
+        if let Some(fields) = &self.static_fields {
+            crate::static_fields::apply(fields, record);
+        }
+        if let Some(allowlist) = &self.allowlist {
+            allowlist.apply(record);
+        }
+        if let Some(formatters) = &self.formatters {
+            formatters.apply(record);
+        }
+        if let Some(redactor) = &self.redactor {
+            redactor.redact(record);
+        }
+        if let Some(normalize) = self.normalize {
+            normalize.apply(record);
+        }
+        if let Some(limits) = &self.limits {
+            limits.truncate_fields(record);
+        }
+    }
+
+    /// Steps applied to the reshaped record: span flattening, then key renames
+    fn finish(&self, record: &mut Map<String, Value>) {
+        self.flatten(record);
+        if let Some(key_map) = &self.key_map {
+            key_map.apply(record);
+        }
+    }
+
+    /// Lift span fields to the top level; runs after reshaping so backends still find span ids
+    fn flatten(&self, record: &mut Map<String, Value>) {
+        if !self.flatten_span {
+            return;
+        }
+        if let Some(Value::Object(span)) = record.remove("span") {
+            for (key, value) in span {
+                let key = if key == "name" {
+                    "span_name".to_string()
+                } else {
+                    key
+                };
+                record.entry(key).or_insert(value);
+            }
+        }
+    }
+
+    /// Reshape an enriched record and render it as a single line
+    pub(crate) fn render(&self, mut record: Map<String, Value>) -> Result<String, fmt::Error> {
+        if let Some(formatter) = &self.record_formatter {
+            self.finish(&mut record);
+            let mut out = String::new();
+            formatter.format_record(&record, &mut out)?;
+            return Ok(out);
+        }
+        match self.style {
+            LogFormat::Json => {}
+            LogFormat::Datadog => to_datadog(&mut record),
+            LogFormat::Ecs => to_ecs(&mut record),
+            LogFormat::Gcp => to_gcp(&mut record, self.gcp_project.as_deref()),
+            LogFormat::Logfmt => {
+                self.finish(&mut record);
+                return Ok(to_logfmt(record));
+            }
+        }
+        self.finish(&mut record);
+        serde_json::to_string(&record).map_err(|_| fmt::Error)
+    }
+}
+
+/// Move the standard keys onto Datadog's reserved attributes
+///
+/// `level` becomes a lowercase `status`, `fields.message` is lifted to a
+/// top-level `message`, and `trace_id`/`span_id` found on the event or its
+/// span are nested under `dd` so Datadog correlates them with APM traces.
+fn to_datadog(record: &mut Map<String, Value>) {
+    let mut fields = match record.remove("fields") {
+        Some(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    let message = fields.remove("message");
+
+    let mut dd = Map::new();
+    for key in ["trace_id", "span_id"] {
+        let value = fields.remove(key).or_else(|| {
+            record
+                .get_mut("span")
+                .and_then(Value::as_object_mut)
+                .and_then(|span| span.remove(key))
+        });
+        if let Some(value) = value {
+            dd.insert(key.to_string(), value);
+        }
+    }
+
+    let mut shaped = Map::new();
+    for (key, value) in std::mem::take(record) {
+        match key.as_str() {
+            "level" => {
+                let status = value.as_str().unwrap_or_default().to_ascii_lowercase();
+                shaped.insert("status".to_string(), status.into());
+                if let Some(message) = message.clone() {
+                    shaped.insert("message".to_string(), message);
+                }
+            }
+            _ => {
+                shaped.insert(key, value);
+            }
+        }
+    }
+    if !fields.is_empty() {
+        shaped.insert("fields".to_string(), Value::Object(fields));
+    }
+    if !dd.is_empty() {
+        shaped.insert("dd".to_string(), Value::Object(dd));
+    }
+    *record = shaped;
+}
+
+/// Reshape a record into Elastic Common Schema
+///
+/// `timestamp` becomes `@timestamp`, `level` and `target` move under
+/// `log.level`/`log.logger`, `fields.message` is lifted to `message` and
+/// the remaining event fields stay under `fields`.
+fn to_ecs(record: &mut Map<String, Value>) {
+    let mut fields = match record.remove("fields") {
+        Some(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    let mut log = Map::new();
+    let mut shaped = Map::new();
+
+    for (key, value) in std::mem::take(record) {
+        match key.as_str() {
+            "timestamp" => {
+                shaped.insert("@timestamp".to_string(), value);
+            }
+            "level" => {
+                let level = value.as_str().unwrap_or_default().to_ascii_lowercase();
+                log.insert("level".to_string(), level.into());
+            }
+            "target" => {
+                log.insert("logger".to_string(), value);
+            }
+            _ => {
+                shaped.insert(key, value);
+            }
+        }
+    }
+    if let Some(message) = fields.remove("message") {
+        shaped.insert("message".to_string(), message);
+    }
+    shaped.insert("log".to_string(), Value::Object(log));
+    if !fields.is_empty() {
+        shaped.insert("fields".to_string(), Value::Object(fields));
+    }
+    shaped.insert("ecs".to_string(), serde_json::json!({ "version": "8.11" }));
+    *record = shaped;
+}
+
+/// Map tracing levels onto Cloud Logging's `LogSeverity` enum
+fn gcp_severity(level: &str) -> &'static str {
+    match level {
+        "TRACE" | "DEBUG" => "DEBUG",
+        "INFO" => "INFO",
+        "WARN" => "WARNING",
+        "ERROR" => "ERROR",
+        _ => "DEFAULT",
+    }
+}
+
+/// Reshape a record for Google Cloud Logging's structured stdout parsing
+///
+/// `level` becomes `severity`, `timestamp` becomes `time`, and event fields
+/// are lifted to the top level so they land directly in `jsonPayload`. A
+/// `trace_id`/`span_id` on the event or its span is emitted as
+/// `logging.googleapis.com/trace` (qualified with `GOOGLE_CLOUD_PROJECT`
+/// when set) and `logging.googleapis.com/spanId`.
+fn to_gcp(record: &mut Map<String, Value>, project: Option<&str>) {
+    let mut fields = match record.remove("fields") {
+        Some(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    let mut take_id = |record: &mut Map<String, Value>, key: &str| {
+        fields.remove(key).or_else(|| {
+            record
+                .get_mut("span")
+                .and_then(Value::as_object_mut)
+                .and_then(|span| span.remove(key))
+        })
+    };
+    let trace_id = take_id(record, "trace_id");
+    let span_id = take_id(record, "span_id");
+
+    let mut shaped = Map::new();
+    for (key, value) in std::mem::take(record) {
+        match key.as_str() {
+            "timestamp" => {
+                shaped.insert("time".to_string(), value);
+            }
+            "level" => {
+                let severity = gcp_severity(value.as_str().unwrap_or_default());
+                shaped.insert("severity".to_string(), severity.into());
+            }
+            _ => {
+                shaped.insert(key, value);
+            }
+        }
+    }
+    if let Some(message) = fields.remove("message") {
+        shaped.insert("message".to_string(), message);
+    }
+    if let Some(trace_id) = trace_id {
+        let trace_id = match (trace_id, project) {
+            (Value::String(id), Some(project)) => {
+                format!("projects/{}/traces/{}", project, id).into()
+            }
+            (id, _) => id,
+        };
+        shaped.insert("logging.googleapis.com/trace".to_string(), trace_id);
+    }
+    if let Some(span_id) = span_id {
+        shaped.insert("logging.googleapis.com/spanId".to_string(), span_id);
+    }
+    for (key, value) in fields {
+        shaped.entry(key).or_insert(value);
+    }
+    *record = shaped;
+}
+
+/// Render a record as logfmt, lifting event fields to the top level and
+/// flattening other nested objects into dotted keys
+fn to_logfmt(record: Map<String, Value>) -> String {
+    fn push_pairs(out: &mut Vec<String>, prefix: Option<&str>, map: Map<String, Value>) {
+        for (key, value) in map {
+            let key = match prefix {
+                Some(prefix) => format!("{}.{}", prefix, key),
+                None => key,
+            };
+            match value {
+                Value::Object(nested) if key == "fields" => push_pairs(out, None, nested),
+                Value::Object(nested) => push_pairs(out, Some(&key), nested),
+                Value::String(s) => out.push(format!("{}={}", key, logfmt_string(&s))),
+                other => out.push(format!("{}={}", key, logfmt_string(&other.to_string()))),
+            }
+        }
+    }
+
+    let mut pairs = Vec::new();
+    push_pairs(&mut pairs, None, record);
+    pairs.join(" ")
+}
+
+fn logfmt_string(s: &str) -> String {
+    if !s.is_empty() && !s.contains(|c: char| c == ' ' || c == '=' || c == '"' || c.is_control()) {
+        return s.to_string();
+    }
+    // JSON string escaping doubles as logfmt quoting
+    Value::String(s.to_string()).to_string()
+}
+
+/// Passes the inner formatter's output through, with `schema_version` as the first key
+struct Versioned<'a, 'writer> {
+    writer: &'a mut Writer<'writer>,
+    started: bool,
+    /// Bytes passed on, for the logger's own stats
+    written: usize,
+}
+
+impl fmt::Write for Versioned<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.written += s.len();
+        if self.started || s.is_empty() {
+            return self.writer.write_str(s);
+        }
+        self.started = true;
+        let rest = s.strip_prefix('{').ok_or(fmt::Error)?;
+        let version = format!(
+            "{{\"{}\":{},",
+            crate::record::SCHEMA_VERSION_KEY,
+            crate::record::SCHEMA_VERSION
+        );
+        self.written += version.len() - 1;
+        self.writer.write_str(&version)?;
+        self.writer.write_str(rest)
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for EventFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        // Span records are produced for every mode and dropped here, so the mode can change at runtime
+        if event.metadata().is_span() && !crate::spans::span_record_enabled(event) {
+            return Ok(());
+        }
+        if let Some(channels) = self.channels {
+            if !channels.contains(crate::channel::of(event)) {
+                return Ok(());
+            }
+        }
+        let timing = crate::spans::span_timing(event, ctx);
+        let backtrace = (self.error_backtrace && crate::error_backtrace::applies_to(event))
+            .then(crate::error_backtrace::current)
+            .flatten();
+        // Records bridged from the `log` crate carry their metadata as `log.*` fields
+        let from_log = event.metadata().fields().field("log.target").is_some();
+        let valuable = crate::valuable_fields::collect(event);
+        let mut buf = String::new();
+        self.inner.format_event(ctx, Writer::new(&mut buf), event)?;
+        // Serialized values must be decoded, so records holding them are never passed through
+        if self.is_passthrough()
+            && timing.is_none()
+            && backtrace.is_none()
+            && !from_log
+            && valuable.is_none()
+            && !crate::context::is_active()
+            && !buf.contains(crate::json_field::SENTINEL_ESCAPED)
+        {
+            let mut versioned = Versioned {
+                writer: &mut writer,
+                started: false,
+                written: 0,
+            };
+            let result = fmt::Write::write_str(&mut versioned, &buf);
+            crate::metrics::add_bytes_written(versioned.written);
+            return result;
+        }
+
+        let mut record: Map<String, Value> = serde_json::from_str(&buf).map_err(|_| fmt::Error)?;
+        crate::json_field::decode_record(&mut record);
+        if let (Some(valuable), Some(Value::Object(fields))) = (valuable, record.get_mut("fields"))
+        {
+            fields.extend(valuable);
+        }
+        if let (Some(timing), Some(Value::Object(fields))) = (timing, record.get_mut("fields")) {
+            fields.extend(timing);
+        }
+        if let (Some(backtrace), Some(Value::Object(fields))) =
+            (backtrace, record.get_mut("fields"))
+        {
+            fields.insert("backtrace".to_string(), Value::from(&*backtrace));
+        }
+        if self.source_location {
+            crate::source_location::apply(&mut record, event.metadata(), from_log);
+        }
+        if let (true, Some(Value::Object(fields))) = (from_log, record.get_mut("fields")) {
+            fields.retain(|key, _| !key.starts_with("log."));
+        }
+        self.enrich(&mut record);
+        if let Some(dedup) = &self.dedup {
+            match dedup.observe(record) {
+                Some(unique) => record = unique,
+                None => return Ok(()),
+            }
+        }
+
+        let stub = self.limits.and_then(|limits| limits.stub_base(&record));
+        let mut line = self.render(record)?;
+        if let (Some(limits), Some(stub)) = (self.limits, stub) {
+            if limits.is_oversized(line.len()) {
+                line = self.render(limits.stub(stub, line.len()))?;
+            }
+        }
+        if let Some(normalize) = self.normalize {
+            line = normalize.single_line(line);
+        }
+        if line.is_empty() {
+            return Ok(());
+        }
+        crate::metrics::add_bytes_written(line.len() + 1);
+        writeln!(writer, "{}", line)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::Mutex;
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// In-memory writer used to inspect formatted output in tests
+    #[derive(Clone, Default)]
+    pub(crate) struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Capture {
+        /// Parse every captured line as a JSON record
+        pub(crate) fn records(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    /// Run `f` with a subscriber formatting through `format` and return the records
+    pub(crate) fn capture_with(format: EventFormat, f: impl FnOnce()) -> Vec<Value> {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .event_format(format)
+                .with_writer(capture.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, f);
+        capture.records()
+    }
+
+    #[test]
+    fn test_passthrough_keeps_standard_fields() {
+        let records = capture_with(EventFormat::new(true), || tracing::info!(user_id = 7, "hi"));
+        assert_eq!(records[0]["fields"]["message"], "hi");
+        assert_eq!(records[0]["fields"]["user_id"], 7);
+        assert!(records[0].get("uptime_ns").is_none());
+    }
+
+    #[test]
+    fn test_redactor_rewrites_event_fields() {
+        let redactor = Redactor::new().field("password");
+        let format = EventFormat::new(true).with_redactor(Some(Arc::new(redactor)));
+        let records = capture_with(format, || tracing::info!(password = "hunter2", "login"));
+        assert_eq!(records[0]["fields"]["password"], "[REDACTED]");
+        assert_eq!(records[0]["fields"]["message"], "login");
+    }
+
+    #[test]
+    fn test_limits_truncate_and_replace_records() {
+        let limits = Limits::new()
+            .max_field_len(2048)
+            .max_event_bytes(4096)
+            .unwrap();
+        let format = EventFormat::new(true).with_limits(Some(limits));
+        let records = capture_with(format, || {
+            tracing::info!(body = %"x".repeat(3000), "upload");
+            let bodies = ["y".repeat(2000), "z".repeat(2000), "w".repeat(2000)];
+            tracing::warn!(a = %bodies[0], b = %bodies[1], c = %bodies[2], "batch");
+        });
+        let body = records[0]["fields"]["body"].as_str().unwrap();
+        assert!(body.ends_with(crate::limits::TRUNCATED));
+        assert_eq!(body.len(), 2048 + crate::limits::TRUNCATED.len());
+        assert_eq!(records[1]["level"], "WARN");
+        assert_eq!(records[1]["fields"]["original_message"], "batch");
+        assert!(records[1]["fields"]["event_bytes"].as_u64().unwrap() > 4096);
+        assert!(records[1]["fields"].get("a").is_none());
+    }
+
+    #[test]
+    fn test_formatters_run_before_redaction() {
+        let formatters = FieldFormatters::new()
+            .field("amount_cents", crate::field_format::cents_as_decimal)
+            .field("card", |v| v.as_str().map(|s| s.replace(' ', "").into()));
+        let redactor = Redactor::new().pattern(crate::redact::CREDIT_CARD).unwrap();
+        let format = EventFormat::new(true)
+            .with_formatters(Some(Arc::new(formatters)))
+            .with_redactor(Some(Arc::new(redactor)));
+        let records = capture_with(format, || {
+            tracing::info!(amount_cents = 250u64, card = "4111 1111 1111 1111", "paid")
+        });
+        assert_eq!(records[0]["fields"]["amount_cents"], "2.50");
+        assert_eq!(records[0]["fields"]["card"], "[REDACTED]");
+    }
+
+    #[test]
+    fn test_static_fields_added_at_top_level() {
+        let mut fields = Map::new();
+        fields.insert("service".to_string(), "payments".into());
+        let format = EventFormat::new(true).with_static_fields(Some(Arc::new(fields)));
+        let records = capture_with(format, || tracing::info!("hi"));
+        assert_eq!(records[0]["service"], "payments");
+        assert_eq!(records[0]["fields"]["message"], "hi");
+    }
+
+    #[test]
+    fn test_record_formatter_replaces_style() {
+        let envelope = |record: &Map<String, Value>, out: &mut String| {
+            if record["level"] == "DEBUG" {
+                return Ok(());
+            }
+            let envelope = serde_json::json!({ "v": 2, "payload": record });
+            fmt::Write::write_fmt(out, format_args!("{}", envelope))
+        };
+        let format = EventFormat::new(false)
+            .with_style(LogFormat::Datadog)
+            .with_record_formatter(Some(Arc::new(envelope)));
+        let records = capture_with(format, || {
+            tracing::debug!("dropped");
+            tracing::info!(user_id = 7, "kept");
+        });
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["v"], 2);
+        assert_eq!(records[0]["payload"]["fields"]["user_id"], 7);
+        assert_eq!(records[0]["payload"]["level"], "INFO");
+    }
+
+    #[test]
+    fn test_records_match_log_record_schema() {
+        use crate::record::{LogRecord, SCHEMA_VERSION};
+
+        for format in [
+            EventFormat::new(true),
+            EventFormat::new(true).with_sequence(true),
+        ] {
+            let records = capture_with(format, || {
+                tracing::info_span!("checkout", order_id = 42)
+                    .in_scope(|| tracing::warn!(retry = true, "slow"));
+            });
+            let record = records.last().unwrap().clone();
+            let parsed = LogRecord::from_value(record.clone()).unwrap();
+            assert_eq!(record["schema_version"], SCHEMA_VERSION);
+            assert_eq!(parsed.message(), Some("slow"));
+            assert_eq!(parsed.span.as_ref().unwrap()["order_id"], 42);
+            assert_eq!(parsed.to_value(), record);
+        }
+    }
+
+    #[test]
+    fn test_key_map_renames_standard_keys() {
+        let key_map = KeyMap::new()
+            .rename("timestamp", "@timestamp")
+            .rename("level", "severity")
+            .rename("fields.message", "message");
+        let format = EventFormat::new(false).with_key_map(Some(Arc::new(key_map)));
+        let records = capture_with(format, || tracing::warn!(port = 80, "Started"));
+        let record = &records[0];
+        assert_eq!(record["severity"], "WARN");
+        assert_eq!(record["message"], "Started");
+        assert!(record["@timestamp"].is_string());
+        assert!(record.get("timestamp").is_none());
+        assert_eq!(record["fields"], serde_json::json!({"port": 80}));
+    }
+
+    #[test]
+    fn test_output_keeps_only_its_channels() {
+        let channels = ChannelSet::parse("business,audit").unwrap();
+        let format = EventFormat::new(false).with_channels(Some(channels));
+        let records = capture_with(format, || {
+            tracing::info!("cache warmed");
+            tracing::info!(channel = "business", plan = "gold", "upgraded");
+            tracing::info!(channel = %crate::channel::Channel::Audit, "role granted");
+        });
+        let messages: Vec<_> = records.iter().map(|r| &r["fields"]["message"]).collect();
+        assert_eq!(messages, ["upgraded", "role granted"]);
+        assert_eq!(records[0]["fields"]["channel"], "business");
+    }
+
+    #[test]
+    fn test_flattened_span_fields() {
+        let format = EventFormat::new(true).with_flattened_span(true);
+        let records = capture_with(format, || {
+            let span = tracing::info_span!("checkout", order_id = 42, level = "gold");
+            span.in_scope(|| tracing::info!("paid"));
+        });
+        let record = &records[0];
+        assert_eq!(record["order_id"], 42);
+        assert_eq!(record["span_name"], "checkout");
+        assert_eq!(record["level"], "INFO");
+        assert!(record.get("span").is_none());
+    }
+
+    #[test]
+    fn test_clock_adds_uptime_field() {
+        let clock: Arc<dyn Clock> = Arc::new(|| 42u64);
+        let format = EventFormat::new(true).with_clock(Some(clock));
+        let records = capture_with(format, || tracing::info!("hi"));
+        assert_eq!(records[0]["uptime_ns"], 42);
+        assert!(records[0].get("timestamp").is_some());
+    }
+
+    #[test]
+    fn test_source_location_fields() {
+        let format = EventFormat::new(true).with_source_location(true);
+        let (records, line) = (capture_with(format, || tracing::info!("hi")), line!());
+        assert_eq!(records[0]["file"], file!());
+        assert_eq!(records[0]["line"], line);
+        assert_eq!(records[0]["module_path"], module_path!());
+    }
+
+    #[test]
+    fn test_datadog_reserved_attributes() {
+        let format = EventFormat::new(true).with_style(LogFormat::Datadog);
+        let records = capture_with(format, || {
+            tracing::warn!(trace_id = "abc", span_id = "def", user_id = 7, "slow")
+        });
+        let record = &records[0];
+        assert_eq!(record["status"], "warn");
+        assert_eq!(record["message"], "slow");
+        assert_eq!(record["dd"]["trace_id"], "abc");
+        assert_eq!(record["dd"]["span_id"], "def");
+        assert_eq!(record["fields"]["user_id"], 7);
+        assert!(record.get("level").is_none());
+    }
+
+    #[test]
+    fn test_ecs_layout() {
+        let format = EventFormat::new(true).with_style(LogFormat::Ecs);
+        let records = capture_with(format, || tracing::info!(user_id = 7, "hi"));
+        let record = &records[0];
+        assert!(record.get("@timestamp").is_some());
+        assert_eq!(record["log"]["level"], "info");
+        assert_eq!(record["message"], "hi");
+        assert_eq!(record["fields"]["user_id"], 7);
+        assert_eq!(record["ecs"]["version"], "8.11");
+    }
+
+    #[test]
+    fn test_gcp_layout() {
+        let mut record: Map<String, Value> = serde_json::from_str(
+            r#"{"timestamp":"2025-01-01T00:00:00Z","level":"WARN","fields":{"message":"slow","trace_id":"abc","order_id":42},"target":"app"}"#,
+        )
+        .unwrap();
+        to_gcp(&mut record, Some("my-project"));
+
+        assert_eq!(record["severity"], "WARNING");
+        assert_eq!(record["time"], "2025-01-01T00:00:00Z");
+        assert_eq!(record["message"], "slow");
+        assert_eq!(record["order_id"], 42);
+        assert_eq!(
+            record["logging.googleapis.com/trace"],
+            "projects/my-project/traces/abc"
+        );
+        assert!(record.get("level").is_none());
+        assert!(record.get("fields").is_none());
+        assert_eq!(gcp_severity("TRACE"), "DEBUG");
+    }
+
+    #[test]
+    fn test_logfmt_rendering() {
+        let mut record = Map::new();
+        record.insert("level".to_string(), "INFO".into());
+        record.insert(
+            "fields".to_string(),
+            serde_json::json!({ "message": "login", "user_id": 7 }),
+        );
+        record.insert("span".to_string(), serde_json::json!({ "name": "auth" }));
+
+        let line = to_logfmt(record);
+        let mut pairs: Vec<&str> = line.split(' ').collect();
+        pairs.sort_unstable();
+        assert_eq!(
+            pairs,
+            ["level=INFO", "message=login", "span.name=auth", "user_id=7"]
+        );
+        assert_eq!(logfmt_string("user logged in"), r#""user logged in""#);
+        assert_eq!(logfmt_string(""), r#""""#);
+    }
+}
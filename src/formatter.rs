@@ -0,0 +1,48 @@
+//! Custom record serialization
+//!
+//! Implement [`RecordFormatter`] to write records in a company-specific
+//! envelope or another encoding, while keeping this crate's initialization,
+//! filtering, enrichment, file rotation and sinks. The formatter receives
+//! each record in the default JSON layout after every enrichment step
+//! (context and static fields, redaction, key renames) and replaces the
+//! [`LogFormat`](crate::LogFormat) reshaping of the outputs it is set on.
+//!
+//! Outputs are line oriented: write one line per record without the
+//! trailing newline, and text-encode binary formats (e.g. CBOR as base64).
+//! The in-memory ring buffer always keeps JSON, so
+//! [`export_recent`](crate::export_recent) is unaffected.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+/// Serializer for formatted records
+///
+/// # Examples
+/// ```no_run
+/// use std::fmt::Write;
+///
+/// // Wrap every record in the envelope the company's log router expects
+/// let envelope = |record: &serde_json::Map<String, serde_json::Value>, out: &mut String| {
+///     let envelope = serde_json::json!({ "v": 2, "source": "payments", "payload": record });
+///     write!(out, "{}", envelope)
+/// };
+/// custom_tracing_logger::builder()
+///     .with_record_formatter(envelope)
+///     .init();
+/// ```
+pub trait RecordFormatter: Send + Sync {
+    /// Write one record to `out`, without the trailing newline
+    ///
+    /// Writing nothing drops the record. Returning an error drops it too.
+    fn format_record(&self, record: &Map<String, Value>, out: &mut String) -> fmt::Result;
+}
+
+impl<F> RecordFormatter for F
+where
+    F: Fn(&Map<String, Value>, &mut String) -> fmt::Result + Send + Sync,
+{
+    fn format_record(&self, record: &Map<String, Value>, out: &mut String) -> fmt::Result {
+        self(record, out)
+    }
+}
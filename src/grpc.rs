@@ -0,0 +1,99 @@
+//! Structured records of gRPC calls
+//!
+//! The gRPC counterpart of [`log_request!`](crate::log_request!): a call is
+//! logged with its service, method, status code and duration. Clients log
+//! calls with [`structured::grpc_call`](crate::structured::grpc_call);
+//! servers get the same record from the `grpc` feature's
+//! [`middleware::grpc`](crate::middleware) layer. With the OpenTelemetry
+//! field style ([`http::set_field_style`](crate::http::set_field_style))
+//! the fields are named `rpc.service`, `rpc.method` and
+//! `rpc.grpc.status_code`, nested like the HTTP fields.
+
+/// Canonical names of the gRPC status codes, indexed by code
+const CODE_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+/// Canonical name of a gRPC status code, e.g. "NOT_FOUND" for 5
+///
+/// Codes outside the specification are "UNKNOWN".
+pub fn code_name(code: u16) -> &'static str {
+    CODE_NAMES
+        .get(usize::from(code))
+        .copied()
+        .unwrap_or(CODE_NAMES[2])
+}
+
+/// Split a gRPC path such as `/shop.Orders/Create` into service and method
+///
+/// The leading slash is optional; a path without a service yields an empty one.
+pub fn split_method(path: &str) -> (&str, &str) {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// Log a completed call; `request_id` is added when known
+pub(crate) fn log_call(path: &str, code: u16, duration_ms: u64, request_id: Option<&str>) {
+    let (service, method) = split_method(path);
+    let status = code_name(code);
+    if crate::http::field_style() == crate::http::FieldStyle::Otel {
+        tracing::info!(
+            rpc.system = "grpc",
+            rpc.service = service,
+            rpc.method = method,
+            rpc.grpc.status_code = code,
+            grpc_status = status,
+            duration_ms = duration_ms,
+            request_id = request_id,
+            "gRPC call completed"
+        );
+    } else {
+        tracing::info!(
+            grpc_service = service,
+            grpc_method = method,
+            grpc_code = code,
+            grpc_status = status,
+            duration_ms = duration_ms,
+            request_id = request_id,
+            "gRPC call completed"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_names_and_methods() {
+        assert_eq!(code_name(0), "OK");
+        assert_eq!(code_name(5), "NOT_FOUND");
+        assert_eq!(code_name(16), "UNAUTHENTICATED");
+        assert_eq!(code_name(99), "UNKNOWN");
+        assert_eq!(
+            split_method("/shop.Orders/Create"),
+            ("shop.Orders", "Create")
+        );
+        assert_eq!(
+            split_method("shop.Orders/Create"),
+            ("shop.Orders", "Create")
+        );
+        assert_eq!(split_method("Ping"), ("", "Ping"));
+    }
+}
@@ -0,0 +1,710 @@
+//! Correlation headers of HTTP requests
+//!
+//! [`CorrelationHeaders`] maps request headers such as `X-Request-Id`,
+//! `Idempotency-Key` and the B3 trace headers onto span fields. A request
+//! span created with [`Correlation::request_span`] carries them, so every
+//! record logged while handling the request can be joined with the logs of
+//! the calling service, and [`Correlation::headers`] forwards them to
+//! downstream calls. Works with any framework: pass the headers as name and
+//! value pairs (e.g. an `http::HeaderMap`).
+//!
+//! [`set_field_style`] switches the fields of
+//! [`log_request!`](crate::log_request!) and
+//! [`structured::http_request`](crate::structured::http_request) from flat
+//! `method`/`path`/`status` keys to nested objects named after the
+//! OpenTelemetry semantic conventions.
+//!
+//! [`set_quiet_requests`] keeps health checks and other noisy requests out
+//! of the INFO stream of [`log_request!`](crate::log_request!), see
+//! [`QuietRequests`].
+//!
+//! [`DebugHeader`] lets a caller holding a secret token have its request
+//! logged at DEBUG, see [`context::enable_debug`](crate::context::enable_debug).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use serde_json::{Map, Value};
+use tracing::{Level, Span};
+
+static OTEL_FIELDS: AtomicBool = AtomicBool::new(false);
+
+/// Requests logged below INFO or not at all, `None` until configured
+static QUIET: RwLock<Option<QuietRequests>> = RwLock::new(None);
+
+/// Shape of the fields of HTTP request records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldStyle {
+    /// `method`, `path` and `status` (default)
+    #[default]
+    Flat,
+    /// `http.request.method`, `url.path` and `http.response.status_code`, as nested objects
+    Otel,
+}
+
+impl std::str::FromStr for FieldStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "flat" => Ok(FieldStyle::Flat),
+            "otel" => Ok(FieldStyle::Otel),
+            other => Err(format!(
+                "unknown HTTP field style '{}', expected flat or otel",
+                other
+            )),
+        }
+    }
+}
+
+/// Select the field names of HTTP request records
+///
+/// ```
+/// use custom_tracing_logger::http::{self, FieldStyle};
+///
+/// http::set_field_style(FieldStyle::Otel);
+/// custom_tracing_logger::log_request!("GET", "/api/users", 200, 45);
+/// // "fields":{"message":"HTTP request completed","duration_ms":45,
+/// //           "http":{"request":{"method":"GET"},"response":{"status_code":200}},"url":{"path":"/api/users"}}
+/// # http::set_field_style(FieldStyle::Flat);
+/// ```
+pub fn set_field_style(style: FieldStyle) {
+    OTEL_FIELDS.store(style == FieldStyle::Otel, Ordering::Relaxed);
+}
+
+/// Current field names of HTTP request records
+pub fn field_style() -> FieldStyle {
+    if OTEL_FIELDS.load(Ordering::Relaxed) {
+        FieldStyle::Otel
+    } else {
+        FieldStyle::Flat
+    }
+}
+
+/// What happens to the records of quiet requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuietAction {
+    /// Log them at DEBUG (default), so they reappear with `RUST_LOG=debug`
+    #[default]
+    Debug,
+    /// Don't log them
+    Drop,
+}
+
+impl std::str::FromStr for QuietAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "debug" => Ok(QuietAction::Debug),
+            "drop" => Ok(QuietAction::Drop),
+            other => Err(format!(
+                "unknown quiet request action '{}', expected debug or drop",
+                other
+            )),
+        }
+    }
+}
+
+/// Requests kept out of the INFO stream of [`log_request!`](crate::log_request!)
+///
+/// Requests to quiet paths, and successful (2xx) requests beyond the
+/// sampled share, are logged at DEBUG or dropped. Requests answered with
+/// 4xx or 5xx are always logged at INFO, so a failing health check still
+/// shows.
+///
+/// # Examples
+/// ```no_run
+/// use custom_tracing_logger::http::{QuietAction, QuietRequests};
+///
+/// custom_tracing_logger::builder()
+///     .with_quiet_requests(
+///         QuietRequests::new()
+///             .path("/health")
+///             .path("/metrics")
+///             .path("/internal/*")
+///             .action(QuietAction::Drop)
+///             .sample_success(0.1),
+///     )
+///     .init();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuietRequests {
+    paths: Vec<String>,
+    action: QuietAction,
+    success_rate: f64,
+}
+
+impl Default for QuietRequests {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            action: QuietAction::default(),
+            success_rate: 1.0,
+        }
+    }
+}
+
+impl QuietRequests {
+    /// No quiet requests; every request is logged at INFO
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Quiet requests to `path`, or to every path under it when it ends with `*`
+    pub fn path(mut self, path: &str) -> Self {
+        self.paths.push(path.trim().to_string());
+        self
+    }
+
+    /// Log quiet requests at DEBUG (default) or drop them
+    pub fn action(mut self, action: QuietAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Keep `rate` (0 to 1) of the successful requests at INFO; the rest are quiet
+    pub fn sample_success(mut self, rate: f64) -> Self {
+        self.success_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Whether every request is logged at INFO
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.success_rate >= 1.0
+    }
+
+    /// Quiet paths, in the form of `LOG_QUIET_PATHS`
+    pub fn paths(&self) -> String {
+        self.paths.join(",")
+    }
+
+    /// Read `LOG_QUIET_PATHS`, `LOG_QUIET_ACTION` and `LOG_SUCCESS_SAMPLE`, if any is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |name: &str| {
+            crate::config_file::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        };
+        let (paths, action, rate) = (
+            var("LOG_QUIET_PATHS"),
+            var("LOG_QUIET_ACTION"),
+            var("LOG_SUCCESS_SAMPLE"),
+        );
+        if paths.is_none() && rate.is_none() {
+            return Ok(None);
+        }
+        let mut quiet = Self::new();
+        for path in paths.iter().flat_map(|paths| paths.split(',')) {
+            if !path.trim().is_empty() {
+                quiet = quiet.path(path);
+            }
+        }
+        if let Some(action) = action {
+            quiet = quiet.action(action.parse()?);
+        }
+        if let Some(rate) = rate {
+            let rate = rate
+                .trim()
+                .parse()
+                .ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .ok_or_else(|| {
+                    format!(
+                        "LOG_SUCCESS_SAMPLE='{}' is not a rate between 0 and 1",
+                        rate.trim()
+                    )
+                })?;
+            quiet = quiet.sample_success(rate);
+        }
+        Ok(Some(quiet))
+    }
+
+    fn is_quiet_path(&self, path: &str) -> bool {
+        let path = path.split('?').next().unwrap_or(path);
+        self.paths
+            .iter()
+            .any(|quiet| match quiet.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == quiet,
+            })
+    }
+
+    /// Level of the record of a request to `path` answered with `status`, `None` to drop it
+    pub fn level(&self, path: &str, status: u16) -> Option<Level> {
+        let quiet = status < 400
+            && (self.is_quiet_path(path)
+                || ((200..300).contains(&status)
+                    && self.success_rate < 1.0
+                    && crate::sampling::uniform() >= self.success_rate));
+        match (quiet, self.action) {
+            (false, _) => Some(Level::INFO),
+            (true, QuietAction::Debug) => Some(Level::DEBUG),
+            (true, QuietAction::Drop) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for QuietRequests {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self.action {
+            QuietAction::Debug => "logged at DEBUG",
+            QuietAction::Drop => "dropped",
+        };
+        match (self.paths.is_empty(), self.success_rate < 1.0) {
+            (false, false) => write!(f, "{} {}", self.paths(), action),
+            (true, _) => write!(
+                f,
+                "{}% of successful requests at INFO, the rest {}",
+                self.success_rate * 100.0,
+                action
+            ),
+            (false, true) => write!(
+                f,
+                "{} and {}% of other successful requests {}",
+                self.paths(),
+                (1.0 - self.success_rate) * 100.0,
+                action
+            ),
+        }
+    }
+}
+
+/// Keep the requests described by `quiet` out of the INFO stream, or stop with `None`
+pub fn set_quiet_requests(quiet: Option<QuietRequests>) {
+    *QUIET.write().unwrap_or_else(|e| e.into_inner()) = quiet.filter(|quiet| !quiet.is_empty());
+}
+
+/// Level [`log_request!`](crate::log_request!) logs a request at, `None` to drop it
+pub fn request_level<P: AsRef<str> + ?Sized>(path: &P, status: u16) -> Option<Level> {
+    match &*QUIET.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(quiet) => quiet.level(path.as_ref(), status),
+        None => Some(Level::INFO),
+    }
+}
+
+/// Nest dotted `http.*`, `url.*` and `rpc.*` event fields into objects
+pub(crate) fn nest_fields(record: &mut Map<String, Value>) {
+    let Some(Value::Object(fields)) = record.get_mut("fields") else {
+        return;
+    };
+    let dotted: Vec<String> = fields
+        .keys()
+        .filter(|key| {
+            ["http.", "url.", "rpc."]
+                .iter()
+                .any(|prefix| key.starts_with(prefix))
+        })
+        .cloned()
+        .collect();
+    for key in dotted {
+        if let Some(value) = fields.remove(&key) {
+            let path: Vec<String> = key.split('.').map(str::to_string).collect();
+            crate::key_map::put(fields, &path, value);
+        }
+    }
+}
+
+/// Span fields that headers can be mapped onto
+pub const CORRELATION_FIELDS: [&str; 7] = [
+    "request_id",
+    "idempotency_key",
+    "correlation_id",
+    "trace_id",
+    "span_id",
+    "parent_span_id",
+    "sampled",
+];
+
+/// Headers mapped by [`CorrelationHeaders::default`]
+const DEFAULT_HEADERS: [(&str, &str); 7] = [
+    ("x-request-id", "request_id"),
+    ("idempotency-key", "idempotency_key"),
+    ("x-correlation-id", "correlation_id"),
+    ("x-b3-traceid", "trace_id"),
+    ("x-b3-spanid", "span_id"),
+    ("x-b3-parentspanid", "parent_span_id"),
+    ("x-b3-sampled", "sampled"),
+];
+
+/// Longest header value kept; longer values are cut so clients cannot bloat every record
+const MAX_VALUE_LEN: usize = 256;
+
+/// Header to span field mapping
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::http::CorrelationHeaders;
+///
+/// let headers = [("X-Request-Id", "req-42"), ("Content-Type", "application/json")];
+/// let correlation = CorrelationHeaders::default().extract(headers);
+///
+/// let span = correlation.request_span("POST", "/payments");
+/// let _entered = span.enter();
+/// tracing::info!("Charging card"); // "span":{"request_id":"req-42",...}
+/// ```
+#[derive(Debug, Clone)]
+pub struct CorrelationHeaders {
+    /// Lowercase header names with their field
+    headers: Vec<(String, &'static str)>,
+}
+
+impl Default for CorrelationHeaders {
+    /// Request id, idempotency key, correlation id and B3 trace headers
+    fn default() -> Self {
+        Self {
+            headers: DEFAULT_HEADERS
+                .iter()
+                .map(|(header, field)| (header.to_string(), *field))
+                .collect(),
+        }
+    }
+}
+
+impl CorrelationHeaders {
+    /// Create a mapping without any headers
+    pub fn empty() -> Self {
+        Self {
+            headers: Vec::new(),
+        }
+    }
+
+    /// Map `header` (case-insensitive) onto `field`, replacing its previous mapping
+    ///
+    /// Fails when `field` is not one of [`CORRELATION_FIELDS`], as span
+    /// fields must be declared up front.
+    pub fn header(mut self, header: &str, field: &str) -> Result<Self, String> {
+        let field = CORRELATION_FIELDS
+            .iter()
+            .find(|known| **known == field.trim())
+            .ok_or_else(|| {
+                format!(
+                    "unknown correlation field '{}', expected one of {}",
+                    field.trim(),
+                    CORRELATION_FIELDS.join(", ")
+                )
+            })?;
+        let header = header.trim().to_ascii_lowercase();
+        self.headers.retain(|(known, _)| *known != header);
+        self.headers.push((header, field));
+        Ok(self)
+    }
+
+    /// Read the mapping from the environment, if one is configured
+    ///
+    /// `LOG_CORRELATION_HEADERS` lists `Header=field` pairs replacing the
+    /// defaults, e.g. "X-Request-Id=request_id,X-Amzn-Trace-Id=trace_id".
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(raw) = crate::config_file::var("LOG_CORRELATION_HEADERS") else {
+            return Ok(None);
+        };
+        let mut mapping = Self::empty();
+        for pair in raw
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (header, field) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not a Header=field pair", pair))?;
+            mapping = mapping.header(header, field)?;
+        }
+        Ok(Some(mapping))
+    }
+
+    /// The mapping from `LOG_CORRELATION_HEADERS`, or the defaults
+    pub fn from_env_or_default() -> Self {
+        Self::from_env()
+            .unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_CORRELATION_HEADERS: {}", e);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    /// Pick the mapped headers out of a request's headers
+    ///
+    /// Values that are not UTF-8 are skipped; the first occurrence of a
+    /// repeated header wins.
+    pub fn extract<I, K, V>(&self, headers: I) -> Correlation
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<[u8]>,
+    {
+        let mut found: Vec<Found> = Vec::new();
+        for (name, value) in headers {
+            let name = name.as_ref();
+            let Some((header, field)) = self
+                .headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            else {
+                continue;
+            };
+            if found.iter().any(|known| known.header == *header) {
+                continue;
+            }
+            let Ok(value) = std::str::from_utf8(value.as_ref()) else {
+                continue;
+            };
+            found.push(Found {
+                header: header.clone(),
+                field,
+                value: truncate(value.trim(), MAX_VALUE_LEN).to_string(),
+            });
+        }
+        Correlation { found }
+    }
+}
+
+fn truncate(value: &str, max: usize) -> &str {
+    if value.len() <= max {
+        return value;
+    }
+    let mut end = max;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+#[derive(Debug, Clone)]
+struct Found {
+    header: String,
+    field: &'static str,
+    value: String,
+}
+
+/// Correlation values found on one request
+#[derive(Debug, Clone, Default)]
+pub struct Correlation {
+    found: Vec<Found>,
+}
+
+impl Correlation {
+    /// Value of a correlation field, if its header was present
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.found
+            .iter()
+            .find(|found| found.field == field)
+            .map(|found| found.value.as_str())
+    }
+
+    /// Create an `http_request` span with `method`, `path` and the correlation fields
+    pub fn request_span(&self, method: &str, path: &str) -> Span {
+        let span = tracing::info_span!(
+            "http_request",
+            method = method,
+            path = path,
+            request_id = tracing::field::Empty,
+            idempotency_key = tracing::field::Empty,
+            correlation_id = tracing::field::Empty,
+            trace_id = tracing::field::Empty,
+            span_id = tracing::field::Empty,
+            parent_span_id = tracing::field::Empty,
+            sampled = tracing::field::Empty,
+        );
+        self.record(&span);
+        span
+    }
+
+    /// Record the correlation fields on an existing span declaring them (e.g. with `Empty`)
+    pub fn record(&self, span: &Span) {
+        for found in &self.found {
+            span.record(found.field, found.value.as_str());
+        }
+    }
+
+    /// Headers to forward on calls made while handling this request
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.found
+            .iter()
+            .map(|found| (found.header.as_str(), found.value.as_str()))
+    }
+}
+
+/// Header name matched by [`DebugHeader::from_env`] unless configured
+pub const DEFAULT_DEBUG_HEADER: &str = "x-debug-log";
+
+/// Request header turning on DEBUG logging for the request carrying it
+///
+/// The request middleware runs requests whose header equals the token in a
+/// [`context::debug`](crate::context::debug) scope. The token keeps callers
+/// from filling the logs at will; treat it like any other secret.
+#[derive(Clone)]
+pub struct DebugHeader {
+    header: String,
+    token: String,
+}
+
+impl DebugHeader {
+    /// Match requests whose `header` (case-insensitive) is `token`
+    pub fn new(header: &str, token: &str) -> Self {
+        Self {
+            header: header.trim().to_ascii_lowercase(),
+            token: token.trim().to_string(),
+        }
+    }
+
+    /// Read `LOG_REQUEST_DEBUG_TOKEN` and `LOG_REQUEST_DEBUG_HEADER`, if a token is set
+    pub fn from_env() -> Option<Self> {
+        let token = crate::config_file::var("LOG_REQUEST_DEBUG_TOKEN")
+            .ok()
+            .filter(|token| !token.trim().is_empty())?;
+        let header = crate::config_file::var("LOG_REQUEST_DEBUG_HEADER")
+            .ok()
+            .filter(|header| !header.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_DEBUG_HEADER.to_string());
+        Some(Self::new(&header, &token))
+    }
+
+    /// Name of the header, lowercase
+    pub fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// Whether the request's headers carry the token
+    pub fn matches<I, K, V>(&self, headers: I) -> bool
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<[u8]>,
+    {
+        let Some((_, value)) = headers
+            .into_iter()
+            .find(|(name, _)| name.as_ref().eq_ignore_ascii_case(&self.header))
+        else {
+            return false;
+        };
+        let given = value.as_ref().trim_ascii();
+        // Compare every byte so the response time does not reveal the matching prefix
+        given.len() == self.token.len()
+            && given
+                .iter()
+                .zip(self.token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+impl std::fmt::Debug for DebugHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugHeader")
+            .field("header", &self.header)
+            .field("token", &"***")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::per_test_subscriber;
+
+    #[test]
+    fn test_debug_header_needs_token() {
+        let header = DebugHeader::new("X-Debug-Log", "s3cret");
+        assert!(header.matches([("x-debug-log", "s3cret")]));
+        assert!(header.matches([("Accept", "*/*"), ("X-DEBUG-LOG", " s3cret ")]));
+        assert!(!header.matches([("x-debug-log", "s3cre")]));
+        assert!(!header.matches([("x-debug-log", "1")]));
+        assert!(!header.matches([("x-other", "s3cret")]));
+        assert!(!format!("{:?}", header).contains("s3cret"));
+    }
+
+    #[test]
+    fn test_nest_otel_fields() {
+        let mut record = serde_json::json!({
+            "fields": {"http.request.method": "GET", "url.path": "/a", "http.response.status_code": 200, "duration_ms": 4, "rpc.method": "Create"}
+        });
+        nest_fields(record.as_object_mut().unwrap());
+        assert_eq!(
+            record["fields"],
+            serde_json::json!({
+                "duration_ms": 4,
+                "http": {"request": {"method": "GET"}, "response": {"status_code": 200}},
+                "url": {"path": "/a"},
+                "rpc": {"method": "Create"}
+            })
+        );
+    }
+
+    #[test]
+    fn test_quiet_requests() {
+        let quiet = QuietRequests::new().path("/health").path("/internal/*");
+        assert_eq!(quiet.level("/health", 200), Some(Level::DEBUG));
+        assert_eq!(quiet.level("/health?probe=1", 204), Some(Level::DEBUG));
+        assert_eq!(quiet.level("/health", 503), Some(Level::INFO));
+        assert_eq!(quiet.level("/healthz", 200), Some(Level::INFO));
+        assert_eq!(quiet.level("/internal/jobs", 200), Some(Level::DEBUG));
+        assert_eq!(quiet.level("/orders", 200), Some(Level::INFO));
+
+        let quiet = quiet.action(QuietAction::Drop).sample_success(0.0);
+        assert_eq!(quiet.level("/health", 200), None);
+        assert_eq!(quiet.level("/orders", 201), None);
+        assert_eq!(quiet.level("/orders", 302), Some(Level::INFO));
+        assert_eq!(quiet.level("/orders", 404), Some(Level::INFO));
+        assert!(QuietRequests::new().sample_success(1.0).is_empty());
+    }
+
+    #[test]
+    fn test_extract_default_headers() {
+        let correlation = CorrelationHeaders::default().extract([
+            ("X-Request-Id", "req-42".as_bytes()),
+            ("x-b3-traceid", b"80f198ee56343ba8"),
+            ("Idempotency-Key", b"\xff\xfe"),
+            ("x-request-id", b"second"),
+            ("Accept", b"*/*"),
+        ]);
+        assert_eq!(correlation.get("request_id"), Some("req-42"));
+        assert_eq!(correlation.get("trace_id"), Some("80f198ee56343ba8"));
+        assert_eq!(correlation.get("idempotency_key"), None);
+        assert_eq!(
+            correlation.headers().collect::<Vec<_>>(),
+            vec![
+                ("x-request-id", "req-42"),
+                ("x-b3-traceid", "80f198ee56343ba8")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_mapping() {
+        let mapping = CorrelationHeaders::empty()
+            .header("X-Amzn-Trace-Id", "trace_id")
+            .unwrap();
+        assert!(mapping.clone().header("X-Tenant", "tenant").is_err());
+
+        let correlation =
+            mapping.extract([("X-Amzn-Trace-Id", "Root=1-abc"), ("X-Request-Id", "r")]);
+        assert_eq!(correlation.get("trace_id"), Some("Root=1-abc"));
+        assert_eq!(correlation.get("request_id"), None);
+    }
+
+    #[test]
+    fn test_request_span_carries_fields() {
+        let logs = per_test_subscriber();
+        let long = "k".repeat(300);
+        let correlation = CorrelationHeaders::default().extract([
+            ("X-Request-Id", "req-7"),
+            ("Idempotency-Key", long.as_str()),
+        ]);
+
+        correlation
+            .request_span("POST", "/payments")
+            .in_scope(|| tracing::info!("Charging card"));
+
+        let record = logs
+            .records()
+            .into_iter()
+            .find(|r| r["fields"]["message"] == "Charging card")
+            .unwrap();
+        assert_eq!(record["span"]["request_id"], "req-7");
+        assert_eq!(record["span"]["method"], "POST");
+        assert_eq!(
+            record["span"]["idempotency_key"].as_str().unwrap().len(),
+            MAX_VALUE_LEN
+        );
+    }
+}
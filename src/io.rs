@@ -0,0 +1,178 @@
+//! Instrumented writers
+//!
+//! [`logged_writer`] wraps any `std::io::Write` and counts the bytes, calls
+//! and time spent writing, emitting one summary record per flush (and when
+//! dropped), so file exports and network streams are measured without
+//! timing code scattered around the call sites.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Target of summary records
+const TARGET: &str = "io";
+
+/// Wrap `inner`, reporting its writes under `op_name`
+///
+/// # Examples
+/// ```no_run
+/// use std::io::Write;
+///
+/// let file = std::fs::File::create("export.csv")?;
+/// let mut out = custom_tracing_logger::logged_writer(file, "export_csv");
+/// out.write_all(b"id,name\n")?;
+/// out.flush()?; // {"fields":{"message":"Write summary","op":"export_csv","bytes":8,...},"target":"io"}
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn logged_writer<W: Write>(inner: W, op_name: impl Into<String>) -> LoggedWriter<W> {
+    LoggedWriter {
+        inner,
+        op: op_name.into(),
+        stats: Stats::new(),
+    }
+}
+
+/// Writer counting bytes and latency, see [`logged_writer`]
+pub struct LoggedWriter<W: Write> {
+    inner: W,
+    op: String,
+    stats: Stats,
+}
+
+/// Counters since the last summary
+struct Stats {
+    started: Instant,
+    bytes: u64,
+    writes: u64,
+    errors: u64,
+    busy: Duration,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            bytes: 0,
+            writes: 0,
+            errors: 0,
+            busy: Duration::ZERO,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.writes == 0 && self.errors == 0
+    }
+}
+
+impl<W: Write> LoggedWriter<W> {
+    /// The wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// The wrapped writer; writes made through it are not counted
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Emit a summary of the writes since the previous one
+    fn report(&mut self) {
+        let stats = std::mem::replace(&mut self.stats, Stats::new());
+        if stats.is_empty() {
+            return;
+        }
+        let elapsed = stats.started.elapsed();
+        let bytes_per_sec = if elapsed.is_zero() {
+            0
+        } else {
+            (stats.bytes as f64 / elapsed.as_secs_f64()) as u64
+        };
+        tracing::info!(
+            target: TARGET,
+            op = %self.op,
+            bytes = stats.bytes,
+            writes = stats.writes,
+            errors = stats.errors,
+            elapsed_ms = elapsed.as_millis() as u64,
+            write_ms = stats.busy.as_millis() as u64,
+            bytes_per_sec,
+            "Write summary"
+        );
+    }
+}
+
+impl<W: Write> Write for LoggedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start = Instant::now();
+        let result = self.inner.write(buf);
+        self.stats.busy += start.elapsed();
+        self.stats.writes += 1;
+        match &result {
+            Ok(n) => self.stats.bytes += *n as u64,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => self.stats.errors += 1,
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let start = Instant::now();
+        let result = self.inner.flush();
+        self.stats.busy += start.elapsed();
+        if result.is_err() {
+            self.stats.errors += 1;
+        }
+        self.report();
+        result
+    }
+}
+
+impl<W: Write> Drop for LoggedWriter<W> {
+    fn drop(&mut self) {
+        self.report();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::per_test_subscriber;
+
+    /// Writer accepting at most 3 bytes per call, failing after 9
+    struct Limited(Vec<u8>);
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0.len() >= 9 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_summary_per_flush_and_drop() {
+        let logs = per_test_subscriber();
+        let mut out = logged_writer(Limited(Vec::new()), "export");
+
+        out.write_all(b"hello").unwrap();
+        out.flush().unwrap();
+        out.flush().unwrap();
+        assert!(out.write_all(b"world!!").is_err());
+        drop(out);
+
+        let records = logs.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["fields"]["op"], "export");
+        assert_eq!(records[0]["fields"]["bytes"], 5);
+        assert_eq!(records[0]["fields"]["writes"], 2);
+        assert_eq!(records[1]["fields"]["bytes"], 6);
+        assert_eq!(records[1]["fields"]["errors"], 1);
+        assert_eq!(records[1]["target"], "io");
+    }
+}
@@ -0,0 +1,157 @@
+//! Serialized values carried through tracing fields
+//!
+//! Tracing fields hold primitives and strings only. A value wrapped with
+//! [`structured::json`](crate::structured::json) is recorded as a string
+//! starting with [`SENTINEL`] followed by its JSON, and the formatter turns
+//! such strings back into nested JSON before enriching the record. Every
+//! other visitor of this crate decodes them too; layers of other crates see
+//! the marked string.
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Start of a string holding serialized JSON; a control character no message starts with
+const SENTINEL: &str = "\u{1}json:";
+
+/// [`SENTINEL`] as it appears in JSON output
+pub(crate) const SENTINEL_ESCAPED: &str = "\"\\u0001json:";
+
+/// Write `value` as a sentinel-marked JSON string, or its error when it can't be serialized
+pub(crate) fn encode<T: Serialize + ?Sized>(value: &T, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match serde_json::to_string(value) {
+        Ok(json) => write!(f, "{}{}", SENTINEL, json),
+        Err(e) => write!(f, "<unserializable: {}>", e),
+    }
+}
+
+/// Replace a sentinel-marked string with the JSON it holds
+pub(crate) fn decode(value: &mut Value) {
+    let Value::String(s) = value else {
+        return;
+    };
+    if let Some(decoded) = s
+        .strip_prefix(SENTINEL)
+        .and_then(|json| serde_json::from_str(json).ok())
+    {
+        *value = decoded;
+    }
+}
+
+/// Decode the event and span fields of a formatted record
+pub(crate) fn decode_record(record: &mut Map<String, Value>) {
+    for key in ["fields", "span"] {
+        if let Some(Value::Object(fields)) = record.get_mut(key) {
+            fields.values_mut().for_each(decode);
+        }
+    }
+    if let Some(Value::Array(spans)) = record.get_mut("spans") {
+        for span in spans {
+            if let Value::Object(fields) = span {
+                fields.values_mut().for_each(decode);
+            }
+        }
+    }
+}
+
+/// Text of a value passed to `record_debug`, for visitors matching on field values
+///
+/// A serialized value becomes its JSON, or the plain text of a JSON string.
+pub(crate) fn debug_text(value: &dyn fmt::Debug) -> String {
+    let text = format!("{:?}", value);
+    match text.strip_prefix(SENTINEL).map(serde_json::from_str) {
+        Some(Ok(Value::String(decoded))) => decoded,
+        Some(Ok(decoded)) => decoded.to_string(),
+        _ => text,
+    }
+}
+
+/// Event format decoding the serialized values of records written by an inner JSON format
+pub(crate) struct Decoded<F>(pub(crate) F);
+
+impl<S, N, F> FormatEvent<S, N> for Decoded<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut buf = String::new();
+        self.0.format_event(ctx, Writer::new(&mut buf), event)?;
+        if !buf.contains(SENTINEL_ESCAPED) {
+            return writer.write_str(&buf);
+        }
+        let mut record: Map<String, Value> = serde_json::from_str(&buf).map_err(|_| fmt::Error)?;
+        decode_record(&mut record);
+        writeln!(writer, "{}", Value::Object(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Wrapped<'a>(&'a Value);
+
+    impl fmt::Display for Wrapped<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            encode(self.0, f)
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let order = json!({"id": 7, "items": [{"sku": "A1", "qty": 2}]});
+        let encoded = Wrapped(&order).to_string();
+        assert!(Value::from(encoded.as_str())
+            .to_string()
+            .starts_with(SENTINEL_ESCAPED));
+
+        let mut record = json!({"fields": {"order": encoded, "note": "json:{}"}});
+        decode_record(record.as_object_mut().unwrap());
+        assert_eq!(record["fields"]["order"], order);
+        assert_eq!(record["fields"]["note"], "json:{}");
+    }
+
+    #[test]
+    fn test_decoded_format_and_matching_text() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let order = json!({"id": 7});
+        assert_eq!(
+            debug_text(&format_args!("{}", Wrapped(&json!("acme")))),
+            "acme"
+        );
+        assert_eq!(
+            debug_text(&format_args!("{}", Wrapped(&order))),
+            r#"{"id":7}"#
+        );
+
+        let buffer = crate::testing::Buffer::default();
+        let layer = tracing_subscriber::fmt::layer()
+            .json()
+            .event_format(Decoded(tracing_subscriber::fmt::format().json()))
+            .with_writer(buffer.clone());
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let span = tracing::info_span!("checkout", cart = %Wrapped(&order));
+            let _entered = span.enter();
+            tracing::info!(order = %Wrapped(&order), "Order created");
+        });
+        let out = String::from_utf8(buffer.lock().clone()).unwrap();
+        let record: Value = serde_json::from_str(out.trim()).unwrap();
+        assert_eq!(record["fields"]["order"], order);
+        assert_eq!(record["span"]["cart"], order);
+        assert_eq!(record["spans"][0]["cart"], order);
+    }
+}
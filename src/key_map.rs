@@ -0,0 +1,145 @@
+//! Renaming of record keys
+//!
+//! Log backends reserve different names for the standard keys (`@timestamp`
+//! for Elasticsearch, `severity` for Google Cloud, a top-level `message`
+//! almost everywhere). A [`KeyMap`] renames keys of every record as it is
+//! written, instead of maintaining an ingest pipeline per backend. Keys are
+//! addressed by dotted paths, so `fields.message` is the event message.
+
+use serde_json::{Map, Value};
+
+/// Key renames applied to every record
+///
+/// # Examples
+/// ```no_run
+/// use custom_tracing_logger::key_map::KeyMap;
+///
+/// let keys = KeyMap::new()
+///     .rename("timestamp", "@timestamp")
+///     .rename("level", "severity")
+///     .rename("fields.message", "message");
+/// custom_tracing_logger::builder().with_key_map(keys).init();
+///
+/// tracing::info!("Started"); // {"@timestamp":"...","severity":"INFO","fields":{},"message":"Started",...}
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMap {
+    renames: Vec<(Vec<String>, Vec<String>)>,
+}
+
+impl KeyMap {
+    /// Create a map without any renames
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the value at path `from` to path `to`, replacing any value there
+    ///
+    /// Renames are applied in the order they were added, after the record
+    /// was reshaped for its [`LogFormat`](crate::LogFormat).
+    pub fn rename(mut self, from: &str, to: &str) -> Self {
+        self.renames.push((path(from), path(to)));
+        self
+    }
+
+    /// Whether no renames are configured
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    /// Read renames from `LOG_KEY_MAP`, if set
+    ///
+    /// The variable lists `from=to` pairs, e.g.
+    /// "timestamp=@timestamp,level=severity,fields.message=message".
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(raw) = crate::config_file::var("LOG_KEY_MAP") else {
+            return Ok(None);
+        };
+        let mut keys = Self::new();
+        for pair in raw
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (from, to) = pair
+                .split_once('=')
+                .map(|(from, to)| (from.trim(), to.trim()))
+                .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+                .ok_or_else(|| format!("'{}' is not a from=to pair", pair))?;
+            keys = keys.rename(from, to);
+        }
+        Ok(Some(keys))
+    }
+
+    /// Rename the keys of a formatted record in place
+    pub(crate) fn apply(&self, record: &mut Map<String, Value>) {
+        for (from, to) in &self.renames {
+            if let Some(value) = take(record, from) {
+                put(record, to, value);
+            }
+        }
+    }
+}
+
+fn path(dotted: &str) -> Vec<String> {
+    dotted.trim().split('.').map(str::to_string).collect()
+}
+
+/// Remove the value at `path`
+fn take(record: &mut Map<String, Value>, path: &[String]) -> Option<Value> {
+    let (last, parents) = path.split_last()?;
+    let mut map = record;
+    for key in parents {
+        map = map.get_mut(key)?.as_object_mut()?;
+    }
+    map.remove(last)
+}
+
+/// Insert `value` at `path`, creating intermediate objects
+pub(crate) fn put(record: &mut Map<String, Value>, path: &[String], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut map = record;
+    for key in parents {
+        let entry = map
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        map = entry.as_object_mut().expect("just made an object");
+    }
+    map.insert(last.clone(), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_renames_nested_and_top_level_keys() {
+        let keys = KeyMap::new()
+            .rename("timestamp", "@timestamp")
+            .rename("fields.message", "message")
+            .rename("target", "log.logger")
+            .rename("missing", "ignored");
+        let mut record = json!({
+            "timestamp": "2025-01-01T00:00:00Z",
+            "fields": {"message": "Started", "port": 80},
+            "target": "app"
+        });
+        keys.apply(record.as_object_mut().unwrap());
+
+        assert_eq!(
+            record,
+            json!({
+                "fields": {"port": 80},
+                "@timestamp": "2025-01-01T00:00:00Z",
+                "message": "Started",
+                "log": {"logger": "app"}
+            })
+        );
+    }
+}
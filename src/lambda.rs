@@ -0,0 +1,92 @@
+//! AWS Lambda integration
+//!
+//! On Lambda, records go to CloudWatch Logs through stdout, one JSON object
+//! per line, and the execution environment is frozen as soon as the handler
+//! returns. With the `lambda` feature the logger detects Lambda
+//! (`AWS_LAMBDA_FUNCTION_NAME`) and skips the daily file output, and
+//! [`invocation`] runs a handler with its request id and cold-start flag on
+//! every record, flushing the outputs before returning:
+//!
+//! ```
+//! # async fn handle(_event: ()) -> Result<(), ()> { Ok(()) }
+//! # async fn run(request_id: String, event: ()) -> Result<(), ()> {
+//! use custom_tracing_logger::lambda;
+//!
+//! lambda::invocation(request_id, async {
+//!     tracing::info!("Processing order"); // "fields":{"message":"Processing order","aws_request_id":"...","cold_start":true}
+//!     handle(event).await
+//! })
+//! .await
+//! # }
+//! ```
+
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::Value;
+
+static COLD_START: AtomicBool = AtomicBool::new(true);
+
+/// Whether the process runs in an AWS Lambda execution environment
+pub fn is_lambda() -> bool {
+    std::env::var_os("AWS_LAMBDA_FUNCTION_NAME").is_some()
+}
+
+/// Fields of the next invocation; only the first one of the process is a cold start
+fn fields(request_id: String) -> [(&'static str, Value); 2] {
+    let cold_start = COLD_START.swap(false, Ordering::Relaxed);
+    [
+        ("aws_request_id", request_id.into()),
+        ("cold_start", cold_start.into()),
+    ]
+}
+
+/// Run an invocation's handler with `aws_request_id` and `cold_start` on its records
+///
+/// The outputs, including events batched for the CloudWatch Logs sink, are
+/// flushed when the handler completes, before Lambda freezes the environment.
+pub async fn invocation<F: Future>(request_id: impl Into<String>, handler: F) -> F::Output {
+    let output = crate::context::scope(fields(request_id.into()), handler).await;
+    flush();
+    output
+}
+
+/// [`invocation`] for a synchronous handler
+pub fn invocation_sync<R>(request_id: impl Into<String>, handler: impl FnOnce() -> R) -> R {
+    let output = crate::context::scope_sync(fields(request_id.into()), handler);
+    flush();
+    output
+}
+
+/// Flush stdout, registered sinks and the log file
+///
+/// The CloudWatch Logs, Loki, webhook and Sentry sinks push their queued
+/// events before this returns, waiting up to 5 seconds, so nothing is left
+/// in a batch when the environment is frozen.
+pub fn flush() {
+    // Also drains the batching sinks
+    crate::sink::flush();
+    let _ = std::io::stdout().flush();
+    if let Err(e) = crate::file::flush_installed() {
+        eprintln!("Failed to flush log file: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::per_test_subscriber;
+
+    #[test]
+    fn test_invocation_adds_request_id_and_cold_start() {
+        let logs = per_test_subscriber();
+        invocation_sync("req-1", || tracing::info!("first"));
+        invocation_sync("req-2", || tracing::info!("second"));
+
+        let records = logs.records();
+        assert_eq!(records[0]["fields"]["aws_request_id"], "req-1");
+        assert_eq!(records[1]["fields"]["aws_request_id"], "req-2");
+        assert_eq!(records[1]["fields"]["cold_start"], false);
+    }
+}
@@ -4,36 +4,192 @@
 //! using the tracing ecosystem. All logs are output as structured JSON with
 //! metadata including timestamp, level, target, and message.
 
-use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use std::collections::HashMap;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+
+pub use alert::ErrorEvent;
+use clock::{Clock, MonotonicClock};
+#[cfg(feature = "macros")]
+pub use custom_tracing_logger_macros::{log_timed, logged_test};
+pub use file::reopen_log_files;
+use format::EventFormat;
+pub use format::LogFormat;
+pub use formatter::RecordFormatter;
+pub use io::logged_writer;
+pub use metrics::{render_prometheus, stats, LoggerStats};
+pub use panic_hook::install_panic_hook;
+use redact::Redactor;
+pub use reload::ReloadHandle;
+pub use ring::export_recent;
+pub use sink::{flush, LogSink};
+use tracing_subscriber::{
+    filter::{self, LevelFilter},
+    fmt::{self, MakeWriter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter, Layer, Registry,
+};
+
+mod admin;
+pub mod alert;
+pub mod allowlist;
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "async-writer")]
+pub mod async_writer;
+pub mod audit;
+#[cfg(any(feature = "cloudwatch", feature = "archive"))]
+mod aws;
+#[cfg(any(
+    feature = "loki",
+    feature = "cloudwatch",
+    feature = "webhook",
+    feature = "sentry"
+))]
+mod batch;
+pub mod build_info;
+pub mod channel;
+pub mod clock;
+#[cfg(feature = "cloudwatch")]
+pub mod cloudwatch;
+pub mod config;
+pub mod config_file;
+mod console;
+pub mod context;
+pub mod counter;
+mod debug_users;
+mod dedup;
+mod diff;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+mod error_backtrace;
+pub mod error_report;
+pub mod event_filter;
+pub mod field_format;
+mod file;
+mod flusher;
+mod format;
+pub mod formatter;
+pub mod grpc;
+pub mod http;
+pub mod io;
+mod json_field;
+pub mod key_map;
+#[cfg(feature = "lambda")]
+pub mod lambda;
+pub mod limits;
+#[cfg(feature = "log")]
+mod log_bridge;
+#[cfg(feature = "loki")]
+pub mod loki;
+pub mod metrics;
+pub mod middleware;
+pub mod naming;
+pub mod net;
+pub mod normalize;
+mod ordered;
+mod panic_hook;
+pub mod partition;
+pub mod paths;
+pub mod permissions;
+pub mod presets;
+mod process_info;
+pub mod rate_limit;
+pub mod reader;
+pub mod record;
+pub mod redact;
+mod reentrant;
+pub mod reload;
+pub mod request_id;
+pub mod ring;
+pub mod routes;
+pub mod sampling;
+pub mod schema;
+#[cfg(feature = "sentry")]
+pub mod sentry;
+mod sequence;
+#[cfg(all(unix, feature = "signals"))]
+mod signals;
+pub mod sink;
+mod source_location;
+pub mod spans;
+mod static_fields;
+pub mod testing;
+mod valuable_fields;
+// Lets #[logged_test] expand inside this crate's own tests
+#[cfg(all(test, feature = "macros"))]
+extern crate self as custom_tracing_logger;
+pub mod time_fields;
+pub mod timing;
+pub mod trace_context;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 
 /// Convenience macro for HTTP request logging
+///
+/// Requests made quiet by [`http::set_quiet_requests`] (e.g. health checks)
+/// are logged at DEBUG or not at all.
 #[macro_export]
 macro_rules! log_request {
-    ($method:expr, $path:expr, $status:expr, $duration:expr) => {
-        tracing::info!(
-            method = $method,
-            path = $path,
-            status = $status,
-            duration_ms = $duration,
-            "HTTP request completed"
-        );
+    ($method:expr, $path:expr, $status:expr, $duration:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        match $crate::http::request_level(&$path, $status as u16) {
+            Some(tracing::Level::INFO) => {
+                $crate::__log_request!(info, $method, $path, $status, $duration $(, $key = $value)*)
+            }
+            Some(_) => {
+                $crate::__log_request!(debug, $method, $path, $status, $duration $(, $key = $value)*)
+            }
+            None => {}
+        }
     };
-    ($method:expr, $path:expr, $status:expr, $duration:expr, $($key:ident = $value:expr),+) => {
-        tracing::info!(
-            method = $method,
-            path = $path,
-            status = $status,
-            duration_ms = $duration,
-            $($key = $value),+,
-            "HTTP request completed"
-        );
+}
+
+/// Log a request at the level of the macro `$level`, for [`log_request!`]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_request {
+    ($level:ident, $method:expr, $path:expr, $status:expr, $duration:expr $(, $key:ident = $value:expr)*) => {
+        if $crate::http::field_style() == $crate::http::FieldStyle::Otel {
+            tracing::$level!(
+                http.request.method = $method,
+                url.path = $path,
+                http.response.status_code = $status,
+                duration_ms = $duration,
+                $($key = $value,)*
+                "HTTP request completed"
+            );
+        } else {
+            tracing::$level!(
+                method = $method,
+                path = $path,
+                status = $status,
+                duration_ms = $duration,
+                $($key = $value,)*
+                "HTTP request completed"
+            );
+        }
     };
 }
 
 /// Convenience macro for error logging with context
+///
+/// With the `anyhow` feature, `error = err` logs an `anyhow::Error` with its
+/// message, context chain and backtrace as fields, see [`error_report`]:
+/// `log_error!("CONFIG", error = err, attempt = 2)`.
 #[macro_export]
 macro_rules! log_error {
+    ($error_code:expr, error = $error:expr $(, $key:ident = $value:expr)* $(,)?) => {{
+        let report = $crate::error_report::ErrorReport::new(&$error);
+        tracing::error!(
+            error_code = $error_code,
+            error_chain = %report.chain_json(),
+            backtrace = report.backtrace.as_deref(),
+            $($key = $value,)*
+            "{}",
+            report.message
+        );
+    }};
     ($error_code:expr, $message:expr) => {
         tracing::error!(
             error_code = $error_code,
@@ -49,14 +205,206 @@ macro_rules! log_error {
     };
 }
 
+/// Capture the application's build metadata at compile time, see [`build_info`]
+///
+/// Reads `CARGO_PKG_VERSION` of the calling crate, plus `GIT_SHA`,
+/// `RUSTC_VERSION` and `BUILD_TIMESTAMP` when they are set while compiling
+/// (e.g. by a build script). Extra `name = value` pairs are added or override
+/// those fields.
+///
+/// ```
+/// use custom_tracing_logger::build_info;
+///
+/// let info = build_info!();
+/// let info = build_info!(git_sha = "3f2c1ab", profile = "release");
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    ($($name:ident = $value:expr),* $(,)?) => {
+        $crate::build_info::BuildInfo::from_compile_env(
+            ::core::env!("CARGO_PKG_VERSION"),
+            ::core::option_env!("GIT_SHA"),
+            ::core::option_env!("RUSTC_VERSION"),
+            ::core::option_env!("BUILD_TIMESTAMP"),
+        )
+        $(.field(stringify!($name), $value))*
+    };
+}
+
+/// Log the start, end and `duration_ms` of a block, see [`timing`]
+///
+/// The block's value is returned; a `Result` also sets `success`.
+///
+/// ```
+/// use custom_tracing_logger::timed;
+///
+/// let rows = timed!("import_users", {
+///     std::thread::sleep(std::time::Duration::from_millis(5));
+///     120
+/// });
+/// ```
+#[macro_export]
+macro_rules! timed {
+    ($operation:expr, $body:block) => {{
+        let timer = $crate::timing::Timer::start($operation);
+        let value = $body;
+        {
+            // Only one of the two applies to the block's type
+            #[allow(unused_imports)]
+            use $crate::timing::{OtherOutcome as _, ResultOutcome as _};
+            timer.finish((&$crate::timing::Probe(&value)).outcome());
+        }
+        value
+    }};
+}
+
+/// Count an occurrence instead of logging it, see [`counter`]
+///
+/// ```
+/// use custom_tracing_logger::count;
+///
+/// let region = "eu-west-1";
+/// count!("cache_miss");
+/// count!("cache_miss", by = 3);
+/// count!("cache_miss", by = 1, region = region, tier = "gold");
+/// ```
+#[macro_export]
+macro_rules! count {
+    ($name:expr) => {
+        $crate::counter::increment($name, 1, &[])
+    };
+    ($name:expr, by = $by:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::counter::increment(
+            $name,
+            $by,
+            &[$((stringify!($key), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
+    ($name:expr $(, $key:ident = $value:expr)+ $(,)?) => {
+        $crate::counter::increment(
+            $name,
+            1,
+            &[$((stringify!($key), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
+}
+
+/// Assert that a record was captured since [`testing::init_for_tests`]
+///
+/// Passes when some record's message contains the text and, if given, its
+/// fields have the listed values.
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::assert_logged;
+///
+/// custom_tracing_logger::testing::init_for_tests();
+/// tracing::warn!(attempt = 3, retry = true, "Payment retried");
+/// assert_logged!("Payment retried");
+/// assert_logged!("retried", attempt = 3, retry = true);
+/// ```
+#[macro_export]
+macro_rules! assert_logged {
+    ($text:expr $(, $field:ident = $value:expr)* $(,)?) => {
+        $crate::testing::assert_logged(
+            $text,
+            &[$((stringify!($field), $crate::testing::field_value($value))),*],
+        )
+    };
+}
+
 /// Initialize the JSON logger
 ///
 /// Behavior controlled by environment variables:
 /// - `RUST_LOG`: Log level filtering (e.g., "info", "debug", "off")
-/// - `LOG_FILE_DIR`: Directory for log files (e.g., "./logs")
+/// - `LOG_CONFIG_FILE`: TOML (or YAML, `yaml` feature) file providing any of these options, see [`config_file`]
+/// - `LOG_DISABLED`: Set to "true" to write nothing, see [`init_disabled`]
+/// - `LOG_FILE_DIR`: Directory for log files (e.g., "./logs", "~/logs", "${TEMP}\app", UNC shares)
 /// - `LOG_FILE_PREFIX`: Prefix for log files (e.g., "myapp")
 /// - `LOG_FILE_ONLY`: Set to "true" to disable console output
+/// - `LOG_FILE_PATTERN` / `LOG_FILE_DATE_FORMAT` / `LOG_FILE_EXTENSION`: File names such as "{prefix}-{date}.{ext}" (default: "{prefix}.{date}" with "%Y-%m-%d"), see [`naming`]
+/// - `LOG_FILE_SYMLINK`: Name of a symlink in the log directory pointing at the active file (e.g. "current")
+/// - `LOG_FILE_MODE`: Octal mode of log files, ignoring the umask (default: "640"; created directories get "750"), see [`permissions`]
+/// - `LOG_FILE_GROUP`: Group name or id owning log files and created directories
+/// - `LOG_FILE_BUFFER_BYTES`: Bytes of records kept in memory while the log file can't be written, e.g. on a full disk (default: 1 MiB; the oldest are dropped beyond it)
+/// - `LOG_FILE_RETRY_MS`: Shortest time between attempts to write buffered records (default: 1000)
+/// - `LOG_FILE_SHARED`: Set to "true" to lock the log file around each record when other processes write to it too
+/// - `LOG_FILE_FALLBACK`: When the log file can't be created: "fallback_console" (default; console plus a warning), "fail_init" or "silent_drop"
+/// - `LOG_ROUTES`: Files of their own for some targets, e.g. "sqlx=>db.log,audit=>audit.log,*=>app.log", see [`routes`]
+/// - `LOG_PARTITION_FIELD`: Field (e.g. "tenant_id") whose value gets its own log file and a `partition` key on every record, see [`partition`]
+/// - `LOG_PARTITION_MAX_FILES`: Partition files open at once; later partitions stay in the regular file (default: 100)
+/// - `LOG_ENCRYPTION_KEY`: 64 hex digits; encrypt log file records with AES-256-GCM (requires the `encryption` feature, see [`encryption`])
+/// - `LOG_AUDIT_DIR`: Directory of the append-only audit file written by [`audit::event`], unaffected by `RUST_LOG`
+/// - `LOG_AUDIT_PREFIX` / `LOG_AUDIT_ROTATION` / `LOG_AUDIT_MAX_FILES`: Audit file prefix (default: "audit"), "daily" (default), "hourly" or "never" rotation, and rotated files kept (default: all)
+/// - `LOG_AUDIT_HASH_CHAIN`: Set to "true" to add the SHA-256 `prev_hash` of the previous audit record to each one, see [`audit::verify_audit_log`]
 /// - `LOG_ENABLE_SPANS`: Set to "false" to disable #[instrument] span events (default: "true")
+/// - `LOG_PANIC_HOOK`: Set to "true" to write panics as ERROR records and flush before the default hook runs, see [`install_panic_hook`]
+/// - `LOG_ORDERED`: Set to "true" to write console and file through one ordered pipeline with `seq` numbers
+/// - `LOG_STDERR_LEVEL`: Write console records at this level or more severe (e.g. "warn") to stderr and the rest to stdout (default: "off")
+/// - `LOG_QUIET_DEFAULTS`: Keep hyper, h2, tower, rustls, sqlx and mio at WARN unless `RUST_LOG` names them (default: false)
+/// - `LOG_FILTER_FILE`: Follow the `RUST_LOG` directives in this file (e.g. a mounted ConfigMap), applying changes live
+/// - `LOG_FILTER_POLL_MS`: How often `LOG_FILTER_FILE` is checked for changes (default: 5000)
+/// - `LOG_SIGNALS`: Set to "true" to handle SIGHUP (reopen files) and SIGUSR1/SIGUSR2 (raise/lower level); Unix, `signals` feature
+/// - `LOG_FLUSH_INTERVAL_MS`: Flush registered sinks and the log file this often while they hold unflushed records
+/// - `LOG_IDLE_FLUSH_MS`: Flush any output whose oldest unflushed record is this old, so records show up promptly
+/// - `LOG_ADMIN_ADDR`: Serve an admin HTTP endpoint here (e.g. "127.0.0.1:9898") to read/change the filter, flush and get stats
+/// - `LOG_ADMIN_TOKEN`: Bearer token required by the admin endpoint; needed unless `LOG_ADMIN_ADDR` is a loopback address
+/// - `LOG_SPAN_EVENTS`: Span records to write, any of "new,enter,exit,close" (default: "enter,exit"), "full" or "none" (see [`spans::set_span_events`])
+/// - `LOG_SPAN_FIELDS`: Set to "false" to start with span field capture off (see [`spans::set_field_capture`])
+/// - `LOG_REDACT_FIELDS`: Field names whose values are replaced with "[REDACTED]" (e.g. "password,ssn")
+/// - `LOG_REDACT_PATTERNS`: Built-in value patterns to redact, "credit_card" and/or "email"
+/// - `LOG_REDACTION_KEY`: Replace redacted values with a keyed HMAC digest instead of "[REDACTED]"
+/// - `LOG_NORMALIZE`: Rewrite line breaks and control characters in string values, "escape" (as `\n`), "replace" (with a space) or "off" (default)
+/// - `LOG_MAX_FIELD_LEN`: Cut string values longer than this many bytes, marked "…(truncated)"
+/// - `LOG_MAX_EVENT_BYTES`: Replace records larger than this with a stub noting their size (at least 1024)
+/// - `LOG_DEBUG_USERS`: Log every level inside spans whose `user_id` is one of these (e.g. "42,1337")
+/// - `LOG_DEBUG_USER_FIELD`: Span field matched by `LOG_DEBUG_USERS` (default: "user_id")
+/// - `LOG_REQUEST_DEBUG`: Log at DEBUG inside [`context::debug`] scopes, checking DEBUG callsites at runtime (default: true when `LOG_REQUEST_DEBUG_TOKEN` is set)
+/// - `LOG_REQUEST_DEBUG_TOKEN`: Secret value of the debug header making the request middleware log a request at DEBUG
+/// - `LOG_REQUEST_DEBUG_HEADER`: Name of that header (default: "x-debug-log")
+/// - `LOG_RATE_LIMIT`: Maximum identical events per second per key, with periodic `suppressed` summaries
+/// - `LOG_RATE_LIMIT_KEY`: Field keying events for `LOG_RATE_LIMIT` instead of target and message
+/// - `LOG_RATE_LIMIT_SUMMARY_MS`: Time between suppression summaries (default: 10000)
+/// - `LOG_SAMPLE`: Keep only a share of chatty targets' events, e.g. "hyper=0.01,myapp::chatty=0.1" (WARN and ERROR kept unless a rule names them, see [`sampling`])
+/// - `LOG_RING_BUFFER_SECS`: Keep this many seconds of records at all levels in memory for [`export_recent`]
+/// - `LOG_RING_BUFFER_MAX` / `LOG_RING_BUFFER_LEVEL`: Record cap (default: 50000) and most verbose level kept (default: "trace")
+/// - `LOG_DEDUP`: Set to "true" to collapse consecutive identical records into one with `repeat_count`
+/// - `LOG_DEDUP_TIMEOUT_MS`: Longest time a collapsed run is held back (default: 5000)
+/// - `LOG_FIELD_ALLOWLIST`: Emit only these event and span field names (e.g. "user_id,order_id")
+/// - `LOG_FIELD_ALLOWLIST_MODE`: "drop" (default) or "extra" to move other fields under `extra`
+/// - `LOG_SCHEMA_VALIDATE`: "true"/"false" to force schema validation on or off (default: debug builds only)
+/// - `LOG_SEQ`: Set to "true" to add a per-process `seq` number to every record
+/// - `LOG_EVENT_ID`: "true" (or "uuid") to add a UUIDv7 `event_id` and a `seq` number to every record, "ulid" for ULIDs
+/// - `LOG_ALERT_INTERVAL_MS`: Shortest time between two calls of [`Builder::on_error`] (default: 10000)
+/// - `LOG_STATS_INTERVAL_SECS`: Write a "Logger stats" record with the counters of [`stats`] this often
+/// - `LOG_ERROR_BACKTRACE`: "true"/"false" to add a `backtrace` field to ERROR events (default: on when `RUST_BACKTRACE` is set and not "0")
+/// - `LOG_HTTP_FIELDS`: "otel" nests `log_request!` fields as `http.request.method`, `url.path` and `http.response.status_code` (default: "flat")
+/// - `LOG_QUIET_PATHS`: Request paths (e.g. "/health,/metrics,/internal/*") that `log_request!` logs at DEBUG unless they fail, see [`http::QuietRequests`]
+/// - `LOG_QUIET_ACTION`: "debug" (default) or "drop" for quiet requests
+/// - `LOG_SUCCESS_SAMPLE`: Share of successful (2xx) requests `log_request!` logs at INFO; the rest are quiet (default: 1)
+/// - `LOG_FLATTEN_SPAN`: Set to "true" to put current span fields at the top level of records (span name as `span_name`)
+/// - `LOG_PROCESS_INFO`: Set to "true" to add `hostname`, `pid`, `thread_id` and `thread_name` to every record
+/// - `LOG_SOURCE_LOCATION`: Set to "true" to add the `file`, `line` and `module_path` of the call site to every record
+/// - `LOG_CORRELATION_HEADERS`: Header to span field map for [`http::CorrelationHeaders`] (e.g. "X-Request-Id=request_id")
+/// - `LOG_REQUEST_ID_FORMAT`: Layout of [`request_id::generate`], "uuid" (UUIDv7, default) or "ulid"
+/// - `LOG_STATIC_FIELDS`: Constant fields added to every record (e.g. "service=payments,env=prod")
+/// - `LOG_UPTIME`: Set to "true" to add a monotonic `uptime_ns` field to every record
+/// - `LOG_FORMAT`: Record layout, "json" (default), "datadog", "ecs", "logfmt" or "gcp"
+/// - `LOG_CONSOLE_FORMAT` / `LOG_FILE_FORMAT`: Per-output layout overriding `LOG_FORMAT`
+/// - `LOG_KEY_MAP`: Rename record keys, e.g. "timestamp=@timestamp,level=severity,fields.message=message"
+/// - `LOG_CONSOLE_CHANNELS` / `LOG_FILE_CHANNELS`: Channels written to that output, e.g. "technical" or "business,audit" (default: all)
+/// - `LOG_DATADOG_AGENT_ADDR`: Also ship records over TCP to a local Datadog agent (e.g. "localhost:10518")
+/// - `LOG_COUNT_INTERVAL_MS`: How often [`count!`] aggregates are emitted (default: 10000)
+/// - `LOG_NET_ADDR`: Also stream NDJSON to a collector (e.g. "tcp://collector:5000" or "udp://collector:5000")
+/// - `LOG_NET_SPILL_FILE` / `LOG_NET_SPILL_MAX_BYTES`: On-disk buffer for TCP records while the collector is down
+/// - `LOG_SOCKET_PATH`: Also stream NDJSON to a Unix domain socket (e.g. "/run/app/log.sock")
+/// - `LOG_CAPTURE_SOCKET`: Listen on a Unix domain socket and stream records to any attached viewer
+/// - `LOG_LOKI_URL`: Push events to Grafana Loki (requires the `loki` feature)
+/// - `LOG_CLOUDWATCH_GROUP`: Ship events to AWS CloudWatch Logs (requires the `cloudwatch` feature)
+/// - `LOG_ARCHIVE_URL`: Upload completed log files to `s3://`, `gs://` or `azblob://` and delete them locally (requires the `archive` feature, see [`archive`])
+/// - `LOG_SENTRY_DSN`: Forward ERROR events and panics to Sentry (requires the `sentry` feature, with `LOG_SENTRY_LEVEL`, `LOG_SENTRY_ENVIRONMENT` and `LOG_SENTRY_RELEASE`)
+/// - `LOG_WEBHOOK_URL`: POST ERROR events to a webhook such as Slack's (requires the `webhook` feature, with `LOG_WEBHOOK_LEVEL` and other `LOG_WEBHOOK_*` options)
+/// - `AWS_LAMBDA_FUNCTION_NAME`: Set by AWS Lambda; with the `lambda` feature the file output is skipped, see [`lambda`]
 ///
 /// # Examples
 /// ```no_run
@@ -72,123 +420,2351 @@ macro_rules! log_error {
 /// // Disable #[instrument] spans (with LOG_ENABLE_SPANS=false)
 /// custom_tracing_logger::init();
 /// ```
-pub fn init() {
-    // Handle RUST_LOG with whitespace trimming for Windows compatibility
-    let env_filter = match std::env::var("RUST_LOG") {
-        Ok(val) => EnvFilter::new(val.trim()),
-        Err(_) => EnvFilter::new("info"),
-    };
+///
+/// The returned [`ReloadHandle`] changes the `RUST_LOG` filter at runtime.
+///
+/// # Panics
+/// When the log file can't be created and `LOG_FILE_FALLBACK=fail_init`;
+/// see [`try_init`].
+pub fn init() -> ReloadHandle {
+    builder().init()
+}
+
+/// Initialize the logger, returning an error instead of panicking when the log file is unusable
+///
+/// Shorthand for `builder().try_init()`, see [`Builder::try_init`].
+pub fn try_init() -> Result<ReloadHandle, String> {
+    builder().try_init()
+}
+
+/// Initialize the logger with console records going to `make_writer`
+///
+/// Shorthand for `builder().with_writer(make_writer).init()`, see
+/// [`Builder::with_writer`].
+pub fn init_with_writer<W>(make_writer: W) -> ReloadHandle
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    builder().with_writer(make_writer).init()
+}
+
+/// Build the logger's layers for a subscriber the application installs itself
+///
+/// Reads the same environment variables as [`init`]; see [`Builder::layer`].
+pub fn layer() -> (impl Layer<Registry> + Send + Sync, ReloadHandle) {
+    builder().layer()
+}
+
+/// Initialize a minimal JSON logger writing to stderr
+///
+/// Reads no environment variables, spawns no threads and touches no files,
+/// which makes it suitable for `build.rs` scripts and proc-macros that still
+/// want structured diagnostics routed through tracing. Only events at
+/// `stderr_level` or more severe are written.
+///
+/// # Examples
+/// ```no_run
+/// custom_tracing_logger::init_minimal(tracing::Level::WARN);
+/// tracing::warn!(file = "schema.json", "Schema missing, using defaults");
+/// ```
+pub fn init_minimal(stderr_level: tracing::Level) {
+    let layer = fmt::layer()
+        .json()
+        .event_format(json_field::Decoded(fmt::format().json()))
+        .with_writer(std::io::stderr)
+        .with_filter(LevelFilter::from_level(stderr_level));
+
+    let _ = tracing_subscriber::registry().with(layer).try_init();
+}
+
+/// Install a logger that writes nothing, at near-zero cost per event
+///
+/// Every callsite is still registered and the API behaves as after [`init`]:
+/// the returned [`ReloadHandle`] accepts filter changes, later `init` calls
+/// return it, and flushing is a no-op. The filter starts at "off", so
+/// disabled callsites are skipped by tracing's cached interest check
+/// without building events. `LOG_DISABLED=true` makes [`init`] do the same,
+/// turning logging off without code changes (e.g. for benchmark runs).
+///
+/// # Examples
+/// ```no_run
+/// custom_tracing_logger::init_disabled();
+/// tracing::info!("never written");
+/// ```
+pub fn init_disabled() -> ReloadHandle {
+    if let Some(handle) = reload::installed() {
+        return handle;
+    }
+    let (subscriber, handle) = disabled_subscriber();
+    if tracing::subscriber::set_global_default(subscriber).is_ok() {
+        reload::install(handle.clone());
+    }
+    handle
+}
+
+/// Subscriber without outputs, behind a reloadable filter set to "off"
+fn disabled_subscriber() -> (impl tracing::Subscriber + Send + Sync, ReloadHandle) {
+    let filter = debug_users::DebugUserFilter::new(EnvFilter::new("off"), None);
+    let (filter, handle) = tracing_subscriber::reload::Layer::new(filter);
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_subscriber::layer::Identity::new().with_filter(filter));
+    (subscriber, ReloadHandle::new(handle))
+}
+
+/// Create a [`Builder`] for options that can't be expressed through environment variables
+///
+/// # Examples
+/// ```no_run
+/// use custom_tracing_logger::clock::MonotonicClock;
+///
+/// custom_tracing_logger::builder()
+///     .with_uptime_clock(MonotonicClock)
+///     .init();
+/// ```
+pub fn builder() -> Builder {
+    Builder::default()
+}
+
+/// Programmatic logger configuration
+///
+/// Environment variables are still honored; builder options are applied on top.
+#[derive(Default)]
+pub struct Builder {
+    clock: Option<Arc<dyn Clock>>,
+    format: Option<LogFormat>,
+    output_formats: HashMap<Output, LogFormat>,
+    output_channels: HashMap<Output, channel::ChannelSet>,
+    record_formatter: Option<Arc<dyn RecordFormatter>>,
+    output_formatters: HashMap<Output, Arc<dyn RecordFormatter>>,
+    ordered: Option<bool>,
+    stderr_level: Option<LevelFilter>,
+    sequence: Option<bool>,
+    event_ids: Option<request_id::IdFormat>,
+    error_backtrace: Option<bool>,
+    span_events: Option<spans::SpanEvents>,
+    process_info: Option<bool>,
+    source_location: Option<bool>,
+    flatten_span: Option<bool>,
+    http_fields: Option<http::FieldStyle>,
+    quiet_requests: Option<http::QuietRequests>,
+    redactor: Option<Redactor>,
+    limits: Option<limits::Limits>,
+    normalize: Option<normalize::Normalize>,
+    allowlist: Option<allowlist::Allowlist>,
+    formatters: Option<field_format::FieldFormatters>,
+    key_map: Option<key_map::KeyMap>,
+    static_fields: serde_json::Map<String, serde_json::Value>,
+    schemas: Option<schema::SchemaRegistry>,
+    debug_users: Option<debug_users::DebugUsers>,
+    quiet_defaults: Option<bool>,
+    request_debug: Option<bool>,
+    rate_limit: Option<rate_limit::RateLimit>,
+    sampling: Option<sampling::Sampling>,
+    event_filter: Option<event_filter::EventPredicate>,
+    dedup: Option<std::time::Duration>,
+    sinks: Vec<Box<dyn LogSink>>,
+    writer: Option<fmt::writer::BoxMakeWriter>,
+    on_error: Option<alert::ErrorCallback>,
+    file_permissions: Option<permissions::FilePermissions>,
+    file_fallback: Option<config::FileFallback>,
+    shared_file: Option<bool>,
+    file_naming: Option<naming::FileNaming>,
+    routes: Option<routes::Routes>,
+    partitioning: Option<partition::Partitioning>,
+    #[cfg(feature = "encryption")]
+    file_encryption: Option<encryption::KeyProvider>,
+    #[cfg(feature = "archive")]
+    archive: Option<archive::ArchiveConfig>,
+}
+
+/// Destinations records are written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Output {
+    /// Standard output
+    Console,
+    /// Daily rotating files under `LOG_FILE_DIR`
+    File,
+    /// Local Datadog agent (`LOG_DATADOG_AGENT_ADDR`)
+    DatadogAgent,
+    /// TCP/UDP collector (`LOG_NET_ADDR`)
+    Network,
+    /// Local agent on a Unix domain socket (`LOG_SOCKET_PATH`)
+    UnixSocket,
+    /// Consumers attached to the capture socket (`LOG_CAPTURE_SOCKET`)
+    CaptureSocket,
+    /// Grafana Loki push API (`loki` feature)
+    Loki,
+    /// AWS CloudWatch Logs (`cloudwatch` feature)
+    CloudWatch,
+    /// Webhook receiving critical events (`webhook` feature)
+    Webhook,
+    /// Custom sinks added with [`Builder::add_sink`]
+    Sinks,
+}
+
+impl Output {
+    /// Environment variable overriding the layout of this output, if any
+    fn format_env_var(self) -> Option<&'static str> {
+        match self {
+            Output::Console => Some("LOG_CONSOLE_FORMAT"),
+            Output::File => Some("LOG_FILE_FORMAT"),
+            Output::DatadogAgent
+            | Output::Network
+            | Output::UnixSocket
+            | Output::CaptureSocket
+            | Output::Loki
+            | Output::CloudWatch
+            | Output::Webhook
+            | Output::Sinks => None,
+        }
+    }
+
+    /// Environment variable restricting the channels of this output, if any
+    fn channels_env_var(self) -> Option<&'static str> {
+        match self {
+            Output::Console => Some("LOG_CONSOLE_CHANNELS"),
+            Output::File => Some("LOG_FILE_CHANNELS"),
+            _ => None,
+        }
+    }
+}
+
+impl Builder {
+    /// Select the record layout, overriding `LOG_FORMAT`
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Select the record layout of a single output
+    ///
+    /// Enrichment (e.g. `uptime_ns`) is shared by all outputs and applied
+    /// before the per-output layout.
+    pub fn with_output_format(mut self, output: Output, format: LogFormat) -> Self {
+        self.output_formats.insert(output, format);
+        self
+    }
+
+    /// Write console records to `make_writer` instead of standard output
+    ///
+    /// Accepts anything tracing-subscriber can write to: an in-memory buffer
+    /// in tests, a pipe, or a custom transport. The writer takes the place of
+    /// the console output, so `LOG_CONSOLE_FORMAT`, `LOG_CONSOLE_CHANNELS` and
+    /// `LOG_FILE_ONLY` apply to it.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// custom_tracing_logger::builder()
+    ///     .with_writer(std::io::stderr)
+    ///     .init();
+    /// ```
+    pub fn with_writer<W>(mut self, make_writer: W) -> Self
+    where
+        W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+    {
+        self.writer = Some(fmt::writer::BoxMakeWriter::new(make_writer));
+        self
+    }
+
+    /// Serialize records of every output with a custom [`RecordFormatter`]
+    ///
+    /// Replaces the [`LogFormat`] of the outputs; enrichment, filtering and
+    /// routing are unchanged.
+    pub fn with_record_formatter(mut self, formatter: impl RecordFormatter + 'static) -> Self {
+        self.record_formatter = Some(Arc::new(formatter));
+        self
+    }
+
+    /// Serialize records of a single output with a custom [`RecordFormatter`]
+    pub fn with_output_record_formatter(
+        mut self,
+        output: Output,
+        formatter: impl RecordFormatter + 'static,
+    ) -> Self {
+        self.output_formatters.insert(output, Arc::new(formatter));
+        self
+    }
+
+    /// Write only records of `channels` to a single output
+    ///
+    /// Outputs accept every channel unless restricted, e.g. to send
+    /// `business` events only to an analytics sink. Overrides
+    /// `LOG_CONSOLE_CHANNELS` and `LOG_FILE_CHANNELS`; not applied with
+    /// ordered outputs, which write every record to console and file.
+    pub fn with_output_channels(
+        mut self,
+        output: Output,
+        channels: impl IntoIterator<Item = channel::Channel>,
+    ) -> Self {
+        self.output_channels
+            .insert(output, channels.into_iter().collect());
+        self
+    }
+
+    /// Write console and file output through a single ordered pipeline
+    ///
+    /// Each record is formatted once, stamped with a `seq` number and written
+    /// to both outputs under one lock, so they never disagree about event
+    /// order. Both outputs then share the global layout. Overrides `LOG_ORDERED`.
+    pub fn with_ordered_outputs(mut self, ordered: bool) -> Self {
+        self.ordered = Some(ordered);
+        self
+    }
+
+    /// Write console records at `level` or more severe to stderr and the rest to stdout
+    ///
+    /// `LevelFilter::WARN` sends WARN and ERROR to stderr, `LevelFilter::OFF`
+    /// keeps everything on stdout. Overrides `LOG_STDERR_LEVEL`; has no effect
+    /// on a writer from [`Builder::with_writer`] or on ordered outputs.
+    pub fn with_stderr_level(mut self, level: LevelFilter) -> Self {
+        self.stderr_level = Some(level);
+        self
+    }
+
+    /// Stamp every record with a per-process `seq` number, overriding `LOG_SEQ`
+    ///
+    /// The same event carries the same number in every output, so gaps can be
+    /// detected downstream with [`reader::check_sequence`].
+    pub fn with_sequence_numbers(mut self, sequence: bool) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Stamp every record with a unique `event_id` and a `seq` number, overriding `LOG_EVENT_ID`
+    ///
+    /// Ids are time-ordered and the same in every output, so downstream
+    /// pipelines can deduplicate on them; `seq` orders records created in
+    /// the same millisecond.
+    pub fn with_event_ids(mut self, format: request_id::IdFormat) -> Self {
+        self.event_ids = Some(format);
+        self
+    }
+
+    /// Add a `backtrace` field to ERROR events, overriding `LOG_ERROR_BACKTRACE`
+    ///
+    /// Capturing a backtrace is slow, so this is meant for services that log
+    /// few errors. Panic records keep the backtrace of the panic.
+    pub fn with_error_backtraces(mut self, enabled: bool) -> Self {
+        self.error_backtrace = Some(enabled);
+        self
+    }
+
+    /// Select the span records to write, overriding `LOG_SPAN_EVENTS`
+    ///
+    /// Can be changed later with [`ReloadHandle::set_span_events`]. Has no
+    /// effect when spans are disabled with `LOG_ENABLE_SPANS=false`.
+    pub fn with_span_events(mut self, events: spans::SpanEvents) -> Self {
+        self.span_events = Some(events);
+        self
+    }
+
+    /// Send every record to a custom [`LogSink`] as well
+    ///
+    /// Records are formatted once and fanned out to all added sinks. A sink
+    /// that panics is isolated from the others and from the application.
+    pub fn add_sink(mut self, sink: Box<dyn LogSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Call `callback` for ERROR events, at most once per `LOG_ALERT_INTERVAL_MS`
+    ///
+    /// The [`ErrorEvent`] carries the target, message and fields of the event,
+    /// and how many ERROR events were skipped since the previous call. See
+    /// [`alert`] for the thread the callback runs on.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// custom_tracing_logger::builder()
+    ///     .on_error(Box::new(|event| {
+    ///         eprintln!("page on-call: {} {:?}", event.target, event.message);
+    ///     }))
+    ///     .init();
+    /// ```
+    pub fn on_error(mut self, callback: Box<dyn Fn(&ErrorEvent) + Send + Sync>) -> Self {
+        self.on_error = Some(callback);
+        self
+    }
+
+    /// Create log files and directories with these permissions, replacing `LOG_FILE_MODE` and `LOG_FILE_GROUP`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use custom_tracing_logger::permissions::FilePermissions;
+    ///
+    /// custom_tracing_logger::builder()
+    ///     .with_file_permissions(FilePermissions::mode(0o640).group("adm").unwrap())
+    ///     .init();
+    /// ```
+    pub fn with_file_permissions(mut self, permissions: permissions::FilePermissions) -> Self {
+        self.file_permissions = Some(permissions);
+        self
+    }
+
+    /// What to do when the log file can't be created, replacing `LOG_FILE_FALLBACK`
+    ///
+    /// With [`config::FileFallback::FailInit`], use [`Builder::try_init`] to
+    /// handle the error instead of panicking.
+    pub fn with_file_fallback(mut self, fallback: config::FileFallback) -> Self {
+        self.file_fallback = Some(fallback);
+        self
+    }
+
+    /// Lock the log file around each record, overriding `LOG_FILE_SHARED`
+    ///
+    /// Enable this when other processes write to the same file, so their
+    /// records never interleave with ours.
+    pub fn with_shared_file(mut self, shared: bool) -> Self {
+        self.shared_file = Some(shared);
+        self
+    }
+
+    /// Name log files after `naming`, replacing `LOG_FILE_PATTERN`, `LOG_FILE_DATE_FORMAT`, `LOG_FILE_EXTENSION` and `LOG_FILE_SYMLINK`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use custom_tracing_logger::naming::FileNaming;
+    ///
+    /// // logs/myapp-20250307.log, with logs/current pointing at it
+    /// custom_tracing_logger::builder()
+    ///     .with_file_naming(
+    ///         FileNaming::pattern("{prefix}-{date}.{ext}")
+    ///             .unwrap()
+    ///             .date_format("%Y%m%d")
+    ///             .unwrap()
+    ///             .extension("log")
+    ///             .symlink("current"),
+    ///     )
+    ///     .init();
+    /// ```
+    pub fn with_file_naming(mut self, naming: naming::FileNaming) -> Self {
+        self.file_naming = Some(naming);
+        self
+    }
+
+    /// Write records of some targets to files of their own, replacing `LOG_ROUTES`
+    ///
+    /// Routes apply when file output is enabled and outputs aren't
+    /// [ordered](Builder::with_ordered_outputs). See [`routes`].
+    pub fn with_routes(mut self, routes: routes::Routes) -> Self {
+        self.routes = Some(routes);
+        self
+    }
+
+    /// Partition records by a field such as a tenant id, replacing `LOG_PARTITION_*`
+    ///
+    /// See [`partition`].
+    pub fn with_partitioning(mut self, partitioning: partition::Partitioning) -> Self {
+        self.partitioning = Some(partitioning);
+        self
+    }
+
+    /// Encrypt log file records with the key returned by `key`, replacing `LOG_ENCRYPTION_KEY`
+    ///
+    /// `key` is called once while the logger is built, e.g. to fetch or
+    /// unwrap the key with a KMS. If it fails, file logging is disabled
+    /// rather than writing records in the clear. See [`encryption`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use custom_tracing_logger::encryption::EncryptionKey;
+    ///
+    /// # fn fetch_from_kms() -> Result<[u8; 32], String> { Ok([0; 32]) }
+    /// custom_tracing_logger::builder()
+    ///     .with_file_encryption(|| fetch_from_kms().map(EncryptionKey::from_bytes))
+    ///     .init();
+    /// ```
+    #[cfg(feature = "encryption")]
+    pub fn with_file_encryption(
+        mut self,
+        key: impl FnOnce() -> Result<encryption::EncryptionKey, String> + Send + 'static,
+    ) -> Self {
+        self.file_encryption = Some(Box::new(key));
+        self
+    }
+
+    /// Upload completed log files with `config`, replacing `LOG_ARCHIVE_*`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use custom_tracing_logger::archive::{ArchiveConfig, S3Store};
+    ///
+    /// let mut archive = ArchiveConfig::new(S3Store::new("my-logs", "eu-west-1"));
+    /// archive.key_prefix = "prod/web/".to_string();
+    /// custom_tracing_logger::builder().with_archive(archive).init();
+    /// ```
+    #[cfg(feature = "archive")]
+    pub fn with_archive(mut self, config: archive::ArchiveConfig) -> Self {
+        self.archive = Some(config);
+        self
+    }
+
+    /// Redact sensitive fields in every output, replacing `LOG_REDACT_*`
+    pub fn with_redaction(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Truncate long strings and replace oversized records, replacing `LOG_MAX_FIELD_LEN` and `LOG_MAX_EVENT_BYTES`
+    pub fn with_limits(mut self, limits: limits::Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Keep string values on one line, replacing `LOG_NORMALIZE`
+    pub fn with_normalize(mut self, normalize: normalize::Normalize) -> Self {
+        self.normalize = Some(normalize);
+        self
+    }
+
+    /// Add `hostname`, `pid`, `thread_id` and `thread_name` to every record, replacing `LOG_PROCESS_INFO`
+    pub fn with_process_info(mut self, process_info: bool) -> Self {
+        self.process_info = Some(process_info);
+        self
+    }
+
+    /// Add `file`, `line` and `module_path` of the call site to every record, replacing `LOG_SOURCE_LOCATION`
+    pub fn with_source_location(mut self, source_location: bool) -> Self {
+        self.source_location = Some(source_location);
+        self
+    }
+
+    /// Select the field names of HTTP request records, overriding `LOG_HTTP_FIELDS`
+    ///
+    /// See [`http::set_field_style`]; can also be changed later at runtime.
+    pub fn with_http_field_style(mut self, style: http::FieldStyle) -> Self {
+        self.http_fields = Some(style);
+        self
+    }
+
+    /// Log health checks and other noisy requests at DEBUG or drop them, replacing `LOG_QUIET_*` and `LOG_SUCCESS_SAMPLE`
+    ///
+    /// See [`http::QuietRequests`]; can also be changed later with
+    /// [`http::set_quiet_requests`].
+    pub fn with_quiet_requests(mut self, quiet: http::QuietRequests) -> Self {
+        self.quiet_requests = Some(quiet);
+        self
+    }
+
+    /// Put the current span's fields at the top level of records instead of under `span`, replacing `LOG_FLATTEN_SPAN`
+    ///
+    /// The span name becomes `span_name`; record keys such as `level` are
+    /// never replaced by a span field of the same name.
+    pub fn with_flattened_span_fields(mut self, flatten: bool) -> Self {
+        self.flatten_span = Some(flatten);
+        self
+    }
+
+    /// Add a constant top-level field to every record (e.g. `service`, `version`)
+    ///
+    /// Builder fields are added to those from `LOG_STATIC_FIELDS`, replacing
+    /// any with the same key. Keys already in a record, such as `level`, are
+    /// never replaced.
+    pub fn with_static_field(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.static_fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// Stamp every record with build metadata under `build`, see [`build_info!`]
+    pub fn with_build_info(self, info: build_info::BuildInfo) -> Self {
+        self.with_static_field(build_info::BUILD, info)
+    }
+
+    /// Render the values of registered fields with their formatting functions
+    ///
+    /// Formatters run after the field allowlist and before redaction.
+    pub fn with_field_formatters(mut self, formatters: field_format::FieldFormatters) -> Self {
+        self.formatters = Some(formatters);
+        self
+    }
+
+    /// Rename record keys in every output, replacing `LOG_KEY_MAP`
+    ///
+    /// Renames apply after the record was reshaped for its layout, so they
+    /// see e.g. Datadog's `status` rather than `level`.
+    pub fn with_key_map(mut self, key_map: key_map::KeyMap) -> Self {
+        self.key_map = Some(key_map);
+        self
+    }
+
+    /// Emit only allowlisted event and span fields, replacing `LOG_FIELD_ALLOWLIST`
+    pub fn with_field_allowlist(mut self, allowlist: allowlist::Allowlist) -> Self {
+        self.allowlist = Some(allowlist);
+        self
+    }
+
+    /// Log every level inside spans whose `field` has one of `values`, replacing `LOG_DEBUG_USERS`
+    ///
+    /// Every callsite is then checked at runtime, which costs some
+    /// throughput; enable it only while investigating.
+    pub fn with_debug_users(
+        mut self,
+        field: &str,
+        values: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.debug_users = Some(debug_users::DebugUsers::new(field, values));
+        self
+    }
+
+    /// Log at DEBUG inside [`context::debug`] scopes, overriding `LOG_REQUEST_DEBUG`
+    ///
+    /// Every disabled DEBUG callsite is then checked at runtime, which
+    /// costs some throughput.
+    pub fn with_request_debug(mut self, enabled: bool) -> Self {
+        self.request_debug = Some(enabled);
+        self
+    }
+
+    /// Keep chatty dependencies at WARN unless `RUST_LOG` names them, overriding `LOG_QUIET_DEFAULTS`
+    ///
+    /// See [`presets`] for the crates quieted.
+    pub fn with_quiet_defaults(mut self, quiet: bool) -> Self {
+        self.quiet_defaults = Some(quiet);
+        self
+    }
+
+    /// Cap identical events per second, replacing `LOG_RATE_LIMIT`
+    pub fn with_rate_limit(mut self, rate_limit: rate_limit::RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Drop the events for which `keep` returns `false`, e.g. successful health checks
+    ///
+    /// See [`event_filter`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use custom_tracing_logger::event_filter::EventView;
+    ///
+    /// custom_tracing_logger::builder()
+    ///     .with_event_filter(|event: &EventView<'_>| {
+    ///         let health = event.field("path").and_then(|path| path.as_str()) == Some("/health");
+    ///         let ok = event.field("status").and_then(|status| status.as_u64()) == Some(200);
+    ///         !(health && ok)
+    ///     })
+    ///     .init();
+    /// ```
+    pub fn with_event_filter(
+        mut self,
+        keep: impl Fn(&event_filter::EventView<'_>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.event_filter = Some(Box::new(keep));
+        self
+    }
+
+    /// Collapse consecutive identical records into one with `repeat_count`, replacing `LOG_DEDUP`
+    ///
+    /// A collapsed run is written when a different record arrives, or after
+    /// `timeout` at the latest.
+    pub fn with_deduplication(mut self, timeout: std::time::Duration) -> Self {
+        self.dedup = Some(timeout);
+        self
+    }
+
+    /// Validate events against registered schemas
+    ///
+    /// Validation runs in debug builds, or when `LOG_SCHEMA_VALIDATE=true`;
+    /// each deviation is reported once as a warning.
+    pub fn with_schema_registry(mut self, registry: schema::SchemaRegistry) -> Self {
+        self.schemas = Some(registry);
+        self
+    }
+
+    /// Keep only a share of the events of chatty targets, replacing `LOG_SAMPLE`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use custom_tracing_logger::sampling::Sampling;
+    ///
+    /// custom_tracing_logger::builder()
+    ///     .with_sampling(Sampling::new().target("hyper", 0.01))
+    ///     .init();
+    /// ```
+    pub fn with_sampling(mut self, sampling: sampling::Sampling) -> Self {
+        self.sampling = Some(sampling);
+        self
+    }
+
+    /// Layout for `output`: builder override, then its env variable, then the global layout
+    fn format_for(&self, output: Output) -> LogFormat {
+        self.output_formats
+            .get(&output)
+            .copied()
+            .or_else(|| output.format_env_var().and_then(LogFormat::from_env_var))
+            .or(self.format)
+            .unwrap_or_else(LogFormat::from_env)
+    }
+
+    /// Channels of `output`: builder override, then its env variable, else all
+    fn channels_for(&self, output: Output) -> Option<channel::ChannelSet> {
+        self.output_channels.get(&output).copied().or_else(|| {
+            let var = output.channels_env_var()?;
+            channel::ChannelSet::from_env_var(var).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                None
+            })
+        })
+    }
+
+    /// Add a monotonic `uptime_ns` field to every record, sourced from `clock`
+    pub fn with_uptime_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Effective configuration of this builder, with the source of each option
+    ///
+    /// Options set on the builder win over environment variables, which win
+    /// over `LOG_CONFIG_FILE` and the defaults. Builder values without a
+    /// textual form, such as a custom [`Redactor`], read "custom".
+    pub fn resolve_config(&self) -> Result<config::Config, String> {
+        let mut config = config::Config::resolve()?;
+        let flag = |set: bool| set.to_string();
+        if let Some(format) = self.format {
+            config.set_by_builder("LOG_FORMAT", format.as_str());
+        }
+        for (output, format) in &self.output_formats {
+            if let Some(var) = output.format_env_var() {
+                config.set_by_builder(var, format.as_str());
+            }
+        }
+        for (output, channels) in &self.output_channels {
+            if let Some(var) = output.channels_env_var() {
+                config.set_by_builder(var, channels.names().join(","));
+            }
+        }
+        if let Some(ordered) = self.ordered {
+            config.set_by_builder("LOG_ORDERED", flag(ordered));
+        }
+        if let Some(level) = self.stderr_level {
+            config.set_by_builder("LOG_STDERR_LEVEL", level.to_string());
+        }
+        if let Some(sequence) = self.sequence {
+            config.set_by_builder("LOG_SEQ", flag(sequence));
+        }
+        if let Some(format) = self.event_ids {
+            config.set_by_builder("LOG_EVENT_ID", format.to_string());
+        }
+        if let Some(error_backtrace) = self.error_backtrace {
+            config.set_by_builder("LOG_ERROR_BACKTRACE", flag(error_backtrace));
+        }
+        if self.span_events.is_some() {
+            config.set_by_builder("LOG_SPAN_EVENTS", "custom");
+        }
+        if let Some(process_info) = self.process_info {
+            config.set_by_builder("LOG_PROCESS_INFO", flag(process_info));
+        }
+        if let Some(source_location) = self.source_location {
+            config.set_by_builder("LOG_SOURCE_LOCATION", flag(source_location));
+        }
+        if let Some(flatten) = self.flatten_span {
+            config.set_by_builder("LOG_FLATTEN_SPAN", flag(flatten));
+        }
+        if let Some(style) = self.http_fields {
+            let style = match style {
+                http::FieldStyle::Flat => "flat",
+                http::FieldStyle::Otel => "otel",
+            };
+            config.set_by_builder("LOG_HTTP_FIELDS", style);
+        }
+        if let Some(quiet) = &self.quiet_requests {
+            config.set_by_builder("LOG_QUIET_PATHS", quiet.paths());
+        }
+        if self.clock.is_some() {
+            config.set_by_builder("LOG_UPTIME", "true");
+        }
+        if let Some(timeout) = self.dedup {
+            config.set_by_builder("LOG_DEDUP", "true");
+            config.set_by_builder("LOG_DEDUP_TIMEOUT_MS", timeout.as_millis().to_string());
+        }
+        if !self.static_fields.is_empty() {
+            let mut fields = static_fields::from_env().ok().flatten().unwrap_or_default();
+            fields.extend(self.static_fields.clone());
+            let pairs: Vec<String> = fields
+                .iter()
+                .map(|(key, value)| {
+                    // Strings without their JSON quotes, as LOG_STATIC_FIELDS spells them
+                    let value = value
+                        .as_str()
+                        .map_or_else(|| value.to_string(), str::to_string);
+                    format!("{}={}", key, value)
+                })
+                .collect();
+            config.set_by_builder("LOG_STATIC_FIELDS", pairs.join(","));
+        }
+        let custom = [
+            ("LOG_REDACT_FIELDS", self.redactor.is_some()),
+            ("LOG_FIELD_ALLOWLIST", self.allowlist.is_some()),
+            ("LOG_KEY_MAP", self.key_map.is_some()),
+            ("LOG_DEBUG_USERS", self.debug_users.is_some()),
+            ("LOG_RATE_LIMIT", self.rate_limit.is_some()),
+            ("LOG_SAMPLE", self.sampling.is_some()),
+        ];
+        for (var, set) in custom {
+            if set {
+                config.set_by_builder(var, "custom");
+            }
+        }
+        if let Some(permissions) = &self.file_permissions {
+            config.set_by_builder("LOG_FILE_MODE", format!("{:o}", permissions.file_mode()));
+        }
+        if let Some(fallback) = self.file_fallback {
+            config.set_by_builder("LOG_FILE_FALLBACK", fallback.to_string());
+        }
+        if let Some(shared) = self.shared_file {
+            config.set_by_builder("LOG_FILE_SHARED", flag(shared));
+        }
+        if let Some(naming) = &self.file_naming {
+            config.set_by_builder("LOG_FILE_PATTERN", naming.to_string());
+        }
+        if let Some(routes) = &self.routes {
+            config.set_by_builder("LOG_ROUTES", routes.to_string());
+        }
+        if let Some(normalize) = self.normalize {
+            config.set_by_builder("LOG_NORMALIZE", normalize.to_string());
+        }
+        if let Some(limits) = &self.limits {
+            if let Some(len) = limits.field_len() {
+                config.set_by_builder("LOG_MAX_FIELD_LEN", len.to_string());
+            }
+            if let Some(bytes) = limits.event_bytes() {
+                config.set_by_builder("LOG_MAX_EVENT_BYTES", bytes.to_string());
+            }
+        }
+        if let Some(quiet) = self.quiet_defaults {
+            config.set_by_builder("LOG_QUIET_DEFAULTS", flag(quiet));
+        }
+        if let Some(enabled) = self.request_debug {
+            config.set_by_builder("LOG_REQUEST_DEBUG", flag(enabled));
+        }
+        if let Some(partitioning) = &self.partitioning {
+            config.set_by_builder("LOG_PARTITION_FIELD", partitioning.field());
+        }
+        #[cfg(feature = "encryption")]
+        if self.file_encryption.is_some() {
+            config.set_by_builder("LOG_ENCRYPTION_KEY", "custom");
+        }
+        #[cfg(feature = "archive")]
+        if let Some(archive) = &self.archive {
+            config.set_by_builder("LOG_ARCHIVE_URL", archive.location());
+        }
+        Ok(config)
+    }
+
+    /// Install the logger as the global default subscriber
+    ///
+    /// The returned [`ReloadHandle`] changes the `RUST_LOG` filter at runtime.
+    /// If this crate already installed a logger in this process, nothing is
+    /// built and the handle of that logger is returned, so calling `init`
+    /// from several tests opens no extra files or threads.
+    ///
+    /// # Panics
+    /// When the log file can't be created and `LOG_FILE_FALLBACK=fail_init`
+    /// (or [`Builder::with_file_fallback`]) asks for that; use
+    /// [`Builder::try_init`] to handle the error instead.
+    pub fn init(self) -> ReloadHandle {
+        self.try_init().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Install the logger as the global default subscriber, failing if the log file is unusable
+    ///
+    /// Only fails with the [`config::FileFallback::FailInit`] policy;
+    /// otherwise behaves like [`Builder::init`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use custom_tracing_logger::config::FileFallback;
+    ///
+    /// let handle = custom_tracing_logger::builder()
+    ///     .with_file_fallback(FileFallback::FailInit)
+    ///     .try_init()
+    ///     .unwrap_or_else(|e| {
+    ///         eprintln!("{}", e);
+    ///         std::process::exit(1);
+    ///     });
+    /// ```
+    pub fn try_init(mut self) -> Result<ReloadHandle, String> {
+        if let Some(handle) = reload::installed() {
+            return Ok(handle);
+        }
+        if let Err(e) = config_file::load() {
+            eprintln!("Ignoring LOG_CONFIG_FILE: {}", e);
+        }
+        if config_file::var("LOG_DISABLED").unwrap_or_default() == "true" {
+            return Ok(init_disabled());
+        }
+        let (stack, parts) = self.build()?;
+        let subscriber = tracing_subscriber::registry().with(stack);
+        let installed = tracing::subscriber::set_global_default(subscriber).is_ok();
+        #[cfg(feature = "log")]
+        if installed {
+            log_bridge::install();
+        }
+        let handle = parts.activate(installed, true);
+        if installed && config_file::var("LOG_PANIC_HOOK").unwrap_or_default() == "true" {
+            install_panic_hook();
+        }
+        Ok(handle)
+    }
+
+    /// Build the logger's layers without installing them
+    ///
+    /// For applications that assemble their own subscriber, e.g. with an
+    /// OpenTelemetry layer. Add the returned layer directly on
+    /// [`tracing_subscriber::registry()`] and install the subscriber once;
+    /// outputs, sinks, flushing and [`flush`] work as after [`init`], and a
+    /// later [`init`] returns the same [`ReloadHandle`]. The "logger
+    /// initialized" record is not written, since the logger does not know when
+    /// the subscriber is installed.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let (layer, handle) = custom_tracing_logger::builder().layer();
+    /// tracing_subscriber::registry()
+    ///     .with(layer)
+    ///     // .with(opentelemetry_layer)
+    ///     .init();
+    /// handle.set_filter("debug").unwrap();
+    /// ```
+    ///
+    /// # Panics
+    /// Like [`Builder::init`], when the log file can't be created with the
+    /// [`config::FileFallback::FailInit`] policy.
+    pub fn layer(mut self) -> (impl Layer<Registry> + Send + Sync, ReloadHandle) {
+        if let Err(e) = config_file::load() {
+            eprintln!("Ignoring LOG_CONFIG_FILE: {}", e);
+        }
+        if config_file::var("LOG_DISABLED").unwrap_or_default() == "true" {
+            let filter = debug_users::DebugUserFilter::new(EnvFilter::new("off"), None);
+            let (filter, handle) = tracing_subscriber::reload::Layer::new(filter);
+            let layer: BoxedLayer =
+                Box::new(tracing_subscriber::layer::Identity::new().with_filter(filter));
+            return (vec![layer], ReloadHandle::new(handle));
+        }
+        let (stack, parts) = self.build().unwrap_or_else(|e| panic!("{}", e));
+        let handle = parts.activate(reload::installed().is_none(), false);
+        (stack, handle)
+    }
+
+    /// Create the layers of every configured output
+    ///
+    /// Fails only when the log file can't be used and the fallback is
+    /// [`config::FileFallback::FailInit`].
+    fn build(&mut self) -> Result<(Vec<BoxedLayer>, Parts), String> {
+        let sinks = std::mem::take(&mut self.sinks);
+        let schemas = self.schemas.take();
+        let ring = ring::RingConfig::from_env().unwrap_or_else(|e| {
+            eprintln!("Ring buffer disabled: {}", e);
+            None
+        });
+        let rate_limit = self.rate_limit.take().or_else(|| {
+            rate_limit::RateLimit::from_env().unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_RATE_LIMIT: {}", e);
+                None
+            })
+        });
+        let sampling = self
+            .sampling
+            .take()
+            .or_else(|| {
+                sampling::Sampling::from_env().unwrap_or_else(|e| {
+                    eprintln!("Invalid LOG_SAMPLE: {}", e);
+                    None
+                })
+            })
+            .filter(|sampling| !sampling.is_empty());
+        let event_filter = self.event_filter.take();
+
+        // Handle RUST_LOG with whitespace trimming for Windows compatibility
+        let filter = config_file::var("RUST_LOG")
+            .map_or_else(|_| "info".to_string(), |val| val.trim().to_string());
+        presets::set_quiet_defaults(
+            self.quiet_defaults
+                .unwrap_or_else(presets::quiet_defaults_from_env),
+        );
+        let filter = presets::apply(&filter);
+        let env_filter = EnvFilter::new(&filter);
+        // Full logging inside spans of selected users
+        let debug_users = self
+            .debug_users
+            .take()
+            .or_else(debug_users::DebugUsers::from_env);
+        let env_filter = debug_users::DebugUserFilter::new(env_filter, debug_users)
+            .with_request_debug(
+                self.request_debug
+                    .unwrap_or_else(context::request_debug_from_env),
+            );
+        let (env_filter, reload_handle) = tracing_subscriber::reload::Layer::new(env_filter);
+
+        // Check for file logging configuration, falling back to console on a bad directory
+        let file_permissions = self
+            .file_permissions
+            .take()
+            .unwrap_or_else(permissions::FilePermissions::from_env_lenient);
+        let fallback = self
+            .file_fallback
+            .unwrap_or_else(config::FileFallback::from_env_lenient);
+        // Why a configured file output can't be used, handled by `fallback`
+        let mut file_error = None;
+        let log_file_dir = match config_file::var("LOG_FILE_DIR") {
+            Ok(raw) => match paths::resolve_log_dir_with(&raw, &file_permissions) {
+                Ok(dir) => Some(dir),
+                Err(e) => {
+                    file_error = Some(e.to_string());
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+        // Lambda collects stdout; files would only fill the ephemeral /tmp
+        #[cfg(feature = "lambda")]
+        let log_file_dir = log_file_dir.filter(|_| {
+            let lambda = lambda::is_lambda();
+            if lambda {
+                eprintln!("File logging disabled on AWS Lambda, records go to stdout");
+            }
+            !lambda
+        });
+        // Records asked to be encrypted are never written in the clear
+        #[cfg(feature = "encryption")]
+        let file_key = match self.file_encryption.take() {
+            Some(key) => key().map(Some),
+            None => encryption::EncryptionKey::from_env(),
+        };
+        #[cfg(feature = "encryption")]
+        let (log_file_dir, file_key) = match file_key {
+            Ok(key) => (log_file_dir, key),
+            Err(e) => {
+                if log_file_dir.is_some() {
+                    file_error = Some(format!("no encryption key: {}", e));
+                }
+                (None, None)
+            }
+        };
+        #[cfg(not(feature = "encryption"))]
+        let log_file_dir = log_file_dir.filter(|_| {
+            let encrypted = config_file::var("LOG_ENCRYPTION_KEY").is_ok();
+            if encrypted {
+                eprintln!(
+                    "File logging disabled: LOG_ENCRYPTION_KEY requires the `encryption` feature"
+                );
+            }
+            !encrypted
+        });
+        let log_file_prefix =
+            config_file::var("LOG_FILE_PREFIX").unwrap_or_else(|_| "app".to_string());
+        let file_only = config_file::var("LOG_FILE_ONLY").unwrap_or_default() == "true";
+
+        // Daily rotating file output, opened now so an unusable file is noticed at init
+        let file_options = file::FileOptions {
+            permissions: file_permissions,
+            naming: self
+                .file_naming
+                .take()
+                .unwrap_or_else(naming::FileNaming::from_env_lenient),
+            buffer: file::WriteBuffer::from_env_lenient(),
+            shared: self.shared_file.unwrap_or_else(|| {
+                config_file::var("LOG_FILE_SHARED").unwrap_or_default() == "true"
+            }),
+            #[cfg(feature = "encryption")]
+            encryption_key: file_key,
+        };
+        let log_file = log_file_dir.as_ref().and_then(|log_dir| {
+            let log_file =
+                file::LogFile::open(log_dir.clone(), &log_file_prefix, file_options.clone());
+            match log_file.ensure_open() {
+                Ok(()) => Some(log_file),
+                Err(e) => {
+                    file_error = Some(format!(
+                        "Cannot open log file '{}': {}",
+                        log_dir
+                            .join(file_options.naming.describe(&log_file_prefix))
+                            .display(),
+                        e
+                    ));
+                    None
+                }
+            }
+        });
+        let file_error = match (file_error, fallback) {
+            (Some(e), config::FileFallback::FailInit) => {
+                return Err(format!("File logging unavailable: {}", e))
+            }
+            (file_error, _) => file_error,
+        };
+        // Console output, unless file-only logging was requested and the file works or may be dropped
+        let console = match (&log_file, &file_error) {
+            (Some(_), _) => !file_only,
+            (None, Some(_)) if fallback == config::FileFallback::SilentDrop => !file_only,
+            (None, _) => true,
+        };
+        let startup = Startup {
+            mode: match (log_file.is_some(), console) {
+                (true, true) => config::Mode::ConsoleAndFile,
+                (true, false) => config::Mode::FileOnly,
+                (false, true) => config::Mode::Console,
+                (false, false) => config::Mode::Disabled,
+            },
+            filter,
+            file: log_file.as_ref().and(log_file_dir.as_ref()).map(|dir| {
+                dir.join(file_options.naming.describe(&log_file_prefix))
+                    .display()
+                    .to_string()
+            }),
+            format: self.format.unwrap_or_else(LogFormat::from_env),
+            file_error: file_error.filter(|_| fallback == config::FileFallback::FallbackConsole),
+        };
+        let enable_spans =
+            config_file::var("LOG_ENABLE_SPANS").unwrap_or_else(|_| "true".to_string()) == "true";
+        let uptime = config_file::var("LOG_UPTIME").unwrap_or_default() == "true";
+        if config_file::var("LOG_SPAN_FIELDS").unwrap_or_default() == "false" {
+            spans::set_field_capture(false);
+        }
+        if let Some(style) = self.http_fields {
+            http::set_field_style(style);
+        } else if let Ok(style) = config_file::var("LOG_HTTP_FIELDS") {
+            match style.parse() {
+                Ok(style) => http::set_field_style(style),
+                Err(e) => eprintln!("Invalid LOG_HTTP_FIELDS: {}", e),
+            }
+        }
+        match self.quiet_requests.take() {
+            Some(quiet) => http::set_quiet_requests(Some(quiet)),
+            None => match http::QuietRequests::from_env() {
+                Ok(Some(quiet)) => http::set_quiet_requests(Some(quiet)),
+                Ok(None) => {}
+                Err(e) => eprintln!("Invalid LOG_QUIET_PATHS: {}", e),
+            },
+        }
+        if let Some(events) = self.span_events {
+            spans::set_span_events(events);
+        } else if let Ok(mode) = config_file::var("LOG_SPAN_EVENTS") {
+            match mode.parse() {
+                Ok(mode) => spans::set_span_events(mode),
+                Err(e) => eprintln!("Invalid LOG_SPAN_EVENTS: {}", e),
+            }
+        }
+        let ordered = self
+            .ordered
+            .unwrap_or_else(|| config_file::var("LOG_ORDERED").unwrap_or_default() == "true");
+        let stderr_level = match self.stderr_level {
+            Some(level) => level.into_level(),
+            None => console::stderr_level_from_env().unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_STDERR_LEVEL: {}", e);
+                None
+            }),
+        }
+        .filter(|_| {
+            if ordered {
+                eprintln!("LOG_STDERR_LEVEL ignored: ordered outputs write one console stream");
+            }
+            !ordered
+        });
+
+        // Files of routed targets, next to the regular file
+        let routes = self
+            .routes
+            .take()
+            .or_else(|| {
+                routes::Routes::from_env().unwrap_or_else(|e| {
+                    eprintln!("Invalid LOG_ROUTES: {}", e);
+                    None
+                })
+            })
+            .filter(|routes| !routes.is_empty() && log_file.is_some())
+            .filter(|_| {
+                if ordered {
+                    eprintln!("LOG_ROUTES ignored: ordered outputs write one file");
+                }
+                !ordered
+            });
+        let routed_files: Vec<(String, Arc<file::LogFile>)> = match (&routes, log_file_dir.as_ref())
+        {
+            (Some(routes), Some(dir)) => routes
+                .files()
+                .into_iter()
+                .map(|name| {
+                    // The symlink keeps pointing at the regular file
+                    let options = file::FileOptions {
+                        naming: file_options.naming.clone().symlink(""),
+                        ..file_options.clone()
+                    };
+                    (
+                        name.to_string(),
+                        file::LogFile::open(dir.clone(), name, options),
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let partitioning = self.partitioning.take().or_else(|| {
+            partition::Partitioning::from_env().unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_PARTITION_MAX_FILES: {}", e);
+                None
+            })
+        });
+
+        let clock = self
+            .clock
+            .clone()
+            .or_else(|| uptime.then(|| Arc::new(MonotonicClock) as Arc<dyn Clock>));
+        let event_ids = self.event_ids.or_else(|| {
+            sequence::event_ids_from_env().unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_EVENT_ID: {}", e);
+                None
+            })
+        });
+        // Event ids come with sequence numbers, which order ids of the same millisecond
+        let sequence = event_ids.is_some()
+            || self
+                .sequence
+                .unwrap_or_else(|| config_file::var("LOG_SEQ").unwrap_or_default() == "true");
+        let error_backtrace = self
+            .error_backtrace
+            .unwrap_or_else(error_backtrace::enabled_from_env);
+        let redactor = self
+            .redactor
+            .take()
+            .or_else(Redactor::from_env_lenient)
+            .filter(|redactor| !redactor.is_empty())
+            .map(Arc::new);
+        let limits = self
+            .limits
+            .or_else(|| {
+                limits::Limits::from_env().unwrap_or_else(|e| {
+                    eprintln!("Invalid record size limits: {}", e);
+                    None
+                })
+            })
+            .filter(|limits| !limits.is_empty());
+        let normalize = self.normalize.or_else(|| {
+            normalize::Normalize::from_env().unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_NORMALIZE: {}", e);
+                None
+            })
+        });
+        let allowlist = self
+            .allowlist
+            .take()
+            .or_else(allowlist::Allowlist::from_env_lenient)
+            .map(Arc::new);
+        let key_map = self.key_map.take().or_else(|| {
+            key_map::KeyMap::from_env().unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_KEY_MAP: {}", e);
+                None
+            })
+        });
+        let mut static_fields = static_fields::from_env()
+            .unwrap_or_else(|e| {
+                eprintln!("Invalid LOG_STATIC_FIELDS: {}", e);
+                None
+            })
+            .unwrap_or_default();
+        static_fields.extend(std::mem::take(&mut self.static_fields));
+        let format = EventFormat::new(enable_spans)
+            .with_clock(clock)
+            // Ordered mode numbers records itself, in write order
+            .with_sequence(sequence && !ordered)
+            .with_event_id(event_ids.is_some())
+            .with_partition(partitioning.is_some())
+            .with_error_backtrace(error_backtrace)
+            .with_flattened_span(self.flatten_span.unwrap_or_else(|| {
+                config_file::var("LOG_FLATTEN_SPAN").unwrap_or_default() == "true"
+            }))
+            .with_process_info(
+                self.process_info
+                    .unwrap_or_else(process_info::enabled_from_env),
+            )
+            .with_source_location(
+                self.source_location
+                    .unwrap_or_else(source_location::enabled_from_env),
+            )
+            .with_allowlist(allowlist.clone())
+            .with_static_fields((!static_fields.is_empty()).then(|| Arc::new(static_fields)))
+            .with_formatters(
+                self.formatters
+                    .take()
+                    .filter(|formatters| !formatters.is_empty())
+                    .map(Arc::new),
+            )
+            .with_redactor(redactor.clone())
+            .with_normalize(normalize)
+            .with_limits(limits)
+            .with_key_map(key_map.filter(|keys| !keys.is_empty()).map(Arc::new))
+            .with_dedup(self.dedup.or_else(dedup::timeout_from_env));
+        let writer = self.writer.take();
+        let format_for = |output| {
+            format
+                .clone()
+                .with_style(self.format_for(output))
+                .with_channels(self.channels_for(output))
+                .with_record_formatter(
+                    self.output_formatters
+                        .get(&output)
+                        .or(self.record_formatter.as_ref())
+                        .cloned(),
+                )
+        };
+
+        let mut layers: Vec<BoxedLayer> = Vec::new();
+
+        // The event filter drops rejected events before they are sampled
+        if let Some(predicate) = event_filter {
+            layers.push(Box::new(event_filter::EventFilterLayer::new(predicate)));
+        }
+
+        // Sampling drops its share of events before they use up rate limit tokens
+        if let Some(sampling) = sampling {
+            layers.push(Box::new(sampling::SamplingLayer::new(sampling)));
+        }
+
+        // Rate limiting drops events before any other layer sees them
+        if let Some(rate_limit) = rate_limit {
+            layers.push(Box::new(rate_limit::RateLimitLayer::new(rate_limit)));
+        }
+
+        // Counts for `stats()`, of events that passed the filter and rate limit
+        layers.push(Box::new(metrics::StatsLayer));
+
+        // Schema validation, debug builds only unless forced
+        if let Some(registry) = schemas {
+            if schema::validation_enabled() {
+                layers.push(Box::new(schema::SchemaLayer::new(registry)));
+            }
+        }
+
+        // Busy and idle time of spans, for close records
+        if enable_spans {
+            layers.push(Box::new(spans::TimingLayer));
+        }
+
+        // Sequence numbers and event ids are assigned before any output formats the event
+        if (sequence && !ordered) || event_ids.is_some() {
+            layers.push(Box::new(
+                sequence::SequenceLayer::new(enable_spans).with_event_ids(event_ids),
+            ));
+        }
+
+        // The partition of each record is found once for every output
+        if let Some(partitioning) = &partitioning {
+            layers.push(Box::new(partition::PartitionLayer::new(partitioning)));
+        }
+
+        // Backtraces of ERROR events are captured once for every output
+        if error_backtrace {
+            layers.push(Box::new(error_backtrace::BacktraceLayer));
+        }
+
+        let file_appender = log_file.clone().map(file::SharedLogFile);
+
+        if ordered {
+            let mut outputs: Vec<Box<dyn std::io::Write + Send>> = Vec::new();
+            match (console, writer) {
+                (true, Some(writer)) => outputs.push(Box::new(ordered::MakeWriterOutput(writer))),
+                (true, None) => outputs.push(Box::new(std::io::stdout())),
+                (false, _) => {}
+            }
+            if let Some(file_appender) = file_appender {
+                outputs.push(Box::new(file_appender));
+            }
+            let format = format
+                .clone()
+                .with_style(self.format.unwrap_or_else(LogFormat::from_env));
+            let writer = ordered::OrderedWriter::new(outputs);
+            layers.push(json_layer(writer, enable_spans, &format));
+        } else {
+            if console {
+                let format = format_for(Output::Console);
+                match writer {
+                    Some(writer) => layers.push(json_layer(writer, enable_spans, &format)),
+                    None => match stderr_level {
+                        Some(level) => layers.push(json_layer(
+                            console::split(level, std::io::stdout, std::io::stderr),
+                            enable_spans,
+                            &format,
+                        )),
+                        None => layers.push(json_layer(std::io::stdout, enable_spans, &format)),
+                    },
+                }
+            }
+            if let Some(file_appender) = file_appender {
+                let format = format_for(Output::File);
+                // Records of each partition go to a file of their own
+                let regular = match (&partitioning, log_file_dir.as_ref()) {
+                    (Some(partitioning), Some(dir)) => json_layer(
+                        partition::PartitionedFile::new(
+                            Arc::clone(&file_appender.0),
+                            dir.clone(),
+                            &log_file_prefix,
+                            file_options.clone(),
+                            partitioning,
+                        ),
+                        enable_spans,
+                        &format,
+                    ),
+                    _ => json_layer(file_appender, enable_spans, &format),
+                };
+                match &routes {
+                    // Each record goes to the one file its target is routed to
+                    Some(routes) => {
+                        let routes = Arc::new(routes.clone());
+                        if !routes.has_catch_all() {
+                            let routes = Arc::clone(&routes);
+                            layers.push(Box::new(regular.with_filter(filter::filter_fn(
+                                move |metadata| routes.file_for(metadata.target()).is_none(),
+                            ))));
+                        }
+                        for (name, routed_file) in &routed_files {
+                            let (routes, name) = (Arc::clone(&routes), name.clone());
+                            let writer = file::SharedLogFile(Arc::clone(routed_file));
+                            let layer = json_layer(writer, enable_spans, &format);
+                            layers.push(Box::new(layer.with_filter(filter::filter_fn(
+                                move |metadata| {
+                                    routes.file_for(metadata.target()) == Some(name.as_str())
+                                },
+                            ))));
+                        }
+                    }
+                    None => layers.push(regular),
+                }
+            }
+        }
+
+        // Local Datadog agent over TCP
+        if let Ok(addr) = config_file::var("LOG_DATADOG_AGENT_ADDR") {
+            let writer = net::TcpWriter::new(addr.trim());
+            let format = format_for(Output::DatadogAgent);
+            layers.push(json_layer(writer, enable_spans, &format));
+        }
+
+        // NDJSON collector over TCP or UDP
+        if let Ok(url) = config_file::var("LOG_NET_ADDR") {
+            match net::NetWriter::from_url(&url) {
+                Ok(net::NetWriter::Tcp(writer)) => {
+                    let writer = match net::spill_from_env() {
+                        Some((path, max_bytes)) => writer.with_spill(path, max_bytes),
+                        None => writer,
+                    };
+                    let format = format_for(Output::Network);
+                    layers.push(json_layer(writer, enable_spans, &format));
+                }
+                Ok(writer) => {
+                    let format = format_for(Output::Network);
+                    layers.push(json_layer(writer, enable_spans, &format));
+                }
+                Err(e) => eprintln!("Invalid LOG_NET_ADDR: {}", e),
+            }
+        }
+
+        // Local agent over a Unix domain socket
+        if let Ok(path) = config_file::var("LOG_SOCKET_PATH") {
+            #[cfg(unix)]
+            {
+                let writer = net::UnixSocketWriter::new(path.trim());
+                let format = format_for(Output::UnixSocket);
+                layers.push(json_layer(writer, enable_spans, &format));
+            }
+            #[cfg(not(unix))]
+            eprintln!(
+                "LOG_SOCKET_PATH={} ignored: Unix domain sockets are not supported on this platform",
+                path
+            );
+        }
+
+        // Passive capture socket for locally attached viewers
+        if let Ok(path) = config_file::var("LOG_CAPTURE_SOCKET") {
+            #[cfg(unix)]
+            match net::CaptureSocket::bind(path.trim()) {
+                Ok(writer) => {
+                    let format = format_for(Output::CaptureSocket);
+                    layers.push(json_layer(writer, enable_spans, &format));
+                }
+                Err(e) => eprintln!("Cannot listen on LOG_CAPTURE_SOCKET={}: {}", path, e),
+            }
+            #[cfg(not(unix))]
+            eprintln!(
+                "LOG_CAPTURE_SOCKET={} ignored: Unix domain sockets are not supported on this platform",
+                path
+            );
+        }
+
+        // Grafana Loki push sink
+        #[cfg(feature = "loki")]
+        if let Some(config) = loki::LokiConfig::from_env() {
+            match loki::LokiWriter::new(config) {
+                Ok(writer) => {
+                    let format = format_for(Output::Loki);
+                    layers.push(json_layer(writer, enable_spans, &format));
+                }
+                Err(e) => eprintln!("Loki sink disabled: {}", e),
+            }
+        }
+
+        // AWS CloudWatch Logs sink
+        #[cfg(feature = "cloudwatch")]
+        match cloudwatch::CloudWatchConfig::from_env() {
+            Ok(Some(config)) => match cloudwatch::CloudWatchWriter::new(config) {
+                Ok(writer) => {
+                    let format = format_for(Output::CloudWatch);
+                    layers.push(json_layer(writer, enable_spans, &format));
+                }
+                Err(e) => eprintln!("CloudWatch sink disabled: {}", e),
+            },
+            Ok(None) => {}
+            Err(e) => eprintln!("CloudWatch sink disabled: {}", e),
+        }
+
+        // Webhook for critical events
+        #[cfg(feature = "webhook")]
+        match webhook::WebhookConfig::from_env() {
+            Ok(Some(config)) => {
+                let format = format_for(Output::Webhook);
+                let level = tracing::level_filters::LevelFilter::from_level(config.level);
+                match webhook::WebhookWriter::new(config) {
+                    Ok(writer) => {
+                        let layer = json_layer(writer, enable_spans, &format);
+                        layers.push(Box::new(layer.with_filter(level)));
+                    }
+                    Err(e) => eprintln!("Webhook sink disabled: {}", e),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Webhook sink disabled: {}", e),
+        }
+
+        // Sentry error reporting
+        #[cfg(feature = "sentry")]
+        match sentry::SentryConfig::from_env() {
+            Ok(Some(config)) => match sentry::SentryLayer::new(config) {
+                Ok(layer) => layers.push(Box::new(layer.with_scrubbing(allowlist, redactor))),
+                Err(e) => eprintln!("Sentry integration disabled: {}", e),
+            },
+            Ok(None) => {}
+            Err(e) => eprintln!("Sentry integration disabled: {}", e),
+        }
+
+        // Custom sinks share one formatting layer
+        let fan_out = (!sinks.is_empty()).then(|| Arc::new(sink::FanOut::new(sinks)));
+        if let Some(fan_out) = &fan_out {
+            let format = format_for(Output::Sinks);
+            let writer = sink::FanOutWriter(Arc::clone(fan_out));
+            layers.push(json_layer(writer, enable_spans, &format));
+        }
+
+        // The error callback runs once the outputs have the record
+        if let Some(callback) = self.on_error.take() {
+            layers.push(Box::new(alert::AlertLayer::new(
+                callback,
+                alert::interval_from_env(),
+            )));
+        }
 
-    // Check for file logging configuration
-    let log_file_dir = std::env::var("LOG_FILE_DIR").ok();
-    let log_file_prefix = std::env::var("LOG_FILE_PREFIX").unwrap_or_else(|_| "app".to_string());
-    let file_only = std::env::var("LOG_FILE_ONLY").unwrap_or_default() == "true";
-    let enable_spans =
-        std::env::var("LOG_ENABLE_SPANS").unwrap_or_else(|_| "true".to_string()) == "true";
+        // RUST_LOG applies to the outputs; the ring buffer sees every level it keeps
+        let outputs = reentrant::Guarded::new(layers).with_filter(env_filter);
+        let mut stack: Vec<BoxedLayer> = vec![Box::new(outputs)];
+        let ring = ring.map(|config| {
+            let ring = Arc::new(ring::RingBuffer::new(config));
+            let writer = ring::RingWriter(Arc::clone(&ring));
+            // Events below RUST_LOG never reach the partition layer
+            let format = format
+                .clone()
+                .with_style(LogFormat::Json)
+                .with_partition(false)
+                .with_record_formatter(None);
+            stack.push(Box::new(
+                json_layer(writer, enable_spans, &format).with_filter(config.level),
+            ));
+            ring
+        });
 
-    let registry = tracing_subscriber::registry().with(env_filter);
+        // Uploads of completed files, only with file output
+        #[cfg(feature = "archive")]
+        let archive = match self.archive.take() {
+            Some(config) => Some(config),
+            None => archive::ArchiveConfig::from_env().unwrap_or_else(|e| {
+                eprintln!("Log file archiving disabled: {}", e);
+                None
+            }),
+        }
+        .filter(|_| log_file.is_some());
 
-    match (log_file_dir, file_only) {
-        // File logging + console
-        (Some(log_dir), false) => {
-            let mut console_layer = fmt::layer()
-                .json()
-                .with_current_span(enable_spans)
-                .with_span_list(false);
+        let parts = Parts {
+            ring,
+            fan_out,
+            log_file,
+            routed_files: routed_files.into_iter().map(|(_, file)| file).collect(),
+            reload_handle,
+            startup,
+            #[cfg(feature = "archive")]
+            archive,
+        };
+        Ok((stack, parts))
+    }
+}
 
-            if enable_spans {
-                console_layer = console_layer
-                    .with_span_events(fmt::format::FmtSpan::ENTER | fmt::format::FmtSpan::EXIT);
-            }
+/// State shared between the layers of a built logger and the rest of the crate
+struct Parts {
+    ring: Option<Arc<ring::RingBuffer>>,
+    fan_out: Option<Arc<sink::FanOut>>,
+    log_file: Option<Arc<file::LogFile>>,
+    /// Files of [`routes`], besides `log_file`
+    routed_files: Vec<Arc<file::LogFile>>,
+    reload_handle: tracing_subscriber::reload::Handle<debug_users::DebugUserFilter, Registry>,
+    startup: Startup,
+    #[cfg(feature = "archive")]
+    archive: Option<archive::ArchiveConfig>,
+}
 
-            let file_appender =
-                RollingFileAppender::new(Rotation::DAILY, &log_dir, &log_file_prefix);
-            let mut file_layer = fmt::layer()
-                .json()
-                .with_current_span(enable_spans)
-                .with_span_list(false)
-                .with_writer(file_appender);
+impl Parts {
+    /// Start background work and register the logger's state, if `installed`
+    ///
+    /// With `announce`, an installed logger writes its "logger initialized"
+    /// record.
+    fn activate(self, installed: bool, announce: bool) -> ReloadHandle {
+        let Parts {
+            ring,
+            fan_out,
+            log_file,
+            routed_files,
+            reload_handle,
+            startup,
+            #[cfg(feature = "archive")]
+            archive,
+        } = self;
+        let log_files: Vec<_> = log_file.into_iter().chain(routed_files).collect();
 
-            if enable_spans {
-                file_layer = file_layer
-                    .with_span_events(fmt::format::FmtSpan::ENTER | fmt::format::FmtSpan::EXIT);
-            }
+        // Timed flushes of buffered sinks and the log file
+        if installed {
+            let policy = flusher::FlushPolicy::from_env().unwrap_or_else(|e| {
+                eprintln!("Timed flushing disabled: {}", e);
+                flusher::FlushPolicy::default()
+            });
+            let mut targets = fan_out.as_ref().map_or_else(Vec::new, sink::flush_targets);
+            targets.extend(log_files.iter().map(file::LogFile::flush_target));
+            flusher::spawn(policy, targets);
+        }
+
+        if let (true, Some(ring)) = (installed, ring) {
+            ring::install(ring);
+        }
 
-            let _ = registry.with(console_layer).with(file_layer).try_init();
+        if let (true, Some(fan_out)) = (installed, fan_out) {
+            sink::install(fan_out);
         }
-        // File logging only (no console)
-        (Some(log_dir), true) => {
-            let file_appender =
-                RollingFileAppender::new(Rotation::DAILY, &log_dir, &log_file_prefix);
-            let mut file_layer = fmt::layer()
-                .json()
-                .with_current_span(enable_spans)
-                .with_span_list(false)
-                .with_writer(file_appender);
 
-            if enable_spans {
-                file_layer = file_layer
-                    .with_span_events(fmt::format::FmtSpan::ENTER | fmt::format::FmtSpan::EXIT);
+        #[cfg(feature = "archive")]
+        if let (true, Some(config)) = (installed, archive) {
+            for log_file in &log_files {
+                archive::spawn(config.clone(), Arc::clone(log_file));
             }
+        }
 
-            let _ = registry.with(file_layer).try_init();
+        if installed && !log_files.is_empty() {
+            file::install(log_files);
         }
-        // Console only
-        (None, _) => {
-            let mut console_layer = fmt::layer()
-                .json()
-                .with_current_span(enable_spans)
-                .with_span_list(false);
 
-            if enable_spans {
-                console_layer = console_layer
-                    .with_span_events(fmt::format::FmtSpan::ENTER | fmt::format::FmtSpan::EXIT);
-            }
+        let handle = ReloadHandle::new(reload_handle);
+        if installed {
+            reload::install(handle.clone());
+        }
+        if let (true, Some((path, interval))) = (installed, reload::watch_from_env()) {
+            handle.watch_file(path, interval);
+        }
+        #[cfg(all(unix, feature = "signals"))]
+        if installed && signals::enabled() {
+            signals::spawn(handle.clone());
+        }
+        if let (true, Some(config)) = (installed, admin::AdminConfig::from_env()) {
+            admin::spawn(config, handle.clone());
+        }
+        match metrics::interval_from_env() {
+            Ok(Some(interval)) if installed => metrics::spawn_summary(interval),
+            Ok(_) => {}
+            Err(e) => eprintln!("Invalid LOG_STATS_INTERVAL_SECS: {}", e),
+        }
+        if installed && announce {
+            startup.log();
+        } else if let Some(error) = &startup.file_error {
+            eprintln!("File logging disabled, writing to the console: {}", error);
+        }
+        handle
+    }
+}
+
+/// Type-erased output layer, so outputs can be collected and installed together
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Build a JSON formatting layer writing to `writer`
+fn json_layer<W>(writer: W, enable_spans: bool, format: &EventFormat) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    // Each output collapses its own repeats and writes summaries through its own writer
+    if let Some(timeout) = format.dedup_timeout() {
+        let writer = Arc::new(writer);
+        let dedup = dedup::Dedup::start(timeout, format, Arc::clone(&writer));
+        let format = format.clone().with_dedup_state(dedup);
+        return fmt_layer(dedup::SharedWriter(writer), enable_spans, format);
+    }
+    fmt_layer(writer, enable_spans, format.clone())
+}
+
+fn fmt_layer<W>(writer: W, enable_spans: bool, format: EventFormat) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let mut layer = fmt::layer()
+        .json()
+        .event_format(format)
+        .fmt_fields(spans::SpanFields::default())
+        .with_writer(writer);
+
+    if enable_spans {
+        // Every span record is produced; the formatter keeps those selected by `spans::span_events`
+        layer.set_span_events(fmt::format::FmtSpan::FULL);
+    }
+
+    Box::new(layer)
+}
+
+/// Return the global logger state to a fresh start, for tests
+///
+/// Flushes sinks, flushes and closes log files (a file is reopened on its
+/// next write, so a test can delete its log directory), clears the ring
+/// buffer, pending [`count!`] totals and [`stats`], and restarts sequence
+/// numbers at 1.
+/// `tracing` cannot uninstall a global subscriber, so the logger itself
+/// stays installed and a later [`init`] returns its [`ReloadHandle`].
+pub fn reset_for_tests() {
+    sink::flush();
+    file::close_all();
+    ring::clear();
+    counter::reset();
+    sequence::reset();
+    reentrant::reset_stats();
+    metrics::reset();
+}
+
+/// Configuration announced by [`Builder::init`]
+struct Startup {
+    mode: config::Mode,
+    filter: String,
+    file: Option<String>,
+    format: LogFormat,
+    /// Why the log file is unusable, when records fall back to the console
+    file_error: Option<String>,
+}
 
-            let _ = registry.with(console_layer).try_init();
+impl Startup {
+    /// Write the "logger initialized" record, so the configuration can be
+    /// found in the log stream itself
+    fn log(&self) {
+        let version = env!("CARGO_PKG_VERSION");
+        match &self.file {
+            Some(file) => tracing::info!(
+                mode = %self.mode,
+                filter = %self.filter,
+                format = self.format.as_str(),
+                file = %file,
+                rotation = %config::Rotation::Daily,
+                logger_version = version,
+                "logger initialized"
+            ),
+            None => tracing::info!(
+                mode = %self.mode,
+                filter = %self.filter,
+                format = self.format.as_str(),
+                logger_version = version,
+                "logger initialized"
+            ),
+        }
+        if let Some(error) = &self.file_error {
+            tracing::warn!(error = %error, "File logging unavailable, writing to the console");
         }
     }
 }
 
 /// Validate current logging configuration without initializing
-pub fn validate_config() -> Result<String, String> {
-    let rust_log = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
-    let log_file_dir = std::env::var("LOG_FILE_DIR").ok();
-    let log_file_prefix = std::env::var("LOG_FILE_PREFIX").unwrap_or_else(|_| "app".to_string());
-    let file_only = std::env::var("LOG_FILE_ONLY").unwrap_or_default() == "true";
+///
+/// The returned [`config::LoggingConfig`] displays as a human-readable
+/// report and exposes the settings as typed fields for health checks and
+/// status endpoints.
+///
+/// Validation has no side effects: `LOG_CONFIG_FILE` is read but not
+/// applied, and a missing `LOG_FILE_DIR` is checked but not created.
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::config::Mode;
+///
+/// let config = custom_tracing_logger::validate_config().unwrap();
+/// assert!(config.spans);
+/// if config.mode == Mode::Console {
+///     assert!(config.log_dir.is_none());
+/// }
+/// println!("{}", config);
+/// ```
+pub fn validate_config() -> Result<config::LoggingConfig, String> {
+    config_file::preview(check_config).map_err(|e| format!("Invalid LOG_CONFIG_FILE: {}", e))?
+}
+
+/// [`validate_config`], given the configuration file that was read
+fn check_config(config_file: Option<String>) -> Result<config::LoggingConfig, String> {
+    let rust_log = config_file::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
+    let log_file_prefix = config_file::var("LOG_FILE_PREFIX").unwrap_or_else(|_| "app".to_string());
+    let file_only = config_file::var("LOG_FILE_ONLY").unwrap_or_default() == "true";
     let enable_spans =
-        std::env::var("LOG_ENABLE_SPANS").unwrap_or_else(|_| "true".to_string()) == "true";
+        config_file::var("LOG_ENABLE_SPANS").unwrap_or_else(|_| "true".to_string()) == "true";
 
     // Validate RUST_LOG format by trying to create an EnvFilter
     if let Err(e) = EnvFilter::try_new(rust_log.trim()) {
         return Err(format!("Invalid RUST_LOG format: {}", e));
     }
 
-    // Validate file directory if specified
-    if let Some(ref dir) = log_file_dir {
-        if let Err(e) = std::fs::create_dir_all(dir) {
-            return Err(format!("Cannot create log directory '{}': {}", dir, e));
-        }
+    if config_file::var("LOG_DISABLED").unwrap_or_default() == "true" {
+        return Ok(config::LoggingConfig {
+            filter: rust_log,
+            mode: config::Mode::Disabled,
+            log_dir: None,
+            file_prefix: log_file_prefix,
+            rotation: None,
+            spans: enable_spans,
+            format: LogFormat::from_env(),
+            config_file,
+            effective: config::Config::current(),
+            report: "✓ Mode: disabled (LOG_DISABLED=true), nothing is written".to_string(),
+        });
     }
 
-    let config = match (log_file_dir.as_ref(), file_only) {
+    // Validate file directory if specified
+    let file_permissions = permissions::FilePermissions::from_env()?;
+    let log_file_dir = match config_file::var("LOG_FILE_DIR") {
+        Ok(raw) => Some(paths::check_log_dir(&raw).map_err(|e| e.to_string())?),
+        Err(_) => None,
+    };
+    #[cfg(feature = "lambda")]
+    let log_file_dir = log_file_dir.filter(|_| !lambda::is_lambda());
+
+    let mode = match (log_file_dir.is_some(), file_only) {
+        (true, false) => config::Mode::ConsoleAndFile,
+        (true, true) => config::Mode::FileOnly,
+        (false, _) => config::Mode::Console,
+    };
+    let file_naming = naming::FileNaming::from_env()?;
+    let mode_description = match (log_file_dir.as_ref(), file_only) {
         (Some(dir), false) => format!(
-            "Console + File logging to {}/{}.YYYY-MM-DD",
-            dir, log_file_prefix
+            "Console + File logging to {}",
+            dir.join(file_naming.describe(&log_file_prefix)).display()
         ),
         (Some(dir), true) => format!(
-            "File-only logging to {}/{}.YYYY-MM-DD",
-            dir, log_file_prefix
+            "File-only logging to {}",
+            dir.join(file_naming.describe(&log_file_prefix)).display()
         ),
         (None, _) => "Console-only logging".to_string(),
     };
+    #[cfg(feature = "lambda")]
+    let mode_description = if lambda::is_lambda() {
+        format!("{} (AWS Lambda, no file output)", mode_description)
+    } else {
+        mode_description
+    };
 
     let spans_status = if enable_spans { "enabled" } else { "disabled" };
 
-    Ok(format!(
+    let mut report = format!(
         "✓ RUST_LOG: {}\n✓ Mode: {}\n✓ Spans: {}",
-        rust_log, config, spans_status
-    ))
+        rust_log, mode_description, spans_status
+    );
+    if let Some(path) = &config_file {
+        report.push_str(&format!("\n✓ Config file: {}", path));
+    }
+    if log_file_dir.is_some() && cfg!(unix) {
+        report.push_str(&format!(
+            "\n✓ File permissions: {:o}{}",
+            file_permissions.file_mode(),
+            match config_file::var("LOG_FILE_GROUP") {
+                Ok(group) if !group.trim().is_empty() => format!(", group {}", group.trim()),
+                _ => String::new(),
+            }
+        ));
+    }
+    if log_file_dir.is_some() {
+        let fallback = match config_file::var("LOG_FILE_FALLBACK") {
+            Ok(value) => value
+                .parse::<config::FileFallback>()
+                .map_err(|e| format!("Invalid LOG_FILE_FALLBACK: {}", e))?,
+            Err(_) => config::FileFallback::default(),
+        };
+        report.push_str(&format!("\n✓ File fallback: {}", fallback));
+        if config_file::var("LOG_FILE_SHARED").unwrap_or_default() == "true" {
+            report.push_str("\n✓ Shared file: records written under an advisory lock");
+        }
+        let buffer = file::WriteBuffer::from_env()?;
+        report.push_str(&format!(
+            "\n✓ File write buffer: {} bytes, retried every {}ms",
+            buffer.capacity,
+            buffer.retry.as_millis()
+        ));
+        match routes::Routes::from_env() {
+            Ok(Some(routes)) if !routes.is_empty() => {
+                report.push_str(&format!("\n✓ Routes: {}", routes))
+            }
+            Ok(_) => {}
+            Err(e) => return Err(format!("Invalid LOG_ROUTES: {}", e)),
+        }
+    }
+
+    match partition::Partitioning::from_env() {
+        Ok(Some(partitioning)) => report.push_str(&format!("\n✓ Partitioning: {}", partitioning)),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_PARTITION_MAX_FILES: {}", e)),
+    }
+
+    let log_format = config_file::var("LOG_FORMAT").unwrap_or_else(|_| "json".to_string());
+    let format = log_format
+        .parse::<LogFormat>()
+        .map_err(|e| format!("Invalid LOG_FORMAT: {}", e))?;
+    report.push_str(&format!("\n✓ Format: {}", log_format.trim()));
+
+    for var in ["LOG_CONSOLE_FORMAT", "LOG_FILE_FORMAT"] {
+        if let Ok(value) = config_file::var(var) {
+            if let Err(e) = value.parse::<LogFormat>() {
+                return Err(format!("Invalid {}: {}", var, e));
+            }
+            report.push_str(&format!("\n✓ {}: {}", var, value.trim()));
+        }
+    }
+
+    match key_map::KeyMap::from_env() {
+        Ok(Some(_)) => report.push_str(&format!(
+            "\n✓ Key map: {}",
+            config_file::var("LOG_KEY_MAP").unwrap_or_default().trim()
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_KEY_MAP: {}", e)),
+    }
+
+    for var in ["LOG_CONSOLE_CHANNELS", "LOG_FILE_CHANNELS"] {
+        if let Some(channels) = channel::ChannelSet::from_env_var(var)? {
+            report.push_str(&format!("\n✓ {}: {}", var, channels.names().join(", ")));
+        }
+    }
+
+    if let Ok(addr) = config_file::var("LOG_DATADOG_AGENT_ADDR") {
+        report.push_str(&format!("\n✓ Datadog agent: tcp://{}", addr.trim()));
+    }
+
+    if let Ok(url) = config_file::var("LOG_NET_ADDR") {
+        match net::NetWriter::from_url(&url) {
+            Ok(net::NetWriter::Tcp(_)) => match net::spill_from_env() {
+                Some((path, max_bytes)) => report.push_str(&format!(
+                    "\n✓ Network: {} (spill to {}, max {} bytes)",
+                    url.trim(),
+                    path.display(),
+                    max_bytes
+                )),
+                None => report.push_str(&format!("\n✓ Network: {} (no spill)", url.trim())),
+            },
+            Ok(net::NetWriter::Udp(_)) => report.push_str(&format!("\n✓ Network: {}", url.trim())),
+            Err(e) => return Err(format!("Invalid LOG_NET_ADDR: {}", e)),
+        }
+    }
+
+    if config_file::var("LOG_ENCRYPTION_KEY").is_ok() {
+        #[cfg(feature = "encryption")]
+        if let Err(e) = encryption::EncryptionKey::from_env() {
+            return Err(format!("Invalid LOG_ENCRYPTION_KEY: {}", e));
+        }
+        if cfg!(feature = "encryption") {
+            report.push_str("\n✓ Encryption: log file records sealed with AES-256-GCM");
+        } else {
+            return Err("LOG_ENCRYPTION_KEY requires the `encryption` feature".to_string());
+        }
+    }
+
+    if config_file::var("LOG_ARCHIVE_URL").is_ok() {
+        #[cfg(feature = "archive")]
+        match archive::ArchiveConfig::from_env() {
+            Ok(Some(archive)) => report.push_str(&format!(
+                "\n✓ Archive: completed files uploaded to {} every {}s{}",
+                archive.location(),
+                archive.interval.as_secs(),
+                if archive.keep_local {
+                    ""
+                } else {
+                    ", then deleted"
+                }
+            )),
+            Ok(None) => {}
+            Err(e) => return Err(format!("Invalid LOG_ARCHIVE_URL: {}", e)),
+        }
+        #[cfg(not(feature = "archive"))]
+        return Err("LOG_ARCHIVE_URL requires the `archive` feature".to_string());
+    }
+
+    if config_file::var("LOG_ORDERED").unwrap_or_default() == "true" {
+        report.push_str("\n✓ Ordering: console and file share one sequenced pipeline");
+    }
+
+    match console::stderr_level_from_env() {
+        Ok(Some(level)) => report.push_str(&format!(
+            "\n✓ Console streams: {} and more severe to stderr, the rest to stdout",
+            level
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_STDERR_LEVEL: {}", e)),
+    }
+
+    if config_file::var("LOG_PANIC_HOOK").unwrap_or_default() == "true" {
+        report.push_str("\n✓ Panics: written as ERROR records");
+    }
+
+    match Redactor::from_env() {
+        Ok(Some(_)) if config_file::var("LOG_REDACTION_KEY").is_ok_and(|key| !key.is_empty()) => {
+            report.push_str("\n✓ Redaction: enabled, values hashed with HMAC-SHA256")
+        }
+        Ok(Some(_)) => report.push_str("\n✓ Redaction: enabled"),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_REDACT_PATTERNS: {}", e)),
+    }
+
+    match normalize::Normalize::from_env() {
+        Ok(Some(normalize)) => report.push_str(&format!(
+            "\n✓ Normalization: {} line breaks and control characters",
+            normalize
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_NORMALIZE: {}", e)),
+    }
+
+    match limits::Limits::from_env() {
+        Ok(Some(limits)) => report.push_str(&format!("\n✓ Limits: {}", limits)),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid record size limits: {}", e)),
+    }
+
+    if presets::quiet_defaults_from_env() {
+        report.push_str(&format!(
+            "\n✓ Quiet defaults: {} at WARN unless named in RUST_LOG",
+            presets::QUIET_CRATES.join(", ")
+        ));
+    }
+
+    if let Some(users) = debug_users::DebugUsers::from_env() {
+        report.push_str(&format!(
+            "\n✓ Debug users: all levels for {}",
+            users.describe()
+        ));
+    }
+
+    if context::request_debug_from_env() {
+        match http::DebugHeader::from_env() {
+            Some(header) => report.push_str(&format!(
+                "\n✓ Request debug: enabled, also for requests with the {} header",
+                header.header()
+            )),
+            None => report.push_str("\n✓ Request debug: enabled"),
+        }
+    }
+
+    match rate_limit::RateLimit::from_env() {
+        Ok(Some(_)) => {
+            let limit = config_file::var("LOG_RATE_LIMIT").unwrap_or_default();
+            report.push_str(&format!("\n✓ Rate limit: {}/s per key", limit.trim()));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_RATE_LIMIT: {}", e)),
+    }
+
+    if config_file::var("LOG_SIGNALS").unwrap_or_default() == "true" {
+        if cfg!(all(unix, feature = "signals")) {
+            report.push_str(
+                "\n✓ Signals: SIGHUP reopens files, SIGUSR1/SIGUSR2 raise/lower the level",
+            );
+        } else {
+            return Err("LOG_SIGNALS requires the `signals` feature on Unix".to_string());
+        }
+    }
+
+    let flush = flusher::FlushPolicy::from_env()?;
+    if let Some(interval) = flush.interval {
+        report.push_str(&format!(
+            "\n✓ Timed flush: sinks and log file every {}ms",
+            interval.as_millis()
+        ));
+    }
+    if let Some(idle) = flush.idle {
+        report.push_str(&format!(
+            "\n✓ Idle flush: records visible within {}ms",
+            idle.as_millis()
+        ));
+    }
+
+    if let Some(config) = admin::AdminConfig::from_env() {
+        let addrs = config
+            .addr
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid LOG_ADMIN_ADDR: {}", e))?;
+        for addr in addrs {
+            config
+                .check_exposure(addr)
+                .map_err(|e| format!("Invalid LOG_ADMIN_ADDR: {}", e))?;
+        }
+        report.push_str(&format!(
+            "\n✓ Admin endpoint: http://{}{}",
+            config.addr,
+            if config.token.is_some() {
+                " (bearer token required)"
+            } else {
+                ""
+            }
+        ));
+    }
+
+    if let Some((path, interval)) = reload::watch_from_env() {
+        report.push_str(&format!(
+            "\n✓ Filter file: {}, checked every {}ms",
+            path.display(),
+            interval.as_millis()
+        ));
+    }
+
+    if let Some(timeout) = dedup::timeout_from_env() {
+        report.push_str(&format!(
+            "\n✓ Deduplication: repeats collapsed for up to {}ms",
+            timeout.as_millis()
+        ));
+    }
+
+    match ring::RingConfig::from_env() {
+        Ok(Some(config)) => report.push_str(&format!(
+            "\n✓ Ring buffer: last {}s, up to {} records at {} and above",
+            config.retention.as_secs(),
+            config.max_records,
+            config.level
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(e),
+    }
+
+    match allowlist::Allowlist::from_env() {
+        Ok(Some(_)) => {
+            let mode =
+                config_file::var("LOG_FIELD_ALLOWLIST_MODE").unwrap_or_else(|_| "drop".into());
+            report.push_str(&format!("\n✓ Field allowlist: enabled, {}", mode.trim()));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_FIELD_ALLOWLIST_MODE: {}", e)),
+    }
+
+    match sampling::Sampling::from_env() {
+        Ok(Some(_)) => {
+            let rules = config_file::var("LOG_SAMPLE").unwrap_or_default();
+            report.push_str(&format!("\n✓ Sampling: {}", rules.trim()));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_SAMPLE: {}", e)),
+    }
+
+    if let Ok(value) = config_file::var("LOG_SCHEMA_VALIDATE") {
+        let state = if value == "true" { "on" } else { "off" };
+        report.push_str(&format!("\n✓ Schema validation: forced {}", state));
+    }
+
+    if config_file::var("LOG_SEQ").unwrap_or_default() == "true" {
+        report.push_str("\n✓ Sequence: per-process seq field enabled");
+    }
+
+    match sequence::event_ids_from_env() {
+        Ok(Some(format)) => report.push_str(&format!(
+            "\n✓ Event ids: {} event_id and seq on every record",
+            format
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_EVENT_ID: {}", e)),
+    }
+
+    if let Ok(value) = config_file::var("LOG_ERROR_BACKTRACE") {
+        match value.as_str() {
+            "true" => report.push_str("\n✓ Error backtraces: captured on ERROR events"),
+            "false" => {}
+            other => return Err(format!("Invalid LOG_ERROR_BACKTRACE: {}", other)),
+        }
+    } else if error_backtrace::enabled_from_env() {
+        report.push_str("\n✓ Error backtraces: captured on ERROR events (RUST_BACKTRACE)");
+    }
+
+    if let Ok(value) = config_file::var("LOG_ALERT_INTERVAL_MS") {
+        match value.trim().parse::<u64>() {
+            Ok(ms) => report.push_str(&format!("\n✓ Error callback: at most once every {}ms", ms)),
+            Err(_) => return Err(format!("Invalid LOG_ALERT_INTERVAL_MS: {}", value)),
+        }
+    }
+
+    match metrics::interval_from_env() {
+        Ok(Some(interval)) => report.push_str(&format!(
+            "\n✓ Logger stats: summary every {}s",
+            interval.as_secs()
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_STATS_INTERVAL_SECS: {}", e)),
+    }
+
+    if config_file::var("LOG_SPAN_FIELDS").unwrap_or_default() == "false" {
+        report.push_str("\n✓ Span fields: capture off, span names only");
+    }
+
+    if let Ok(mode) = config_file::var("LOG_SPAN_EVENTS") {
+        match mode.parse::<spans::SpanEvents>() {
+            Ok(_) => report.push_str(&format!("\n✓ Span events: {}", mode.trim())),
+            Err(e) => return Err(format!("Invalid LOG_SPAN_EVENTS: {}", e)),
+        }
+    }
+
+    if let Ok(style) = config_file::var("LOG_HTTP_FIELDS") {
+        match style.parse::<http::FieldStyle>() {
+            Ok(_) => report.push_str(&format!("\n✓ HTTP fields: {}", style.trim())),
+            Err(e) => return Err(format!("Invalid LOG_HTTP_FIELDS: {}", e)),
+        }
+    }
+    match http::QuietRequests::from_env() {
+        Ok(Some(quiet)) => report.push_str(&format!("\n✓ Quiet requests: {}", quiet)),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_QUIET_PATHS: {}", e)),
+    }
+    match audit::AuditConfig::check_env() {
+        Ok(Some(audit)) => report.push_str(&format!(
+            "\n✓ Audit file: {}{}",
+            audit.dir.join(&audit.prefix).display(),
+            if audit.hash_chain {
+                " (hash chained)"
+            } else {
+                ""
+            }
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid audit configuration: {}", e)),
+    }
+    if let Ok(format) = config_file::var("LOG_REQUEST_ID_FORMAT") {
+        match format.parse::<request_id::IdFormat>() {
+            Ok(_) => report.push_str(&format!("\n✓ Request ids: {}", format.trim())),
+            Err(e) => return Err(format!("Invalid LOG_REQUEST_ID_FORMAT: {}", e)),
+        }
+    }
+
+    if config_file::var("LOG_FLATTEN_SPAN").unwrap_or_default() == "true" {
+        report.push_str("\n✓ Span fields: flattened to top-level keys");
+    }
+
+    if process_info::enabled_from_env() {
+        report.push_str(&format!(
+            "\n✓ Process info: hostname {}, pid {}, thread id and name",
+            process_info::hostname(),
+            std::process::id()
+        ));
+    }
+
+    if source_location::enabled_from_env() {
+        report.push_str("\n✓ Source location: file, line and module_path on every record");
+    }
+
+    match http::CorrelationHeaders::from_env() {
+        Ok(Some(_)) => {
+            let headers = config_file::var("LOG_CORRELATION_HEADERS").unwrap_or_default();
+            report.push_str(&format!("\n✓ Correlation headers: {}", headers.trim()));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid LOG_CORRELATION_HEADERS: {}", e)),
+    }
+
+    match static_fields::from_env() {
+        Ok(Some(fields)) if !fields.is_empty() => {
+            let keys: Vec<&str> = fields.keys().map(String::as_str).collect();
+            report.push_str(&format!("\n✓ Static fields: {}", keys.join(", ")));
+        }
+        Ok(_) => {}
+        Err(e) => return Err(format!("Invalid LOG_STATIC_FIELDS: {}", e)),
+    }
+
+    if config_file::var("LOG_UPTIME").unwrap_or_default() == "true" {
+        report.push_str("\n✓ Uptime: monotonic uptime_ns field enabled");
+    }
+
+    if let Ok(path) = config_file::var("LOG_SOCKET_PATH") {
+        if cfg!(unix) {
+            report.push_str(&format!("\n✓ Unix socket: {}", path.trim()));
+        } else {
+            return Err("LOG_SOCKET_PATH is only supported on Unix".to_string());
+        }
+    }
+
+    if let Ok(path) = config_file::var("LOG_CAPTURE_SOCKET") {
+        if cfg!(unix) {
+            report.push_str(&format!("\n✓ Capture socket: {}", path.trim()));
+        } else {
+            return Err("LOG_CAPTURE_SOCKET is only supported on Unix".to_string());
+        }
+    }
+
+    #[cfg(feature = "loki")]
+    if let Some(loki) = loki::LokiConfig::from_env() {
+        report.push_str(&format!("\n✓ Loki: {}", loki.push_url()));
+    }
+
+    #[cfg(feature = "cloudwatch")]
+    if let Some(cw) = cloudwatch::CloudWatchConfig::from_env()? {
+        report.push_str(&format!(
+            "\n✓ CloudWatch: {}/{} in {}",
+            cw.log_group, cw.log_stream, cw.region
+        ));
+    }
+
+    #[cfg(feature = "webhook")]
+    match webhook::WebhookConfig::from_env() {
+        Ok(Some(webhook)) => report.push_str(&format!(
+            "\n✓ Webhook: {} events to {}",
+            webhook.level,
+            webhook.redacted_url()
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid webhook configuration: {}", e)),
+    }
+
+    #[cfg(feature = "sentry")]
+    match sentry::SentryConfig::from_env() {
+        Ok(Some(sentry)) => report.push_str(&format!(
+            "\n✓ Sentry: {} events to project {} at {}",
+            sentry.level, sentry.dsn.project_id, sentry.dsn.host
+        )),
+        Ok(None) => {}
+        Err(e) => return Err(format!("Invalid Sentry configuration: {}", e)),
+    }
+
+    let effective = config::Config::current();
+    report.push_str("\n✓ Effective configuration:");
+    for line in effective.to_string().lines() {
+        report.push_str(&format!("\n    {}", line));
+    }
+
+    Ok(config::LoggingConfig {
+        filter: rust_log,
+        mode,
+        rotation: log_file_dir.is_some().then_some(config::Rotation::Daily),
+        log_dir: log_file_dir,
+        file_prefix: log_file_prefix,
+        spans: enable_spans,
+        format,
+        config_file,
+        effective,
+        report,
+    })
 }
 
 /// Print current logging configuration
@@ -210,6 +2786,93 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_init_minimal_does_not_panic() {
+        let _ = std::panic::catch_unwind(|| {
+            init_minimal(tracing::Level::WARN);
+        });
+    }
+
+    #[test]
+    fn test_disabled_subscriber_enables_nothing() {
+        let (subscriber, handle) = disabled_subscriber();
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(tracing::Level::ERROR));
+            assert_eq!(handle.filter().as_deref(), Some("off"));
+            handle.set_filter("debug").unwrap();
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+        });
+    }
+
+    #[test]
+    fn test_serialized_fields_are_nested() {
+        #[derive(serde::Serialize)]
+        struct Order {
+            id: u64,
+            skus: Vec<&'static str>,
+        }
+
+        let logs = testing::per_test_subscriber();
+        let order = Order {
+            id: 7,
+            skus: vec!["A1", "B2"],
+        };
+        structured::event("order_created", &order);
+        tracing::info_span!("checkout", cart = %structured::json(&["A1"])).in_scope(|| {
+            tracing::info!(order = ?structured::json(&order), "Order stored");
+        });
+
+        let records = logs.records();
+        let expected = serde_json::json!({"id": 7, "skus": ["A1", "B2"]});
+        assert_eq!(records[0]["fields"]["message"], "order_created");
+        assert_eq!(records[0]["fields"]["data"], expected);
+        let stored = records.last().unwrap();
+        assert_eq!(stored["fields"]["order"], expected);
+        assert_eq!(stored["span"]["cart"], serde_json::json!(["A1"]));
+    }
+
+    #[cfg(feature = "valuable")]
+    #[test]
+    fn test_valuable_fields_are_nested() {
+        use std::collections::BTreeMap;
+        #[cfg(tracing_unstable)]
+        use valuable::Valuable;
+
+        let logs = testing::per_test_subscriber();
+        let totals = BTreeMap::from([("A1", 2), ("B2", 1)]);
+        tracing::info_span!("checkout", skus = %structured::valuable(&vec!["A1", "B2"])).in_scope(
+            || {
+                tracing::info!(totals = %structured::valuable(&totals), "Order stored");
+                // Valuable values are field values only with `--cfg tracing_unstable`
+                #[cfg(tracing_unstable)]
+                tracing::info!(totals = totals.as_value(), "Order recorded");
+            },
+        );
+
+        let records = logs.records();
+        let stored = &records[0];
+        assert_eq!(
+            stored["fields"]["totals"],
+            serde_json::json!({"A1": 2, "B2": 1})
+        );
+        assert_eq!(stored["span"]["skus"], serde_json::json!(["A1", "B2"]));
+        #[cfg(tracing_unstable)]
+        assert_eq!(records[1]["fields"]["totals"], stored["fields"]["totals"]);
+    }
+
+    #[test]
+    fn test_with_writer_replaces_stdout() {
+        let buffer = testing::Buffer::default();
+        let (stack, _parts) = builder().with_writer(buffer.clone()).build().unwrap();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(stack), || {
+            tracing::info!(order_id = 7, "to buffer");
+        });
+        let out = String::from_utf8(buffer.lock().clone()).unwrap();
+        let record: serde_json::Value = serde_json::from_str(out.trim()).unwrap();
+        assert_eq!(record["fields"]["order_id"], 7);
+        assert_eq!(record["fields"]["message"], "to buffer");
+    }
+
     #[test]
     fn test_env_var_parsing() {
         // Test that environment variables are read correctly
@@ -222,17 +2885,131 @@ mod tests {
 
 /// Structured logging helpers
 pub mod structured {
-    use tracing::{error, info};
+    use std::fmt;
+
+    use serde::Serialize;
+    use tracing::{info, warn};
+
+    /// Field value written as nested JSON, see [`json`]
+    pub struct Json<'a, T: ?Sized>(&'a T);
+
+    impl<T: Serialize + ?Sized> fmt::Display for Json<'_, T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            crate::json_field::encode(self.0, f)
+        }
+    }
+
+    impl<T: Serialize + ?Sized> fmt::Debug for Json<'_, T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Display::fmt(self, f)
+        }
+    }
+
+    /// Record `value` in an event or span field as nested JSON instead of a string
+    ///
+    /// Pass it with `%` (or `?`) in the tracing macros, or wrapped in
+    /// `tracing::field::display` where an expression is expected, such as
+    /// the extra fields of [`log_request!`](crate::log_request!). A value
+    /// that fails to serialize is written as a string naming the error.
+    ///
+    /// The value travels through tracing as a string marked with a leading
+    /// control character. This crate's outputs, callbacks and Sentry
+    /// forwarding decode it; layers of other crates stacked on the same
+    /// subscriber see the marked string, e.g. `"\u0001json:{\"id\":7}"`.
+    ///
+    /// # Examples
+    /// ```
+    /// use custom_tracing_logger::structured;
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Order {
+    ///     id: u64,
+    ///     skus: Vec<&'static str>,
+    /// }
+    ///
+    /// let order = Order { id: 7, skus: vec!["A1", "B2"] };
+    /// tracing::info!(order = %structured::json(&order), "Order created");
+    /// // "fields":{"message":"Order created","order":{"id":7,"skus":["A1","B2"]}}
+    /// ```
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Json<'_, T> {
+        Json(value)
+    }
+
+    /// Log `data` under the message `name`, with `data` as nested JSON
+    ///
+    /// # Examples
+    /// ```
+    /// use custom_tracing_logger::structured;
+    /// use serde_json::json;
+    ///
+    /// structured::event("order_created", &json!({"id": 7, "total_cents": 1250}));
+    /// // "fields":{"message":"order_created","data":{"id":7,"total_cents":1250}}
+    /// ```
+    pub fn event<T: Serialize + ?Sized>(name: &str, data: &T) {
+        info!(data = %json(data), "{}", name);
+    }
+
+    /// Field value written as nested JSON, see [`valuable()`]
+    #[cfg(feature = "valuable")]
+    pub struct Valuable<'a, T: ?Sized>(&'a T);
+
+    #[cfg(feature = "valuable")]
+    impl<T: valuable::Valuable + ?Sized> fmt::Display for Valuable<'_, T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            crate::json_field::encode(&crate::valuable_fields::to_json(self.0.as_value()), f)
+        }
+    }
+
+    #[cfg(feature = "valuable")]
+    impl<T: valuable::Valuable + ?Sized> fmt::Debug for Valuable<'_, T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Display::fmt(self, f)
+        }
+    }
+
+    /// Record a `valuable::Valuable` value in a field as nested JSON (`valuable` feature)
+    ///
+    /// Like [`json`], for types deriving `Valuable` rather than `Serialize`.
+    /// This is the way to log valuable types on stable tracing: recording
+    /// `value.as_value()` directly compiles only with
+    /// `RUSTFLAGS="--cfg tracing_unstable"`, and is then nested as well.
+    ///
+    /// # Examples
+    /// ```
+    /// use custom_tracing_logger::structured;
+    ///
+    /// let skus = vec!["A1", "B2"];
+    /// tracing::info!(skus = %structured::valuable(&skus), "Order created");
+    /// // "fields":{"message":"Order created","skus":["A1","B2"]}
+    /// ```
+    #[cfg(feature = "valuable")]
+    pub fn valuable<T: valuable::Valuable + ?Sized>(value: &T) -> Valuable<'_, T> {
+        Valuable(value)
+    }
 
     /// Log HTTP request with standard fields
+    ///
+    /// Field names follow [`http::field_style`](crate::http::field_style).
     pub fn http_request(method: &str, path: &str, status: u16, duration_ms: u64) {
-        info!(
-            method = method,
-            path = path,
-            status = status,
-            duration_ms = duration_ms,
-            "HTTP request completed"
-        );
+        crate::log_request!(method, path, status, duration_ms);
+    }
+
+    /// Log a gRPC call made by a client
+    ///
+    /// `method` is the call's path, e.g. `/shop.Orders/Create`, and `code`
+    /// its gRPC status code (`tonic::Code` as `u16`). Field names follow
+    /// [`http::field_style`](crate::http::field_style), see [`grpc`](crate::grpc).
+    ///
+    /// # Examples
+    /// ```
+    /// use custom_tracing_logger::structured;
+    ///
+    /// structured::grpc_call("/shop.Orders/Create", 5, 12);
+    /// // "fields":{"message":"gRPC call completed","grpc_service":"shop.Orders",
+    /// //           "grpc_method":"Create","grpc_code":5,"grpc_status":"NOT_FOUND","duration_ms":12}
+    /// ```
+    pub fn grpc_call(method: &str, code: u16, duration_ms: u64) {
+        crate::grpc::log_call(method, code, duration_ms, None);
     }
 
     /// Log database operation
@@ -256,8 +3033,77 @@ pub mod structured {
         );
     }
 
+    /// Log what changed between two versions of a value, e.g. a reloaded config
+    ///
+    /// Emits one record with `label`, the number of `changed` paths and
+    /// `changes`, a JSON array of `{"path","old","new"}` objects (`old` or
+    /// `new` missing for added or removed keys). Objects are compared key by
+    /// key, arrays whole. At most 32 changes are listed and values longer
+    /// than 256 bytes are cut. Nothing is logged when the values are equal.
+    ///
+    /// # Examples
+    /// ```
+    /// use serde_json::json;
+    /// use custom_tracing_logger::structured;
+    ///
+    /// let old = json!({"pool": {"max": 10}, "debug": true});
+    /// let new = json!({"pool": {"max": 20}, "debug": true});
+    /// structured::diff("config", &old, &new);
+    /// // "fields":{"message":"Value changed","label":"config","changed":1,
+    /// //           "changes":[{"path":"pool.max","old":10,"new":20}]}
+    /// ```
+    pub fn diff<T: Serialize + ?Sized, U: Serialize + ?Sized>(label: &str, old: &T, new: &U) {
+        let (old, new) = match (serde_json::to_value(old), serde_json::to_value(new)) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!(label = label, error = %e, "Could not serialize value to diff");
+                return;
+            }
+        };
+        let changes = crate::diff::changes(&old, &new);
+        if changes.total == 0 {
+            return;
+        }
+        info!(
+            label = label,
+            changed = changes.total,
+            changes = %json(&changes.listed),
+            "Value changed"
+        );
+    }
+
+    /// Log a product analytics event on the `business` channel
+    ///
+    /// `properties` is written as nested JSON under `properties`.
+    pub fn business_event<T: Serialize + ?Sized>(event: &str, properties: &T) {
+        info!(
+            channel = %crate::channel::Channel::Business,
+            event = event,
+            properties = %json(properties),
+            "Business event"
+        );
+    }
+
+    /// Log a security-relevant action on the `audit` channel
+    pub fn audit_event(actor: &str, action: &str, resource: Option<&str>) {
+        info!(
+            channel = %crate::channel::Channel::Audit,
+            actor = actor,
+            action = action,
+            resource = resource,
+            "Audit event"
+        );
+    }
+
     /// Log error with structured context
-    pub fn error_with_context(error_code: &str, message: &str) {
-        error!(error_code = error_code, "{}" = message);
+    ///
+    /// `message` is a string, or with the `anyhow` feature an `anyhow::Error`
+    /// whose context chain and backtrace are added as fields, see
+    /// [`error_report`](crate::error_report).
+    pub fn error_with_context<M: crate::error_report::ErrorMessage + ?Sized>(
+        error_code: &str,
+        message: &M,
+    ) {
+        message.log_with_code(error_code);
     }
 }
@@ -0,0 +1,251 @@
+//! Size limits of records
+//!
+//! One accidental `info!(body = %huge_payload)` writes a multi-megabyte line
+//! that collectors reject or split. [`Limits`] caps each string value of a
+//! record (`LOG_MAX_FIELD_LEN`), cutting it at a character boundary and
+//! marking it with [`TRUNCATED`], and the size of each rendered record
+//! (`LOG_MAX_EVENT_BYTES`). A record still over the size limit is replaced
+//! by a stub keeping its timestamp, level and target, with a message naming
+//! its size and the start of the original message.
+//!
+//! Strings are truncated after redaction, so redaction patterns see the
+//! whole value. Either limit turns off the fast path that writes records
+//! without re-parsing them.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+/// Marker appended to truncated strings
+pub const TRUNCATED: &str = "…(truncated)";
+
+/// Smallest accepted `LOG_MAX_EVENT_BYTES`, leaving room for the stub record
+pub const MIN_EVENT_BYTES: usize = 1024;
+
+/// Length the original message is cut to in the stub of an oversized record
+const STUB_MESSAGE_LEN: usize = 256;
+
+/// Maximum sizes of string values and records
+///
+/// # Examples
+/// ```no_run
+/// use custom_tracing_logger::limits::Limits;
+///
+/// let limits = Limits::new()
+///     .max_field_len(8 * 1024)
+///     .max_event_bytes(256 * 1024)
+///     .unwrap();
+/// custom_tracing_logger::builder().with_limits(limits).init();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    max_field_len: Option<usize>,
+    max_event_bytes: Option<usize>,
+}
+
+impl Limits {
+    /// Create limits that let everything through
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cut string values longer than `len` bytes, marker excluded
+    pub fn max_field_len(mut self, len: usize) -> Self {
+        self.max_field_len = Some(len.max(1));
+        self
+    }
+
+    /// Replace records rendered to more than `bytes` bytes with a stub
+    ///
+    /// Fails below [`MIN_EVENT_BYTES`].
+    pub fn max_event_bytes(mut self, bytes: usize) -> Result<Self, String> {
+        if bytes < MIN_EVENT_BYTES {
+            return Err(format!(
+                "maximum event size {} is below {} bytes",
+                bytes, MIN_EVENT_BYTES
+            ));
+        }
+        self.max_event_bytes = Some(bytes);
+        Ok(self)
+    }
+
+    /// Maximum length of string values, if limited
+    pub(crate) fn field_len(&self) -> Option<usize> {
+        self.max_field_len
+    }
+
+    /// Maximum size of records, if limited
+    pub(crate) fn event_bytes(&self) -> Option<usize> {
+        self.max_event_bytes
+    }
+
+    /// Whether neither limit is set
+    pub fn is_empty(&self) -> bool {
+        self.max_field_len.is_none() && self.max_event_bytes.is_none()
+    }
+
+    /// Read `LOG_MAX_FIELD_LEN` and `LOG_MAX_EVENT_BYTES`, if either is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |name: &str| -> Result<Option<usize>, String> {
+            match crate::config_file::var(name) {
+                Ok(raw) if !raw.trim().is_empty() => raw
+                    .trim()
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("{} '{}' is not a number of bytes", name, raw.trim())),
+                _ => Ok(None),
+            }
+        };
+        let mut limits = Self::new();
+        if let Some(len) = var("LOG_MAX_FIELD_LEN")? {
+            limits = limits.max_field_len(len);
+        }
+        if let Some(bytes) = var("LOG_MAX_EVENT_BYTES")? {
+            limits = limits.max_event_bytes(bytes)?;
+        }
+        Ok((!limits.is_empty()).then_some(limits))
+    }
+
+    /// Truncate every string value of `record`, at any depth
+    pub(crate) fn truncate_fields(&self, record: &mut Map<String, Value>) {
+        if let Some(max) = self.max_field_len {
+            for value in record.values_mut() {
+                truncate_value(value, max);
+            }
+        }
+    }
+
+    /// Whether a rendered record of `len` bytes must be replaced with a stub
+    pub(crate) fn is_oversized(&self, len: usize) -> bool {
+        self.max_event_bytes.is_some_and(|max| len > max)
+    }
+
+    /// Parts of `record` kept in its stub, when records have a size limit
+    ///
+    /// Taken before rendering, which consumes the record.
+    pub(crate) fn stub_base(&self, record: &Map<String, Value>) -> Option<Map<String, Value>> {
+        self.max_event_bytes?;
+        let mut base: Map<String, Value> = record
+            .iter()
+            .filter(|(_, value)| !value.is_object() && !value.is_array())
+            .map(|(key, value)| {
+                let mut value = value.clone();
+                truncate_value(&mut value, STUB_MESSAGE_LEN);
+                (key.clone(), value)
+            })
+            .collect();
+        let mut fields = Map::new();
+        if let Some(message) = record
+            .get("fields")
+            .and_then(|fields| fields.get("message"))
+            .and_then(Value::as_str)
+        {
+            let mut message = Value::from(message);
+            truncate_value(&mut message, STUB_MESSAGE_LEN);
+            fields.insert("original_message".to_string(), message);
+        }
+        base.insert("fields".to_string(), Value::Object(fields));
+        Some(base)
+    }
+
+    /// Stub from `base` replacing a record that rendered to `len` bytes
+    pub(crate) fn stub(&self, mut base: Map<String, Value>, len: usize) -> Map<String, Value> {
+        if let Some(Value::Object(fields)) = base.get_mut("fields") {
+            fields.insert(
+                "message".to_string(),
+                Value::from(format!(
+                    "Record of {} bytes replaced: larger than LOG_MAX_EVENT_BYTES ({})",
+                    len,
+                    self.max_event_bytes.unwrap_or_default()
+                )),
+            );
+            fields.insert("event_bytes".to_string(), Value::from(len));
+        }
+        base
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut limits = Vec::new();
+        if let Some(len) = self.max_field_len {
+            limits.push(format!("fields up to {} bytes", len));
+        }
+        if let Some(bytes) = self.max_event_bytes {
+            limits.push(format!("records up to {} bytes", bytes));
+        }
+        f.write_str(&limits.join(", "))
+    }
+}
+
+fn truncate_value(value: &mut Value, max: usize) {
+    match value {
+        Value::String(s) if s.len() > max => {
+            let mut end = max;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            s.truncate(end);
+            s.push_str(TRUNCATED);
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| truncate_value(value, max)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| truncate_value(value, max)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_long_strings_are_truncated() {
+        let limits = Limits::new().max_field_len(4);
+        let mut record = json!({
+            "level": "INFO",
+            "fields": {"message": "héllo world", "body": ["abcdef", 123456789]},
+            "span": {"name": "req"},
+        });
+        limits.truncate_fields(record.as_object_mut().unwrap());
+        assert_eq!(record["level"], "INFO");
+        assert_eq!(record["fields"]["message"], "hél…(truncated)");
+        assert_eq!(record["fields"]["body"][0], "abcd…(truncated)");
+        assert_eq!(record["fields"]["body"][1], 123456789);
+        assert_eq!(record["span"]["name"], "req");
+    }
+
+    #[test]
+    fn test_oversized_record_is_replaced_with_stub() {
+        let limits = Limits::new().max_event_bytes(1024).unwrap();
+        assert!(!limits.is_oversized(1024));
+        assert!(limits.is_oversized(1025));
+
+        let record = json!({
+            "timestamp": "2025-03-07T10:00:00Z",
+            "level": "INFO",
+            "fields": {"message": "x".repeat(2000), "body": "payload"},
+            "target": "app",
+            "span": {"name": "req"},
+        });
+        assert!(Limits::new()
+            .stub_base(record.as_object().unwrap())
+            .is_none());
+        let base = limits.stub_base(record.as_object().unwrap()).unwrap();
+        let stub = limits.stub(base, 4096);
+        assert_eq!(stub["level"], "INFO");
+        assert_eq!(stub["target"], "app");
+        assert!(stub.get("span").is_none());
+        assert_eq!(stub["fields"]["event_bytes"], 4096);
+        assert!(stub["fields"].get("body").is_none());
+        assert_eq!(
+            stub["fields"]["original_message"].as_str().unwrap().len(),
+            STUB_MESSAGE_LEN + TRUNCATED.len()
+        );
+        assert!(Limits::new().max_event_bytes(100).is_err());
+    }
+}
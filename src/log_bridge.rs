@@ -0,0 +1,57 @@
+//! Records from the `log` facade
+//!
+//! Many dependencies (hyper, rustls, ...) log through the `log` crate
+//! rather than `tracing`. Once the logger is installed, a `LogTracer`
+//! turns their records into tracing events, which are filtered by
+//! `RUST_LOG` and formatted like any other event, with the target of the
+//! original record.
+//!
+//! `log` applies its own maximum level before a record reaches the bridge,
+//! so it is kept in step with the filter: raising the filter to debug also
+//! lets `log::debug!` records through.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::level_filters::LevelFilter;
+use tracing_log::AsLog;
+
+/// Whether this crate's bridge is the `log` logger of the process
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Route `log` records to the installed subscriber
+///
+/// Fails when the application already set another `log` logger, whose
+/// records then keep going there.
+pub(crate) fn install() {
+    match tracing_log::LogTracer::builder()
+        .with_max_level(LevelFilter::current().as_log())
+        .init()
+    {
+        Ok(()) => INSTALLED.store(true, Ordering::Relaxed),
+        Err(e) => eprintln!("Records from the log crate are not captured: {}", e),
+    }
+}
+
+/// Match `log`'s maximum level to the current filter, after it changed
+pub(crate) fn sync_max_level() {
+    if INSTALLED.load(Ordering::Relaxed) {
+        tracing_log::log::set_max_level(LevelFilter::current().as_log());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_log_records_keep_their_own_metadata() {
+        let _ = tracing_log::LogTracer::init();
+        let logs = crate::testing::per_test_subscriber();
+        tracing_log::log::warn!(target: "hyper::proto", "connection reset");
+
+        let records = logs.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["target"], "hyper::proto");
+        assert_eq!(records[0]["level"], "WARN");
+        assert_eq!(records[0]["fields"]["message"], "connection reset");
+        assert!(records[0]["fields"].get("log.target").is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! Grafana Loki push sink
+//!
+//! Batches JSON events on a background thread and ships them to Loki's
+//! HTTP push API (`/loki/api/v1/push`), so no promtail sidecar is needed.
+//! Enabled with the `loki` feature and configured through `LOG_LOKI_*`
+//! environment variables.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::batch::{BatchEventWriter, BatchPolicy, BatchWriter, Entry};
+
+/// Loki sink configuration
+#[derive(Debug, Clone)]
+pub struct LokiConfig {
+    /// Base URL of the Loki instance (e.g. "http://localhost:3100")
+    pub url: String,
+    /// Labels attached to every stream (e.g. service, env)
+    pub labels: BTreeMap<String, String>,
+    /// Basic auth credentials (Grafana Cloud user id and API token)
+    pub basic_auth: Option<(String, String)>,
+    /// Maximum number of events sent in one push
+    pub batch_size: usize,
+    /// Maximum time an event waits before being pushed
+    pub batch_interval: Duration,
+    /// Attempts per push; throttling, server and transport errors are retried with backoff
+    pub max_retries: u32,
+}
+
+impl LokiConfig {
+    /// Create a configuration pushing to `url` with default batching
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            labels: BTreeMap::new(),
+            basic_auth: None,
+            batch_size: 500,
+            batch_interval: Duration::from_millis(1000),
+            max_retries: 3,
+        }
+    }
+
+    /// Add a static stream label
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Read the configuration from the environment
+    ///
+    /// - `LOG_LOKI_URL`: Loki base URL (required, sink is disabled without it)
+    /// - `LOG_LOKI_LABELS`: Stream labels (e.g. "service=payments,env=prod")
+    /// - `LOG_LOKI_USER` / `LOG_LOKI_TOKEN`: Basic auth credentials
+    /// - `LOG_LOKI_BATCH_SIZE`: Events per push (default: 500)
+    /// - `LOG_LOKI_BATCH_INTERVAL_MS`: Maximum push delay (default: 1000)
+    /// - `LOG_LOKI_MAX_RETRIES`: Attempts per push (default: 3)
+    pub fn from_env() -> Option<Self> {
+        let url = crate::config_file::var("LOG_LOKI_URL").ok()?;
+        let mut config = Self::new(url.trim());
+
+        if let Ok(labels) = crate::config_file::var("LOG_LOKI_LABELS") {
+            for pair in labels.split(',') {
+                if let Some((key, value)) = pair.split_once('=') {
+                    config = config.label(key.trim(), value.trim());
+                }
+            }
+        }
+
+        if let (Ok(user), Ok(token)) = (
+            crate::config_file::var("LOG_LOKI_USER"),
+            crate::config_file::var("LOG_LOKI_TOKEN"),
+        ) {
+            config.basic_auth = Some((user, token));
+        }
+
+        if let Some(size) = env_parse::<usize>("LOG_LOKI_BATCH_SIZE") {
+            config.batch_size = size.max(1);
+        }
+        if let Some(ms) = env_parse::<u64>("LOG_LOKI_BATCH_INTERVAL_MS") {
+            config.batch_interval = Duration::from_millis(ms);
+        }
+        if let Some(retries) = env_parse::<u32>("LOG_LOKI_MAX_RETRIES") {
+            config.max_retries = retries.max(1);
+        }
+
+        Some(config)
+    }
+
+    /// Full URL of the push endpoint
+    pub fn push_url(&self) -> String {
+        let base = self.url.trim_end_matches('/');
+        if base.ends_with("/loki/api/v1/push") {
+            base.to_string()
+        } else {
+            format!("{}/loki/api/v1/push", base)
+        }
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    crate::config_file::var(name).ok()?.trim().parse().ok()
+}
+
+/// `MakeWriter` that hands formatted events to the Loki push thread
+///
+/// Events are dropped rather than blocking the application when the
+/// push thread falls behind.
+pub struct LokiWriter {
+    inner: BatchWriter,
+}
+
+impl LokiWriter {
+    /// Spawn the push thread and return a writer feeding it
+    ///
+    /// Fails only when the thread can't be spawned.
+    pub fn new(config: LokiConfig) -> io::Result<Self> {
+        let policy = BatchPolicy {
+            max_events: config.batch_size,
+            max_bytes: usize::MAX,
+            overhead: 0,
+            interval: config.batch_interval,
+        };
+        let push_url = config.push_url();
+        let auth = config.basic_auth.as_ref().map(|(user, token)| {
+            format!("Basic {}", base64(format!("{}:{}", user, token).as_bytes()))
+        });
+        let labels = config.labels;
+        let max_retries = config.max_retries;
+
+        let inner = BatchWriter::spawn("loki-push", policy, move |batch| {
+            let body = push_body(&labels, &batch).to_string();
+            if !push(&push_url, auth.as_deref(), max_retries, &body) {
+                eprintln!("Loki dropped a batch of {} events", batch.len());
+                crate::metrics::add_sink_failure();
+                crate::metrics::add_dropped(batch.len());
+            }
+        })?;
+        Ok(Self { inner })
+    }
+}
+
+/// POST `body`, retrying throttling, server and transport errors; `true` once delivered
+fn push(url: &str, auth: Option<&str>, max_retries: u32, body: &str) -> bool {
+    let mut backoff = Duration::from_millis(100);
+    for attempt in 1..=max_retries {
+        let mut request = ureq::post(url).set("Content-Type", "application/json");
+        if let Some(auth) = auth {
+            request = request.set("Authorization", auth);
+        }
+        match request.send_string(body) {
+            Ok(_) => return true,
+            Err(ureq::Error::Status(status, _)) if status != 429 && status < 500 => {
+                eprintln!("Loki rejected the push with status {}", status);
+                return false;
+            }
+            Err(e) => eprintln!("Loki push attempt {} failed: {}", attempt, e),
+        }
+        if attempt < max_retries {
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_secs(10));
+        }
+    }
+    false
+}
+
+impl<'a> MakeWriter<'a> for LokiWriter {
+    type Writer = BatchEventWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.inner.make_writer()
+    }
+}
+
+/// Build the push API payload, one stream per log level and partition
+fn push_body(labels: &BTreeMap<String, String>, batch: &[Entry]) -> Value {
+    let mut streams: BTreeMap<(String, Option<String>), Vec<Value>> = BTreeMap::new();
+    for entry in batch {
+        let record = serde_json::from_str::<Value>(&entry.line).ok();
+        let level = record
+            .as_ref()
+            .and_then(|v| {
+                // Datadog, GCP and ECS layouts move the level elsewhere
+                v.get("level")
+                    .or_else(|| v.get("status"))
+                    .or_else(|| v.get("severity"))
+                    .or_else(|| v.pointer("/log/level"))
+                    .and_then(Value::as_str)
+                    .map(str::to_lowercase)
+            })
+            .unwrap_or_else(|| "unknown".to_string());
+        let partition = record
+            .as_ref()
+            .and_then(|v| v.get("partition")?.as_str().map(String::from));
+        streams
+            .entry((level, partition))
+            .or_default()
+            .push(json!([timestamp_ns(entry.timestamp), entry.line]));
+    }
+
+    let streams: Vec<Value> = streams
+        .into_iter()
+        .map(|((level, partition), values)| {
+            let mut stream = labels.clone();
+            stream.insert("level".to_string(), level);
+            if let Some(partition) = partition {
+                stream.insert("partition".to_string(), partition);
+            }
+            json!({ "stream": stream, "values": values })
+        })
+        .collect();
+
+    json!({ "streams": streams })
+}
+
+fn timestamp_ns(timestamp: SystemTime) -> String {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_body_groups_by_level() {
+        let labels = LokiConfig::new("http://loki:3100")
+            .label("service", "payments")
+            .labels;
+        let batch = vec![
+            Entry {
+                timestamp: UNIX_EPOCH + Duration::from_nanos(1),
+                line: r#"{"level":"INFO","fields":{"message":"a"}}"#.to_string(),
+            },
+            Entry {
+                timestamp: UNIX_EPOCH + Duration::from_nanos(2),
+                line: r#"{"level":"ERROR","fields":{"message":"b"}}"#.to_string(),
+            },
+            Entry {
+                timestamp: UNIX_EPOCH + Duration::from_nanos(3),
+                line: r#"{"level":"INFO","partition":"acme","fields":{"message":"c"}}"#.to_string(),
+            },
+        ];
+
+        let body = push_body(&labels, &batch);
+        let streams = body["streams"].as_array().unwrap();
+        assert_eq!(streams.len(), 3);
+        assert_eq!(streams[0]["stream"]["level"], "error");
+        assert_eq!(streams[0]["stream"]["service"], "payments");
+        assert_eq!(streams[1]["values"][0][0], "1");
+        assert!(streams[1]["stream"].get("partition").is_none());
+        assert_eq!(streams[2]["stream"]["partition"], "acme");
+        assert_eq!(streams[2]["values"][0][0], "3");
+    }
+
+    #[test]
+    fn test_push_url_and_auth_encoding() {
+        assert_eq!(
+            LokiConfig::new("http://loki:3100/").push_url(),
+            "http://loki:3100/loki/api/v1/push"
+        );
+        assert_eq!(base64(b"user:token"), "dXNlcjp0b2tlbg==");
+        assert_eq!(base64(b"ab"), "YWI=");
+    }
+
+    /// Answer one request per status on a local port, returning its push URL
+    fn serve(statuses: &'static [u16]) -> String {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/loki/api/v1/push", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                let response = format!("HTTP/1.1 {} X\r\nContent-Length: 0\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_push_retries_server_errors_only() {
+        assert!(push(&serve(&[503, 429, 204]), None, 3, "{}"));
+        assert!(!push(&serve(&[500, 500]), None, 2, "{}"));
+        assert!(!push(
+            &serve(&[400]),
+            Some("Basic dXNlcjp0b2tlbg=="),
+            3,
+            "{}"
+        ));
+    }
+}
@@ -0,0 +1,343 @@
+//! Counters describing the logger itself
+//!
+//! [`stats`] reports how many events passed the filter, per level and per
+//! target, how many bytes the outputs were given, and how many records were
+//! lost to write errors, failing sinks or full queues. With
+//! `LOG_STATS_INTERVAL_SECS` the same counters are also written as a
+//! periodic "Logger stats" record, so a logger silently losing data shows
+//! up in the logs themselves, and [`render_prometheus`] exposes them to a
+//! Prometheus scraper.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Target of the periodic summary records
+const TARGET: &str = "logger_stats";
+
+/// Targets counted separately; events of later targets are counted under [`OTHER_TARGETS`]
+const MAX_TARGETS: usize = 256;
+
+/// Key collecting events of targets beyond [`MAX_TARGETS`]
+const OTHER_TARGETS: &str = "_other";
+
+const LEVELS: [Level; 5] = [
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+static BY_LEVEL: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+static BY_TARGET: Mutex<Option<HashMap<String, u64>>> = Mutex::new(None);
+static BYTES_WRITTEN: AtomicU64 = AtomicU64::new(0);
+static WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static SINK_FAILURES: AtomicU64 = AtomicU64::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static DROPPED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Snapshot of the logger's counters since startup
+///
+/// # Examples
+/// ```
+/// let stats = custom_tracing_logger::stats();
+/// if stats.dropped_events > 0 || stats.write_errors > 0 {
+///     eprintln!("logger lost records: {:?}", stats);
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LoggerStats {
+    /// Events that passed the filter
+    pub events: u64,
+    /// Events per level ("ERROR", "WARN", ...)
+    pub events_by_level: BTreeMap<String, u64>,
+    /// Events per target; targets beyond the first 256 are counted under "_other"
+    pub events_by_target: BTreeMap<String, u64>,
+    /// Bytes of formatted records handed to the console, file and sink outputs
+    pub bytes_written: u64,
+    /// Records an output failed to write (e.g. a full disk)
+    pub write_errors: u64,
+    /// Sink calls that panicked and pushes a remote backend rejected
+    pub sink_failures: u64,
+    /// Records lost to full queues, unreachable collectors or failed pushes
+    pub dropped_events: u64,
+    /// Bytes of log file records lost because the file stayed unwritable and its buffer filled
+    pub dropped_bytes: u64,
+}
+
+/// Current counters of the logger, see [`LoggerStats`]
+pub fn stats() -> LoggerStats {
+    let events_by_level: BTreeMap<String, u64> = LEVELS
+        .iter()
+        .zip(&BY_LEVEL)
+        .map(|(level, count)| (level.to_string(), count.load(Ordering::Relaxed)))
+        .collect();
+    let events_by_target = lock_targets()
+        .as_ref()
+        .map(|targets| targets.iter().map(|(t, n)| (t.clone(), *n)).collect())
+        .unwrap_or_default();
+    LoggerStats {
+        events: events_by_level.values().sum(),
+        events_by_level,
+        events_by_target,
+        bytes_written: BYTES_WRITTEN.load(Ordering::Relaxed),
+        write_errors: WRITE_ERRORS.load(Ordering::Relaxed),
+        sink_failures: SINK_FAILURES.load(Ordering::Relaxed),
+        dropped_events: DROPPED.load(Ordering::Relaxed),
+        dropped_bytes: DROPPED_BYTES.load(Ordering::Relaxed),
+    }
+}
+
+/// The counters of [`stats`] in the Prometheus text exposition format
+///
+/// Mount the result on an existing metrics endpoint, or scrape `/metrics`
+/// of the admin endpoint (`LOG_ADMIN_ADDR`). Levels are lowercase labels,
+/// so `log_events_total{level="error"}` gives the error rate.
+///
+/// # Examples
+/// ```
+/// let body = custom_tracing_logger::render_prometheus();
+/// assert!(body.contains("# TYPE log_events_total counter"));
+/// ```
+pub fn render_prometheus() -> String {
+    use std::fmt::Write;
+
+    let stats = stats();
+    let mut out = String::new();
+    let mut family = |name: &str, help: &str, samples: Vec<(String, u64)>| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    let label = |name: &str, value: &str| format!("{{{}=\"{}\"}}", name, escape_label(value));
+
+    family(
+        "log_events_total",
+        "Events that passed the logger's filter, by level.",
+        stats
+            .events_by_level
+            .iter()
+            .map(|(level, n)| (label("level", &level.to_lowercase()), *n))
+            .collect(),
+    );
+    family(
+        "log_target_events_total",
+        "Events that passed the logger's filter, by target.",
+        stats
+            .events_by_target
+            .iter()
+            .map(|(target, n)| (label("target", target), *n))
+            .collect(),
+    );
+    let totals = [
+        (
+            "log_bytes_written_total",
+            "Bytes of formatted records handed to the outputs.",
+            stats.bytes_written,
+        ),
+        (
+            "log_write_errors_total",
+            "Records an output failed to write.",
+            stats.write_errors,
+        ),
+        (
+            "log_sink_failures_total",
+            "Sink calls that panicked and rejected pushes.",
+            stats.sink_failures,
+        ),
+        (
+            "log_dropped_events_total",
+            "Records lost to full queues, unreachable collectors or failed pushes.",
+            stats.dropped_events,
+        ),
+        (
+            "log_dropped_bytes_total",
+            "Bytes of log file records lost while the file was unwritable.",
+            stats.dropped_bytes,
+        ),
+    ];
+    for (name, help, value) in totals {
+        family(name, help, vec![(String::new(), value)]);
+    }
+    out
+}
+
+/// Escape a label value as the exposition format requires
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Set every counter back to zero
+pub(crate) fn reset() {
+    let counters = BY_LEVEL.iter().chain([
+        &BYTES_WRITTEN,
+        &WRITE_ERRORS,
+        &SINK_FAILURES,
+        &DROPPED,
+        &DROPPED_BYTES,
+    ]);
+    for counter in counters {
+        counter.store(0, Ordering::Relaxed);
+    }
+    *lock_targets() = None;
+}
+
+fn lock_targets() -> std::sync::MutexGuard<'static, Option<HashMap<String, u64>>> {
+    BY_TARGET.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn add_bytes_written(bytes: usize) {
+    BYTES_WRITTEN.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn add_write_error() {
+    WRITE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn add_sink_failure() {
+    SINK_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn add_dropped(events: usize) {
+    DROPPED.fetch_add(events as u64, Ordering::Relaxed);
+}
+
+/// Count a log file record lost with its size
+pub(crate) fn add_dropped_file_record(bytes: usize) {
+    DROPPED.fetch_add(1, Ordering::Relaxed);
+    DROPPED_BYTES.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Layer counting events by level and target; runs after rate limiting
+pub(crate) struct StatsLayer;
+
+impl<S: Subscriber> Layer<S> for StatsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if let Some(index) = LEVELS.iter().position(|level| level == metadata.level()) {
+            BY_LEVEL[index].fetch_add(1, Ordering::Relaxed);
+        }
+        let mut targets = lock_targets();
+        let targets = targets.get_or_insert_with(HashMap::new);
+        if let Some(count) = targets.get_mut(metadata.target()) {
+            *count += 1;
+        } else if targets.len() < MAX_TARGETS {
+            targets.insert(metadata.target().to_string(), 1);
+        } else {
+            *targets.entry(OTHER_TARGETS.to_string()).or_default() += 1;
+        }
+    }
+}
+
+/// Read `LOG_STATS_INTERVAL_SECS`, if periodic summaries are requested
+pub(crate) fn interval_from_env() -> Result<Option<Duration>, String> {
+    match crate::config_file::var("LOG_STATS_INTERVAL_SECS") {
+        Ok(value) => parse_interval(&value).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parse a summary interval in whole seconds
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    match value.trim().parse::<u64>() {
+        Ok(0) | Err(_) => Err(format!("'{}' is not a positive number of seconds", value)),
+        Ok(secs) => Ok(Duration::from_secs(secs)),
+    }
+}
+
+/// Write a "Logger stats" record every `interval`
+pub(crate) fn spawn_summary(interval: Duration) {
+    let spawned = thread::Builder::new()
+        .name("log-stats".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            summarize(&stats());
+        });
+    if let Err(e) = spawned {
+        eprintln!("Failed to spawn logger stats thread: {}", e);
+    }
+}
+
+fn summarize(stats: &LoggerStats) {
+    let level = |level: Level| stats.events_by_level.get(level.as_str()).copied();
+    tracing::info!(
+        target: TARGET,
+        events = stats.events,
+        errors = level(Level::ERROR),
+        warnings = level(Level::WARN),
+        targets = stats.events_by_target.len(),
+        bytes_written = stats.bytes_written,
+        write_errors = stats.write_errors,
+        sink_failures = stats.sink_failures,
+        dropped_events = stats.dropped_events,
+        dropped_bytes = stats.dropped_bytes,
+        "Logger stats"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_counts_events_and_summarizes() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(StatsLayer).with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(capture.clone()),
+        );
+
+        // Counters are process-wide, so only growth is checked
+        let before = stats();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(target: "stats_test", "failed");
+            tracing::warn!(target: "stats_test", "slow");
+            summarize(&stats());
+        });
+        let after = stats();
+
+        assert!(after.events >= before.events + 2);
+        assert!(after.events_by_level["ERROR"] > before.events_by_level["ERROR"]);
+        assert!(after.events_by_target["stats_test"] >= 2);
+        let records = capture.records();
+        let summary = &records[2];
+        assert_eq!(summary["target"], TARGET);
+        assert!(summary["fields"]["events"].as_u64().unwrap() >= 2);
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        add_dropped(1);
+        let body = render_prometheus();
+        assert!(body.contains("# TYPE log_events_total counter\n"));
+        assert!(body.contains("log_events_total{level=\"error\"} "));
+        let dropped = body
+            .lines()
+            .find_map(|line| line.strip_prefix("log_dropped_events_total "))
+            .unwrap();
+        assert!(dropped.parse::<u64>().unwrap() >= 1);
+        assert_eq!(escape_label("a\"b\\c\n"), "a\\\"b\\\\c\\n");
+    }
+
+    #[test]
+    fn test_interval_rejects_zero() {
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("soon").is_err());
+        assert_eq!(parse_interval(" 60 "), Ok(Duration::from_secs(60)));
+    }
+}
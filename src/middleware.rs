@@ -0,0 +1,10 @@
+//! Request logging middleware for web frameworks
+//!
+//! Each module wraps a framework's services so every request is timed and
+//! logged with [`log_request!`](crate::log_request!) without a call in every
+//! handler.
+
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "grpc")]
+pub mod grpc;
@@ -0,0 +1,214 @@
+//! Tower middleware for axum (and any other `http`-based tower stack)
+//!
+//! [`LogRequestLayer`] times each request and logs it when the response is
+//! ready, with the same fields as [`log_request!`](crate::log_request!):
+//! `method`, `path`, `status` and `duration_ms`, plus `request_id` when the
+//! request carries one. The handler runs inside the `http_request` span of
+//! [`Correlation::request_span`], so its own records carry the correlation
+//! headers (`LOG_CORRELATION_HEADERS`) too.
+//!
+//! ```ignore
+//! use axum::{routing::get, Router};
+//! use custom_tracing_logger::middleware::axum::LogRequestLayer;
+//!
+//! let app: Router = Router::new()
+//!     .route("/health", get(|| async { "ok" }))
+//!     .layer(LogRequestLayer::new());
+//! // {"fields":{"message":"HTTP request completed","method":"GET","path":"/health",
+//! //            "status":200,"duration_ms":0,"request_id":"req-42"},...}
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use ::http::{Request, Response};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Instrument;
+
+use crate::context::LogContext;
+use crate::http::{Correlation, CorrelationHeaders, DebugHeader};
+
+/// Tower layer logging every request, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct LogRequestLayer {
+    headers: CorrelationHeaders,
+    debug_header: Option<DebugHeader>,
+}
+
+impl LogRequestLayer {
+    /// Log requests, reading correlation headers from `LOG_CORRELATION_HEADERS` or the defaults
+    pub fn new() -> Self {
+        Self::with_headers(CorrelationHeaders::from_env_or_default())
+    }
+
+    /// Log requests, mapping correlation headers with `headers`
+    pub fn with_headers(headers: CorrelationHeaders) -> Self {
+        Self {
+            headers,
+            debug_header: DebugHeader::from_env(),
+        }
+    }
+
+    /// Log requests carrying `header` at DEBUG, replacing `LOG_REQUEST_DEBUG_TOKEN`
+    pub fn with_debug_header(mut self, header: DebugHeader) -> Self {
+        self.debug_header = Some(header);
+        self
+    }
+}
+
+impl Default for LogRequestLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for LogRequestLayer {
+    type Service = LogRequest<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LogRequest {
+            inner,
+            headers: self.headers.clone(),
+            debug_header: self.debug_header.clone(),
+        }
+    }
+}
+
+/// Service created by [`LogRequestLayer`]
+#[derive(Debug, Clone)]
+pub struct LogRequest<S> {
+    inner: S,
+    headers: CorrelationHeaders,
+    debug_header: Option<DebugHeader>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for LogRequest<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let start = Instant::now();
+        let method = request.method().as_str().to_string();
+        let path = request.uri().path().to_string();
+        let correlation = self.headers.extract(request.headers());
+        let span = correlation.request_span(&method, &path);
+        let debug = self
+            .debug_header
+            .as_ref()
+            .filter(|header| header.matches(request.headers()))
+            .map(|_| LogContext::default().with_debug());
+        let response = {
+            let _entered = span.enter();
+            match &debug {
+                Some(context) => context.in_scope(|| self.inner.call(request)),
+                None => self.inner.call(request),
+            }
+        };
+
+        let future = async move {
+            let result = response.await;
+            // A failed service has no response; log it as a server error
+            let status = match &result {
+                Ok(response) => response.status().as_u16(),
+                Err(_) => 500,
+            };
+            completed(&method, &path, status, start, &correlation);
+            result
+        }
+        .instrument(span);
+        match debug {
+            Some(context) => Box::pin(context.scope(future)),
+            None => Box::pin(future),
+        }
+    }
+}
+
+fn completed(method: &str, path: &str, status: u16, start: Instant, correlation: &Correlation) {
+    let duration_ms = start.elapsed().as_millis() as u64;
+    match correlation.get("request_id") {
+        Some(request_id) => {
+            crate::log_request!(method, path, status, duration_ms, request_id = request_id)
+        }
+        None => crate::log_request!(method, path, status, duration_ms),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    struct Created;
+
+    impl Service<Request<()>> for Created {
+        type Response = Response<()>;
+        type Error = std::convert::Infallible;
+        type Future = std::future::Ready<Result<Response<()>, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: Request<()>) -> Self::Future {
+            tracing::info!("Handling");
+            std::future::ready(Ok(Response::builder().status(201).body(()).unwrap()))
+        }
+    }
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn test_logs_request_with_request_id() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_writer(capture.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut service =
+                LogRequestLayer::with_headers(CorrelationHeaders::default()).layer(Created);
+            let request = Request::builder()
+                .method("POST")
+                .uri("/orders?draft=1")
+                .header("x-request-id", "req-42")
+                .body(())
+                .unwrap();
+            let mut future = service.call(request);
+            let waker = Waker::from(Arc::new(Noop));
+            let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+            assert!(matches!(poll, Poll::Ready(Ok(_))));
+        });
+
+        let records = capture.records();
+        assert_eq!(records[0]["span"]["request_id"], "req-42");
+        let fields = &records[1]["fields"];
+        assert_eq!(fields["message"], "HTTP request completed");
+        assert_eq!(fields["method"], "POST");
+        assert_eq!(fields["path"], "/orders");
+        assert_eq!(fields["status"], 201);
+        assert_eq!(fields["request_id"], "req-42");
+        assert!(fields["duration_ms"].is_u64());
+    }
+}
@@ -0,0 +1,237 @@
+//! Tower middleware for tonic gRPC servers
+//!
+//! [`GrpcLogLayer`] logs every call with the fields of
+//! [`structured::grpc_call`](crate::structured::grpc_call): service,
+//! method, status code and duration, plus `request_id` when the call
+//! carries one. Add it with `Server::builder().layer(...)`; a tonic
+//! `Interceptor` only sees the request, so it cannot log status or duration.
+//!
+//! ```ignore
+//! use custom_tracing_logger::middleware::grpc::GrpcLogLayer;
+//!
+//! tonic::transport::Server::builder()
+//!     .layer(GrpcLogLayer::new())
+//!     .add_service(OrdersServer::new(orders))
+//!     .serve(addr)
+//!     .await?;
+//! // {"fields":{"message":"gRPC call completed","grpc_service":"shop.Orders","grpc_method":"Create",
+//! //            "grpc_code":5,"grpc_status":"NOT_FOUND","duration_ms":3},...}
+//! ```
+//!
+//! The status is read from the response headers, where tonic puts it for
+//! calls that fail before sending a message. A streaming call failing after
+//! its first message reports its status in trailers and is logged as "OK".
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use ::http::{Request, Response};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Instrument;
+
+use crate::context::LogContext;
+use crate::http::{CorrelationHeaders, DebugHeader};
+
+/// Tower layer logging every gRPC call, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct GrpcLogLayer {
+    headers: CorrelationHeaders,
+    debug_header: Option<DebugHeader>,
+}
+
+impl GrpcLogLayer {
+    /// Log calls, reading correlation headers from `LOG_CORRELATION_HEADERS` or the defaults
+    pub fn new() -> Self {
+        Self::with_headers(CorrelationHeaders::from_env_or_default())
+    }
+
+    /// Log calls, mapping correlation metadata with `headers`
+    pub fn with_headers(headers: CorrelationHeaders) -> Self {
+        Self {
+            headers,
+            debug_header: DebugHeader::from_env(),
+        }
+    }
+
+    /// Log calls carrying `header` at DEBUG, replacing `LOG_REQUEST_DEBUG_TOKEN`
+    pub fn with_debug_header(mut self, header: DebugHeader) -> Self {
+        self.debug_header = Some(header);
+        self
+    }
+}
+
+impl Default for GrpcLogLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for GrpcLogLayer {
+    type Service = GrpcLog<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcLog {
+            inner,
+            headers: self.headers.clone(),
+            debug_header: self.debug_header.clone(),
+        }
+    }
+}
+
+/// Service created by [`GrpcLogLayer`]
+#[derive(Debug, Clone)]
+pub struct GrpcLog<S> {
+    inner: S,
+    headers: CorrelationHeaders,
+    debug_header: Option<DebugHeader>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for GrpcLog<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let start = Instant::now();
+        let path = request.uri().path().to_string();
+        let correlation = self.headers.extract(request.headers());
+        let span = correlation.request_span(request.method().as_str(), &path);
+        let debug = self
+            .debug_header
+            .as_ref()
+            .filter(|header| header.matches(request.headers()))
+            .map(|_| LogContext::default().with_debug());
+        let response = {
+            let _entered = span.enter();
+            match &debug {
+                Some(context) => context.in_scope(|| self.inner.call(request)),
+                None => self.inner.call(request),
+            }
+        };
+
+        let future = async move {
+            let result = response.await;
+            let code = match &result {
+                Ok(response) => status_code(response),
+                Err(_) => 2,
+            };
+            let duration_ms = start.elapsed().as_millis() as u64;
+            crate::grpc::log_call(&path, code, duration_ms, correlation.get("request_id"));
+            result
+        }
+        .instrument(span);
+        match debug {
+            Some(context) => Box::pin(context.scope(future)),
+            None => Box::pin(future),
+        }
+    }
+}
+
+/// gRPC status of a response: its `grpc-status` header, or one derived from the HTTP status
+fn status_code<B>(response: &Response<B>) -> u16 {
+    if let Some(value) = response.headers().get("grpc-status") {
+        return std::str::from_utf8(value.as_bytes())
+            .ok()
+            .and_then(|code| code.trim().parse().ok())
+            .unwrap_or(2);
+    }
+    // The mapping of the gRPC spec for responses that did not come from a gRPC server
+    match response.status().as_u16() {
+        200 => 0,
+        400 => 13,
+        401 => 16,
+        403 => 7,
+        404 => 12,
+        429 | 502 | 503 | 504 => 14,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    struct Unavailable;
+
+    impl Service<Request<()>> for Unavailable {
+        type Response = Response<()>;
+        type Error = std::convert::Infallible;
+        type Future = std::future::Ready<Result<Response<()>, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: Request<()>) -> Self::Future {
+            std::future::ready(Ok(Response::builder().status(503).body(()).unwrap()))
+        }
+    }
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn test_status_code_from_header_or_http_status() {
+        let failed = Response::builder()
+            .status(200)
+            .header("grpc-status", "5")
+            .body(())
+            .unwrap();
+        assert_eq!(status_code(&failed), 5);
+        assert_eq!(status_code(&Response::new(())), 0);
+        let missing = Response::builder().status(404).body(()).unwrap();
+        assert_eq!(status_code(&missing), 12);
+    }
+
+    #[test]
+    fn test_logs_call_with_status() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(capture.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut service =
+                GrpcLogLayer::with_headers(CorrelationHeaders::default()).layer(Unavailable);
+            let request = Request::builder()
+                .method("POST")
+                .uri("/shop.Orders/Create")
+                .header("x-request-id", "req-7")
+                .body(())
+                .unwrap();
+            let mut future = service.call(request);
+            let waker = Waker::from(Arc::new(Noop));
+            let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+            assert!(matches!(poll, Poll::Ready(Ok(_))));
+        });
+
+        let records = capture.records();
+        let fields = &records[0]["fields"];
+        assert_eq!(fields["message"], "gRPC call completed");
+        assert_eq!(fields["grpc_service"], "shop.Orders");
+        assert_eq!(fields["grpc_method"], "Create");
+        assert_eq!(fields["grpc_code"], 14);
+        assert_eq!(fields["grpc_status"], "UNAVAILABLE");
+        assert_eq!(fields["request_id"], "req-7");
+    }
+}
@@ -0,0 +1,333 @@
+//! Names of the daily log files
+//!
+//! Files are named `<prefix>.<YYYY-MM-DD>` unless a pattern says otherwise,
+//! so names can match existing logrotate and tail tooling:
+//!
+//! - `LOG_FILE_PATTERN`: Template with `{prefix}`, `{date}` and `{ext}` (default: "{prefix}.{date}")
+//! - `LOG_FILE_DATE_FORMAT`: `{date}` layout from `%Y`, `%y`, `%m` and `%d` (default: "%Y-%m-%d")
+//! - `LOG_FILE_EXTENSION`: Extension such as "log" or "json", for `{ext}` or appended after a dot
+//! - `LOG_FILE_SYMLINK`: Name of a symlink in the log directory kept pointing at the active file (Unix only)
+//!
+//! With an empty prefix the separator after `{prefix}` is dropped, and
+//! without an extension so is the dot before `{ext}`.
+
+use std::fmt;
+
+/// Layout of `{date}` unless configured
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Template of file names unless configured
+pub const DEFAULT_PATTERN: &str = "{prefix}.{date}";
+
+/// Characters dropped next to an empty placeholder
+const SEPARATORS: &[char] = &['.', '-', '_'];
+
+/// How daily log files are named
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::naming::FileNaming;
+///
+/// let naming = FileNaming::pattern("{prefix}-{date}.{ext}")
+///     .unwrap()
+///     .date_format("%Y%m%d")
+///     .unwrap()
+///     .extension("log");
+/// assert_eq!(naming.describe("app"), "app-YYYYMMDD.log");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNaming {
+    pattern: String,
+    date_format: String,
+    extension: Option<String>,
+    symlink: Option<String>,
+}
+
+impl Default for FileNaming {
+    fn default() -> Self {
+        Self {
+            pattern: DEFAULT_PATTERN.to_string(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            extension: None,
+            symlink: None,
+        }
+    }
+}
+
+impl FileNaming {
+    /// Name files after `pattern`, which must contain `{date}`
+    pub fn pattern(pattern: &str) -> Result<Self, String> {
+        let mut rest = pattern;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed '{{' in file pattern '{}'", pattern))?;
+            let name = &rest[start + 1..start + end];
+            if !matches!(name, "prefix" | "date" | "ext") {
+                return Err(format!(
+                    "unknown placeholder '{{{}}}' in file pattern '{}'",
+                    name, pattern
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if !pattern.contains("{date}") {
+            return Err(format!(
+                "file pattern '{}' has no {{date}}, so every day would write the same file",
+                pattern
+            ));
+        }
+        if pattern.contains(['/', '\\']) {
+            return Err(format!(
+                "file pattern '{}' contains a path separator",
+                pattern
+            ));
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+            ..Self::default()
+        })
+    }
+
+    /// Lay `{date}` out with `%Y`, `%y`, `%m` and `%d`, e.g. `"%Y%m%d"`
+    pub fn date_format(mut self, format: &str) -> Result<Self, String> {
+        render_date(format, 2025, 1, 1)?;
+        self.date_format = format.to_string();
+        Ok(self)
+    }
+
+    /// Give files the extension `ext` ("log", "json"; a leading dot is ignored)
+    pub fn extension(mut self, ext: &str) -> Self {
+        let ext = ext.trim().trim_start_matches('.');
+        self.extension = (!ext.is_empty()).then(|| ext.to_string());
+        self
+    }
+
+    /// Keep a symlink named `name` in the log directory pointing at the active file
+    pub fn symlink(mut self, name: &str) -> Self {
+        let name = name.trim();
+        self.symlink = (!name.is_empty()).then(|| name.to_string());
+        self
+    }
+
+    /// Name of the symlink to the active file, if any
+    pub fn symlink_name(&self) -> Option<&str> {
+        self.symlink.as_deref()
+    }
+
+    /// Name of the file of `prefix` for the given date
+    pub fn file_name(&self, prefix: &str, year: i64, month: u32, day: u32) -> String {
+        let date = render_date(&self.date_format, year, month, day).unwrap_or_default();
+        self.render(prefix, &date)
+    }
+
+    /// File name of `prefix` with the date shown as `YYYY-MM-DD` (or the configured layout)
+    pub fn describe(&self, prefix: &str) -> String {
+        let date = self
+            .date_format
+            .replace("%Y", "YYYY")
+            .replace("%y", "YY")
+            .replace("%m", "MM")
+            .replace("%d", "DD")
+            .replace("%%", "%");
+        self.render(prefix, &date)
+    }
+
+    /// Date (year, month, day) of the file of `prefix` named `name`, if it is one
+    ///
+    /// Lets tooling find the daily files among others in the log directory.
+    pub fn date_of(&self, prefix: &str, name: &str) -> Option<(i64, u32, u32)> {
+        let rendered = self.render(prefix, "\0");
+        let (before, after) = rendered.split_once('\0')?;
+        let date = name.strip_prefix(before)?.strip_suffix(after)?;
+        parse_date(&self.date_format, date)
+    }
+
+    fn render(&self, prefix: &str, date: &str) -> String {
+        let mut name = self.pattern.clone();
+        if prefix.is_empty() {
+            name = remove_placeholder(&name, "{prefix}", Side::After);
+        }
+        match &self.extension {
+            Some(ext) if name.contains("{ext}") => name = name.replace("{ext}", ext),
+            Some(ext) => name = format!("{}.{}", name, ext),
+            None => name = remove_placeholder(&name, "{ext}", Side::Before),
+        }
+        name.replace("{prefix}", prefix).replace("{date}", date)
+    }
+
+    /// Read `LOG_FILE_PATTERN`, `LOG_FILE_DATE_FORMAT`, `LOG_FILE_EXTENSION` and `LOG_FILE_SYMLINK`
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| {
+            crate::config_file::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        };
+        let mut naming = match var("LOG_FILE_PATTERN") {
+            Some(pattern) => Self::pattern(pattern.trim())?,
+            None => Self::default(),
+        };
+        if let Some(format) = var("LOG_FILE_DATE_FORMAT") {
+            naming = naming.date_format(format.trim())?;
+        }
+        if let Some(ext) = var("LOG_FILE_EXTENSION") {
+            naming = naming.extension(&ext);
+        }
+        if let Some(symlink) = var("LOG_FILE_SYMLINK") {
+            naming = naming.symlink(&symlink);
+        }
+        Ok(naming)
+    }
+
+    /// Read the environment, reporting invalid values on stderr and using the defaults
+    pub(crate) fn from_env_lenient() -> Self {
+        Self::from_env().unwrap_or_else(|e| {
+            eprintln!("Invalid log file naming: {}", e);
+            Self::default()
+        })
+    }
+}
+
+impl fmt::Display for FileNaming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe("{prefix}"))
+    }
+}
+
+/// Side of a placeholder whose separator goes with it
+enum Side {
+    Before,
+    After,
+}
+
+/// Remove `placeholder` and one separator next to it
+fn remove_placeholder(name: &str, placeholder: &str, side: Side) -> String {
+    let Some(start) = name.find(placeholder) else {
+        return name.to_string();
+    };
+    let (mut before, mut after) = (&name[..start], &name[start + placeholder.len()..]);
+    match side {
+        Side::Before => before = before.strip_suffix(SEPARATORS).unwrap_or(before),
+        Side::After => after = after.strip_prefix(SEPARATORS).unwrap_or(after),
+    }
+    format!("{}{}", before, after)
+}
+
+/// Lay a date out with `%Y`, `%y`, `%m`, `%d` and `%%`
+fn render_date(format: &str, year: i64, month: u32, day: u32) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", year)),
+            Some('y') => out.push_str(&format!("{:02}", year.rem_euclid(100))),
+            Some('m') => out.push_str(&format!("{:02}", month)),
+            Some('d') => out.push_str(&format!("{:02}", day)),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                return Err(format!(
+                    "unsupported '%{}' in date format '{}' (use %Y, %y, %m and %d)",
+                    other, format
+                ))
+            }
+            None => return Err(format!("date format '{}' ends with '%'", format)),
+        }
+    }
+    if out.contains(['/', '\\']) {
+        return Err(format!(
+            "date format '{}' contains a path separator",
+            format
+        ));
+    }
+    Ok(out)
+}
+
+/// Read a date laid out by `format`, the inverse of [`render_date`]
+fn parse_date(format: &str, mut date: &str) -> Option<(i64, u32, u32)> {
+    let (mut year, mut month, mut day) = (None, None, None);
+    let number = |date: &mut &str, digits: usize| {
+        let value = date.get(..digits)?;
+        if !value.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        *date = &date[digits..];
+        value.parse::<i64>().ok()
+    };
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            date = date.strip_prefix(c)?;
+            continue;
+        }
+        match chars.next()? {
+            'Y' => year = Some(number(&mut date, 4)?),
+            'y' => year = Some(2000 + number(&mut date, 2)?),
+            'm' => month = Some(number(&mut date, 2)? as u32),
+            'd' => day = Some(number(&mut date, 2)? as u32),
+            _ => date = date.strip_prefix('%')?,
+        }
+    }
+    let (month, day) = (month?, day?);
+    (date.is_empty() && (1..=12).contains(&month) && (1..=31).contains(&day))
+        .then_some((year?, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_names() {
+        let default = FileNaming::default();
+        assert_eq!(default.file_name("app", 2025, 3, 7), "app.2025-03-07");
+        assert_eq!(default.file_name("", 2025, 3, 7), "2025-03-07");
+        assert_eq!(default.describe("app"), "app.YYYY-MM-DD");
+        assert_eq!(
+            default
+                .clone()
+                .extension(".log")
+                .file_name("app", 2025, 3, 7),
+            "app.2025-03-07.log"
+        );
+
+        let naming = FileNaming::pattern("{prefix}-{date}.{ext}")
+            .unwrap()
+            .date_format("%y%m%d")
+            .unwrap();
+        assert_eq!(naming.file_name("app", 2025, 3, 7), "app-250307");
+        assert_eq!(
+            naming.extension("json").file_name("", 2025, 3, 7),
+            "250307.json"
+        );
+    }
+
+    #[test]
+    fn test_dates_of_file_names() {
+        let default = FileNaming::default();
+        assert_eq!(default.date_of("app", "app.2025-03-07"), Some((2025, 3, 7)));
+        assert_eq!(default.date_of("app", "other.2025-03-07"), None);
+        assert_eq!(default.date_of("app", "app.2025-03-07.gz"), None);
+        assert_eq!(default.date_of("app", "app.2025-13-07"), None);
+
+        let naming = FileNaming::pattern("{prefix}-{date}.{ext}")
+            .unwrap()
+            .date_format("%y%m%d")
+            .unwrap()
+            .extension("log");
+        assert_eq!(naming.date_of("app", "app-250307.log"), Some((2025, 3, 7)));
+        assert_eq!(naming.date_of("app", "current"), None);
+    }
+
+    #[test]
+    fn test_invalid_naming_is_rejected() {
+        assert!(FileNaming::pattern("{prefix}.log").is_err());
+        assert!(FileNaming::pattern("{prefix}.{day}").is_err());
+        assert!(FileNaming::pattern("logs/{date}").is_err());
+        assert!(FileNaming::default().date_format("%Y/%m").is_err());
+        assert!(FileNaming::default().date_format("%H").is_err());
+    }
+}
@@ -0,0 +1,656 @@
+//! Network writers
+//!
+//! Stream newline-delimited JSON records to a remote collector or a local
+//! agent (e.g. the Datadog agent's TCP log intake).
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::Arc;
+use std::sync::{Mutex, MutexGuard};
+#[cfg(unix)]
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+/// How long to wait before retrying a failed connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Timeout for establishing and writing to a connection
+const IO_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default cap on records buffered on disk during an outage
+const DEFAULT_SPILL_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Spilled bytes sent ahead of each new record while catching up
+const DRAIN_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// Spill buffer for `LOG_NET_ADDR` from the environment
+///
+/// - `LOG_NET_SPILL_FILE`: Buffer file (default: "<temp dir>/<LOG_FILE_PREFIX>.spill")
+/// - `LOG_NET_SPILL_MAX_BYTES`: Buffer size cap, 0 disables spilling (default: 64 MiB)
+pub(crate) fn spill_from_env() -> Option<(PathBuf, u64)> {
+    let max_bytes = crate::config_file::var("LOG_NET_SPILL_MAX_BYTES")
+        .ok()
+        .and_then(|max| max.trim().parse().ok())
+        .unwrap_or(DEFAULT_SPILL_MAX_BYTES);
+    if max_bytes == 0 {
+        return None;
+    }
+    let path = match crate::config_file::var("LOG_NET_SPILL_FILE") {
+        Ok(path) => PathBuf::from(path.trim()),
+        Err(_) => {
+            let prefix =
+                crate::config_file::var("LOG_FILE_PREFIX").unwrap_or_else(|_| "app".to_string());
+            std::env::temp_dir().join(format!("{}.spill", prefix))
+        }
+    };
+    Some((path, max_bytes))
+}
+
+/// `MakeWriter` streaming records over a TCP connection
+///
+/// The connection is opened lazily and re-established after failures.
+/// While the remote end is unreachable, records are dropped instead of
+/// blocking the application.
+pub struct TcpWriter {
+    addr: String,
+    state: Mutex<Connection>,
+}
+
+#[derive(Default)]
+struct Connection {
+    stream: Option<TcpStream>,
+    last_failure: Option<Instant>,
+    spill: Option<Spill>,
+}
+
+impl TcpWriter {
+    /// Create a writer for `addr` (e.g. "localhost:10518")
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            state: Mutex::new(Connection::default()),
+        }
+    }
+
+    /// Buffer records in the file at `path` while the remote is unreachable
+    ///
+    /// Buffered records are sent ahead of new ones once the connection is
+    /// back, including records left over by a previous run. They are sent a
+    /// chunk at a time with each new record, which is queued behind them
+    /// until the buffer is empty, so catching up never stalls a logging
+    /// thread for long. At most `max_bytes` are buffered; records beyond
+    /// that are dropped.
+    pub fn with_spill(self, path: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        self.lock().spill = Some(Spill::new(path.into(), max_bytes));
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "address did not resolve");
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, IO_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Write one complete record, spilling it if the remote is unreachable
+    fn send(&self, line: &[u8]) {
+        let mut conn = self.lock();
+        if !self.deliver(&mut conn, line) {
+            let spilled = conn.spill.as_mut().is_some_and(|spill| spill.push(line));
+            if !spilled {
+                crate::metrics::add_dropped(1);
+            }
+        }
+    }
+
+    /// Write one record after any spilled ones, reconnecting once if the
+    /// connection dropped
+    fn deliver(&self, conn: &mut Connection, line: &[u8]) -> bool {
+        for _ in 0..2 {
+            if conn.stream.is_none() {
+                if conn
+                    .last_failure
+                    .is_some_and(|at| at.elapsed() < RECONNECT_DELAY)
+                {
+                    return false;
+                }
+                match self.connect() {
+                    Ok(stream) => conn.stream = Some(stream),
+                    Err(_) => {
+                        conn.last_failure = Some(Instant::now());
+                        return false;
+                    }
+                }
+            }
+
+            let Connection { stream, spill, .. } = &mut *conn;
+            if let Some(stream) = stream.as_mut() {
+                let sent = match spill.as_mut() {
+                    Some(spill) => match spill.drain_chunk(stream) {
+                        // Keep records in order until the backlog is sent
+                        Ok(()) if spill.is_pending() => {
+                            spill.push(line) || stream.write_all(line).is_ok()
+                        }
+                        Ok(()) => stream.write_all(line).is_ok(),
+                        Err(_) => false,
+                    },
+                    None => stream.write_all(line).is_ok(),
+                };
+                if sent {
+                    return true;
+                }
+            }
+            conn.stream = None;
+        }
+        false
+    }
+}
+
+impl Transport for TcpWriter {
+    fn send(&self, line: &[u8]) {
+        TcpWriter::send(self, line)
+    }
+}
+
+/// On-disk buffer for records that could not be sent
+///
+/// Records are appended to the file and sent from offset `sent` onwards;
+/// the file is emptied once everything in it was sent.
+struct Spill {
+    path: PathBuf,
+    max_bytes: u64,
+    len: u64,
+    sent: u64,
+}
+
+impl Spill {
+    fn new(path: PathBuf, max_bytes: u64) -> Self {
+        let len = std::fs::metadata(&path).map_or(0, |meta| meta.len());
+        Self {
+            path,
+            max_bytes,
+            len,
+            sent: 0,
+        }
+    }
+
+    /// Whether records are still waiting to be sent
+    fn is_pending(&self) -> bool {
+        self.sent < self.len
+    }
+
+    /// Append a record; `false` when it was dropped because the buffer is full or unwritable
+    fn push(&mut self, line: &[u8]) -> bool {
+        if self.len + line.len() as u64 > self.max_bytes {
+            return false;
+        }
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line));
+        if written.is_ok() {
+            self.len += line.len() as u64;
+        }
+        written.is_ok()
+    }
+
+    /// Send the next spilled records, about `DRAIN_CHUNK_BYTES` of whole lines
+    ///
+    /// The offset only moves past a chunk once it was written, so a
+    /// connection failing halfway resends that chunk rather than the whole
+    /// buffer. Records sent before a restart are sent again by the next run.
+    fn drain_chunk(&mut self, stream: &mut impl Write) -> io::Result<()> {
+        if self.is_pending() {
+            let mut file = File::open(&self.path)?;
+            file.seek(SeekFrom::Start(self.sent))?;
+            let mut reader = BufReader::new(file.take(self.len - self.sent));
+            let mut chunk = Vec::new();
+            while (chunk.len() as u64) < DRAIN_CHUNK_BYTES
+                && reader.read_until(b'\n', &mut chunk)? > 0
+            {}
+            stream.write_all(&chunk)?;
+            // A file shortened behind our back has nothing more to send
+            self.sent = if chunk.is_empty() {
+                self.len
+            } else {
+                self.sent + chunk.len() as u64
+            };
+        }
+        if self.len > 0 && !self.is_pending() && File::create(&self.path).is_ok() {
+            self.len = 0;
+            self.sent = 0;
+        }
+        Ok(())
+    }
+}
+
+/// `MakeWriter` sending each record as one UDP datagram
+///
+/// Datagrams are fire-and-forget: records are dropped if the collector is
+/// down, and records larger than a datagram are lost.
+pub struct UdpWriter {
+    addr: String,
+    socket: Mutex<Option<UdpSocket>>,
+}
+
+impl UdpWriter {
+    /// Create a writer for `addr` (e.g. "collector:5000")
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            socket: Mutex::new(None),
+        }
+    }
+
+    fn bind(&self) -> io::Result<UdpSocket> {
+        let socket = UdpSocket::bind("0.0.0.0:0").or_else(|_| UdpSocket::bind("[::]:0"))?;
+        socket.connect(self.addr.as_str())?;
+        Ok(socket)
+    }
+}
+
+impl Transport for UdpWriter {
+    fn send(&self, line: &[u8]) {
+        let mut socket = self.socket.lock().unwrap_or_else(|e| e.into_inner());
+        if socket.is_none() {
+            *socket = self.bind().ok();
+        }
+        let sent = socket.as_ref().is_some_and(|udp| udp.send(line).is_ok());
+        if !sent {
+            *socket = None;
+            crate::metrics::add_dropped(1);
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for UdpWriter {
+    type Writer = LineWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LineWriter {
+            buf: Vec::new(),
+            target: self,
+        }
+    }
+}
+
+/// `MakeWriter` streaming records to a Unix domain socket
+///
+/// Lets a local agent (Vector, Fluent Bit) consume records without tailing
+/// files. Like [`TcpWriter`], the connection is opened lazily and
+/// re-established after failures, and records are dropped while the agent
+/// is not listening.
+#[cfg(unix)]
+pub struct UnixSocketWriter {
+    path: PathBuf,
+    state: Mutex<(Option<UnixStream>, Option<Instant>)>,
+}
+
+#[cfg(unix)]
+impl UnixSocketWriter {
+    /// Create a writer for the socket at `path` (e.g. "/run/app/log.sock")
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            state: Mutex::new((None, None)),
+        }
+    }
+
+    fn connect(&self) -> io::Result<UnixStream> {
+        let stream = UnixStream::connect(&self.path)?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixSocketWriter {
+    fn send(&self, line: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (stream, last_failure) = &mut *state;
+        for _ in 0..2 {
+            if stream.is_none() {
+                if last_failure.is_some_and(|at| at.elapsed() < RECONNECT_DELAY) {
+                    break;
+                }
+                match self.connect() {
+                    Ok(connected) => *stream = Some(connected),
+                    Err(_) => {
+                        *last_failure = Some(Instant::now());
+                        break;
+                    }
+                }
+            }
+
+            if let Some(connected) = stream.as_mut() {
+                if connected.write_all(line).is_ok() {
+                    return;
+                }
+            }
+            *stream = None;
+        }
+        crate::metrics::add_dropped(1);
+    }
+}
+
+#[cfg(unix)]
+impl<'a> MakeWriter<'a> for UnixSocketWriter {
+    type Writer = LineWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LineWriter {
+            buf: Vec::new(),
+            target: self,
+        }
+    }
+}
+
+/// `MakeWriter` streaming records to whoever is attached to a listening socket
+///
+/// The process listens on a Unix domain socket; any number of local
+/// consumers (e.g. `socat - UNIX-CONNECT:/tmp/app.logsock | jq`) can attach
+/// and detach at any time. With nobody attached writing is a no-op, and a
+/// consumer that cannot keep up is disconnected instead of slowing the
+/// application down.
+#[cfg(unix)]
+pub struct CaptureSocket {
+    clients: Arc<Mutex<Vec<UnixStream>>>,
+    attached: Arc<AtomicBool>,
+}
+
+#[cfg(unix)]
+impl CaptureSocket {
+    /// Listen on `path`, replacing a stale socket left by a previous run
+    ///
+    /// The socket is only accessible to the owning user.
+    pub fn bind(path: impl Into<PathBuf>) -> io::Result<Self> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        let path = path.into();
+        if std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let attached = Arc::new(AtomicBool::new(false));
+        let (accepted, flag) = (Arc::clone(&clients), Arc::clone(&attached));
+        thread::Builder::new()
+            .name("log-capture-socket".to_string())
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if stream.set_nonblocking(true).is_ok() {
+                        let mut clients = accepted.lock().unwrap_or_else(|e| e.into_inner());
+                        clients.push(stream);
+                        flag.store(true, Ordering::Release);
+                    }
+                }
+            })?;
+        Ok(Self { clients, attached })
+    }
+}
+
+#[cfg(unix)]
+impl Transport for CaptureSocket {
+    fn send(&self, line: &[u8]) {
+        if !self.attached.load(Ordering::Acquire) {
+            return;
+        }
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        // A partial write would corrupt the stream, so any failure detaches
+        clients.retain_mut(|client| client.write_all(line).is_ok());
+        self.attached.store(!clients.is_empty(), Ordering::Release);
+    }
+}
+
+#[cfg(unix)]
+impl<'a> MakeWriter<'a> for CaptureSocket {
+    type Writer = LineWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LineWriter {
+            buf: Vec::new(),
+            target: self,
+        }
+    }
+}
+
+/// Network writer selected by URL scheme
+pub enum NetWriter {
+    /// Newline-delimited records over TCP
+    Tcp(TcpWriter),
+    /// One datagram per record over UDP
+    Udp(UdpWriter),
+}
+
+impl NetWriter {
+    /// Create a writer from a URL such as "tcp://collector:5000" or "udp://collector:5000"
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        match url.trim().split_once("://") {
+            Some(("tcp", addr)) if !addr.is_empty() => Ok(NetWriter::Tcp(TcpWriter::new(addr))),
+            Some(("udp", addr)) if !addr.is_empty() => Ok(NetWriter::Udp(UdpWriter::new(addr))),
+            _ => Err(format!(
+                "'{}' is not a tcp://host:port or udp://host:port address",
+                url.trim()
+            )),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for NetWriter {
+    type Writer = LineWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        match self {
+            NetWriter::Tcp(writer) => writer.make_writer(),
+            NetWriter::Udp(writer) => writer.make_writer(),
+        }
+    }
+}
+
+/// Destination of a complete record
+trait Transport: Sync {
+    fn send(&self, line: &[u8]);
+}
+
+impl<'a> MakeWriter<'a> for TcpWriter {
+    type Writer = LineWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LineWriter {
+            buf: Vec::new(),
+            target: self,
+        }
+    }
+}
+
+/// Buffers one formatted record and sends it as a whole when dropped
+pub struct LineWriter<'a> {
+    buf: Vec<u8>,
+    target: &'a dyn Transport,
+}
+
+impl Write for LineWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LineWriter<'_> {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            self.target.send(&self.buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_tcp_writer_sends_complete_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let writer = TcpWriter::new(listener.local_addr().unwrap().to_string());
+
+        {
+            let mut line = writer.make_writer();
+            line.write_all(b"{\"a\":1}").unwrap();
+            line.write_all(b"\n").unwrap();
+        }
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        BufReader::new(stream).read_line(&mut received).unwrap();
+        assert_eq!(received, "{\"a\":1}\n");
+    }
+
+    #[test]
+    fn test_unreachable_address_drops_records() {
+        let writer = TcpWriter::new("127.0.0.1:1");
+        writer.make_writer().write_all(b"lost\n").unwrap();
+        assert!(writer.lock().last_failure.is_some());
+    }
+
+    #[test]
+    fn test_spilled_records_are_sent_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let spill = std::env::temp_dir().join(format!("net-spill-{}", std::process::id()));
+        let _ = std::fs::remove_file(&spill);
+        let writer =
+            TcpWriter::new(listener.local_addr().unwrap().to_string()).with_spill(&spill, 1024);
+
+        // Simulate an outage while the connection is down
+        writer.lock().last_failure = Some(Instant::now());
+        writer.make_writer().write_all(b"{\"a\":1}\n").unwrap();
+        writer.lock().last_failure = None;
+        writer.make_writer().write_all(b"{\"a\":2}\n").unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "{\"a\":1}");
+        assert_eq!(lines.next().unwrap().unwrap(), "{\"a\":2}");
+        assert_eq!(std::fs::metadata(&spill).unwrap().len(), 0);
+        let _ = std::fs::remove_file(&spill);
+    }
+
+    #[test]
+    fn test_spill_drains_in_chunks_from_an_offset() {
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("net-spill-chunks-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut spill = Spill::new(path.clone(), u64::MAX);
+        let records: Vec<String> = (0..2000)
+            .map(|i| format!("{{\"n\":{:0>60}}}\n", i))
+            .collect();
+        for record in &records {
+            assert!(spill.push(record.as_bytes()));
+        }
+
+        let mut received = Vec::new();
+        spill.drain_chunk(&mut received).unwrap();
+        assert!(received.len() as u64 >= DRAIN_CHUNK_BYTES);
+        assert!((received.len() as u64) < 2 * DRAIN_CHUNK_BYTES);
+        assert!(received.ends_with(b"\n"));
+        assert!(spill.is_pending());
+
+        // A failed write keeps the offset, so nothing is skipped or repeated
+        assert!(spill.drain_chunk(&mut Broken).is_err());
+        while spill.is_pending() {
+            spill.drain_chunk(&mut received).unwrap();
+        }
+        assert_eq!(String::from_utf8(received).unwrap(), records.concat());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_udp_writer_sends_datagrams() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let url = format!("udp://{}", socket.local_addr().unwrap());
+        let writer = NetWriter::from_url(&url).unwrap();
+        writer.make_writer().write_all(b"{\"a\":1}\n").unwrap();
+
+        let mut buf = [0; 64];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"{\"a\":1}\n");
+        assert!(NetWriter::from_url("collector:5000").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_writer_sends_lines() {
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("net-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let writer = UnixSocketWriter::new(&path);
+        writer.make_writer().write_all(b"{\"a\":1}\n").unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        BufReader::new(stream).read_line(&mut received).unwrap();
+        assert_eq!(received, "{\"a\":1}\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_socket_streams_to_attached_consumers() {
+        let path = std::env::temp_dir().join(format!("capture-{}.sock", std::process::id()));
+        let writer = CaptureSocket::bind(&path).unwrap();
+        writer.make_writer().write_all(b"{\"before\":1}\n").unwrap();
+
+        let consumer = UnixStream::connect(&path).unwrap();
+        for _ in 0..100 {
+            if writer.attached.load(Ordering::Acquire) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        writer.make_writer().write_all(b"{\"after\":1}\n").unwrap();
+
+        let mut received = String::new();
+        BufReader::new(consumer).read_line(&mut received).unwrap();
+        assert_eq!(received, "{\"after\":1}\n");
+        let _ = std::fs::remove_file(&path);
+    }
+}
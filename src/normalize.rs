@@ -0,0 +1,153 @@
+//! Single-line string values
+//!
+//! JSON and logfmt output already escape line breaks, but a stack trace
+//! pasted into a message still comes back multi-line once a collector
+//! decodes it, and a custom [`RecordFormatter`](crate::formatter::RecordFormatter)
+//! may write it raw. With `LOG_NORMALIZE` every string value of a record is
+//! rewritten so that it holds no line breaks or other control characters:
+//!
+//! - `escape`: as the text `\n`, `\r`, `\t` or `\u001b`, so the original can be recovered
+//! - `replace`: as a single space, a `\r\n` pair included
+//!
+//! The Unicode line and paragraph separators (U+2028, U+2029) count as line
+//! breaks too. A rendered line still containing a line break is normalized
+//! as a whole, so every output stays one record per line.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+/// How line breaks and control characters are rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalize {
+    /// Write them as escape sequences, e.g. `\n`
+    Escape,
+    /// Replace each with a space
+    Replace,
+}
+
+impl Normalize {
+    /// Read `LOG_NORMALIZE`, `None` when unset or "off"
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match crate::config_file::var("LOG_NORMALIZE") {
+            Ok(raw) if !matches!(raw.trim(), "" | "off") => raw.parse().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// `s` without line breaks or control characters
+    pub fn normalize(self, s: &str) -> String {
+        let mut out = String::with_capacity(s.len());
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            if !needs_rewrite(c) {
+                out.push(c);
+                continue;
+            }
+            match self {
+                Normalize::Escape => match c {
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c => out.push_str(&format!("\\u{:04x}", c as u32)),
+                },
+                Normalize::Replace => {
+                    if c == '\r' && chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                    out.push(' ');
+                }
+            }
+        }
+        out
+    }
+
+    /// Normalize every string value of `record`, at any depth
+    pub(crate) fn apply(self, record: &mut Map<String, Value>) {
+        for value in record.values_mut() {
+            self.apply_value(value);
+        }
+    }
+
+    fn apply_value(self, value: &mut Value) {
+        match value {
+            Value::String(s) if s.contains(needs_rewrite) => *s = self.normalize(s),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.apply_value(value)),
+            Value::Object(map) => map.values_mut().for_each(|value| self.apply_value(value)),
+            _ => {}
+        }
+    }
+
+    /// A rendered record as a single line, for formatters writing raw line breaks
+    pub(crate) fn single_line(self, line: String) -> String {
+        if line.contains(is_line_break) {
+            self.normalize(&line)
+        } else {
+            line
+        }
+    }
+}
+
+impl std::str::FromStr for Normalize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "escape" => Ok(Normalize::Escape),
+            "replace" => Ok(Normalize::Replace),
+            other => Err(format!(
+                "unknown normalization '{}', expected escape, replace or off",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Normalize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Normalize::Escape => "escape",
+            Normalize::Replace => "replace",
+        })
+    }
+}
+
+fn is_line_break(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+fn needs_rewrite(c: char) -> bool {
+    c.is_control() || is_line_break(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_strings_become_single_line() {
+        let trace = "panicked at src/main.rs:3\r\nstack:\n\tat main\u{1b}[0m\u{2028}";
+        assert_eq!(
+            Normalize::Escape.normalize(trace),
+            "panicked at src/main.rs:3\\r\\nstack:\\n\\tat main\\u001b[0m\\u2028"
+        );
+        assert_eq!(
+            Normalize::Replace.normalize(trace),
+            "panicked at src/main.rs:3 stack:  at main [0m "
+        );
+
+        let mut record =
+            json!({"fields": {"message": "a\nb", "lines": ["c\nd", 1]}, "level": "INFO"});
+        Normalize::Escape.apply(record.as_object_mut().unwrap());
+        assert_eq!(record["fields"]["message"], "a\\nb");
+        assert_eq!(record["fields"]["lines"][0], "c\\nd");
+        assert_eq!(record["fields"]["lines"][1], 1);
+
+        assert_eq!(
+            Normalize::Replace.single_line("a=1\nb=2".to_string()),
+            "a=1 b=2"
+        );
+        assert!("trim".parse::<Normalize>().is_err());
+    }
+}
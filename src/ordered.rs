@@ -0,0 +1,167 @@
+//! Ordered fan-out of records to several writers
+//!
+//! Separate console and file layers write independently, so concurrent
+//! events can land in a different order in each output. In ordered mode a
+//! single layer formats each event once and this writer stamps it with a
+//! sequence number and writes it to every output under one lock, so all
+//! outputs agree on event order and `seq` always matches write order.
+
+use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// `MakeWriter` serializing records to several outputs in one order
+pub(crate) struct OrderedWriter {
+    state: Mutex<State>,
+}
+
+struct State {
+    next_seq: u64,
+    outputs: Vec<Box<dyn Write + Send>>,
+}
+
+impl OrderedWriter {
+    pub(crate) fn new(outputs: Vec<Box<dyn Write + Send>>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                next_seq: 1,
+                outputs,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_record(&self, record: &[u8]) {
+        let mut state = self.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+
+        let line = with_seq(record, seq);
+        for output in state.outputs.iter_mut() {
+            // One failing output must not starve the others
+            if output.write_all(&line).is_err() {
+                crate::metrics::add_write_error();
+            }
+        }
+    }
+}
+
+/// Prepend a `seq` field to a formatted JSON or logfmt record
+fn with_seq(record: &[u8], seq: u64) -> Vec<u8> {
+    let mut line = Vec::with_capacity(record.len() + 24);
+    match record.split_first() {
+        Some((b'{', rest)) => {
+            let separator = if rest.first() == Some(&b'}') { "" } else { "," };
+            line.extend_from_slice(format!("{{\"seq\":{}{}", seq, separator).as_bytes());
+            line.extend_from_slice(rest);
+        }
+        _ => {
+            line.extend_from_slice(format!("seq={} ", seq).as_bytes());
+            line.extend_from_slice(record);
+        }
+    }
+    line
+}
+
+impl<'a> MakeWriter<'a> for OrderedWriter {
+    type Writer = OrderedRecord<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        OrderedRecord {
+            buf: Vec::new(),
+            target: self,
+        }
+    }
+}
+
+/// Buffers one formatted record and hands it to the ordered pipeline when dropped
+pub(crate) struct OrderedRecord<'a> {
+    buf: Vec<u8>,
+    target: &'a OrderedWriter,
+}
+
+impl Write for OrderedRecord<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for OrderedRecord<'_> {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            self.target.write_record(&self.buf);
+        }
+    }
+}
+
+/// Output writing through a user-supplied `MakeWriter`
+pub(crate) struct MakeWriterOutput(pub(crate) BoxMakeWriter);
+
+impl Write for MakeWriterOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.make_writer().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0.make_writer().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.make_writer().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_outputs_agree_on_order() {
+        let console = Capture::default();
+        let file = Capture::default();
+        let writer = Arc::new(OrderedWriter::new(vec![
+            Box::new(console.clone()),
+            Box::new(file.clone()),
+        ]));
+
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let writer = Arc::clone(&writer);
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        let mut record = writer.make_writer();
+                        writeln!(record, "{{\"thread\":{},\"i\":{}}}", t, i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let console = console.records();
+        assert_eq!(console, file.records());
+        assert_eq!(console.len(), 200);
+        for (i, record) in console.iter().enumerate() {
+            assert_eq!(record["seq"], i as u64 + 1);
+        }
+    }
+
+    #[test]
+    fn test_seq_prefix() {
+        assert_eq!(with_seq(b"{}\n", 3), b"{\"seq\":3}\n");
+        assert_eq!(with_seq(b"level=INFO\n", 4), b"seq=4 level=INFO\n");
+    }
+}
@@ -0,0 +1,100 @@
+//! Panics as log records
+//!
+//! By default a panic only prints plain text to stderr, so it never reaches
+//! log files or sinks. [`install_panic_hook`] writes every panic as an ERROR
+//! record, flushes the outputs and then runs the previous hook, so the
+//! record is on disk, and on its way to Sentry and the other push sinks,
+//! even when the process aborts right after.
+
+use std::backtrace::Backtrace;
+use std::panic::{self, PanicHookInfo};
+use std::sync::Once;
+
+/// Target of panic records
+const TARGET: &str = "panic";
+
+/// Write panics as ERROR records before the previous panic hook runs
+///
+/// Records have target "panic" and the fields `panic_message`, `location`
+/// (`file:line:column`), `thread_name` and `backtrace`. The hook then runs
+/// [`flush`](crate::flush), which waits up to 5 seconds for the push sinks
+/// to send the record. Installing more than once has no further effect.
+/// [`init`](crate::init) calls this when `LOG_PANIC_HOOK=true`.
+///
+/// # Examples
+/// ```no_run
+/// custom_tracing_logger::init();
+/// custom_tracing_logger::install_panic_hook();
+///
+/// let orders: Vec<u32> = Vec::new();
+/// let _ = orders[3]; // {"level":"ERROR","fields":{"message":"Thread panicked","panic_message":"index out of bounds: ...",...},"target":"panic"}
+/// ```
+pub fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            log_panic(info);
+            crate::flush();
+            let _ = crate::file::flush_installed();
+            previous(info);
+        }));
+    });
+}
+
+fn log_panic(info: &PanicHookInfo<'_>) {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    let location = info
+        .location()
+        .map(|location| location.to_string())
+        .unwrap_or_default();
+    let thread = std::thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+    let backtrace = Backtrace::force_capture();
+    tracing::error!(
+        target: TARGET,
+        panic_message = message,
+        location = %location,
+        thread_name = thread_name,
+        backtrace = %backtrace,
+        "Thread panicked"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_record_fields() {
+        let logs = crate::testing::per_test_subscriber();
+        // Only this test's panic, other tests may panic concurrently
+        let test_thread = std::thread::current().id();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if std::thread::current().id() == test_thread {
+                log_panic(info);
+            }
+        }));
+        let result = std::panic::catch_unwind(|| panic!("order {} missing", 42));
+        panic::set_hook(previous);
+        assert!(result.is_err());
+
+        let records = logs.records();
+        assert_eq!(records.len(), 1);
+        let fields = &records[0]["fields"];
+        assert_eq!(records[0]["target"], "panic");
+        assert_eq!(records[0]["level"], "ERROR");
+        assert_eq!(fields["panic_message"], "order 42 missing");
+        assert!(fields["location"]
+            .as_str()
+            .unwrap()
+            .contains("panic_hook.rs:"));
+        assert!(fields["backtrace"].is_string());
+    }
+}
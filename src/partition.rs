@@ -0,0 +1,429 @@
+//! Partitioning of records by tenant
+//!
+//! SaaS operators need one customer's logs at a time, e.g. to hand them
+//! over on request, without filtering terabytes after the fact. With
+//! [`Partitioning`] (`LOG_PARTITION_FIELD=tenant_id`), the value of that
+//! field on an event, or on the closest span it is in, is the record's
+//! partition:
+//!
+//! - with file output, the record is written to its partition's own file
+//!   next to the regular one, `app.acme.2025-03-07` for tenant `acme`
+//! - every record carries the partition in a top-level `partition` key, and
+//!   Loki streams get a `partition` label, so collectors can split the
+//!   stream too
+//!
+//! Records without the field stay in the regular file. Values name files
+//! only with letters, digits, `-` and `_`: other characters become `_`, long
+//! values are cut to 64 characters, and a value changed either way gets `~`
+//! and a digest of the original appended (`acme_corp~80a1d5cc623b` for
+//! `acme.corp`), so two partitions never share a file. At most
+//! `LOG_PARTITION_MAX_FILES` partition files are open; records of further
+//! partitions go to the regular file. Partition files are not archived.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::file::{FileOptions, LogFile, SharedLogFile};
+
+/// Partition files open at once, unless configured
+pub const DEFAULT_MAX_FILES: usize = 100;
+
+/// Longest partition value used in a file name
+const MAX_NAME_LEN: usize = 64;
+
+/// Digest bytes appended to file names that differ from their partition
+const DIGEST_BYTES: usize = 6;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Partition of the record currently being formatted on this thread
+pub(crate) fn current() -> Option<Arc<str>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+fn set_current(partition: Option<Arc<str>>) {
+    CURRENT.with(|current| *current.borrow_mut() = partition);
+}
+
+/// Field partitioning records
+///
+/// # Examples
+/// ```no_run
+/// use custom_tracing_logger::partition::Partitioning;
+///
+/// custom_tracing_logger::builder()
+///     .with_partitioning(Partitioning::by_field("tenant_id"))
+///     .init();
+///
+/// let span = tracing::info_span!("request", tenant_id = "acme");
+/// let _guard = span.enter();
+/// tracing::info!("Invoice sent"); // written to app.acme.<date>
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partitioning {
+    field: String,
+    max_files: usize,
+}
+
+impl Partitioning {
+    /// Partition records by the value of `field`
+    pub fn by_field(field: &str) -> Self {
+        Self {
+            field: field.trim().to_string(),
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+
+    /// Keep at most `max` partition files open
+    pub fn max_files(mut self, max: usize) -> Self {
+        self.max_files = max;
+        self
+    }
+
+    /// Name of the partitioning field
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Read `LOG_PARTITION_FIELD` and `LOG_PARTITION_MAX_FILES`, if the field is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let field = match crate::config_file::var("LOG_PARTITION_FIELD") {
+            Ok(field) if !field.trim().is_empty() => field,
+            _ => return Ok(None),
+        };
+        let mut partitioning = Self::by_field(&field);
+        if let Ok(raw) = crate::config_file::var("LOG_PARTITION_MAX_FILES") {
+            partitioning.max_files = raw
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' is not a number of files", raw))?;
+        }
+        Ok(Some(partitioning))
+    }
+}
+
+impl fmt::Display for Partitioning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "by {} (max {} files)", self.field, self.max_files)
+    }
+}
+
+/// Partition recorded on a span, inherited by its events and children
+struct SpanPartition(Arc<str>);
+
+/// Layer finding the partition of each record; must run before the output layers
+pub(crate) struct PartitionLayer {
+    field: String,
+}
+
+impl PartitionLayer {
+    pub(crate) fn new(partitioning: &Partitioning) -> Self {
+        Self {
+            field: partitioning.field.clone(),
+        }
+    }
+
+    /// Value of the field among recorded values
+    fn find(&self, record: impl FnOnce(&mut dyn Visit)) -> Option<Arc<str>> {
+        let mut finder = Finder {
+            field: &self.field,
+            value: None,
+        };
+        record(&mut finder);
+        finder.value.map(Arc::from)
+    }
+
+    fn mark<S>(&self, id: &Id, ctx: &Context<'_, S>, record: impl FnOnce(&mut dyn Visit))
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        if let (Some(partition), Some(span)) = (self.find(record), ctx.span(id)) {
+            span.extensions_mut().replace(SpanPartition(partition));
+        }
+    }
+
+    /// Partition of the span `id` or its closest ancestor having one
+    fn of_span<S>(id: &Id, ctx: &Context<'_, S>) -> Option<Arc<str>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        ctx.span_scope(id)?.find_map(|span| {
+            span.extensions()
+                .get::<SpanPartition>()
+                .map(|partition| Arc::clone(&partition.0))
+        })
+    }
+}
+
+impl<S> Layer<S> for PartitionLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.mark(id, &ctx, |visitor| attrs.record(visitor));
+        set_current(Self::of_span(id, &ctx));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.mark(id, &ctx, |visitor| values.record(visitor));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let partition = self.find(|visitor| event.record(visitor)).or_else(|| {
+            ctx.event_scope(event)?.find_map(|span| {
+                span.extensions()
+                    .get::<SpanPartition>()
+                    .map(|partition| Arc::clone(&partition.0))
+            })
+        });
+        set_current(partition);
+    }
+
+    // Span records are formatted between these calls and the next event
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        set_current(Self::of_span(id, &ctx));
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        set_current(Self::of_span(id, &ctx));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        set_current(Self::of_span(&id, &ctx));
+    }
+}
+
+/// Finds the value of one field
+struct Finder<'a> {
+    field: &'a str,
+    value: Option<String>,
+}
+
+impl Visit for Finder<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if field.name() == self.field {
+            self.value = Some(value.to_string());
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == self.field {
+            self.value = Some(value.to_string());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.value = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == self.field {
+            self.value = Some(
+                crate::json_field::debug_text(value)
+                    .trim_matches('"')
+                    .to_string(),
+            );
+        }
+    }
+}
+
+/// Partition value as it appears in a file name, `None` if nothing is left
+///
+/// Names differing from the value end in `~` and a digest of it; `~` never
+/// appears otherwise, so distinct values get distinct names.
+fn file_name_part(partition: &str) -> Option<String> {
+    let mut name: String = partition
+        .chars()
+        .take(MAX_NAME_LEN)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.trim_matches('_').is_empty() {
+        return None;
+    }
+    if name != partition {
+        name.push('~');
+        for byte in &Sha256::digest(partition.as_bytes())[..DIGEST_BYTES] {
+            name.push_str(&format!("{:02x}", byte));
+        }
+    }
+    Some(name)
+}
+
+/// Regular log file writing each partition's records to a file of its own
+pub(crate) struct PartitionedFile {
+    regular: Arc<LogFile>,
+    dir: PathBuf,
+    prefix: String,
+    options: FileOptions,
+    max_files: usize,
+    files: Mutex<HashMap<String, Arc<LogFile>>>,
+    /// Whether reaching `max_files` was reported
+    full: AtomicBool,
+}
+
+impl PartitionedFile {
+    pub(crate) fn new(
+        regular: Arc<LogFile>,
+        dir: PathBuf,
+        prefix: &str,
+        options: FileOptions,
+        partitioning: &Partitioning,
+    ) -> Self {
+        Self {
+            regular,
+            dir,
+            prefix: prefix.to_string(),
+            // The symlink keeps pointing at the regular file
+            options: FileOptions {
+                naming: options.naming.clone().symlink(""),
+                ..options
+            },
+            max_files: partitioning.max_files,
+            files: Mutex::default(),
+            full: AtomicBool::new(false),
+        }
+    }
+
+    /// File of `partition`, opened on its first record
+    fn file_for(&self, partition: Option<&str>) -> Arc<LogFile> {
+        let Some(name) = partition.and_then(file_name_part) else {
+            return Arc::clone(&self.regular);
+        };
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = files.get(&name) {
+            return Arc::clone(file);
+        }
+        if files.len() >= self.max_files {
+            if !self.full.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "{} partition files open; records of new partitions go to the regular file",
+                    self.max_files
+                );
+            }
+            return Arc::clone(&self.regular);
+        }
+        let prefix = match self.prefix.as_str() {
+            "" => name.clone(),
+            prefix => format!("{}.{}", prefix, name),
+        };
+        let file = LogFile::open(self.dir.clone(), &prefix, self.options.clone());
+        files.insert(name, Arc::clone(&file));
+        file
+    }
+}
+
+impl<'a> MakeWriter<'a> for PartitionedFile {
+    type Writer = SharedLogFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SharedLogFile(self.file_for(current().as_deref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Output layer noting the partition of each event
+    struct Seen(Arc<Mutex<Vec<Option<Arc<str>>>>>);
+
+    impl<S: Subscriber> Layer<S> for Seen {
+        fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.lock().unwrap().push(current());
+        }
+    }
+
+    #[test]
+    fn test_partition_from_event_or_span() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry()
+            .with(PartitionLayer::new(&Partitioning::by_field("tenant_id")))
+            .with(Seen(Arc::clone(&seen)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(tenant_id = "acme", "Direct");
+            tracing::info!("None");
+            let span = tracing::info_span!("request", tenant_id = 42);
+            let _guard = span.enter();
+            tracing::info!("From span");
+        });
+
+        let seen = seen.lock().unwrap();
+        let partitions: Vec<_> = seen.iter().map(|p| p.as_deref()).collect();
+        assert_eq!(partitions, vec![Some("acme"), None, Some("42")]);
+    }
+
+    #[test]
+    fn test_file_names_never_collide() {
+        let long = "t".repeat(MAX_NAME_LEN);
+        let partitions = [
+            "acme_corp".to_string(),
+            "acme.corp".to_string(),
+            "acme corp".to_string(),
+            format!("{}-1", long),
+            format!("{}-2", long),
+            long,
+        ];
+        let names: Vec<String> = partitions
+            .iter()
+            .map(|partition| file_name_part(partition).unwrap())
+            .collect();
+        for (i, name) in names.iter().enumerate() {
+            assert!(!names[i + 1..].contains(name), "{} is shared", name);
+        }
+        assert_eq!(names[0], "acme_corp");
+        assert!(names[1].starts_with("acme_corp~"));
+        assert_eq!(file_name_part("acme.corp"), Some(names[1].clone()));
+    }
+
+    #[test]
+    fn test_partition_files() {
+        let dir = std::env::temp_dir().join(format!("partition-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let regular = LogFile::open(dir.clone(), "app", FileOptions::default());
+        let partitioned = PartitionedFile::new(
+            Arc::clone(&regular),
+            dir.clone(),
+            "app",
+            FileOptions::default(),
+            &Partitioning::by_field("tenant_id").max_files(1),
+        );
+
+        assert!(Arc::ptr_eq(&partitioned.file_for(None), &regular));
+        assert!(Arc::ptr_eq(&partitioned.file_for(Some("../")), &regular));
+        let acme = partitioned.file_for(Some("acme corp"));
+        assert!(Arc::ptr_eq(&partitioned.file_for(Some("acme corp")), &acme));
+        assert!(Arc::ptr_eq(&partitioned.file_for(Some("globex")), &regular));
+
+        let names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert!(names.iter().any(|name| name.starts_with("app.acme_corp~")));
+        assert!(!names.iter().any(|name| name.contains("globex")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
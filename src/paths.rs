@@ -0,0 +1,319 @@
+//! Log directory resolution
+//!
+//! `LOG_FILE_DIR` is expanded and validated once at init, so path quirks
+//! (quoted values, `~`, `${TEMP}`, UNC shares, drive-relative paths on
+//! Windows) either resolve to a usable directory or fail with a clear error
+//! instead of silently losing the file output.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::permissions::FilePermissions;
+
+/// Why a log directory could not be used
+#[derive(Debug)]
+pub enum LogDirError {
+    /// The value is empty after trimming
+    Empty,
+    /// A `${NAME}` or `%NAME%` placeholder names an unset variable
+    UnknownVariable(String),
+    /// `~` was used but neither `HOME` nor `USERPROFILE` is set
+    NoHomeDir,
+    /// The directory could not be created or resolved
+    Create { path: PathBuf, source: io::Error },
+    /// The path exists but is not a directory
+    NotADirectory(PathBuf),
+    /// The directory exists but files cannot be created in it
+    NotWritable { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for LogDirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogDirError::Empty => write!(f, "LOG_FILE_DIR is empty"),
+            LogDirError::UnknownVariable(name) => {
+                write!(f, "LOG_FILE_DIR references unset variable '{}'", name)
+            }
+            LogDirError::NoHomeDir => {
+                write!(f, "LOG_FILE_DIR uses '~' but HOME/USERPROFILE is not set")
+            }
+            LogDirError::Create { path, source } => {
+                write!(
+                    f,
+                    "Cannot create log directory '{}': {}",
+                    path.display(),
+                    source
+                )
+            }
+            LogDirError::NotADirectory(path) => {
+                write!(f, "Log directory '{}' is not a directory", path.display())
+            }
+            LogDirError::NotWritable { path, source } => {
+                write!(
+                    f,
+                    "Log directory '{}' is not writable: {}",
+                    path.display(),
+                    source
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LogDirError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LogDirError::Create { source, .. } | LogDirError::NotWritable { source, .. } => {
+                Some(source)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Expand, create and validate a log directory
+///
+/// Surrounding quotes are removed, a leading `~` expands to the home
+/// directory and `${NAME}` / `%NAME%` to environment variables. The result
+/// is absolute (drive-relative Windows paths such as `D:logs` resolve
+/// against that drive's current directory) and UNC shares are returned in
+/// their usual `\\server\share` form. Missing directories are created with
+/// the default [`FilePermissions`] (mode 0o750 on Unix).
+pub fn resolve_log_dir(raw: &str) -> Result<PathBuf, LogDirError> {
+    resolve_log_dir_with(raw, &FilePermissions::default())
+}
+
+/// [`resolve_log_dir`], creating missing directories with `permissions`
+pub(crate) fn resolve_log_dir_with(
+    raw: &str,
+    permissions: &FilePermissions,
+) -> Result<PathBuf, LogDirError> {
+    let expanded = expand(raw, |name| std::env::var(name).ok())?;
+    let path = PathBuf::from(expanded);
+
+    permissions
+        .create_dir_all(&path)
+        .map_err(|source| LogDirError::Create {
+            path: path.clone(),
+            source,
+        })?;
+    let resolved = path
+        .canonicalize()
+        .or_else(|_| std::path::absolute(&path))
+        .map(simplify)
+        .map_err(|source| LogDirError::Create {
+            path: path.clone(),
+            source,
+        })?;
+    if !resolved.is_dir() {
+        return Err(LogDirError::NotADirectory(resolved));
+    }
+    check_writable(&resolved)?;
+    Ok(resolved)
+}
+
+/// [`resolve_log_dir`] without creating anything
+///
+/// A missing directory passes when its nearest existing parent is a
+/// directory. Writability is judged from permissions alone, so problems
+/// such as ownership only surface when the logger starts.
+pub(crate) fn check_log_dir(raw: &str) -> Result<PathBuf, LogDirError> {
+    let expanded = expand(raw, |name| std::env::var(name).ok())?;
+    let path = PathBuf::from(expanded);
+
+    let resolved = path
+        .canonicalize()
+        .or_else(|_| std::path::absolute(&path))
+        .map(simplify)
+        .map_err(|source| LogDirError::Create {
+            path: path.clone(),
+            source,
+        })?;
+    let existing = resolved
+        .ancestors()
+        .find(|dir| dir.exists())
+        .unwrap_or(&resolved);
+    if !existing.is_dir() {
+        return Err(LogDirError::NotADirectory(existing.to_path_buf()));
+    }
+    if existing
+        .metadata()
+        .is_ok_and(|metadata| metadata.permissions().readonly())
+    {
+        return Err(LogDirError::NotWritable {
+            path: existing.to_path_buf(),
+            source: io::ErrorKind::PermissionDenied.into(),
+        });
+    }
+    Ok(resolved)
+}
+
+/// Create and remove a probe file, so permission problems surface at init
+fn check_writable(dir: &Path) -> Result<(), LogDirError> {
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    std::fs::File::create(&probe)
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|source| LogDirError::NotWritable {
+            path: dir.to_path_buf(),
+            source,
+        })
+}
+
+/// Strip quotes and expand `~`, `${NAME}` and `%NAME%`
+fn expand(raw: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, LogDirError> {
+    let trimmed = raw.trim();
+    let trimmed = trimmed
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .or_else(|| {
+            trimmed
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+        })
+        .unwrap_or(trimmed)
+        .trim();
+    if trimmed.is_empty() {
+        return Err(LogDirError::Empty);
+    }
+
+    let mut rest = trimmed;
+    let mut out = String::with_capacity(trimmed.len());
+    if let Some(after) = rest.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(['/', '\\']) {
+            let home = lookup("HOME")
+                .or_else(|| lookup("USERPROFILE"))
+                .ok_or(LogDirError::NoHomeDir)?;
+            out.push_str(&home);
+            rest = after;
+        }
+    }
+
+    while let Some(start) = rest.find(['$', '%']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let placeholder = if let Some(inner) = tail.strip_prefix("${") {
+            inner.find('}').map(|end| (&inner[..end], end + 3))
+        } else if let Some(inner) = tail.strip_prefix('%') {
+            inner
+                .find('%')
+                .map(|end| &inner[..end])
+                .filter(|name| is_variable_name(name))
+                .map(|name| (name, name.len() + 2))
+        } else {
+            None
+        };
+
+        match placeholder {
+            Some((name, len)) => {
+                let value =
+                    lookup(name).ok_or_else(|| LogDirError::UnknownVariable(name.to_string()))?;
+                out.push_str(&value);
+                rest = &tail[len..];
+            }
+            None => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '(' || c == ')')
+}
+
+/// Turn Windows verbatim paths from `canonicalize` back into their usual form
+///
+/// `\\?\C:\logs` becomes `C:\logs` and `\\?\UNC\server\share` becomes
+/// `\\server\share`, which other tools and older APIs understand.
+fn simplify(path: PathBuf) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path;
+    };
+    if let Some(unc) = text.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{}", unc));
+    }
+    match text.strip_prefix(r"\\?\") {
+        Some(local) if local.as_bytes().get(1) == Some(&b':') => PathBuf::from(local),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/app".to_string()),
+            "TEMP" => Some(r"C:\Temp".to_string()),
+            "ProgramData" => Some(r"C:\ProgramData".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        assert_eq!(expand("~/logs", lookup).unwrap(), "/home/app/logs");
+        assert_eq!(expand(r#""${TEMP}\app""#, lookup).unwrap(), r"C:\Temp\app");
+        assert_eq!(
+            expand(r" %ProgramData%\app\logs ", lookup).unwrap(),
+            r"C:\ProgramData\app\logs"
+        );
+        assert_eq!(expand("logs/100%", lookup).unwrap(), "logs/100%");
+        assert_eq!(
+            expand(r"\\server\share\logs", lookup).unwrap(),
+            r"\\server\share\logs"
+        );
+        assert!(matches!(
+            expand("${MISSING}/logs", lookup),
+            Err(LogDirError::UnknownVariable(name)) if name == "MISSING"
+        ));
+        assert!(matches!(expand("  ", lookup), Err(LogDirError::Empty)));
+    }
+
+    #[test]
+    fn test_simplify_verbatim_paths() {
+        assert_eq!(
+            simplify(PathBuf::from(r"\\?\UNC\server\share\logs")),
+            PathBuf::from(r"\\server\share\logs")
+        );
+        assert_eq!(
+            simplify(PathBuf::from(r"\\?\C:\logs")),
+            PathBuf::from(r"C:\logs")
+        );
+        assert_eq!(
+            simplify(PathBuf::from("/var/log/app")),
+            PathBuf::from("/var/log/app")
+        );
+    }
+
+    #[test]
+    fn test_resolve_creates_absolute_dir() {
+        let dir = std::env::temp_dir().join(format!("paths-test-{}", std::process::id()));
+        let resolved = resolve_log_dir(dir.to_str().unwrap()).unwrap();
+        assert!(resolved.is_absolute() && resolved.is_dir());
+        let _ = std::fs::remove_dir(&dir);
+    }
+
+    #[test]
+    fn test_check_creates_nothing() {
+        let dir = std::env::temp_dir().join(format!("paths-check-{}", std::process::id()));
+        let resolved = check_log_dir(dir.join("nested").to_str().unwrap()).unwrap();
+        assert!(resolved.is_absolute());
+        assert!(!dir.exists());
+
+        std::fs::write(&dir, "").unwrap();
+        assert!(matches!(
+            check_log_dir(dir.join("nested").to_str().unwrap()),
+            Err(LogDirError::NotADirectory(path)) if path.ends_with(dir.file_name().unwrap())
+        ));
+        let _ = std::fs::remove_file(&dir);
+    }
+}
@@ -0,0 +1,205 @@
+//! Permissions and ownership of log files
+//!
+//! Log files are created with mode 0o640 (owner read/write, group read)
+//! instead of whatever the process umask allows, and directories the logger
+//! creates with the matching 0o750. Files can also be handed to a group,
+//! e.g. one a log shipper runs as:
+//!
+//! - `LOG_FILE_MODE`: Octal mode of log files (default: "640")
+//! - `LOG_FILE_GROUP`: Group name or numeric id owning log files and created directories
+//!
+//! The mode is set explicitly after opening, so a restrictive umask cannot
+//! widen it and an existing file with a wider mode is tightened. On other
+//! platforms these settings are ignored.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Mode of log files unless configured
+pub const DEFAULT_MODE: u32 = 0o640;
+
+/// Permissions applied to log files and the directories created for them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePermissions {
+    mode: u32,
+    group: Option<u32>,
+}
+
+impl Default for FilePermissions {
+    fn default() -> Self {
+        Self::mode(DEFAULT_MODE)
+    }
+}
+
+impl FilePermissions {
+    /// Create files with `mode` (e.g. `0o600`), ignoring the umask
+    pub fn mode(mode: u32) -> Self {
+        Self {
+            mode: mode & 0o777,
+            group: None,
+        }
+    }
+
+    /// Hand files and created directories to the group with id `gid`
+    pub fn group_id(mut self, gid: u32) -> Self {
+        self.group = Some(gid);
+        self
+    }
+
+    /// Hand files and created directories to the group `name` (or numeric id)
+    ///
+    /// Names are looked up in `/etc/group`.
+    pub fn group(self, name: &str) -> Result<Self, String> {
+        let gid = lookup_group(name.trim())?;
+        Ok(self.group_id(gid))
+    }
+
+    /// Mode of files
+    pub fn file_mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Mode of created directories: the file mode plus search access wherever it grants reading
+    pub fn dir_mode(&self) -> u32 {
+        self.mode | ((self.mode & 0o444) >> 2)
+    }
+
+    /// Read `LOG_FILE_MODE` and `LOG_FILE_GROUP`, defaulting to 0o640 without a group
+    pub fn from_env() -> Result<Self, String> {
+        let mut permissions = match crate::config_file::var("LOG_FILE_MODE") {
+            Ok(mode) => {
+                let mode = mode.trim();
+                let digits = mode.strip_prefix("0o").unwrap_or(mode);
+                match u32::from_str_radix(digits, 8) {
+                    Ok(bits) if bits <= 0o777 => Self::mode(bits),
+                    _ => {
+                        return Err(format!(
+                            "LOG_FILE_MODE '{}' is not an octal file mode",
+                            mode
+                        ))
+                    }
+                }
+            }
+            Err(_) => Self::default(),
+        };
+        if let Ok(group) = crate::config_file::var("LOG_FILE_GROUP") {
+            if !group.trim().is_empty() {
+                permissions = permissions
+                    .group(&group)
+                    .map_err(|e| format!("LOG_FILE_GROUP: {}", e))?;
+            }
+        }
+        Ok(permissions)
+    }
+
+    /// Read the environment, reporting invalid values on stderr and using the defaults
+    pub(crate) fn from_env_lenient() -> Self {
+        Self::from_env().unwrap_or_else(|e| {
+            eprintln!("Invalid log file permissions: {}", e);
+            Self::default()
+        })
+    }
+
+    /// Set the mode and group of an open log file
+    pub(crate) fn apply(&self, file: &File) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(self.mode))?;
+            if let Some(gid) = self.group {
+                std::os::unix::fs::fchown(file, None, Some(gid))?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = file;
+        Ok(())
+    }
+
+    /// Create `dir` and its missing parents with the directory mode and group
+    ///
+    /// Directories that already exist are left unchanged.
+    pub(crate) fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        if dir.is_dir() {
+            return Ok(());
+        }
+        if let Some(parent) = dir.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            self.create_dir_all(parent)?;
+        }
+        match std::fs::create_dir(dir) {
+            Ok(()) => {}
+            // Created concurrently by another thread or process
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(self.dir_mode()))?;
+            if let Some(gid) = self.group {
+                std::os::unix::fs::chown(dir, None, Some(gid))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Numeric id of a group given by name or number
+fn lookup_group(name: &str) -> Result<u32, String> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let groups = std::fs::read_to_string("/etc/group")
+        .map_err(|e| format!("cannot look up group '{}': {}", name, e))?;
+    find_group(&groups, name).ok_or_else(|| format!("unknown group '{}'", name))
+}
+
+/// Id of `name` in the contents of an `/etc/group` file
+fn find_group(groups: &str, name: &str) -> Option<u32> {
+    groups.lines().find_map(|line| {
+        let mut parts = line.split(':');
+        if parts.next()? != name {
+            return None;
+        }
+        parts.nth(1)?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes_and_groups() {
+        let permissions = FilePermissions::default();
+        assert_eq!(permissions.file_mode(), 0o640);
+        assert_eq!(permissions.dir_mode(), 0o750);
+        assert_eq!(FilePermissions::mode(0o600).dir_mode(), 0o700);
+        assert_eq!(FilePermissions::mode(0o644).dir_mode(), 0o755);
+
+        let groups = "root:x:0:\nadm:x:4:syslog,alice\n";
+        assert_eq!(find_group(groups, "adm"), Some(4));
+        assert_eq!(find_group(groups, "ad"), None);
+        assert_eq!(lookup_group("1234"), Ok(1234));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_and_dirs_get_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("log-perms-{}", std::process::id()));
+        let dir = root.join("nested");
+        let permissions = FilePermissions::mode(0o600);
+        permissions.create_dir_all(&dir).unwrap();
+        let file = File::create(dir.join("app.log")).unwrap();
+        permissions.apply(&file).unwrap();
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&root), 0o700);
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&dir.join("app.log")), 0o600);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,102 @@
+//! Filter presets for chatty dependencies
+//!
+//! The moment `RUST_LOG=debug` is set, HTTP, TLS and database crates bury
+//! the application's own records. With `LOG_QUIET_DEFAULTS=true` (or
+//! [`Builder::with_quiet_defaults`](crate::Builder::with_quiet_defaults)),
+//! the crates in [`QUIET_CRATES`] log at WARN and above unless `RUST_LOG`
+//! names them itself, e.g. `RUST_LOG=debug,sqlx=debug` still shows the
+//! queries. Filters set at runtime through the
+//! [`ReloadHandle`](crate::ReloadHandle) keep the preset.
+//!
+//! The preset only quiets: with a global level of WARN or less verbose it
+//! adds nothing.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::level_filters::LevelFilter;
+
+/// Crates logged at WARN and above by the quiet preset
+pub const QUIET_CRATES: [&str; 7] = [
+    "hyper",
+    "hyper_util",
+    "h2",
+    "tower",
+    "rustls",
+    "sqlx",
+    "mio",
+];
+
+/// Whether the installed logger applies the preset
+static QUIET_DEFAULTS: AtomicBool = AtomicBool::new(false);
+
+/// Apply the quiet preset to filters of the installed logger, including later reloads
+pub(crate) fn set_quiet_defaults(enabled: bool) {
+    QUIET_DEFAULTS.store(enabled, Ordering::Relaxed);
+}
+
+/// Read `LOG_QUIET_DEFAULTS`
+pub(crate) fn quiet_defaults_from_env() -> bool {
+    crate::config_file::var("LOG_QUIET_DEFAULTS").unwrap_or_default() == "true"
+}
+
+/// `directives` with the quiet preset added, if it is enabled
+pub(crate) fn apply(directives: &str) -> String {
+    if QUIET_DEFAULTS.load(Ordering::Relaxed) {
+        quiet(directives)
+    } else {
+        directives.to_string()
+    }
+}
+
+/// `directives` with a WARN directive for every quiet crate they don't name
+pub fn quiet(directives: &str) -> String {
+    let directives = directives.trim();
+    let parsed: Vec<&str> = directives
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .collect();
+    // The global level is the last directive without a target
+    let global = parsed
+        .iter()
+        .rev()
+        .find_map(|directive| directive.parse::<LevelFilter>().ok());
+    if global.is_none_or(|level| level <= LevelFilter::WARN) {
+        return directives.to_string();
+    }
+    let named = |krate: &str| {
+        parsed.iter().any(|directive| {
+            let target = directive.split(['=', '[']).next().unwrap_or_default();
+            target.trim() == krate
+        })
+    };
+    let mut quieted = parsed.clone();
+    let presets: Vec<String> = QUIET_CRATES
+        .iter()
+        .filter(|krate| !named(krate))
+        .map(|krate| format!("{}=warn", krate))
+        .collect();
+    quieted.extend(presets.iter().map(String::as_str));
+    quieted.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quiet_preset() {
+        assert_eq!(
+            quiet("debug"),
+            "debug,hyper=warn,hyper_util=warn,h2=warn,tower=warn,rustls=warn,sqlx=warn,mio=warn"
+        );
+        assert_eq!(
+            quiet("info, sqlx=debug, hyper::client=trace"),
+            "info,sqlx=debug,hyper::client=trace,hyper=warn,hyper_util=warn,h2=warn,tower=warn,rustls=warn,mio=warn"
+        );
+        // Nothing to quiet below WARN or without a global level
+        assert_eq!(quiet("error"), "error");
+        assert_eq!(quiet("warn,my_app=debug"), "warn,my_app=debug");
+        assert_eq!(quiet("my_app=debug"), "my_app=debug");
+    }
+}
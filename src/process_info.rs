@@ -0,0 +1,83 @@
+//! Host, process and thread attribution
+//!
+//! With `LOG_PROCESS_INFO=true` every record gets `hostname`, `pid`,
+//! `thread_id` and `thread_name` (when the thread is named), so records
+//! aggregated from many replicas can be traced back to where they came from.
+
+use std::sync::OnceLock;
+
+use serde_json::{Map, Value};
+
+/// Whether `LOG_PROCESS_INFO=true` asks for process fields
+pub(crate) fn enabled_from_env() -> bool {
+    crate::config_file::var("LOG_PROCESS_INFO").unwrap_or_default() == "true"
+}
+
+/// Name of this host, looked up once
+pub(crate) fn hostname() -> &'static str {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    HOSTNAME.get_or_init(|| lookup_hostname().unwrap_or_else(|| "unknown".to_string()))
+}
+
+fn lookup_hostname() -> Option<String> {
+    let from_env = ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok());
+    let from_file = || {
+        ["/proc/sys/kernel/hostname", "/etc/hostname"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+    };
+    from_env
+        .or_else(from_file)
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Numeric id of the current thread, from its `ThreadId(N)` debug form
+fn thread_id() -> Option<u64> {
+    let id = format!("{:?}", std::thread::current().id());
+    id.strip_prefix("ThreadId(")?
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+/// Add the process fields to a formatted record, never replacing existing keys
+pub(crate) fn apply(record: &mut Map<String, Value>) {
+    let thread = std::thread::current();
+    let fields = [
+        ("hostname", Some(hostname().into())),
+        ("pid", Some(std::process::id().into())),
+        ("thread_id", thread_id().map(Into::into)),
+        ("thread_name", thread.name().map(Into::into)),
+    ];
+    for (key, value) in fields {
+        if let (Some(value), false) = (value, record.contains_key(key)) {
+            record.insert(key.to_string(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_adds_process_fields() {
+        let mut record = Map::new();
+        record.insert("pid".to_string(), "custom".into());
+        std::thread::Builder::new()
+            .name("worker-1".to_string())
+            .spawn(move || {
+                apply(&mut record);
+                assert_eq!(record["hostname"], hostname());
+                assert_eq!(record["pid"], "custom");
+                assert!(record["thread_id"].as_u64().is_some());
+                assert_eq!(record["thread_name"], "worker-1");
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! Per-key rate limiting
+//!
+//! Identical events (same target and message, or same value of a chosen
+//! field) are capped with a token bucket per key. Suppressed events are
+//! counted, and a summary record with `suppressed` is emitted periodically
+//! per key, so retry loops cannot flood the outputs or the ingestion quota.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Target of summary records, which are never limited
+const TARGET: &str = "rate_limit";
+
+/// Default time between summary records
+const DEFAULT_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Rate limit configuration
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    per_second: u32,
+    key_field: Option<String>,
+    summary_interval: Duration,
+}
+
+impl RateLimit {
+    /// Allow `n` identical events per second per key, with bursts of up to `n`
+    pub fn per_second(n: u32) -> Self {
+        Self {
+            per_second: n.max(1),
+            key_field: None,
+            summary_interval: DEFAULT_SUMMARY_INTERVAL,
+        }
+    }
+
+    /// Key events by target and the value of `field` instead of their message
+    ///
+    /// Events without the field fall back to target and message.
+    pub fn key_field(mut self, field: impl Into<String>) -> Self {
+        self.key_field = Some(field.into());
+        self
+    }
+
+    /// Time between summary records of suppressed events (default: 10s)
+    pub fn summary_interval(mut self, interval: Duration) -> Self {
+        self.summary_interval = interval;
+        self
+    }
+
+    /// Read the configuration from the environment, if a limit is set
+    ///
+    /// - `LOG_RATE_LIMIT`: Events per second per key (e.g. "100")
+    /// - `LOG_RATE_LIMIT_KEY`: Field keying events instead of the message
+    /// - `LOG_RATE_LIMIT_SUMMARY_MS`: Time between summaries (default: 10000)
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(limit) = crate::config_file::var("LOG_RATE_LIMIT") else {
+            return Ok(None);
+        };
+        let per_second = limit
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a number of events per second", limit.trim()))?;
+        let mut rate_limit = Self::per_second(per_second);
+        if let Ok(field) = crate::config_file::var("LOG_RATE_LIMIT_KEY") {
+            rate_limit = rate_limit.key_field(field.trim());
+        }
+        if let Some(ms) = crate::config_file::var("LOG_RATE_LIMIT_SUMMARY_MS")
+            .ok()
+            .and_then(|ms| ms.trim().parse().ok())
+        {
+            rate_limit = rate_limit.summary_interval(Duration::from_millis(ms));
+        }
+        Ok(Some(rate_limit))
+    }
+}
+
+/// Token bucket of one key
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    suppressed: u64,
+}
+
+/// Buckets shared by the layer and the summary thread
+struct Buckets {
+    config: RateLimit,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Buckets {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Bucket>> {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take a token for `key`, counting the event as suppressed if none is left
+    fn allow(&self, key: String, now: Instant) -> bool {
+        let capacity = f64::from(self.config.per_second);
+        let mut buckets = self.lock();
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            refilled: now,
+            suppressed: 0,
+        });
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+        bucket.refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            bucket.suppressed += 1;
+            false
+        }
+    }
+
+    /// Take the suppressed counts, forgetting keys that have gone quiet
+    fn drain(&self, now: Instant) -> Vec<(String, u64)> {
+        let idle = self.config.summary_interval.max(Duration::from_secs(1));
+        let mut suppressed = Vec::new();
+        self.lock().retain(|key, bucket| {
+            if bucket.suppressed > 0 {
+                suppressed.push((key.clone(), std::mem::take(&mut bucket.suppressed)));
+                true
+            } else {
+                now.saturating_duration_since(bucket.refilled) < idle
+            }
+        });
+        suppressed
+    }
+}
+
+/// Layer dropping events over the limit before any output sees them
+pub(crate) struct RateLimitLayer {
+    buckets: Arc<Buckets>,
+}
+
+impl RateLimitLayer {
+    /// Create the layer and start its summary thread
+    pub(crate) fn new(config: RateLimit) -> Self {
+        let interval = config.summary_interval;
+        let buckets = Arc::new(Buckets {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        });
+        let summarized = Arc::clone(&buckets);
+        let spawned = thread::Builder::new()
+            .name("log-rate-limit".to_string())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                for (key, suppressed) in summarized.drain(Instant::now()) {
+                    tracing::warn!(
+                        target: TARGET,
+                        key = key.as_str(),
+                        suppressed,
+                        "Events suppressed by rate limit"
+                    );
+                }
+            });
+        if let Err(e) = spawned {
+            eprintln!("Failed to spawn rate limit summary thread: {}", e);
+        }
+        Self { buckets }
+    }
+
+    fn key(&self, event: &Event<'_>) -> String {
+        let mut visitor = KeyVisitor {
+            field: self.buckets.config.key_field.as_deref(),
+            message: None,
+            value: None,
+        };
+        event.record(&mut visitor);
+        let target = event.metadata().target();
+        match (visitor.value, visitor.message) {
+            (Some(value), _) => format!("{}:{}", target, value),
+            (None, Some(message)) => format!("{}:{}", target, message),
+            (None, None) => format!("{}:{}", target, event.metadata().name()),
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for RateLimitLayer {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        if event.metadata().target() == TARGET {
+            return true;
+        }
+        self.buckets.allow(self.key(event), Instant::now())
+    }
+}
+
+/// Extracts the message and the key field of an event
+struct KeyVisitor<'a> {
+    field: Option<&'a str>,
+    message: Option<String>,
+    value: Option<String>,
+}
+
+impl KeyVisitor<'_> {
+    fn record(&mut self, field: &Field, value: String) {
+        if Some(field.name()) == self.field {
+            self.value = Some(value);
+        } else if field.name() == "message" {
+            self.message = Some(value);
+        }
+    }
+}
+
+impl Visit for KeyVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, crate::json_field::debug_text(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let buckets = Buckets {
+            config: RateLimit::per_second(2),
+            buckets: Mutex::new(HashMap::new()),
+        };
+        let start = Instant::now();
+        let allowed: Vec<_> = (0..4)
+            .map(|_| buckets.allow("k".to_string(), start))
+            .collect();
+        assert_eq!(allowed, vec![true, true, false, false]);
+        assert!(buckets.allow("k".to_string(), start + Duration::from_millis(500)));
+        assert!(buckets.allow("other".to_string(), start));
+        assert_eq!(buckets.drain(start), vec![("k".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_identical_events_are_limited() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(RateLimitLayer::new(
+                RateLimit::per_second(3).summary_interval(Duration::from_secs(3600)),
+            ))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_writer(capture.clone()),
+            );
+
+        tracing::subscriber::with_default(subscriber, || {
+            for attempt in 0..10 {
+                tracing::warn!(attempt, "Retrying connection");
+                tracing::info!(user_id = attempt, "Cache miss");
+            }
+        });
+
+        let count = |message: &str| {
+            capture
+                .records()
+                .iter()
+                .filter(|r| r["fields"]["message"] == message)
+                .count()
+        };
+        assert_eq!(count("Retrying connection"), 3);
+        assert_eq!(count("Cache miss"), 3);
+    }
+}
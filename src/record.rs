@@ -0,0 +1,169 @@
+//! Record schema and versioning
+//!
+//! Every record starts with a `schema_version` key. [`LogRecord`] is the
+//! shape of records in the default JSON layout for that version; it is what
+//! the crate's own tests check formatted output against, so a change to the
+//! layout fails them until [`SCHEMA_VERSION`] is raised and the change is
+//! listed in the changelog.
+//!
+//! Downstream parsers read records with [`LogRecord::parse`], which also
+//! accepts records written by older versions and migrates them to the
+//! current shape:
+//!
+//! | Version | Change |
+//! |---------|--------|
+//! | 0 | Records before versioning, without `schema_version` |
+//! | 1 | `schema_version` added as the first key |
+
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+/// Version of the record layout written by this crate
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Key of the schema version in records
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// A record in the default JSON layout
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::record::{LogRecord, SCHEMA_VERSION};
+///
+/// let line = r#"{"timestamp":"2025-01-01T00:00:00Z","level":"INFO","fields":{"message":"hi"},"target":"app"}"#;
+/// let record = LogRecord::parse(line).unwrap();
+/// assert_eq!(record.schema_version, SCHEMA_VERSION); // migrated from version 0
+/// assert_eq!(record.message(), Some("hi"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    /// Layout version, always [`SCHEMA_VERSION`] after parsing
+    pub schema_version: u64,
+    /// RFC 3339 UTC time the event was recorded
+    pub timestamp: String,
+    /// "TRACE", "DEBUG", "INFO", "WARN" or "ERROR"
+    pub level: String,
+    /// Event fields, including `message`
+    pub fields: Map<String, Value>,
+    /// Module path or explicit target of the event
+    pub target: String,
+    /// Current span, with its `name` and fields
+    pub span: Option<Map<String, Value>>,
+    /// Optional top-level keys added by configuration (`seq`, `uptime_ns`, static fields, ...)
+    pub extra: Map<String, Value>,
+}
+
+impl LogRecord {
+    /// Parse one line of JSON, migrating older layouts
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+        Self::from_value(value)
+    }
+
+    /// Read a record from a parsed JSON value, migrating older layouts
+    pub fn from_value(value: Value) -> Result<Self, String> {
+        let Value::Object(mut record) = value else {
+            return Err("record is not a JSON object".to_string());
+        };
+        let version = match record.remove(SCHEMA_VERSION_KEY) {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| format!("{} is not a number", SCHEMA_VERSION_KEY))?,
+        };
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "schema version {} is newer than the supported {}",
+                version, SCHEMA_VERSION
+            ));
+        }
+        // Versions 0 and 1 only differ in the version key itself
+
+        let mut string = |key: &str| match record.remove(key) {
+            Some(Value::String(value)) => Ok(value),
+            _ => Err(format!("missing string '{}'", key)),
+        };
+        let timestamp = string("timestamp")?;
+        let level = string("level")?;
+        let target = string("target")?;
+        let fields = match record.remove("fields") {
+            Some(Value::Object(fields)) => fields,
+            _ => return Err("missing object 'fields'".to_string()),
+        };
+        let span = match record.remove("span") {
+            None => None,
+            Some(Value::Object(span)) => Some(span),
+            Some(_) => return Err("'span' is not an object".to_string()),
+        };
+        Ok(Self {
+            schema_version: SCHEMA_VERSION,
+            timestamp,
+            level,
+            fields,
+            target,
+            span,
+            extra: record,
+        })
+    }
+
+    /// The event message, if any
+    pub fn message(&self) -> Option<&str> {
+        self.fields.get("message").and_then(Value::as_str)
+    }
+
+    /// The record as JSON, with keys in the order they are written
+    pub fn to_value(&self) -> Value {
+        let mut record = Map::new();
+        record.insert(SCHEMA_VERSION_KEY.to_string(), self.schema_version.into());
+        record.insert("timestamp".to_string(), self.timestamp.clone().into());
+        record.insert("level".to_string(), self.level.clone().into());
+        record.insert("fields".to_string(), Value::Object(self.fields.clone()));
+        record.insert("target".to_string(), self.target.clone().into());
+        if let Some(span) = &self.span {
+            record.insert("span".to_string(), Value::Object(span.clone()));
+        }
+        record.extend(self.extra.clone());
+        Value::Object(record)
+    }
+}
+
+impl Serialize for LogRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().serialize(serializer)
+    }
+}
+
+/// Put the schema version first in a formatted record
+pub(crate) fn stamp(record: &mut Map<String, Value>) {
+    let mut versioned = Map::new();
+    versioned.insert(SCHEMA_VERSION_KEY.to_string(), SCHEMA_VERSION.into());
+    versioned.append(record);
+    *record = versioned;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_newer_and_malformed_records() {
+        assert!(LogRecord::parse(
+            r#"{"schema_version":99,"timestamp":"t","level":"INFO","fields":{},"target":"a"}"#
+        )
+        .is_err());
+        assert!(LogRecord::parse(
+            r#"{"schema_version":1,"level":"INFO","fields":{},"target":"a"}"#
+        )
+        .is_err());
+        assert!(LogRecord::parse("[]").is_err());
+    }
+
+    #[test]
+    fn test_round_trip_keeps_extra_keys() {
+        let line = r#"{"schema_version":1,"timestamp":"t","level":"INFO","fields":{"message":"m"},"target":"a","span":{"name":"s"},"seq":7}"#;
+        let record = LogRecord::parse(line).unwrap();
+        assert_eq!(record.extra["seq"], 7);
+        let value: Value = serde_json::from_str(line).unwrap();
+        assert_eq!(serde_json::to_value(&record).unwrap(), value);
+    }
+}
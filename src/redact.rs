@@ -0,0 +1,298 @@
+//! Redaction of sensitive fields
+//!
+//! Records are rewritten before they reach any output: values of configured
+//! field names (e.g. `password`, `authorization`) are replaced as a whole,
+//! and substrings matching configured patterns (card numbers, emails) are
+//! replaced wherever they appear in string values, including the message
+//! and span fields.
+//!
+//! In hashing mode values are replaced with a keyed HMAC-SHA256 digest
+//! instead, so the same user id or email can still be correlated across
+//! events without being readable.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde_json::{Map, Value};
+use sha2::Sha256;
+
+/// Replacement for redacted values
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Payment card numbers: 13-19 digits, optionally grouped with spaces or dashes
+pub const CREDIT_CARD: &str = r"\b\d(?:[ -]?\d){12,18}\b";
+
+/// Prefix of hashed values
+pub const HASH_PREFIX: &str = "hmac:";
+
+/// Digest bytes kept in hashed values, enough to correlate without collisions
+const HASH_BYTES: usize = 16;
+
+/// Email addresses
+pub const EMAIL: &str = r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b";
+
+/// A value pattern, with an optional check to cut false positives
+#[derive(Debug, Clone)]
+struct Pattern {
+    regex: Regex,
+    check: Option<fn(&str) -> bool>,
+}
+
+/// Redaction rules applied to every record
+#[derive(Clone, Default)]
+pub struct Redactor {
+    fields: HashSet<String>,
+    patterns: Vec<Pattern>,
+    hash_key: Option<Arc<[u8]>>,
+}
+
+impl Redactor {
+    /// Create a redactor without any rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redact the whole value of fields named `name` (case-insensitive)
+    pub fn field(mut self, name: impl AsRef<str>) -> Self {
+        self.fields
+            .insert(name.as_ref().trim().to_ascii_lowercase());
+        self
+    }
+
+    /// Redact substrings of string values matching `pattern`
+    pub fn pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.patterns.push(Pattern {
+            regex: Regex::new(pattern)?,
+            check: None,
+        });
+        Ok(self)
+    }
+
+    /// Redact payment card numbers that pass the Luhn checksum
+    pub fn credit_cards(mut self) -> Self {
+        self.patterns.push(Pattern {
+            regex: Regex::new(CREDIT_CARD).expect("valid card pattern"),
+            check: Some(luhn),
+        });
+        self
+    }
+
+    /// Redact email addresses
+    pub fn emails(self) -> Self {
+        self.pattern(EMAIL).expect("valid email pattern")
+    }
+
+    /// Replace values with `hmac:` and a keyed digest instead of `[REDACTED]`
+    ///
+    /// Equal values produce equal digests under the same key, so they can be
+    /// correlated across events and restarts; without the key they cannot be
+    /// recovered or brute-forced from the logs.
+    pub fn hash_with(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.hash_key = Some(key.as_ref().into());
+        self
+    }
+
+    /// Read the rules from the environment, if any are configured
+    ///
+    /// - `LOG_REDACT_FIELDS`: Field names (e.g. "password,ssn,authorization")
+    /// - `LOG_REDACT_PATTERNS`: Built-in patterns, "credit_card" and/or "email"
+    /// - `LOG_REDACTION_KEY`: Hash values with this key instead of masking them
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let mut unknown = Vec::new();
+        let redactor = Self::read_env(&mut unknown);
+        match unknown.first() {
+            Some(pattern) => Err(format!(
+                "unknown redaction pattern '{}', expected credit_card or email",
+                pattern
+            )),
+            None => Ok(redactor),
+        }
+    }
+
+    /// Like [`from_env`](Self::from_env), keeping the valid rules when some
+    /// patterns are unknown so a typo never disables redaction entirely
+    pub(crate) fn from_env_lenient() -> Option<Self> {
+        let mut unknown = Vec::new();
+        let redactor = Self::read_env(&mut unknown);
+        for pattern in unknown {
+            eprintln!("Ignoring unknown LOG_REDACT_PATTERNS entry '{}'", pattern);
+        }
+        redactor
+    }
+
+    fn read_env(unknown: &mut Vec<String>) -> Option<Self> {
+        let mut redactor = Self::new();
+        if let Ok(fields) = crate::config_file::var("LOG_REDACT_FIELDS") {
+            for field in fields.split(',').filter(|f| !f.trim().is_empty()) {
+                redactor = redactor.field(field);
+            }
+        }
+        if let Ok(patterns) = crate::config_file::var("LOG_REDACT_PATTERNS") {
+            for pattern in patterns.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                redactor = match pattern {
+                    "credit_card" => redactor.credit_cards(),
+                    "email" => redactor.emails(),
+                    other => {
+                        unknown.push(other.to_string());
+                        redactor
+                    }
+                };
+            }
+        }
+        if let Ok(key) = crate::config_file::var("LOG_REDACTION_KEY") {
+            if !key.is_empty() {
+                redactor = redactor.hash_with(key);
+            }
+        }
+        (!redactor.is_empty()).then_some(redactor)
+    }
+
+    /// Whether no rules are configured
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Redact a formatted record in place
+    pub(crate) fn redact(&self, record: &mut Map<String, Value>) {
+        for (key, value) in record.iter_mut() {
+            if self.fields.contains(&key.to_ascii_lowercase()) {
+                let replacement = match &*value {
+                    Value::String(s) => self.replacement(s),
+                    other => self.replacement(&other.to_string()),
+                };
+                *value = Value::String(replacement);
+            } else {
+                self.redact_value(value);
+            }
+        }
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => self.redact(map),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::String(s) => {
+                for pattern in &self.patterns {
+                    if !pattern.regex.is_match(s) {
+                        continue;
+                    }
+                    let replaced = pattern.regex.replace_all(s, |caps: &regex::Captures<'_>| {
+                        let found = &caps[0];
+                        match pattern.check {
+                            Some(check) if !check(found) => found.to_string(),
+                            _ => self.replacement(found),
+                        }
+                    });
+                    *s = replaced.into_owned();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Masked or hashed form of a sensitive value
+    fn replacement(&self, value: &str) -> String {
+        let Some(key) = &self.hash_key else {
+            return REDACTED.to_string();
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(value.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        let hex: String = digest[..HASH_BYTES]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("{}{}", HASH_PREFIX, hex)
+    }
+}
+
+impl std::fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the hashing key
+        f.debug_struct("Redactor")
+            .field("fields", &self.fields)
+            .field("patterns", &self.patterns.len())
+            .field("hashed", &self.hash_key.is_some())
+            .finish()
+    }
+}
+
+/// Luhn checksum over the digits of `s`
+fn luhn(s: &str) -> bool {
+    let digits: Vec<u32> = s.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields_and_patterns_are_redacted() {
+        let redactor = Redactor::new()
+            .field("password")
+            .field("Authorization")
+            .credit_cards()
+            .emails();
+        let mut record = json!({
+            "fields": {
+                "message": "charge 4111 1111 1111 1111 for bob@example.com, order 1234567890123",
+                "password": "hunter2",
+                "authorization": {"scheme": "Bearer", "token": "abc"},
+                "attempts": 3
+            },
+            "span": {"name": "checkout", "PASSWORD": "x"}
+        });
+        redactor.redact(record.as_object_mut().unwrap());
+
+        assert_eq!(
+            record["fields"]["message"],
+            "charge [REDACTED] for [REDACTED], order 1234567890123"
+        );
+        assert_eq!(record["fields"]["password"], REDACTED);
+        assert_eq!(record["fields"]["authorization"], REDACTED);
+        assert_eq!(record["fields"]["attempts"], 3);
+        assert_eq!(record["span"]["PASSWORD"], REDACTED);
+        assert_eq!(record["span"]["name"], "checkout");
+    }
+
+    #[test]
+    fn test_hashing_is_consistent_per_key() {
+        let redactor = Redactor::new().field("email").emails().hash_with("secret");
+        let mut first = json!({"email": "bob@example.com", "message": "login bob@example.com"});
+        let mut second = json!({"email": "bob@example.com", "message": "reset bob@example.com"});
+        redactor.redact(first.as_object_mut().unwrap());
+        redactor.redact(second.as_object_mut().unwrap());
+
+        let hashed = first["email"].as_str().unwrap();
+        assert!(hashed.starts_with(HASH_PREFIX));
+        assert_eq!(hashed.len(), HASH_PREFIX.len() + 2 * HASH_BYTES);
+        assert_eq!(first["email"], second["email"]);
+        assert_eq!(first["message"], format!("login {}", hashed));
+        assert_eq!(second["message"], format!("reset {}", hashed));
+
+        let other_key = Redactor::new().field("email").hash_with("other");
+        let mut third = json!({"email": "bob@example.com"});
+        other_key.redact(third.as_object_mut().unwrap());
+        assert_ne!(third["email"], first["email"]);
+    }
+
+    #[test]
+    fn test_luhn() {
+        assert!(luhn("4111-1111-1111-1111"));
+        assert!(!luhn("4111 1111 1111 1112"));
+    }
+}
@@ -0,0 +1,345 @@
+//! Logging from inside the pipeline
+//!
+//! Records can be emitted while this thread is already writing one: from a
+//! sink or a `Drop` impl run by a writer, from a panic unwinding through a
+//! writer, or from the crate's own background threads. Dispatching those
+//! normally would recurse into the same writer and deadlock on its locks or
+//! overflow the stack, so they are written to stderr instead, bypassing
+//! every internal lock. Panics raised while writing are caught, so logging
+//! from a `Drop` impl during unwinding can never abort the process.
+
+use std::any::TypeId;
+use std::cell::Cell;
+use std::fmt;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span;
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+thread_local! {
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Set once a panic inside the pipeline has been reported
+static PANIC_REPORTED: AtomicBool = AtomicBool::new(false);
+
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+static REENTRANT: AtomicU64 = AtomicU64::new(0);
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts of events seen by the guarded outputs since startup
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PipelineStats {
+    /// Events handed to the outputs
+    pub(crate) written: u64,
+    /// Events written to stderr because they were emitted inside the pipeline
+    pub(crate) reentrant: u64,
+    /// Panics caught inside the pipeline
+    pub(crate) panics: u64,
+}
+
+pub(crate) fn stats() -> PipelineStats {
+    PipelineStats {
+        written: WRITTEN.load(Ordering::Relaxed),
+        reentrant: REENTRANT.load(Ordering::Relaxed),
+        panics: PANICS.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn reset_stats() {
+    for counter in [&WRITTEN, &REENTRANT, &PANICS] {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Marks this thread as inside the pipeline until dropped
+pub(crate) struct Entered(());
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+    }
+}
+
+/// Mark this thread as inside the pipeline
+///
+/// Background threads hold this for their whole lifetime so events they
+/// trigger (e.g. from an HTTP client bridged into tracing) cannot feed
+/// back into the outputs they are writing.
+pub(crate) fn enter() -> Entered {
+    DEPTH.with(|depth| depth.set(depth.get() + 1));
+    Entered(())
+}
+
+fn is_entered() -> bool {
+    DEPTH.with(|depth| depth.get() > 0)
+}
+
+/// Run `f`, reporting the first panic on stderr instead of unwinding
+fn shielded(f: impl FnOnce()) {
+    if panic::catch_unwind(AssertUnwindSafe(f)).is_err() {
+        PANICS.fetch_add(1, Ordering::Relaxed);
+        if !PANIC_REPORTED.swap(true, Ordering::Relaxed) {
+            eprintln!("Logging panicked while writing a record; the record was dropped");
+        }
+    }
+}
+
+/// Wraps the output layers so they are never re-entered or unwound through
+pub(crate) struct Guarded<L> {
+    inner: L,
+    fallback: fn(&str),
+}
+
+impl<L> Guarded<L> {
+    pub(crate) fn new(inner: L) -> Self {
+        Self {
+            inner,
+            fallback: write_stderr,
+        }
+    }
+
+    /// Run a writing callback of the inner layer, unless already inside one
+    fn write(&self, f: impl FnOnce()) -> bool {
+        if is_entered() {
+            return false;
+        }
+        let _entered = enter();
+        shielded(f);
+        true
+    }
+}
+
+fn write_stderr(line: &str) {
+    let _ = writeln!(std::io::stderr(), "{}", line);
+}
+
+impl<S, L> Layer<S> for Guarded<L>
+where
+    S: Subscriber,
+    L: Layer<S>,
+{
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.inner.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.enabled(metadata, ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    // Span bookkeeping writes no records and must always run, so spans stay consistent
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        shielded(|| self.inner.on_new_span(attrs, id, ctx));
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        shielded(|| self.inner.on_record(span, values, ctx));
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, ctx);
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        shielded(|| self.inner.on_close(id, ctx));
+    }
+
+    fn on_id_change(&self, old: &span::Id, new: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_id_change(old, new, ctx);
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.event_enabled(event, ctx)
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if self.write(|| self.inner.on_event(event, ctx)) {
+            WRITTEN.fetch_add(1, Ordering::Relaxed);
+        } else {
+            REENTRANT.fetch_add(1, Ordering::Relaxed);
+            (self.fallback)(&fallback_record(event));
+        }
+    }
+
+    // Span enter/exit records are skipped when re-entered
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.write(|| self.inner.on_enter(id, ctx));
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.write(|| self.inner.on_exit(id, ctx));
+    }
+
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            Some(self as *const _ as *const ())
+        } else {
+            self.inner.downcast_raw(id)
+        }
+    }
+}
+
+/// Render a re-entrant event as a JSON line without touching any output
+fn fallback_record(event: &Event<'_>) -> String {
+    let mut fields = FieldMap(Map::new());
+    event.record(&mut fields);
+    let metadata = event.metadata();
+    serde_json::json!({
+        "level": metadata.level().as_str(),
+        "fields": fields.0,
+        "target": metadata.target(),
+        "reentrant": true,
+    })
+    .to_string()
+}
+
+/// Event fields collected into a JSON object
+pub(crate) struct FieldMap(pub(crate) Map<String, Value>);
+
+impl Visit for FieldMap {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut value = Value::from(format!("{:?}", value));
+        crate::json_field::decode(&mut value);
+        self.0.insert(field.name().to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use std::io;
+    use std::sync::Mutex;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    static FALLBACK: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn guarded<W>(writer: W) -> Guarded<impl Layer<tracing_subscriber::Registry>>
+    where
+        W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+    {
+        Guarded {
+            inner: tracing_subscriber::fmt::layer().json().with_writer(writer),
+            fallback: |line| FALLBACK.lock().unwrap().push(line.to_string()),
+        }
+    }
+
+    /// Logs when dropped, like application types cleaning up during a panic
+    struct Noisy;
+
+    impl Drop for Noisy {
+        fn drop(&mut self) {
+            tracing::error!(unwinding = std::thread::panicking(), "Dropped");
+        }
+    }
+
+    #[test]
+    fn test_drop_during_unwind_is_logged() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(guarded(capture.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let result = panic::catch_unwind(|| {
+                let _noisy = Noisy;
+                panic!("boom");
+            });
+            assert!(result.is_err());
+        });
+
+        let records = capture.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["fields"]["unwinding"], true);
+    }
+
+    /// Writer whose every write panics
+    #[derive(Clone)]
+    struct Panicking;
+
+    impl io::Write for Panicking {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            panic!("writer failed")
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Panicking {
+        type Writer = Panicking;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            Panicking
+        }
+    }
+
+    #[test]
+    fn test_panicking_writer_during_unwind_does_not_abort() {
+        let subscriber = tracing_subscriber::registry().with(guarded(Panicking));
+
+        tracing::subscriber::with_default(subscriber, || {
+            // A second panic escaping `Noisy::drop` here would abort the test binary
+            let result = panic::catch_unwind(|| {
+                let _noisy = Noisy;
+                panic!("boom");
+            });
+            assert!(result.is_err());
+            tracing::info!("still running");
+        });
+    }
+
+    #[test]
+    fn test_reentrant_event_goes_to_fallback() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(guarded(capture.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _entered = enter();
+            tracing::warn!(sink = "nats", "Reconnecting from inside a sink");
+        });
+
+        assert!(capture.records().is_empty());
+        let fallback = FALLBACK.lock().unwrap();
+        let record: Value = serde_json::from_str(fallback.last().unwrap()).unwrap();
+        assert_eq!(record["fields"]["sink"], "nats");
+        assert_eq!(record["reentrant"], true);
+        assert!(!is_entered());
+    }
+}
@@ -0,0 +1,319 @@
+//! Changing the log level at runtime
+//!
+//! [`init`](crate::init) returns a [`ReloadHandle`] that replaces the
+//! `RUST_LOG` filter of the installed logger, so debug logging can be turned
+//! on for a live incident and off again without restarting the service.
+//! With `LOG_FILTER_FILE` the filter also follows a file, e.g. a mounted
+//! Kubernetes ConfigMap, which is polled for changes.
+
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::debug_users::DebugUserFilter;
+
+/// Default time between reads of the filter file
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Handle of the logger installed as the global default
+static INSTALLED: OnceLock<ReloadHandle> = OnceLock::new();
+
+/// Global levels from least to most verbose
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::OFF,
+    LevelFilter::ERROR,
+    LevelFilter::WARN,
+    LevelFilter::INFO,
+    LevelFilter::DEBUG,
+    LevelFilter::TRACE,
+];
+
+/// Handle to the filter of the installed logger
+///
+/// # Examples
+/// ```no_run
+/// let handle = custom_tracing_logger::init();
+///
+/// handle.set_filter("debug,hyper=info").unwrap();
+/// // ... investigate, then restore
+/// handle.set_filter("info").unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ReloadHandle {
+    inner: reload::Handle<DebugUserFilter, Registry>,
+}
+
+/// Error replacing the filter
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The directives are not valid `RUST_LOG` syntax
+    InvalidFilter(String),
+    /// The logger this handle belongs to was not installed (another subscriber was already set)
+    NotInstalled,
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::InvalidFilter(e) => write!(f, "Invalid filter: {}", e),
+            ReloadError::NotInstalled => write!(f, "Logger is not installed"),
+        }
+    }
+}
+
+impl Error for ReloadError {}
+
+impl ReloadHandle {
+    pub(crate) fn new(inner: reload::Handle<DebugUserFilter, Registry>) -> Self {
+        Self { inner }
+    }
+
+    /// Replace the `RUST_LOG` filter with `directives` (e.g. "debug" or "info,my_app::db=trace")
+    ///
+    /// Debug users and the ring buffer keep their own levels.
+    pub fn set_filter(&self, directives: &str) -> Result<(), ReloadError> {
+        let filter = EnvFilter::try_new(crate::presets::apply(directives))
+            .map_err(|e| ReloadError::InvalidFilter(e.to_string()))?;
+        self.inner
+            .modify(|current| current.set_env_filter(filter))
+            .map_err(|_| ReloadError::NotInstalled)?;
+        #[cfg(feature = "log")]
+        crate::log_bridge::sync_max_level();
+        Ok(())
+    }
+
+    /// Current filter directives, `None` if the logger is not installed
+    pub fn filter(&self) -> Option<String> {
+        self.inner
+            .with_current(|current| current.env_filter().to_string())
+            .ok()
+    }
+
+    /// Replace the filter with the next more verbose global level (e.g. info to debug)
+    ///
+    /// Per-target directives are dropped; returns the new level.
+    pub fn raise_level(&self) -> Result<LevelFilter, ReloadError> {
+        self.step_level(1)
+    }
+
+    /// Replace the filter with the next less verbose global level (e.g. info to warn)
+    ///
+    /// Per-target directives are dropped; returns the new level.
+    pub fn lower_level(&self) -> Result<LevelFilter, ReloadError> {
+        self.step_level(-1)
+    }
+
+    fn step_level(&self, step: isize) -> Result<LevelFilter, ReloadError> {
+        let current = self
+            .inner
+            .with_current(|current| current.env_filter().max_level_hint())
+            .map_err(|_| ReloadError::NotInstalled)?
+            .unwrap_or(LevelFilter::INFO);
+        let index = LEVELS
+            .iter()
+            .position(|level| *level == current)
+            .unwrap_or(3);
+        let next = LEVELS[(index as isize + step).clamp(0, LEVELS.len() as isize - 1) as usize];
+        self.set_filter(&next.to_string())?;
+        Ok(next)
+    }
+
+    /// Apply the filter found in `path` now and whenever its contents change
+    ///
+    /// The file holds `RUST_LOG` directives, or a `RUST_LOG=...` line among
+    /// others; blank lines and `#` comments are ignored. A missing file or
+    /// invalid directives keep the current filter. The background thread
+    /// polls every `interval` and exits when the logger is gone.
+    pub fn watch_file(&self, path: impl Into<PathBuf>, interval: Duration) {
+        let handle = self.clone();
+        let path = path.into();
+        let _ = thread::Builder::new()
+            .name("log-filter-watch".to_string())
+            .spawn(move || handle.watch_loop(&path, interval));
+    }
+
+    fn watch_loop(&self, path: &Path, interval: Duration) {
+        let mut last: Option<String> = None;
+        while self.filter().is_some() {
+            let directives = read_filter_file(path);
+            if let Some(directives) = directives.filter(|d| last.as_ref() != Some(d)) {
+                match self.set_filter(&directives) {
+                    Ok(()) => {
+                        tracing::info!(filter = %directives, path = %path.display(), "Log filter reloaded")
+                    }
+                    Err(ReloadError::NotInstalled) => return,
+                    Err(e) => eprintln!("Ignoring filter in '{}': {}", path.display(), e),
+                }
+                last = Some(directives);
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    /// Select the enter, exit and close span records to write, see [`spans::set_span_events`](crate::spans::set_span_events)
+    pub fn set_span_events(&self, events: crate::spans::SpanEvents) {
+        crate::spans::set_span_events(events);
+    }
+
+    /// Turn span field capture on or off, see [`spans::set_field_capture`](crate::spans::set_field_capture)
+    pub fn set_span_field_capture(&self, enabled: bool) {
+        crate::spans::set_field_capture(enabled);
+    }
+}
+
+/// Handle of the installed logger, if [`init`](crate::init) already installed one
+pub(crate) fn installed() -> Option<ReloadHandle> {
+    INSTALLED.get().cloned()
+}
+
+pub(crate) fn install(handle: ReloadHandle) {
+    let _ = INSTALLED.set(handle);
+}
+
+/// Read `LOG_FILTER_FILE` and `LOG_FILTER_POLL_MS`, if a filter file is configured
+pub(crate) fn watch_from_env() -> Option<(PathBuf, Duration)> {
+    let path = crate::config_file::var("LOG_FILTER_FILE").ok()?;
+    let interval = crate::config_file::var("LOG_FILTER_POLL_MS")
+        .ok()
+        .and_then(|ms| ms.trim().parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_POLL_INTERVAL);
+    Some((PathBuf::from(path.trim()), interval))
+}
+
+/// Filter directives in the file at `path`, `None` when it is missing or has none
+pub(crate) fn read_filter_file(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| filter_from_file(&contents))
+}
+
+/// Filter directives in a filter file's contents, `None` when it has none
+fn filter_from_file(contents: &str) -> Option<String> {
+    let lines: Vec<&str> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    if let Some(value) = lines.iter().find_map(|line| line.strip_prefix("RUST_LOG=")) {
+        return Some(
+            value
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string(),
+        );
+    }
+    let directives = lines.join(",");
+    (!directives.is_empty()).then_some(directives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use tracing_subscriber::layer::{Layer, SubscriberExt};
+
+    #[test]
+    fn test_set_filter_changes_level() {
+        let capture = Capture::default();
+        let filter = DebugUserFilter::new(EnvFilter::new("info"), None);
+        let (filter, handle) = reload::Layer::new(filter);
+        let handle = ReloadHandle::new(handle);
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(capture.clone())
+                .with_filter(filter),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("hidden");
+            handle.set_filter("debug").unwrap();
+            tracing::debug!("shown");
+            assert_eq!(handle.filter().as_deref(), Some("debug"));
+            assert!(matches!(
+                handle.set_filter("debug[="),
+                Err(ReloadError::InvalidFilter(_))
+            ));
+        });
+
+        // The subscriber is gone once the scope ends
+        assert!(matches!(
+            handle.set_filter("info"),
+            Err(ReloadError::NotInstalled)
+        ));
+        let messages: Vec<_> = capture
+            .records()
+            .iter()
+            .map(|r| r["fields"]["message"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(messages, vec!["shown"]);
+    }
+
+    #[test]
+    fn test_filter_from_file() {
+        assert_eq!(filter_from_file("debug\n").as_deref(), Some("debug"));
+        assert_eq!(
+            filter_from_file("# levels\ninfo\nmy_app::db=trace\n").as_deref(),
+            Some("info,my_app::db=trace")
+        );
+        assert_eq!(
+            filter_from_file("LOG_FORMAT=ecs\nRUST_LOG=\"warn\"\n").as_deref(),
+            Some("warn")
+        );
+        assert_eq!(filter_from_file("\n# nothing\n"), None);
+    }
+
+    #[test]
+    fn test_watch_file_applies_changes() {
+        let path = std::env::temp_dir().join(format!("log-filter-{}", std::process::id()));
+        std::fs::write(&path, "debug\n").unwrap();
+        let filter = DebugUserFilter::new(EnvFilter::new("info"), None);
+        let (filter, handle) = reload::Layer::new(filter);
+        let handle = ReloadHandle::new(handle);
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::sink)
+                .with_filter(filter),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            handle.watch_file(&path, Duration::from_millis(10));
+            let applied = (0..200).any(|_| {
+                thread::sleep(Duration::from_millis(10));
+                handle.filter().as_deref() == Some("debug")
+            });
+            assert!(applied);
+        });
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_raise_and_lower_level() {
+        let filter = DebugUserFilter::new(EnvFilter::new("info,my_app=trace"), None);
+        let (filter, handle) = reload::Layer::new(filter);
+        let handle = ReloadHandle::new(handle);
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::sink)
+                .with_filter(filter),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            // The most verbose directive sets the starting point
+            assert_eq!(handle.lower_level().unwrap(), LevelFilter::DEBUG);
+            assert_eq!(handle.lower_level().unwrap(), LevelFilter::INFO);
+            assert_eq!(handle.raise_level().unwrap(), LevelFilter::DEBUG);
+            assert_eq!(handle.raise_level().unwrap(), LevelFilter::TRACE);
+            assert_eq!(handle.raise_level().unwrap(), LevelFilter::TRACE);
+            assert_eq!(handle.filter().as_deref(), Some("trace"));
+        });
+    }
+}
@@ -0,0 +1,260 @@
+//! Request id generation and propagation
+//!
+//! [`generate`] creates a time-ordered id (UUIDv7 by default, or a ULID
+//! with `LOG_REQUEST_ID_FORMAT=ulid`), [`from_headers_or_generate`] takes
+//! the caller's `x-request-id` instead when it sent one, and [`scope`] adds
+//! it as `request_id` to every record the request's future emits. Calls to
+//! other services forward it with [`header`].
+//!
+//! ```
+//! # async fn handle() {}
+//! # async fn run(headers: Vec<(String, String)>) {
+//! use custom_tracing_logger::request_id;
+//!
+//! let id = request_id::from_headers_or_generate(headers);
+//! request_id::scope(id, async {
+//!     tracing::info!("Handling order"); // "fields":{"message":"Handling order","request_id":"0190b3c4-..."}
+//!     if let Some((name, value)) = request_id::header() {
+//!         // outgoing = outgoing.header(name, value);
+//!     }
+//!     handle().await;
+//! })
+//! .await;
+//! # }
+//! ```
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::context::{self, Scoped};
+
+/// Header carrying the request id
+pub const HEADER: &str = "x-request-id";
+
+/// Field the request id is added as
+pub const FIELD: &str = "request_id";
+
+/// Longest accepted incoming id; longer ones are replaced by a generated id
+const MAX_LEN: usize = 128;
+
+/// Crockford's base32 alphabet used by ULIDs
+const ULID_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Layout of generated ids
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdFormat {
+    /// UUID version 7, e.g. "0190b3c4-5f6e-7a8b-9c0d-1e2f3a4b5c6d" (default)
+    #[default]
+    UuidV7,
+    /// ULID, e.g. "01J2SW8QVE7ABCDEFGHJKMNPQR"
+    Ulid,
+}
+
+impl std::str::FromStr for IdFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "uuid" | "uuidv7" => Ok(IdFormat::UuidV7),
+            "ulid" => Ok(IdFormat::Ulid),
+            other => Err(format!(
+                "unknown request id format '{}', expected uuid or ulid",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for IdFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IdFormat::UuidV7 => "uuid",
+            IdFormat::Ulid => "ulid",
+        })
+    }
+}
+
+impl IdFormat {
+    /// A new id in this format
+    pub fn generate(self) -> String {
+        match self {
+            IdFormat::UuidV7 => uuid_v7(),
+            IdFormat::Ulid => ulid(),
+        }
+    }
+}
+
+/// Format of [`generate`], read once from `LOG_REQUEST_ID_FORMAT`
+pub fn id_format() -> IdFormat {
+    static FORMAT: OnceLock<IdFormat> = OnceLock::new();
+    *FORMAT.get_or_init(|| match crate::config_file::var("LOG_REQUEST_ID_FORMAT") {
+        Ok(value) => value.parse().unwrap_or_else(|e| {
+            eprintln!("Invalid LOG_REQUEST_ID_FORMAT: {}", e);
+            IdFormat::default()
+        }),
+        Err(_) => IdFormat::default(),
+    })
+}
+
+/// A new request id in the configured [`IdFormat`]
+pub fn generate() -> String {
+    id_format().generate()
+}
+
+/// A new UUIDv7: milliseconds since the epoch followed by random bits
+pub fn uuid_v7() -> String {
+    encode_uuid_v7(unix_millis(), random_bytes())
+}
+
+/// A new ULID: milliseconds since the epoch followed by random bits
+pub fn ulid() -> String {
+    encode_ulid(unix_millis(), random_bytes())
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// 16 bytes that differ for every call within the process and across processes
+pub(crate) fn random_bytes() -> [u8; 16] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let seed = format!(
+        "{:?}-{}-{}-{:?}",
+        SystemTime::now(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        std::thread::current().id()
+    );
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&Sha256::digest(seed.as_bytes())[..16]);
+    bytes
+}
+
+fn encode_uuid_v7(millis: u64, random: [u8; 16]) -> String {
+    let mut bytes = random;
+    bytes[..6].copy_from_slice(&millis.to_be_bytes()[2..]);
+    bytes[6] = 0x70 | (bytes[6] & 0x0f);
+    bytes[8] = 0x80 | (bytes[8] & 0x3f);
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn encode_ulid(millis: u64, random: [u8; 16]) -> String {
+    let mut random_part = [0; 16];
+    random_part[6..].copy_from_slice(&random[..10]);
+    let value = (u128::from(millis & 0xffff_ffff_ffff) << 80) | u128::from_be_bytes(random_part);
+    (0..26)
+        .rev()
+        .map(|digit| ULID_ALPHABET[((value >> (digit * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// The `x-request-id` of a request's headers, if present and well-formed
+///
+/// Values longer than 128 bytes or with characters other than visible
+/// ASCII are ignored, so a client cannot inject arbitrary text into every
+/// record of the request.
+pub fn from_headers<I, K, V>(headers: I) -> Option<String>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<[u8]>,
+{
+    headers
+        .into_iter()
+        .find(|(name, _)| name.as_ref().eq_ignore_ascii_case(HEADER))
+        .and_then(|(_, value)| {
+            let value = std::str::from_utf8(value.as_ref()).ok()?.trim();
+            let valid = !value.is_empty()
+                && value.len() <= MAX_LEN
+                && value.bytes().all(|byte| byte.is_ascii_graphic());
+            valid.then(|| value.to_string())
+        })
+}
+
+/// The incoming `x-request-id`, or a newly [generated](generate) id
+pub fn from_headers_or_generate<I, K, V>(headers: I) -> String
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<[u8]>,
+{
+    from_headers(headers).unwrap_or_else(generate)
+}
+
+/// Run `future` with `request_id` added to every record it emits
+pub fn scope<F: Future>(request_id: impl Into<String>, future: F) -> Scoped<F> {
+    context::scope([(FIELD, request_id.into())], future)
+}
+
+/// Run `f` with `request_id` added to every record it emits
+pub fn scope_sync<R>(request_id: impl Into<String>, f: impl FnOnce() -> R) -> R {
+    context::scope_sync([(FIELD, request_id.into())], f)
+}
+
+/// Request id of the current [`scope`]
+pub fn current() -> Option<String> {
+    context::current()
+        .get(FIELD)
+        .and_then(|id| id.as_str())
+        .map(str::to_string)
+}
+
+/// Header name and value forwarding the current request id to another service
+pub fn header() -> Option<(&'static str, String)> {
+    current().map(|id| (HEADER, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::per_test_subscriber;
+
+    #[test]
+    fn test_encodings() {
+        let random = [0xff; 16];
+        assert_eq!(
+            encode_uuid_v7(0x0190_b3c4_5f6e, random),
+            "0190b3c4-5f6e-7fff-bfff-ffffffffffff"
+        );
+        assert_eq!(encode_ulid(0, [0; 16]), "00000000000000000000000000");
+        assert_eq!(encode_ulid(1, [0; 16]), "00000000010000000000000000");
+        assert_eq!(ulid().len(), 26);
+        assert_ne!(uuid_v7(), uuid_v7());
+    }
+
+    #[test]
+    fn test_from_headers_rejects_malformed_ids() {
+        assert_eq!(
+            from_headers([("X-Request-Id", "req-42")]).as_deref(),
+            Some("req-42")
+        );
+        assert_eq!(from_headers([("x-request-id", "two words")]), None);
+        assert_eq!(from_headers([("x-request-id", "a".repeat(200))]), None);
+        assert_eq!(from_headers_or_generate([("accept", "*/*")]).len(), 36);
+    }
+
+    #[test]
+    fn test_scope_adds_request_id() {
+        let logs = per_test_subscriber();
+        scope_sync("req-7", || {
+            assert_eq!(header(), Some((HEADER, "req-7".to_string())));
+            tracing::info!("handled");
+        });
+        assert_eq!(current(), None);
+        assert_eq!(logs.records()[0]["fields"]["request_id"], "req-7");
+    }
+}
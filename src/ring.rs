@@ -0,0 +1,238 @@
+//! In-memory buffer of recent records
+//!
+//! Keeps the last few minutes of records at every level, independent of
+//! `RUST_LOG` and per-output filters, so an operator can dump everything
+//! around "what just happened" with [`export_recent`] (e.g. from an admin
+//! endpoint) without running the service at debug level.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Default time records are kept for
+const DEFAULT_RETENTION: Duration = Duration::from_secs(300);
+
+/// Default cap on buffered records
+const DEFAULT_MAX_RECORDS: usize = 50_000;
+
+/// Buffer installed by the global logger, kept for [`export_recent`]
+static INSTALLED: OnceLock<Arc<RingBuffer>> = OnceLock::new();
+
+/// Write the records of the last `duration` to `path`, oldest first
+///
+/// Returns the number of records written. Fails with
+/// [`io::ErrorKind::NotFound`] when the buffer is not enabled
+/// (`LOG_RING_BUFFER_SECS`).
+pub fn export_recent(duration: Duration, path: impl AsRef<Path>) -> io::Result<usize> {
+    let ring = INSTALLED.get().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "ring buffer is not enabled, set LOG_RING_BUFFER_SECS",
+        )
+    })?;
+    let mut file = BufWriter::new(File::create(path)?);
+    let written = ring.write_recent(duration, &mut file)?;
+    file.flush()?;
+    Ok(written)
+}
+
+/// Number of records held by the installed ring buffer, `None` when it is not enabled
+pub(crate) fn buffered() -> Option<usize> {
+    INSTALLED.get().map(|ring| ring.lock().len())
+}
+
+/// Drop every record held by the installed ring buffer
+pub(crate) fn clear() {
+    if let Some(ring) = INSTALLED.get() {
+        ring.lock().clear();
+    }
+}
+
+pub(crate) fn install(ring: Arc<RingBuffer>) {
+    let _ = INSTALLED.set(ring);
+}
+
+/// Ring buffer configuration
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RingConfig {
+    pub(crate) retention: Duration,
+    pub(crate) max_records: usize,
+    pub(crate) level: LevelFilter,
+}
+
+impl RingConfig {
+    /// Read the configuration from the environment, if the buffer is enabled
+    ///
+    /// - `LOG_RING_BUFFER_SECS`: How long records are kept (enables the buffer)
+    /// - `LOG_RING_BUFFER_MAX`: Maximum records kept (default: 50000)
+    /// - `LOG_RING_BUFFER_LEVEL`: Most verbose level kept (default: "trace")
+    pub(crate) fn from_env() -> Result<Option<Self>, String> {
+        let Ok(secs) = crate::config_file::var("LOG_RING_BUFFER_SECS") else {
+            return Ok(None);
+        };
+        let retention = match secs.trim() {
+            "" => DEFAULT_RETENTION,
+            secs => Duration::from_secs(
+                secs.parse()
+                    .map_err(|_| format!("LOG_RING_BUFFER_SECS '{}' is not a number", secs))?,
+            ),
+        };
+        let max_records = match crate::config_file::var("LOG_RING_BUFFER_MAX") {
+            Ok(max) => max
+                .trim()
+                .parse()
+                .map_err(|_| format!("LOG_RING_BUFFER_MAX '{}' is not a number", max.trim()))?,
+            Err(_) => DEFAULT_MAX_RECORDS,
+        };
+        let level = match crate::config_file::var("LOG_RING_BUFFER_LEVEL") {
+            Ok(level) => level
+                .trim()
+                .parse()
+                .map_err(|_| format!("LOG_RING_BUFFER_LEVEL '{}' is not a level", level.trim()))?,
+            Err(_) => LevelFilter::TRACE,
+        };
+        Ok(Some(Self {
+            retention,
+            max_records,
+            level,
+        }))
+    }
+}
+
+/// Recent formatted records with the time they were written
+pub(crate) struct RingBuffer {
+    config: RingConfig,
+    records: Mutex<VecDeque<(Instant, Vec<u8>)>>,
+}
+
+impl RingBuffer {
+    pub(crate) fn new(config: RingConfig) -> Self {
+        Self {
+            config,
+            records: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<(Instant, Vec<u8>)>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, record: Vec<u8>) {
+        let now = Instant::now();
+        let mut records = self.lock();
+        while records.len() >= self.config.max_records.max(1)
+            || records
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > self.config.retention)
+        {
+            records.pop_front();
+        }
+        records.push_back((now, record));
+    }
+
+    fn write_recent(&self, duration: Duration, out: &mut impl Write) -> io::Result<usize> {
+        // Copy out first so writing the file does not block logging
+        let since = Instant::now().checked_sub(duration);
+        let recent: Vec<Vec<u8>> = self
+            .lock()
+            .iter()
+            .filter(|(at, _)| match since {
+                Some(since) => *at >= since,
+                None => true,
+            })
+            .map(|(_, record)| record.clone())
+            .collect();
+        for record in &recent {
+            out.write_all(record)?;
+        }
+        Ok(recent.len())
+    }
+}
+
+/// `MakeWriter` appending records to a shared [`RingBuffer`]
+pub(crate) struct RingWriter(pub(crate) Arc<RingBuffer>);
+
+impl<'a> MakeWriter<'a> for RingWriter {
+    type Writer = RingRecord<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RingRecord {
+            buf: Vec::new(),
+            ring: &self.0,
+        }
+    }
+}
+
+/// Buffers one formatted record and appends it when dropped
+pub(crate) struct RingRecord<'a> {
+    buf: Vec<u8>,
+    ring: &'a RingBuffer,
+}
+
+impl Write for RingRecord<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RingRecord<'_> {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            self.ring.push(std::mem::take(&mut self.buf));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(max_records: usize) -> RingBuffer {
+        RingBuffer::new(RingConfig {
+            retention: Duration::from_secs(60),
+            max_records,
+            level: LevelFilter::TRACE,
+        })
+    }
+
+    #[test]
+    fn test_ring_keeps_most_recent_records() {
+        let ring = Arc::new(ring(3));
+        let writer = RingWriter(Arc::clone(&ring));
+        for i in 0..5 {
+            writeln!(writer.make_writer(), "{{\"i\":{}}}", i).unwrap();
+        }
+
+        let mut out = Vec::new();
+        assert_eq!(
+            ring.write_recent(Duration::from_secs(60), &mut out)
+                .unwrap(),
+            3
+        );
+        assert_eq!(out, b"{\"i\":2}\n{\"i\":3}\n{\"i\":4}\n");
+    }
+
+    #[test]
+    fn test_export_window_excludes_older_records() {
+        let ring = ring(10);
+        ring.push(b"old\n".to_vec());
+        std::thread::sleep(Duration::from_millis(50));
+        ring.push(b"new\n".to_vec());
+
+        let mut out = Vec::new();
+        ring.write_recent(Duration::from_millis(25), &mut out)
+            .unwrap();
+        assert_eq!(out, b"new\n");
+    }
+}
@@ -0,0 +1,181 @@
+//! Routing of records to log files by target
+//!
+//! One combined file makes it hard to hand a team only the stream it owns.
+//! [`Routes`] send the records of some targets to files of their own in
+//! `LOG_FILE_DIR`, e.g. the database driver's records to `db.log` for the
+//! DBA team. A rule matches its target and every module below it (`sqlx`
+//! matches `sqlx::query`), and the longest matching rule wins. Each record
+//! is written to exactly one file: `*` takes the records no other rule
+//! matches, and without it they stay in the regular file
+//! (`LOG_FILE_PREFIX`).
+//!
+//! Routed files rotate, buffer and lock like the regular file and are named
+//! by the same [`FileNaming`](crate::naming::FileNaming), with the route's
+//! file name as the prefix: `db.log` is written as `db.log.2025-03-07`.
+//! Only the regular file gets the `LOG_FILE_SYMLINK` link. Routes apply to
+//! file output only; the console and network outputs still see every
+//! record.
+
+use std::fmt;
+
+/// Catch-all target of a rule
+pub const ANY: &str = "*";
+
+/// Files records are written to, by target
+///
+/// # Examples
+/// ```no_run
+/// use custom_tracing_logger::routes::Routes;
+///
+/// let routes = Routes::new()
+///     .route("sqlx", "db.log")
+///     .route("audit", "audit.log")
+///     .route("*", "app.log");
+/// custom_tracing_logger::builder().with_routes(routes).init();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Routes {
+    rules: Vec<(String, String)>,
+}
+
+impl Routes {
+    /// Create routes without any rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write records of `target` and the modules below it to `file`
+    ///
+    /// `file` is a name in `LOG_FILE_DIR`; `"*"` as the target takes every
+    /// record no other rule matches. A second rule for the same target
+    /// replaces the first.
+    pub fn route(mut self, target: &str, file: &str) -> Self {
+        let (target, file) = (target.trim(), file.trim());
+        self.rules.retain(|(existing, _)| existing != target);
+        self.rules.push((target.to_string(), file.to_string()));
+        self
+    }
+
+    /// Whether no rules are configured
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// File for records of `target`, or `None` for the regular file
+    pub fn file_for(&self, target: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .filter(|(rule, _)| rule == ANY || matches(rule, target))
+            // Any named target beats the catch-all, the longest beats the others
+            .max_by_key(|(rule, _)| (rule != ANY, rule.len()))
+            .map(|(_, file)| file.as_str())
+    }
+
+    /// Whether a catch-all rule leaves no records for the regular file
+    pub(crate) fn has_catch_all(&self) -> bool {
+        self.rules.iter().any(|(rule, _)| rule == ANY)
+    }
+
+    /// Every routed file name once, in the order of the rules
+    pub(crate) fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = Vec::new();
+        for (_, file) in &self.rules {
+            if !files.contains(&file.as_str()) {
+                files.push(file);
+            }
+        }
+        files
+    }
+
+    /// Read rules from `LOG_ROUTES`, if set
+    ///
+    /// The variable lists `target=>file` rules, e.g.
+    /// "sqlx=>db.log,audit=>audit.log,*=>app.log".
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match crate::config_file::var("LOG_ROUTES") {
+            Ok(raw) => raw.parse().map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl std::str::FromStr for Routes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut routes = Self::new();
+        for rule in s.split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+            let (target, file) = rule
+                .split_once("=>")
+                .map(|(target, file)| (target.trim(), file.trim()))
+                .filter(|(target, file)| !target.is_empty() && !file.is_empty())
+                .ok_or_else(|| format!("'{}' is not a target=>file rule", rule))?;
+            if file.contains(['/', '\\']) {
+                return Err(format!(
+                    "file '{}' of '{}' must be a name in LOG_FILE_DIR",
+                    file, target
+                ));
+            }
+            routes = routes.route(target, file);
+        }
+        Ok(routes)
+    }
+}
+
+impl fmt::Display for Routes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .map(|(target, file)| format!("{}=>{}", target, file))
+            .collect();
+        f.write_str(&rules.join(","))
+    }
+}
+
+/// Whether `target` is `rule` or a module below it
+fn matches(rule: &str, target: &str) -> bool {
+    target
+        .strip_prefix(rule)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_rule_wins() {
+        let routes: Routes = "sqlx=>db.log, sqlx::migrate=>migrations.log, audit=>audit.log"
+            .parse()
+            .unwrap();
+        assert_eq!(routes.file_for("sqlx"), Some("db.log"));
+        assert_eq!(routes.file_for("sqlx::query"), Some("db.log"));
+        assert_eq!(
+            routes.file_for("sqlx::migrate::apply"),
+            Some("migrations.log")
+        );
+        assert_eq!(routes.file_for("sqlx_core"), None);
+        assert_eq!(routes.file_for("app::handlers"), None);
+        assert!(!routes.has_catch_all());
+
+        let routes = routes.route("*", "app.log").route("audit", "security.log");
+        assert_eq!(routes.file_for("app::handlers"), Some("app.log"));
+        assert_eq!(routes.file_for("audit"), Some("security.log"));
+        assert_eq!(
+            routes.files(),
+            vec!["db.log", "migrations.log", "app.log", "security.log"]
+        );
+        assert_eq!(
+            routes.to_string(),
+            "sqlx=>db.log,sqlx::migrate=>migrations.log,*=>app.log,audit=>security.log"
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        assert!("sqlx=db.log".parse::<Routes>().is_err());
+        assert!("sqlx=>".parse::<Routes>().is_err());
+        assert!("sqlx=>../db.log".parse::<Routes>().is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Probabilistic sampling per target and level
+//!
+//! Keeps only a fraction of the events of chatty targets, e.g.
+//! `LOG_SAMPLE=hyper=0.01,myapp::chatty=0.1` keeps 1% of `hyper`'s events
+//! and 10% of `myapp::chatty`'s. A rule applies to INFO and more verbose
+//! events unless it names a level with `@`: `myapp=0.5@debug` samples DEBUG
+//! and TRACE only. WARN and ERROR are never sampled unless a rule names
+//! them. A bare rate (`LOG_SAMPLE=0.2`) applies to every target without a
+//! rule of its own. The most specific target wins, as with `RUST_LOG`.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::str::FromStr;
+
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Most severe level sampled by a rule naming none
+const DEFAULT_LEVEL: Level = Level::INFO;
+
+/// Sampling rule of one target
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    /// Target prefix, empty for the default rule
+    target: String,
+    /// Fraction of events kept, between 0 and 1
+    rate: f64,
+    /// Most severe level sampled; more severe events are always kept
+    level: Level,
+}
+
+impl Rule {
+    fn matches(&self, target: &str) -> bool {
+        self.target.is_empty()
+            || target == self.target
+            || target
+                .strip_prefix(self.target.as_str())
+                .is_some_and(|rest| rest.starts_with("::"))
+    }
+}
+
+/// Sampling configuration
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sampling {
+    /// Rules, most specific target first
+    rules: Vec<Rule>,
+}
+
+impl Sampling {
+    /// No sampling; every event is kept
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep `rate` (0 to 1) of the INFO and more verbose events of `target` and its submodules
+    pub fn target(self, target: impl Into<String>, rate: f64) -> Self {
+        self.target_at(target, rate, DEFAULT_LEVEL)
+    }
+
+    /// Keep `rate` (0 to 1) of the events of `target` at `level` or more verbose
+    ///
+    /// An empty `target` applies to every target without a rule of its own.
+    pub fn target_at(mut self, target: impl Into<String>, rate: f64, level: Level) -> Self {
+        let target = target.into();
+        self.rules.retain(|rule| rule.target != target);
+        self.rules.push(Rule {
+            target,
+            rate: rate.clamp(0.0, 1.0),
+            level,
+        });
+        self.rules
+            .sort_by_key(|rule| std::cmp::Reverse(rule.target.len()));
+        self
+    }
+
+    /// Whether no rule is set
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Fraction of events at `level` from `target` that are kept
+    pub fn rate(&self, target: &str, level: &Level) -> f64 {
+        match self.rules.iter().find(|rule| rule.matches(target)) {
+            // Lower levels are more severe in tracing's ordering
+            Some(rule) if *level >= rule.level => rule.rate,
+            _ => 1.0,
+        }
+    }
+
+    /// Read the rules from `LOG_SAMPLE`, if set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match crate::config_file::var("LOG_SAMPLE") {
+            Ok(value) if !value.trim().is_empty() => value.parse().map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl FromStr for Sampling {
+    type Err = String;
+
+    /// Parse comma-separated `target=rate[@level]` rules and an optional bare `rate[@level]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sampling = Sampling::new();
+        for rule in s.split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+            let (target, rate) = rule.rsplit_once('=').unwrap_or(("", rule));
+            let (rate, level) = match rate.split_once('@') {
+                Some((rate, level)) => {
+                    let level = level
+                        .trim()
+                        .parse::<Level>()
+                        .map_err(|_| format!("unknown level '{}' in '{}'", level.trim(), rule))?;
+                    (rate, level)
+                }
+                None => (rate, DEFAULT_LEVEL),
+            };
+            let rate: f64 = rate
+                .trim()
+                .parse()
+                .ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .ok_or_else(|| format!("'{}' is not a rate between 0 and 1", rate.trim()))?;
+            sampling = sampling.target_at(target.trim(), rate, level);
+        }
+        Ok(sampling)
+    }
+}
+
+/// Layer dropping a share of the events of sampled targets before any output sees them
+pub(crate) struct SamplingLayer {
+    sampling: Sampling,
+}
+
+impl SamplingLayer {
+    pub(crate) fn new(sampling: Sampling) -> Self {
+        Self { sampling }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SamplingLayer {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        let metadata = event.metadata();
+        let rate = self.sampling.rate(metadata.target(), metadata.level());
+        rate >= 1.0 || (rate > 0.0 && uniform() < rate)
+    }
+}
+
+/// Uniform random number in [0, 1) from a per-thread xorshift generator
+pub(crate) fn uniform() -> f64 {
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new({
+            RandomState::new().hash_one(std::thread::current().id()) | 1
+        });
+    }
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_parse_rules() {
+        let sampling: Sampling = "hyper=0.01, myapp::chatty=0.1@debug, 0.5".parse().unwrap();
+        assert_eq!(sampling.rate("hyper::client", &Level::INFO), 0.01);
+        assert_eq!(sampling.rate("hyperlocal", &Level::INFO), 0.5);
+        assert_eq!(sampling.rate("hyper", &Level::WARN), 1.0);
+        assert_eq!(sampling.rate("myapp::chatty", &Level::INFO), 1.0);
+        assert_eq!(sampling.rate("myapp::chatty", &Level::TRACE), 0.1);
+        assert_eq!(sampling.rate("myapp", &Level::DEBUG), 0.5);
+        assert_eq!(sampling.rate("myapp", &Level::ERROR), 1.0);
+
+        assert!("hyper=2".parse::<Sampling>().is_err());
+        assert!("hyper=0.1@loud".parse::<Sampling>().is_err());
+        assert!("".parse::<Sampling>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sampled_events_are_dropped() {
+        let capture = Capture::default();
+        let sampling = Sampling::new().target("chatty", 0.0).target("noisy", 0.5);
+        let subscriber = tracing_subscriber::registry()
+            .with(SamplingLayer::new(sampling))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_writer(capture.clone()),
+            );
+
+        tracing::subscriber::with_default(subscriber, || {
+            for attempt in 0..1000 {
+                tracing::info!(target: "chatty", attempt, "Polling");
+                tracing::warn!(target: "chatty", attempt, "Slow poll");
+                tracing::info!(target: "noisy", attempt, "Cache hit");
+            }
+        });
+
+        let count = |message: &str| {
+            capture
+                .records()
+                .iter()
+                .filter(|r| r["fields"]["message"] == message)
+                .count()
+        };
+        assert_eq!(count("Polling"), 0);
+        assert_eq!(count("Slow poll"), 1000);
+        assert!((300..700).contains(&count("Cache hit")));
+    }
+}
@@ -0,0 +1,343 @@
+//! Event schema registry
+//!
+//! Teams register the fields and types they expect for an event, keyed by
+//! its message or event code. In debug builds (or with
+//! `LOG_SCHEMA_VALIDATE=true`) a validation layer checks every emitted event
+//! against its schema and warns once per deviation on stderr, catching
+//! schema drift before it breaks downstream dashboards.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Fields holding an event code, checked in order
+const CODE_FIELDS: [&str; 2] = ["event_code", "error_code"];
+
+/// Type of a recorded field value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    /// Strings, including `%display` and `?debug` values
+    String,
+    /// Signed or unsigned integers
+    Integer,
+    /// Floating point numbers (integers are accepted too)
+    Float,
+    /// Booleans
+    Bool,
+    /// Any value
+    Any,
+}
+
+impl FieldType {
+    fn accepts(self, found: FieldType) -> bool {
+        self == FieldType::Any
+            || self == found
+            || (self == FieldType::Float && found == FieldType::Integer)
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FieldType::String => "string",
+            FieldType::Integer => "integer",
+            FieldType::Float => "float",
+            FieldType::Bool => "bool",
+            FieldType::Any => "any",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Expected fields of one event
+#[derive(Debug, Clone, Default)]
+pub struct EventSchema {
+    fields: Vec<(String, FieldType, bool)>,
+}
+
+impl EventSchema {
+    /// Create a schema without fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect `name` on every event, with type `ty`
+    pub fn required(mut self, name: impl Into<String>, ty: FieldType) -> Self {
+        self.fields.push((name.into(), ty, true));
+        self
+    }
+
+    /// Allow `name`, checking its type `ty` when present
+    pub fn optional(mut self, name: impl Into<String>, ty: FieldType) -> Self {
+        self.fields.push((name.into(), ty, false));
+        self
+    }
+
+    /// Deviations of the recorded `fields` from this schema
+    pub fn check(&self, fields: &HashMap<String, FieldType>) -> Vec<Deviation> {
+        let mut deviations = Vec::new();
+        for (name, expected, required) in &self.fields {
+            match fields.get(name) {
+                None if *required => deviations.push(Deviation::Missing {
+                    field: name.clone(),
+                }),
+                Some(&found) if !expected.accepts(found) => deviations.push(Deviation::WrongType {
+                    field: name.clone(),
+                    expected: *expected,
+                    found,
+                }),
+                _ => {}
+            }
+        }
+        deviations
+    }
+}
+
+/// How an emitted event differs from its schema
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Deviation {
+    /// A required field was not recorded
+    Missing { field: String },
+    /// A field was recorded with an unexpected type
+    WrongType {
+        field: String,
+        expected: FieldType,
+        found: FieldType,
+    },
+}
+
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Deviation::Missing { field } => write!(f, "missing field '{}'", field),
+            Deviation::WrongType {
+                field,
+                expected,
+                found,
+            } => write!(f, "field '{}' is {}, expected {}", field, found, expected),
+        }
+    }
+}
+
+/// Schemas keyed by event message or event code
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    by_message: HashMap<String, EventSchema>,
+    by_code: HashMap<String, EventSchema>,
+}
+
+impl SchemaRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the schema of events with message `message`
+    pub fn message(mut self, message: impl Into<String>, schema: EventSchema) -> Self {
+        self.by_message.insert(message.into(), schema);
+        self
+    }
+
+    /// Register the schema of events whose `event_code` or `error_code` is `code`
+    pub fn code(mut self, code: impl Into<String>, schema: EventSchema) -> Self {
+        self.by_code.insert(code.into(), schema);
+        self
+    }
+
+    /// Schema registered for an event with the recorded values
+    fn lookup(&self, recorded: &Recorded) -> Option<(&str, &EventSchema)> {
+        CODE_FIELDS
+            .iter()
+            .filter_map(|field| recorded.strings.get(*field))
+            .find_map(|code| self.by_code.get_key_value(code))
+            .or_else(|| {
+                let message = recorded.strings.get("message")?;
+                self.by_message.get_key_value(message)
+            })
+            .map(|(key, schema)| (key.as_str(), schema))
+    }
+}
+
+/// Whether validation runs: debug builds, unless `LOG_SCHEMA_VALIDATE` says otherwise
+pub(crate) fn validation_enabled() -> bool {
+    match crate::config_file::var("LOG_SCHEMA_VALIDATE") {
+        Ok(value) => value == "true",
+        Err(_) => cfg!(debug_assertions),
+    }
+}
+
+/// Layer validating events against a [`SchemaRegistry`]
+pub(crate) struct SchemaLayer {
+    registry: SchemaRegistry,
+    reported: Arc<Mutex<HashSet<(String, Deviation)>>>,
+}
+
+impl SchemaLayer {
+    pub(crate) fn new(registry: SchemaRegistry) -> Self {
+        Self {
+            registry,
+            reported: Arc::default(),
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SchemaLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut recorded = Recorded::default();
+        event.record(&mut recorded);
+        let Some((key, schema)) = self.registry.lookup(&recorded) else {
+            return;
+        };
+
+        for deviation in schema.check(&recorded.types) {
+            let first = self
+                .reported
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert((key.to_string(), deviation.clone()));
+            // Events emitted while dispatching one are dropped, so report on stderr
+            if first {
+                eprintln!(
+                    "Event '{}' deviates from its registered schema: {}",
+                    key, deviation
+                );
+            }
+        }
+    }
+}
+
+/// Field types and string values of one event
+#[derive(Default)]
+struct Recorded {
+    types: HashMap<String, FieldType>,
+    strings: HashMap<String, String>,
+}
+
+impl Recorded {
+    fn insert(&mut self, field: &Field, ty: FieldType) {
+        self.types.insert(field.name().to_string(), ty);
+    }
+}
+
+impl Visit for Recorded {
+    fn record_i64(&mut self, field: &Field, _value: i64) {
+        self.insert(field, FieldType::Integer);
+    }
+
+    fn record_u64(&mut self, field: &Field, _value: u64) {
+        self.insert(field, FieldType::Integer);
+    }
+
+    fn record_i128(&mut self, field: &Field, _value: i128) {
+        self.insert(field, FieldType::Integer);
+    }
+
+    fn record_u128(&mut self, field: &Field, _value: u128) {
+        self.insert(field, FieldType::Integer);
+    }
+
+    fn record_f64(&mut self, field: &Field, _value: f64) {
+        self.insert(field, FieldType::Float);
+    }
+
+    fn record_bool(&mut self, field: &Field, _value: bool) {
+        self.insert(field, FieldType::Bool);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, FieldType::String);
+        self.strings
+            .insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        // Values from `structured::json` are checked as the JSON they hold
+        let mut value = Value::from(format!("{:?}", value));
+        crate::json_field::decode(&mut value);
+        let ty = match value {
+            Value::String(text) => {
+                self.strings.insert(field.name().to_string(), text);
+                FieldType::String
+            }
+            Value::Bool(_) => FieldType::Bool,
+            Value::Number(number) if number.is_f64() => FieldType::Float,
+            Value::Number(_) => FieldType::Integer,
+            Value::Null | Value::Array(_) | Value::Object(_) => FieldType::Any,
+        };
+        self.insert(field, ty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_schema_check() {
+        let schema = EventSchema::new()
+            .required("user_id", FieldType::Integer)
+            .optional("ratio", FieldType::Float)
+            .optional("ip", FieldType::String);
+        let fields = HashMap::from([
+            ("ratio".to_string(), FieldType::Integer),
+            ("ip".to_string(), FieldType::Bool),
+        ]);
+        assert_eq!(
+            schema.check(&fields),
+            vec![
+                Deviation::Missing {
+                    field: "user_id".to_string()
+                },
+                Deviation::WrongType {
+                    field: "ip".to_string(),
+                    expected: FieldType::String,
+                    found: FieldType::Bool
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layer_reports_once_per_deviation() {
+        let registry = SchemaRegistry::new()
+            .message(
+                "User logged in",
+                EventSchema::new().required("user_id", FieldType::Integer),
+            )
+            .code(
+                "E42",
+                EventSchema::new().required("order_id", FieldType::Integer),
+            );
+        let layer = SchemaLayer::new(registry);
+        let reported = Arc::clone(&layer.reported);
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(user_id = 1, "User logged in");
+            tracing::info!(user_id = "bob", "User logged in");
+            tracing::info!(user_id = "alice", "User logged in");
+            crate::log_error!("E42", "Payment failed");
+        });
+
+        let mut reported: Vec<_> = reported
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, deviation)| format!("{}: {}", key, deviation))
+            .collect();
+        reported.sort();
+        assert_eq!(
+            reported,
+            vec![
+                "E42: missing field 'order_id'",
+                "User logged in: field 'user_id' is string, expected integer",
+            ]
+        );
+    }
+}
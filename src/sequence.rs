@@ -0,0 +1,200 @@
+//! Per-process record sequence numbers and event ids
+//!
+//! Every event passing the global filter is given the next number from a
+//! process-wide counter starting at 1. The number is assigned once, before
+//! any output formats the event, so the same event carries the same `seq`
+//! in every output and missing numbers prove records were lost.
+//!
+//! With `LOG_EVENT_ID` each event is also given a time-ordered `event_id`
+//! (UUIDv7, or a ULID with `LOG_EVENT_ID=ulid`), unique across processes,
+//! for pipelines that deduplicate or deliver exactly once. Ids created in
+//! the same millisecond are not ordered among themselves; `seq` is.
+
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::span;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::request_id::IdFormat;
+use crate::spans::{self, SpanEvents};
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static CURRENT_SEQ: Cell<Option<u64>> = const { Cell::new(None) };
+    static CURRENT_EVENT_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Sequence number of the record currently being formatted on this thread
+pub(crate) fn current() -> Option<u64> {
+    CURRENT_SEQ.with(Cell::get)
+}
+
+/// Event id of the record currently being formatted on this thread
+pub(crate) fn current_event_id() -> Option<String> {
+    CURRENT_EVENT_ID.with(|current| current.borrow().clone())
+}
+
+/// Read `LOG_EVENT_ID`: "true" or "uuid" for UUIDv7 ids, "ulid" for ULIDs
+pub(crate) fn event_ids_from_env() -> Result<Option<IdFormat>, String> {
+    match crate::config_file::var("LOG_EVENT_ID") {
+        Ok(raw) => match raw.trim() {
+            "" | "false" => Ok(None),
+            "true" => Ok(Some(IdFormat::default())),
+            other => other
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("'{}', expected true, false, uuid or ulid", other)),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+/// Restart numbering at 1
+pub(crate) fn reset() {
+    NEXT_SEQ.store(1, Ordering::Relaxed);
+}
+
+/// Layer assigning sequence numbers and event ids; must run before the output layers
+pub(crate) struct SequenceLayer {
+    /// Whether span records are emitted and need numbers too
+    span_events: bool,
+    event_ids: Option<IdFormat>,
+}
+
+impl SequenceLayer {
+    pub(crate) fn new(span_events: bool) -> Self {
+        Self {
+            span_events,
+            event_ids: None,
+        }
+    }
+
+    /// Also give every record an event id in this format
+    pub(crate) fn with_event_ids(mut self, event_ids: Option<IdFormat>) -> Self {
+        self.event_ids = event_ids;
+        self
+    }
+
+    fn assign(&self) {
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+        CURRENT_SEQ.with(|current| current.set(Some(seq)));
+        if let Some(format) = self.event_ids {
+            CURRENT_EVENT_ID.with(|current| *current.borrow_mut() = Some(format.generate()));
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SequenceLayer {
+    fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+        self.assign();
+    }
+
+    fn on_new_span(&self, _attrs: &span::Attributes<'_>, _id: &span::Id, _ctx: Context<'_, S>) {
+        if self.span_events && spans::span_events().contains(SpanEvents::NEW) {
+            self.assign();
+        }
+    }
+
+    fn on_enter(&self, _id: &span::Id, _ctx: Context<'_, S>) {
+        if self.span_events && spans::span_events().contains(SpanEvents::ENTER) {
+            self.assign();
+        }
+    }
+
+    fn on_exit(&self, _id: &span::Id, _ctx: Context<'_, S>) {
+        if self.span_events && spans::span_events().contains(SpanEvents::EXIT) {
+            self.assign();
+        }
+    }
+
+    fn on_close(&self, _id: span::Id, _ctx: Context<'_, S>) {
+        if self.span_events && spans::span_events().contains(SpanEvents::CLOSE) {
+            self.assign();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use crate::format::EventFormat;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_outputs_share_sequence_numbers() {
+        let console = Capture::default();
+        let file = Capture::default();
+        let outputs: Vec<_> = [&console, &file]
+            .into_iter()
+            .map(|capture| {
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .event_format(EventFormat::new(false).with_sequence(true))
+                    .with_writer(capture.clone())
+            })
+            .collect();
+        let subscriber = tracing_subscriber::registry()
+            .with(SequenceLayer::new(false))
+            .with(outputs);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("first");
+            tracing::info!("second");
+        });
+
+        let seqs = |capture: &Capture| -> Vec<u64> {
+            capture
+                .records()
+                .iter()
+                .map(|record| record["seq"].as_u64().unwrap())
+                .collect()
+        };
+        let console = seqs(&console);
+        assert_eq!(console, seqs(&file));
+        assert_eq!(console[1], console[0] + 1);
+    }
+
+    #[test]
+    fn test_outputs_share_event_ids() {
+        let console = Capture::default();
+        let file = Capture::default();
+        let outputs: Vec<_> = [&console, &file]
+            .into_iter()
+            .map(|capture| {
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .event_format(
+                        EventFormat::new(false)
+                            .with_sequence(true)
+                            .with_event_id(true),
+                    )
+                    .with_writer(capture.clone())
+            })
+            .collect();
+        let subscriber = tracing_subscriber::registry()
+            .with(SequenceLayer::new(false).with_event_ids(Some(IdFormat::Ulid)))
+            .with(outputs);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("first");
+            tracing::info!("second");
+        });
+
+        let ids = |capture: &Capture| -> Vec<String> {
+            capture
+                .records()
+                .iter()
+                .map(|record| record["event_id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let console_ids = ids(&console);
+        assert_eq!(console_ids, ids(&file));
+        assert_ne!(console_ids[0], console_ids[1]);
+        assert_eq!(console_ids[0].len(), 26);
+        assert!(console.records()[1]["seq"].as_u64() > console.records()[0]["seq"].as_u64());
+    }
+}
@@ -0,0 +1,70 @@
+//! Unix signal handling
+//!
+//! With the `signals` feature and `LOG_SIGNALS=true`, a background thread
+//! handles:
+//! - `SIGHUP`: reopen the log file (for logrotate) and re-apply `LOG_FILTER_FILE`
+//! - `SIGUSR1`: raise the global level one step (e.g. info to debug)
+//! - `SIGUSR2`: lower the global level one step (e.g. info to warn)
+
+use std::thread;
+
+use signal_hook::consts::{SIGHUP, SIGUSR1, SIGUSR2};
+use signal_hook::iterator::Signals;
+
+use crate::reload::{self, ReloadHandle};
+
+/// Target of records reporting handled signals
+const TARGET: &str = "signal";
+
+/// Whether `LOG_SIGNALS=true` asks for signal handling
+pub(crate) fn enabled() -> bool {
+    crate::config_file::var("LOG_SIGNALS").unwrap_or_default() == "true"
+}
+
+/// Start handling log signals for the installed logger
+pub(crate) fn spawn(handle: ReloadHandle) {
+    let mut signals = match Signals::new([SIGHUP, SIGUSR1, SIGUSR2]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            eprintln!("Log signal handling disabled: {}", e);
+            return;
+        }
+    };
+    let spawned = thread::Builder::new()
+        .name("log-signals".to_string())
+        .spawn(move || {
+            for signal in signals.forever() {
+                handle_signal(&handle, signal);
+            }
+        });
+    if let Err(e) = spawned {
+        eprintln!("Failed to spawn log signal thread: {}", e);
+    }
+}
+
+fn handle_signal(handle: &ReloadHandle, signal: i32) {
+    match signal {
+        SIGHUP => {
+            crate::file::reopen_log_files();
+            let filter = reload::watch_from_env()
+                .and_then(|(path, _)| reload::read_filter_file(&path))
+                .map(|directives| handle.set_filter(&directives).map(|()| directives));
+            match filter {
+                Some(Ok(directives)) => {
+                    tracing::info!(target: TARGET, filter = %directives, "Log files reopened, filter reloaded")
+                }
+                Some(Err(e)) => eprintln!("Ignoring LOG_FILTER_FILE on SIGHUP: {}", e),
+                None => tracing::info!(target: TARGET, "Log files reopened"),
+            }
+        }
+        SIGUSR1 => match handle.raise_level() {
+            Ok(level) => tracing::info!(target: TARGET, %level, "Log level raised"),
+            Err(e) => eprintln!("Cannot raise log level: {}", e),
+        },
+        SIGUSR2 => match handle.lower_level() {
+            Ok(level) => tracing::warn!(target: TARGET, %level, "Log level lowered"),
+            Err(e) => eprintln!("Cannot lower log level: {}", e),
+        },
+        _ => {}
+    }
+}
@@ -0,0 +1,206 @@
+//! Pluggable sinks
+//!
+//! Implement [`LogSink`] to ship records anywhere (NATS, Redis, proprietary
+//! agents) without the crate needing a feature per backend, and register it
+//! with [`Builder::add_sink`](crate::Builder::add_sink). Records are
+//! formatted once and fanned out to every sink; a sink that panics is
+//! isolated so the others and the application keep running.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::flusher::{Pending, Target};
+
+/// Destination for formatted records
+pub trait LogSink: Send + Sync {
+    /// Receive one formatted record, without the trailing newline
+    fn write_event(&self, json: &[u8]);
+
+    /// Flush any buffered records
+    fn flush(&self) {}
+
+    /// Longest time between flushes while records are unflushed
+    ///
+    /// `None` (the default) uses `LOG_FLUSH_INTERVAL_MS`; without either the
+    /// sink is only flushed on request or by `LOG_IDLE_FLUSH_MS`.
+    fn flush_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl<F> LogSink for F
+where
+    F: Fn(&[u8]) + Send + Sync,
+{
+    fn write_event(&self, json: &[u8]) {
+        self(json)
+    }
+}
+
+/// Sinks installed by the global logger, kept for [`flush`]
+static INSTALLED: OnceLock<Arc<FanOut>> = OnceLock::new();
+
+/// Flush every sink registered with [`Builder::add_sink`](crate::Builder::add_sink)
+///
+/// The Loki, CloudWatch, webhook and Sentry sinks push what they have queued
+/// as well; this waits up to 5 seconds for them.
+pub fn flush() {
+    if let Some(fan_out) = INSTALLED.get() {
+        fan_out.flush();
+    }
+    #[cfg(any(
+        feature = "loki",
+        feature = "cloudwatch",
+        feature = "webhook",
+        feature = "sentry"
+    ))]
+    crate::batch::flush_all();
+}
+
+/// Number of sinks installed by the global logger
+pub(crate) fn installed_count() -> usize {
+    INSTALLED.get().map_or(0, |fan_out| fan_out.sinks.len())
+}
+
+pub(crate) fn install(fan_out: Arc<FanOut>) {
+    let _ = INSTALLED.set(fan_out);
+}
+
+/// A sink with its isolation state
+struct Isolated {
+    sink: Box<dyn LogSink>,
+    failed: AtomicBool,
+    pending: Arc<Pending>,
+}
+
+impl Isolated {
+    /// Run `f` against the sink, reporting the first panic on stderr
+    fn call(&self, f: impl FnOnce(&dyn LogSink)) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self.sink.as_ref())));
+        if result.is_err() {
+            crate::metrics::add_sink_failure();
+            if !self.failed.swap(true, Ordering::Relaxed) {
+                eprintln!("Log sink panicked; later failures of this sink are not reported");
+            }
+        }
+    }
+}
+
+/// Fans formatted records out to every registered sink
+pub(crate) struct FanOut {
+    sinks: Vec<Isolated>,
+}
+
+impl FanOut {
+    pub(crate) fn new(sinks: Vec<Box<dyn LogSink>>) -> Self {
+        let sinks = sinks
+            .into_iter()
+            .map(|sink| Isolated {
+                sink,
+                failed: AtomicBool::new(false),
+                pending: Arc::default(),
+            })
+            .collect();
+        Self { sinks }
+    }
+
+    fn write_event(&self, json: &[u8]) {
+        for sink in &self.sinks {
+            sink.call(|sink| sink.write_event(json));
+            sink.pending.mark();
+        }
+    }
+
+    fn flush(&self) {
+        for sink in &self.sinks {
+            sink.call(|sink| sink.flush());
+        }
+    }
+}
+
+/// Every sink as a target of the flusher thread
+pub(crate) fn flush_targets(fan_out: &Arc<FanOut>) -> Vec<Target> {
+    (0..fan_out.sinks.len())
+        .map(|index| {
+            let sink = &fan_out.sinks[index];
+            let interval = sink.sink.flush_interval();
+            let pending = Arc::clone(&sink.pending);
+            let fan_out = Arc::clone(fan_out);
+            Target {
+                pending,
+                interval,
+                flush: Box::new(move || fan_out.sinks[index].call(|sink| sink.flush())),
+            }
+        })
+        .collect()
+}
+
+/// `MakeWriter` handing each formatted record to a [`FanOut`]
+pub(crate) struct FanOutWriter(pub(crate) Arc<FanOut>);
+
+impl<'a> MakeWriter<'a> for FanOutWriter {
+    type Writer = FanOutRecord<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        FanOutRecord {
+            buf: Vec::new(),
+            fan_out: &self.0,
+        }
+    }
+}
+
+/// Buffers one formatted record and fans it out when dropped
+pub(crate) struct FanOutRecord<'a> {
+    buf: Vec<u8>,
+    fan_out: &'a FanOut,
+}
+
+impl std::io::Write for FanOutRecord<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for FanOutRecord<'_> {
+    fn drop(&mut self) {
+        let record = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+        if !record.is_empty() {
+            self.fan_out.write_event(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_panicking_sink_is_isolated() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let fan_out = Arc::new(FanOut::new(vec![
+            Box::new(|_: &[u8]| panic!("broken sink")),
+            Box::new(move |json: &[u8]| sink.lock().unwrap().push(json.to_vec())),
+        ]));
+        let writer = FanOutWriter(Arc::clone(&fan_out));
+
+        for _ in 0..2 {
+            writer.make_writer().write_all(b"{\"a\":1}\n").unwrap();
+        }
+        fan_out.flush();
+
+        assert_eq!(*received.lock().unwrap(), vec![b"{\"a\":1}".to_vec(); 2]);
+        assert!(fan_out.sinks[0].failed.load(Ordering::Relaxed));
+    }
+}
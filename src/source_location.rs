@@ -0,0 +1,41 @@
+//! Call site attribution
+//!
+//! With `LOG_SOURCE_LOCATION=true` every record gets the `file`, `line` and
+//! `module_path` of the macro call that emitted it, for when the target
+//! alone doesn't find the call site in a large codebase. Records bridged
+//! from the `log` crate carry the location of the original `log` call.
+
+use serde_json::{Map, Value};
+use tracing::Metadata;
+
+/// Whether `LOG_SOURCE_LOCATION=true` asks for call site fields
+pub(crate) fn enabled_from_env() -> bool {
+    crate::config_file::var("LOG_SOURCE_LOCATION").unwrap_or_default() == "true"
+}
+
+/// Add the call site fields to a formatted record, never replacing existing keys
+///
+/// Must run before the `log.*` fields of bridged records are dropped.
+pub(crate) fn apply(record: &mut Map<String, Value>, metadata: &Metadata<'_>, from_log: bool) {
+    let location = if from_log {
+        // The metadata of bridged records is the bridge's own
+        let fields = record.get("fields");
+        let field = |name: &str| fields.and_then(|fields| fields.get(name)).cloned();
+        [
+            ("file", field("log.file")),
+            ("line", field("log.line")),
+            ("module_path", field("log.module_path")),
+        ]
+    } else {
+        [
+            ("file", metadata.file().map(Into::into)),
+            ("line", metadata.line().map(Into::into)),
+            ("module_path", metadata.module_path().map(Into::into)),
+        ]
+    };
+    for (key, value) in location {
+        if let (Some(value), false) = (value, record.contains_key(key)) {
+            record.insert(key.to_string(), value);
+        }
+    }
+}
@@ -0,0 +1,451 @@
+//! Span field capture and span records
+//!
+//! Formatting span fields (e.g. the arguments recorded by `#[instrument]`)
+//! is a large share of logging CPU, but mostly useful while debugging. Field
+//! capture can be switched off at runtime, keeping only span names in the
+//! output, and switched back on when needed.
+//!
+//! Likewise the records written for spans themselves can be switched between
+//! verbose enter/exit pairs, a single compact record when the span closes
+//! (with how long it was busy and idle), a record when it is created, any
+//! combination, or none at all. Exit records carry `span_duration_ms`, the
+//! time since the matching enter, so `#[instrument]`ed functions are timed
+//! without an `Instant` of their own.
+
+use std::fmt;
+use std::ops::BitOr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::{JsonFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatFields, FormattedFields};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+static CAPTURE_FIELDS: AtomicBool = AtomicBool::new(true);
+
+static SPAN_EVENTS: AtomicU8 = AtomicU8::new(SpanEvents::ENTER_EXIT.0);
+
+/// Which records are written for spans, combined with `|`
+///
+/// # Examples
+/// ```
+/// use custom_tracing_logger::spans::SpanEvents;
+///
+/// let all = SpanEvents::ENTER | SpanEvents::EXIT | SpanEvents::CLOSE;
+/// assert_eq!("enter,exit,close".parse(), Ok(all));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpanEvents(u8);
+
+impl SpanEvents {
+    /// No span records; events still carry their current span
+    pub const NONE: Self = Self(0);
+    /// A record when a span is entered
+    pub const ENTER: Self = Self(1);
+    /// A record when a span is exited
+    pub const EXIT: Self = Self(2);
+    /// One record when a span closes, with `busy_ns`, `idle_ns` and `duration_ns`
+    pub const CLOSE: Self = Self(4);
+    /// A record when a span is created, with its initial fields
+    pub const NEW: Self = Self(8);
+    /// Enter and exit records (default)
+    pub const ENTER_EXIT: Self = Self(Self::ENTER.0 | Self::EXIT.0);
+    /// Every span record: new, enter, exit and close
+    pub const FULL: Self = Self(Self::NEW.0 | Self::ENTER_EXIT.0 | Self::CLOSE.0);
+
+    /// Whether every record of `other` is selected
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether a span record with this message ("new", "enter", "exit" or "close") is written
+    fn allows(self, message: &str) -> bool {
+        match message {
+            "new" => self.contains(Self::NEW),
+            "enter" => self.contains(Self::ENTER),
+            "exit" => self.contains(Self::EXIT),
+            "close" => self.contains(Self::CLOSE),
+            _ => false,
+        }
+    }
+}
+
+impl Default for SpanEvents {
+    fn default() -> Self {
+        Self::ENTER_EXIT
+    }
+}
+
+impl BitOr for SpanEvents {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl std::str::FromStr for SpanEvents {
+    type Err = String;
+
+    /// Parse a comma-separated list of "new", "enter", "exit" and "close", or "full" / "none"
+    ///
+    /// "enter_exit" is accepted as the pair of enter and exit, "off" as none.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events = Self::NONE;
+        for name in s.split(',').map(|name| name.trim().to_ascii_lowercase()) {
+            events = events
+                | match name.as_str() {
+                    "new" => Self::NEW,
+                    "enter" => Self::ENTER,
+                    "exit" => Self::EXIT,
+                    "close" => Self::CLOSE,
+                    "enter_exit" => Self::ENTER_EXIT,
+                    "full" => Self::FULL,
+                    "off" | "none" => Self::NONE,
+                    other => {
+                        return Err(format!(
+                        "unknown span event '{}', expected new, enter, exit, close, full or none",
+                        other
+                    ))
+                    }
+                };
+        }
+        Ok(events)
+    }
+}
+
+/// Select which records are written for spans
+///
+/// Takes effect immediately, also for spans that are already open. Has no
+/// effect when span events were disabled with `LOG_ENABLE_SPANS=false`.
+pub fn set_span_events(events: SpanEvents) {
+    SPAN_EVENTS.store(events.0, Ordering::Relaxed);
+}
+
+/// Which records are currently written for spans
+pub fn span_events() -> SpanEvents {
+    SpanEvents(SPAN_EVENTS.load(Ordering::Relaxed))
+}
+
+/// Busy and idle time of a span, kept in its extensions by [`TimingLayer`]
+#[derive(Debug)]
+struct SpanTiming {
+    opened: Instant,
+    /// Last enter or exit, or when the span was opened
+    last: Instant,
+    busy: Duration,
+    idle: Duration,
+    /// Length of the most recent enter to exit
+    last_busy: Duration,
+}
+
+/// Layer tracking how long spans are busy (entered) and idle, for close records
+pub(crate) struct TimingLayer;
+
+impl<S> Layer<S> for TimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let now = Instant::now();
+            span.extensions_mut().insert(SpanTiming {
+                opened: now,
+                last: now,
+                busy: Duration::ZERO,
+                idle: Duration::ZERO,
+                last_busy: Duration::ZERO,
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                let now = Instant::now();
+                timing.idle += now.saturating_duration_since(timing.last);
+                timing.last = now;
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                let now = Instant::now();
+                timing.last_busy = now.saturating_duration_since(timing.last);
+                timing.busy += timing.last_busy;
+                timing.last = now;
+            }
+        }
+    }
+}
+
+/// Timing fields of a span record
+///
+/// `busy_ns`, `idle_ns` and `duration_ns` for close records, and
+/// `span_duration_ms` (the time since the matching enter) for exit records.
+/// `None` for other records, or when [`TimingLayer`] is not installed.
+pub(crate) fn span_timing<S, N>(
+    event: &Event<'_>,
+    ctx: &FmtContext<'_, S, N>,
+) -> Option<Map<String, Value>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    if !event.metadata().is_span() {
+        return None;
+    }
+    let span = ctx.span(event.parent()?)?;
+    let extensions = span.extensions();
+    let timing = extensions.get::<SpanTiming>()?;
+    let mut fields = Map::new();
+    match span_record_kind(event).as_str() {
+        "exit" => {
+            // Milliseconds with microsecond precision; short functions would all be 0 otherwise
+            let micros = timing.last_busy.as_micros() as f64;
+            fields.insert("span_duration_ms".to_string(), Value::from(micros / 1000.0));
+        }
+        "close" => {
+            let now = Instant::now();
+            // A closing span is not entered, so the time since its last exit was idle
+            let idle = timing.idle + now.saturating_duration_since(timing.last);
+            let nanos = |duration: Duration| Value::from(crate::time_fields::nanos(duration));
+            fields.insert("busy_ns".to_string(), nanos(timing.busy));
+            fields.insert("idle_ns".to_string(), nanos(idle));
+            fields.insert(
+                "duration_ns".to_string(),
+                nanos(now.saturating_duration_since(timing.opened)),
+            );
+        }
+        _ => return None,
+    }
+    Some(fields)
+}
+
+/// Whether a span record produced by the formatting layer should be written
+pub(crate) fn span_record_enabled(event: &Event<'_>) -> bool {
+    span_events().allows(&span_record_kind(event))
+}
+
+/// Message of a span record: "new", "enter", "exit" or "close"
+fn span_record_kind(event: &Event<'_>) -> String {
+    struct Message(String);
+
+    impl Visit for Message {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "message" {
+                self.0 = value.to_string();
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    let mut message = Message(String::new());
+    event.record(&mut message);
+    message.0
+}
+
+/// Enable or disable capturing span fields
+///
+/// Takes effect for spans created afterwards; spans created while capture
+/// was disabled keep an empty field set for their whole lifetime.
+pub fn set_field_capture(enabled: bool) {
+    CAPTURE_FIELDS.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether span fields are currently captured
+pub fn field_capture() -> bool {
+    CAPTURE_FIELDS.load(Ordering::Relaxed)
+}
+
+/// JSON span field formatter honoring [`field_capture`]
+#[derive(Debug, Default)]
+pub(crate) struct SpanFields {
+    inner: JsonFields,
+}
+
+impl<'writer> FormatFields<'writer> for SpanFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        if field_capture() {
+            let valuable = crate::valuable_fields::collect(&fields);
+            if valuable.is_none() {
+                return self.inner.format_fields(writer, fields);
+            }
+            let mut formatted = String::new();
+            self.inner
+                .format_fields(Writer::new(&mut formatted), fields)?;
+            writer.write_str(&crate::valuable_fields::merge(formatted, valuable))
+        } else {
+            // The JSON formatter expects every span to carry a JSON object
+            writer.write_str("{}")
+        }
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &Record<'_>,
+    ) -> fmt::Result {
+        if !field_capture() {
+            return Ok(());
+        }
+
+        let mut added = String::new();
+        self.inner.format_fields(Writer::new(&mut added), fields)?;
+        let added = crate::valuable_fields::merge(added, crate::valuable_fields::collect(fields));
+        if current.fields.is_empty() {
+            current.fields = added;
+            return Ok(());
+        }
+
+        let mut merged: Map<String, Value> =
+            serde_json::from_str(&current.fields).map_err(|_| fmt::Error)?;
+        let added: Map<String, Value> = serde_json::from_str(&added).map_err(|_| fmt::Error)?;
+        merged.extend(added);
+        current.fields = serde_json::to_string(&merged).map_err(|_| fmt::Error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::Capture;
+    use crate::format::EventFormat;
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_span_events_modes() {
+        assert!(SpanEvents::ENTER_EXIT.allows("enter"));
+        assert!(!SpanEvents::ENTER_EXIT.allows("close"));
+        assert!(SpanEvents::CLOSE.allows("close"));
+        assert!(!SpanEvents::NONE.allows("exit"));
+        assert_eq!("CLOSE".parse::<SpanEvents>(), Ok(SpanEvents::CLOSE));
+        assert_eq!(
+            "enter_exit, close".parse::<SpanEvents>(),
+            Ok(SpanEvents::ENTER | SpanEvents::EXIT | SpanEvents::CLOSE)
+        );
+        assert_eq!("off".parse::<SpanEvents>(), Ok(SpanEvents::NONE));
+        assert_eq!(
+            "new,close".parse::<SpanEvents>(),
+            Ok(SpanEvents::NEW | SpanEvents::CLOSE)
+        );
+        assert!(SpanEvents::FULL.allows("new"));
+        assert!(!SpanEvents::default().allows("new"));
+        assert!("verbose".parse::<SpanEvents>().is_err());
+    }
+
+    #[test]
+    fn test_switch_to_close_only_records() {
+        let capture = Capture::default();
+        let mut layer = tracing_subscriber::fmt::layer()
+            .json()
+            .event_format(EventFormat::new(true))
+            .fmt_fields(SpanFields::default())
+            .with_writer(capture.clone());
+        layer.set_span_events(FmtSpan::ENTER | FmtSpan::EXIT | FmtSpan::CLOSE);
+        let subscriber = tracing_subscriber::registry().with(TimingLayer).with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("verbose").in_scope(|| {});
+            set_span_events(SpanEvents::CLOSE);
+            let span = tracing::info_span!("compact");
+            span.in_scope(|| std::thread::sleep(Duration::from_millis(2)));
+            drop(span);
+            set_span_events(SpanEvents::default());
+        });
+
+        let records = capture.records();
+        let messages: Vec<_> = records
+            .iter()
+            .map(|r| r["fields"]["message"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(messages, vec!["enter", "exit", "close"]);
+        assert!(records[0]["fields"].get("span_duration_ms").is_none());
+        assert!(records[1]["fields"]["span_duration_ms"].as_f64().is_some());
+        assert!(records[1]["fields"].get("busy_ns").is_none());
+        let close = &records[2]["fields"];
+        assert!(close.get("time.busy").is_some());
+        let busy = close["busy_ns"].as_u64().unwrap();
+        assert!(busy >= 2_000_000);
+        assert!(
+            close["duration_ns"].as_u64().unwrap() >= busy + close["idle_ns"].as_u64().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_exit_records_carry_span_duration() {
+        let capture = Capture::default();
+        let mut layer = tracing_subscriber::fmt::layer()
+            .json()
+            .event_format(EventFormat::new(true))
+            .fmt_fields(SpanFields::default())
+            .with_writer(capture.clone());
+        layer.set_span_events(FmtSpan::ENTER | FmtSpan::EXIT);
+        let subscriber = tracing_subscriber::registry().with(TimingLayer).with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("work");
+            span.in_scope(|| std::thread::sleep(Duration::from_millis(5)));
+            span.in_scope(|| {});
+        });
+
+        let records = capture.records();
+        let durations: Vec<f64> = records
+            .iter()
+            .filter(|r| r["fields"]["message"] == "exit")
+            .map(|r| r["fields"]["span_duration_ms"].as_f64().unwrap())
+            .collect();
+        assert_eq!(durations.len(), 2);
+        // Each exit is timed from its own enter
+        assert!(durations[0] >= 5.0);
+        assert!(durations[1] < durations[0]);
+    }
+
+    #[test]
+    fn test_disabled_capture_keeps_span_name_only() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .event_format(EventFormat::new(true))
+                .fmt_fields(SpanFields::default())
+                .with_writer(capture.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", user_id = 7, path = tracing::field::Empty);
+            span.record("path", "/a");
+            span.in_scope(|| tracing::info!("captured"));
+
+            set_field_capture(false);
+            let span = tracing::info_span!("request", user_id = 8);
+            set_field_capture(true);
+            span.in_scope(|| tracing::info!("skipped"));
+        });
+
+        let records = capture.records();
+        assert_eq!(records[0]["span"]["user_id"], 7);
+        assert_eq!(records[0]["span"]["path"], "/a");
+        assert_eq!(records[1]["span"]["name"], "request");
+        assert!(records[1]["span"].get("user_id").is_none());
+    }
+}
@@ -0,0 +1,73 @@
+//! Constant fields on every record
+//!
+//! Fields such as `service`, `version`, `environment` and `region` are set
+//! once, from `LOG_STATIC_FIELDS` or the builder, and added at the top level
+//! of every record, so dashboards can rely on them without each call site
+//! repeating them.
+
+use serde_json::{Map, Value};
+
+/// Parse `LOG_STATIC_FIELDS`, if set, else the config file's `[static_fields]`
+///
+/// The value is a comma-separated list of `key=value` pairs (e.g.
+/// "service=payments,env=prod"); values are kept as strings.
+pub(crate) fn from_env() -> Result<Option<Map<String, Value>>, String> {
+    match crate::config_file::var("LOG_STATIC_FIELDS") {
+        Ok(raw) => parse(&raw).map(Some),
+        Err(_) => Ok(crate::config_file::static_fields()),
+    }
+}
+
+fn parse(raw: &str) -> Result<Map<String, Value>, String> {
+    let mut fields = Map::new();
+    for pair in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' is not a key=value pair", pair))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("'{}' has an empty key", pair));
+        }
+        fields.insert(key.to_string(), value.trim().into());
+    }
+    Ok(fields)
+}
+
+/// Add `fields` to a formatted record, never replacing keys it already has
+pub(crate) fn apply(fields: &Map<String, Value>, record: &mut Map<String, Value>) {
+    for (key, value) in fields {
+        if !record.contains_key(key) {
+            record.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse() {
+        let fields = parse(" service=payments, env = prod ,,region=eu-west-1").unwrap();
+        assert_eq!(
+            Value::Object(fields),
+            json!({"service": "payments", "env": "prod", "region": "eu-west-1"})
+        );
+        assert!(parse("service").is_err());
+        assert!(parse("=prod").is_err());
+    }
+
+    #[test]
+    fn test_apply_keeps_existing_keys() {
+        let fields = parse("service=payments,level=custom").unwrap();
+        let mut record = json!({"level": "INFO", "fields": {"message": "hi"}});
+        apply(&fields, record.as_object_mut().unwrap());
+        assert_eq!(record["service"], "payments");
+        assert_eq!(record["level"], "INFO");
+    }
+}
@@ -0,0 +1,274 @@
+//! Log capture for tests
+//!
+//! [`per_test_subscriber`] installs a subscriber for the current thread
+//! only. Since cargo runs each test on its own thread, every test sees just
+//! its own records even with parallel test threads, and capture ends when
+//! the returned guard is dropped.
+//!
+//! With the `macros` feature, `#[logged_test]` does this for a whole test
+//! and names the capture `logs`:
+//!
+//! ```ignore
+//! use custom_tracing_logger::logged_test;
+//!
+//! #[logged_test]
+//! fn logs_login() {
+//!     login(7);
+//!     assert!(logs.contains("User logged in"));
+//! }
+//! ```
+//!
+//! [`init_for_tests`] instead installs the full logger for the whole process
+//! with console records kept in memory, for code that logs from spawned
+//! threads or tasks. Check its records with [`captured_events`],
+//! [`logs_contain`] and [`assert_logged!`](crate::assert_logged).
+
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use serde_json::Value;
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::format::EventFormat;
+use crate::spans::{SpanFields, TimingLayer};
+
+/// Records captured for one test, until dropped
+///
+/// Events emitted on threads spawned by the test are not captured, as
+/// they do not inherit the thread-local subscriber.
+pub struct TestLogs {
+    buffer: Buffer,
+    _guard: DefaultGuard,
+}
+
+/// Capture every record emitted on the current thread
+///
+/// Records use the same JSON layout as [`init`](crate::init) with spans
+/// enabled, at all levels regardless of `RUST_LOG`.
+///
+/// ```
+/// let logs = custom_tracing_logger::testing::per_test_subscriber();
+/// tracing::info!(user_id = 7, "User logged in");
+/// assert!(logs.contains("User logged in"));
+/// assert_eq!(logs.records()[0]["fields"]["user_id"], 7);
+/// ```
+pub fn per_test_subscriber() -> TestLogs {
+    let buffer = Buffer::default();
+    let subscriber = tracing_subscriber::registry().with(TimingLayer).with(
+        tracing_subscriber::fmt::layer()
+            .json()
+            .event_format(EventFormat::new(true))
+            .fmt_fields(SpanFields::default())
+            .with_writer(buffer.clone()),
+    );
+    TestLogs {
+        _guard: tracing::subscriber::set_default(subscriber),
+        buffer,
+    }
+}
+
+impl TestLogs {
+    /// Captured records, parsed as JSON
+    pub fn records(&self) -> Vec<Value> {
+        self.lines()
+            .iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Captured records as raw lines
+    pub fn lines(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.buffer.lock())
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Messages of the captured events, in order
+    pub fn messages(&self) -> Vec<String> {
+        self.records()
+            .iter()
+            .filter_map(|record| record["fields"]["message"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Whether any captured message contains `text`
+    pub fn contains(&self, text: &str) -> bool {
+        self.messages().iter().any(|message| message.contains(text))
+    }
+
+    /// Discard the records captured so far
+    pub fn clear(&self) {
+        self.buffer.lock().clear();
+    }
+}
+
+/// Console records of the logger installed by [`init_for_tests`]
+static CAPTURED: OnceLock<Buffer> = OnceLock::new();
+
+/// Install the logger process-wide, keeping console records in memory
+///
+/// The logger is configured as by [`init`](crate::init), except that
+/// console records go to a buffer instead of stdout and, unless `RUST_LOG`
+/// is set, every level is kept. Calling it from several tests is fine: the
+/// first call installs the logger and later ones return its handle. Nothing
+/// is captured if another logger was installed first.
+///
+/// Records from all tests share the buffer, so assert on values unique to
+/// the test, or use [`per_test_subscriber`] for isolated records.
+///
+/// ```
+/// use custom_tracing_logger::{assert_logged, testing};
+///
+/// testing::init_for_tests();
+/// std::thread::spawn(|| tracing::info!(order_id = 42, "Order shipped"))
+///     .join()
+///     .unwrap();
+/// assert!(testing::logs_contain("Order shipped"));
+/// assert_logged!("Order shipped", order_id = 42);
+/// ```
+pub fn init_for_tests() -> crate::ReloadHandle {
+    let buffer = CAPTURED.get_or_init(Buffer::default).clone();
+    let first = crate::reload::installed().is_none();
+    let handle = crate::builder().with_writer(buffer).init();
+    if first && std::env::var("RUST_LOG").is_err() {
+        let _ = handle.set_filter("trace");
+    }
+    handle
+}
+
+/// Records captured since [`init_for_tests`], parsed as JSON
+pub fn captured_events() -> Vec<Value> {
+    let Some(buffer) = CAPTURED.get() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&buffer.lock())
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Whether any record captured since [`init_for_tests`] has a message containing `text`
+pub fn logs_contain(text: &str) -> bool {
+    captured_events()
+        .iter()
+        .filter_map(|record| record["fields"]["message"].as_str())
+        .any(|message| message.contains(text))
+}
+
+/// Discard the records captured so far by [`init_for_tests`]
+pub fn clear_captured() {
+    if let Some(buffer) = CAPTURED.get() {
+        buffer.lock().clear();
+    }
+}
+
+/// Check for a captured record with a message containing `text` and the given fields
+///
+/// Backs [`assert_logged!`](crate::assert_logged); panics listing the
+/// captured messages when no record matches.
+#[doc(hidden)]
+pub fn assert_logged(text: &str, fields: &[(&str, Value)]) {
+    let records = captured_events();
+    let matches = |record: &Value| {
+        record["fields"]["message"]
+            .as_str()
+            .is_some_and(|message| message.contains(text))
+            && fields
+                .iter()
+                .all(|(name, value)| &record["fields"][*name] == value)
+    };
+    if !records.iter().any(matches) {
+        let messages: Vec<&str> = records
+            .iter()
+            .filter_map(|record| record["fields"]["message"].as_str())
+            .collect();
+        panic!(
+            "no record logged with message containing {:?} and fields {:?}; captured messages: {:?}",
+            text, fields, messages
+        );
+    }
+}
+
+/// Field value compared by [`assert_logged!`](crate::assert_logged)
+#[doc(hidden)]
+pub fn field_value(value: impl Into<Value>) -> Value {
+    value.into()
+}
+
+/// Shared in-memory writer
+#[derive(Clone, Default)]
+pub(crate) struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Buffer {
+    type Writer = Buffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_threads_are_isolated() {
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                std::thread::spawn(move || {
+                    let logs = per_test_subscriber();
+                    for i in 0..20 {
+                        tracing::info!(thread = t, "event {}", i);
+                    }
+                    let records = logs.records();
+                    assert_eq!(records.len(), 20);
+                    assert!(records.iter().all(|r| r["fields"]["thread"] == t));
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[cfg(feature = "macros")]
+    #[crate::logged_test]
+    fn test_logged_test_captures_own_records() {
+        tracing::info!(case = "macro", "inside logged test");
+        assert_eq!(logs.messages(), vec!["inside logged test"]);
+        assert_eq!(logs.records()[0]["fields"]["case"], "macro");
+    }
+
+    #[test]
+    fn test_capture_ends_with_guard() {
+        {
+            let logs = per_test_subscriber();
+            tracing::warn!("captured");
+            assert_eq!(logs.messages(), vec!["captured"]);
+            logs.clear();
+            assert!(logs.records().is_empty());
+        }
+        let logs = per_test_subscriber();
+        assert!(!logs.contains("captured"));
+    }
+}
@@ -0,0 +1,112 @@
+//! Durations and timestamps as field values
+//!
+//! `tracing` records a `Duration` or `SystemTime` passed with `?` as its
+//! Debug string ("1.234567s", "SystemTime { tv_sec: .. }"), which downstream
+//! tools cannot parse. These adapters give canonical forms instead: numbers
+//! for durations and RFC 3339 UTC strings for timestamps.
+//!
+//! ```
+//! use std::time::{Duration, SystemTime};
+//! use custom_tracing_logger::time_fields::{millis, Rfc3339};
+//!
+//! let elapsed = Duration::from_micros(1_234_567);
+//! tracing::info!(elapsed_ms = millis(elapsed), at = %Rfc3339(SystemTime::now()), "Done");
+//! // "elapsed_ms":1234.567,"at":"2025-01-01T12:00:00.000000Z"
+//! ```
+//!
+//! For existing `?duration` call sites, register
+//! [`field_format::debug_duration_as_millis`](crate::field_format::debug_duration_as_millis)
+//! for those field names instead.
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Duration in milliseconds, with sub-millisecond precision
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Duration in whole nanoseconds, saturating at `u64::MAX` (about 584 years)
+pub fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Milliseconds since the Unix epoch, negative for earlier times
+pub fn unix_millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => i64::try_from(after.as_millis()).unwrap_or(i64::MAX),
+        Err(before) => i64::try_from(before.duration().as_millis()).map_or(i64::MIN, |ms| -ms),
+    }
+}
+
+/// Displays a `SystemTime` as an RFC 3339 UTC timestamp with microseconds
+///
+/// Matches the layout of the record `timestamp`, e.g. "2025-01-01T12:00:00.000000Z".
+#[derive(Debug, Clone, Copy)]
+pub struct Rfc3339(pub SystemTime);
+
+impl fmt::Display for Rfc3339 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (secs, micros) = match self.0.duration_since(UNIX_EPOCH) {
+            Ok(after) => (after.as_secs() as i64, after.subsec_micros()),
+            Err(before) => {
+                let before = before.duration();
+                let secs = -(before.as_secs() as i64);
+                match before.subsec_micros() {
+                    0 => (secs, 0),
+                    micros => (secs - 1, 1_000_000 - micros),
+                }
+            }
+        };
+        let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            secs_of_day % 3600 / 60,
+            secs_of_day % 60,
+            micros
+        )
+    }
+}
+
+/// Gregorian date of a day count since 1970-01-01
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(millis(Duration::from_micros(1_234_567)), 1234.567);
+        assert_eq!(nanos(Duration::from_millis(2)), 2_000_000);
+        assert_eq!(nanos(Duration::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let time = UNIX_EPOCH + Duration::from_micros(1_709_210_096_789_012);
+        assert_eq!(Rfc3339(time).to_string(), "2024-02-29T12:34:56.789012Z");
+        assert_eq!(unix_millis(time), 1_709_210_096_789);
+
+        let before = UNIX_EPOCH - Duration::from_micros(1_500_000);
+        assert_eq!(Rfc3339(before).to_string(), "1969-12-31T23:59:58.500000Z");
+        assert_eq!(unix_millis(before), -1500);
+    }
+}
@@ -0,0 +1,169 @@
+//! Duration logging for a block of code
+//!
+//! [`timed!`](crate::timed!) and, with the `macros` feature, the
+//! `#[log_timed]` attribute log when an operation starts and when it ends,
+//! with its `duration_ms`. When the code evaluates to a `Result`, the end
+//! record says whether it succeeded: "Operation completed" with
+//! `success = true` at INFO, or "Operation failed" with `success = false`
+//! at WARN. Other values are logged as completed without `success`.
+//!
+//! ```
+//! use custom_tracing_logger::timed;
+//!
+//! let imported: Result<usize, String> = timed!("import_users", {
+//!     Ok(42)
+//! });
+//! // {"fields":{"message":"Operation started","operation":"import_users"},...}
+//! // {"fields":{"message":"Operation completed","operation":"import_users","duration_ms":0,"success":true},...}
+//! ```
+
+use std::time::Instant;
+
+/// Times an operation and logs its start and end, see the [module docs](self)
+///
+/// Dropping a timer without [`finish`](Self::finish), e.g. when a `?`
+/// leaves the timed code early, logs the end without `success`.
+#[derive(Debug)]
+pub struct Timer {
+    operation: &'static str,
+    start: Instant,
+    finished: bool,
+}
+
+impl Timer {
+    /// Log "Operation started" and start timing
+    pub fn start(operation: &'static str) -> Self {
+        tracing::info!(operation = operation, "Operation started");
+        Self {
+            operation,
+            start: Instant::now(),
+            finished: false,
+        }
+    }
+
+    /// Log the end of the operation; `success` is `None` when it is not known
+    pub fn finish(mut self, success: Option<bool>) {
+        self.finished = true;
+        self.log(success);
+    }
+
+    fn log(&self, success: Option<bool>) {
+        let duration_ms = self.start.elapsed().as_millis() as u64;
+        if success == Some(false) {
+            tracing::warn!(
+                operation = self.operation,
+                duration_ms = duration_ms,
+                success = false,
+                "Operation failed"
+            );
+        } else {
+            tracing::info!(
+                operation = self.operation,
+                duration_ms = duration_ms,
+                success = success,
+                "Operation completed"
+            );
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.log(None);
+        }
+    }
+}
+
+/// Borrowed value of timed code, probed for an outcome by [`timed!`](crate::timed!)
+#[doc(hidden)]
+pub struct Probe<'a, T>(pub &'a T);
+
+/// Outcome of a `Result`; preferred by method resolution over [`OtherOutcome`]
+#[doc(hidden)]
+pub trait ResultOutcome {
+    fn outcome(&self) -> Option<bool>;
+}
+
+impl<T, E> ResultOutcome for Probe<'_, Result<T, E>> {
+    fn outcome(&self) -> Option<bool> {
+        Some(self.0.is_ok())
+    }
+}
+
+/// Outcome of any other value: unknown
+#[doc(hidden)]
+pub trait OtherOutcome {
+    fn outcome(&self) -> Option<bool>;
+}
+
+impl<T> OtherOutcome for &Probe<'_, T> {
+    fn outcome(&self) -> Option<bool> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::per_test_subscriber;
+
+    #[test]
+    fn test_timed_logs_outcome_of_results() {
+        let logs = per_test_subscriber();
+        let parsed: Result<u32, std::num::ParseIntError> = crate::timed!("parse", { "x".parse() });
+        let total = crate::timed!("sum", { 1 + 2 });
+        assert!(parsed.is_err());
+        assert_eq!(total, 3);
+
+        let records = logs.records();
+        assert_eq!(records[0]["fields"]["message"], "Operation started");
+        assert_eq!(records[1]["level"], "WARN");
+        assert_eq!(records[1]["fields"]["message"], "Operation failed");
+        assert_eq!(records[1]["fields"]["success"], false);
+        assert!(records[1]["fields"]["duration_ms"].is_u64());
+        assert_eq!(records[3]["fields"]["message"], "Operation completed");
+        assert_eq!(records[3]["fields"]["operation"], "sum");
+        assert!(records[3]["fields"].get("success").is_none());
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn test_log_timed_attribute() {
+        #[crate::log_timed]
+        fn parse_id(input: &str) -> Result<u32, std::num::ParseIntError> {
+            let id = input.parse()?;
+            Ok(id)
+        }
+
+        #[crate::log_timed("greeting")]
+        fn greet() -> impl std::fmt::Display {
+            "hello"
+        }
+
+        let logs = per_test_subscriber();
+        assert!(parse_id("x").is_err());
+        assert_eq!(parse_id("7"), Ok(7));
+        assert_eq!(greet().to_string(), "hello");
+
+        let records = logs.records();
+        assert_eq!(records[0]["fields"]["operation"], "parse_id");
+        assert_eq!(records[1]["fields"]["message"], "Operation failed");
+        assert_eq!(records[3]["fields"]["success"], true);
+        assert_eq!(records[5]["fields"]["operation"], "greeting");
+    }
+
+    #[test]
+    fn test_dropped_timer_logs_completion() {
+        fn early(input: &str) -> Result<u32, std::num::ParseIntError> {
+            let _timer = super::Timer::start("early");
+            input.parse::<u32>()?;
+            Ok(1)
+        }
+
+        let logs = per_test_subscriber();
+        assert!(early("x").is_err());
+        let records = logs.records();
+        assert_eq!(records[1]["fields"]["message"], "Operation completed");
+        assert_eq!(records[1]["fields"]["operation"], "early");
+    }
+}
@@ -0,0 +1,258 @@
+//! W3C Trace Context (`traceparent`) propagation
+//!
+//! [`TraceParent`] parses the `traceparent` header of an incoming request,
+//! and [`scope`] adds its `trace_id`, a new `span_id` for this service,
+//! the caller's span as `parent_span_id` and `sampled` to every record of
+//! the request. [`header`] renders the current context for outgoing calls,
+//! so traces join up across services without an OpenTelemetry SDK.
+//!
+//! ```
+//! # async fn handle() {}
+//! # async fn run(headers: Vec<(String, String)>) {
+//! use custom_tracing_logger::trace_context::{self, TraceParent};
+//!
+//! let parent = TraceParent::from_headers(headers).unwrap_or_else(TraceParent::new_root);
+//! trace_context::scope(parent.child(), async {
+//!     tracing::info!("Handling order"); // "fields":{...,"trace_id":"4bf92f35...","span_id":"...","sampled":true}
+//!     if let Some((name, value)) = trace_context::header() {
+//!         // outgoing = outgoing.header(name, value);
+//!     }
+//!     handle().await;
+//! })
+//! .await;
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+
+use serde_json::Value;
+use tracing::Span;
+
+use crate::context::{self, Scoped};
+
+/// Header carrying the trace context
+pub const HEADER: &str = "traceparent";
+
+/// Trace flag marking a sampled trace
+const SAMPLED: u8 = 0x01;
+
+/// A parsed `traceparent` header: `00-<trace id>-<span id>-<flags>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    /// 32 lowercase hex digits identifying the trace
+    pub trace_id: String,
+    /// 16 lowercase hex digits identifying the span
+    pub span_id: String,
+    /// Span of the caller, if this context was created with [`child`](Self::child)
+    pub parent_span_id: Option<String>,
+    /// Trace flags; bit 0 is the sampled flag
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// Parse a `traceparent` header value
+    ///
+    /// Returns `None` for malformed values and the all-zero ids the
+    /// specification declares invalid. Versions above `00` are accepted as
+    /// long as their first four parts have the version `00` layout.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if !is_id(trace_id, 32) || !is_id(span_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            parent_span_id: None,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        })
+    }
+
+    /// The `traceparent` header of a request's headers, if present and valid
+    pub fn from_headers<I, K, V>(headers: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<[u8]>,
+    {
+        headers
+            .into_iter()
+            .find(|(name, _)| name.as_ref().eq_ignore_ascii_case(HEADER))
+            .and_then(|(_, value)| Self::parse(std::str::from_utf8(value.as_ref()).ok()?))
+    }
+
+    /// Start a new sampled trace, for requests arriving without a context
+    pub fn new_root() -> Self {
+        let random = crate::request_id::random_bytes();
+        Self {
+            trace_id: hex(&random),
+            span_id: new_span_id(),
+            parent_span_id: None,
+            flags: SAMPLED,
+        }
+    }
+
+    /// Context of a span of this service, called from the span this context describes
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            span_id: new_span_id(),
+            parent_span_id: Some(self.span_id.clone()),
+            flags: self.flags,
+        }
+    }
+
+    /// Whether the caller sampled the trace
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+
+    /// Record the ids on a span declaring `trace_id`, `span_id`, `parent_span_id` and `sampled`
+    ///
+    /// Spans from [`Correlation::request_span`](crate::http::Correlation::request_span) declare them.
+    pub fn record(&self, span: &Span) {
+        span.record("trace_id", self.trace_id.as_str());
+        span.record("span_id", self.span_id.as_str());
+        if let Some(parent) = &self.parent_span_id {
+            span.record("parent_span_id", parent.as_str());
+        }
+        span.record("sampled", self.sampled());
+    }
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        let mut fields = vec![
+            ("trace_id", self.trace_id.clone().into()),
+            ("span_id", self.span_id.clone().into()),
+            ("sampled", self.sampled().into()),
+        ];
+        if let Some(parent) = &self.parent_span_id {
+            fields.push(("parent_span_id", parent.clone().into()));
+        }
+        fields
+    }
+}
+
+impl fmt::Display for TraceParent {
+    /// The `traceparent` header value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+fn is_id(value: &str, len: usize) -> bool {
+    is_hex(value, len) && value.bytes().any(|byte| byte != b'0')
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn new_span_id() -> String {
+    hex(&crate::request_id::random_bytes()[..8])
+}
+
+/// Run `future` with the ids of `trace` added to every record it emits
+pub fn scope<F: Future>(trace: TraceParent, future: F) -> Scoped<F> {
+    context::scope(trace.fields(), future)
+}
+
+/// Run `f` with the ids of `trace` added to every record it emits
+pub fn scope_sync<R>(trace: TraceParent, f: impl FnOnce() -> R) -> R {
+    context::scope_sync(trace.fields(), f)
+}
+
+/// Trace context of the current [`scope`]
+pub fn current() -> Option<TraceParent> {
+    let context = context::current();
+    let text = |field: &str| {
+        context
+            .get(field)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    Some(TraceParent {
+        trace_id: text("trace_id")?,
+        span_id: text("span_id")?,
+        parent_span_id: text("parent_span_id"),
+        flags: match context.get("sampled").and_then(Value::as_bool) {
+            Some(false) => 0,
+            _ => SAMPLED,
+        },
+    })
+}
+
+/// `traceparent` header name and value for a call made in the current [`scope`]
+pub fn header() -> Option<(&'static str, String)> {
+    current().map(|trace| (HEADER, trace.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::per_test_subscriber;
+
+    const EXAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_parse_and_render() {
+        let parent = TraceParent::parse(EXAMPLE).unwrap();
+        assert_eq!(parent.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.span_id, "00f067aa0ba902b7");
+        assert!(parent.sampled());
+        assert_eq!(parent.to_string(), EXAMPLE);
+
+        for invalid in [
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ] {
+            assert_eq!(TraceParent::parse(invalid), None, "{}", invalid);
+        }
+        assert!(TraceParent::parse(&format!("01{}-future", &EXAMPLE[2..])).is_some());
+    }
+
+    #[test]
+    fn test_scope_adds_ids_and_renders_child_header() {
+        let logs = per_test_subscriber();
+        let parent = TraceParent::from_headers([("Traceparent", EXAMPLE)]).unwrap();
+        let child = parent.child();
+        scope_sync(child.clone(), || {
+            let (name, value) = header().unwrap();
+            assert_eq!(name, HEADER);
+            assert_eq!(
+                value,
+                format!("00-{}-{}-01", parent.trace_id, child.span_id)
+            );
+            tracing::info!("handled");
+        });
+
+        let fields = &logs.records()[0]["fields"];
+        assert_eq!(fields["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(fields["parent_span_id"], "00f067aa0ba902b7");
+        assert_eq!(fields["span_id"].as_str().unwrap().len(), 16);
+        assert_eq!(fields["sampled"], true);
+        assert_eq!(TraceParent::new_root().trace_id.len(), 32);
+    }
+}
@@ -0,0 +1,294 @@
+//! Nested JSON for `valuable` fields
+//!
+//! With the `valuable` feature, values implementing `valuable::Valuable`
+//! (e.g. with `#[derive(Valuable)]`) and wrapped with
+//! [`structured::valuable`](crate::structured::valuable) are written as
+//! nested JSON instead of their Debug strings: structs and maps become
+//! objects, lists and tuples arrays, and enums are tagged like serde's
+//! default, e.g. `"Pending"` or `{"Paid":{"cents":1250}}`.
+//!
+//! Recording `order = order.as_value()` without the wrapper is a tracing
+//! feature that is still unstable: it compiles only when the application is
+//! built with `RUSTFLAGS="--cfg tracing_unstable"`. Such fields are then
+//! collected here next to the JSON fields.
+
+use serde_json::{Map, Value};
+use tracing_subscriber::field::RecordFields;
+
+/// Valuable fields among `fields`, as JSON, `None` when there are none
+///
+/// Always `None` unless tracing passes valuable values to subscribers.
+#[cfg(not(all(tracing_unstable, feature = "valuable")))]
+pub(crate) fn collect<R: RecordFields>(_fields: &R) -> Option<Map<String, Value>> {
+    None
+}
+
+/// Valuable fields among `fields`, as JSON, `None` when there are none
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+pub(crate) fn collect<R: RecordFields>(fields: &R) -> Option<Map<String, Value>> {
+    struct Collector(Map<String, Value>);
+
+    impl tracing::field::Visit for Collector {
+        fn record_value(&mut self, field: &tracing::field::Field, value: valuable::Value<'_>) {
+            self.0.insert(field.name().to_string(), to_json(value));
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    let mut collector = Collector(Map::new());
+    fields.record(&mut collector);
+    (!collector.0.is_empty()).then_some(collector.0)
+}
+
+/// JSON object `fields` with `valuable` overriding the Debug strings of the same fields
+pub(crate) fn merge(fields: String, valuable: Option<Map<String, Value>>) -> String {
+    let Some(valuable) = valuable else {
+        return fields;
+    };
+    match serde_json::from_str::<Map<String, Value>>(&fields) {
+        Ok(mut merged) => {
+            merged.extend(valuable);
+            serde_json::to_string(&merged).unwrap_or(fields)
+        }
+        Err(_) => fields,
+    }
+}
+
+/// JSON of a valuable value
+#[cfg(feature = "valuable")]
+pub(crate) fn to_json(value: valuable::Value<'_>) -> Value {
+    use valuable::{Fields, Value as V};
+
+    match value {
+        V::Bool(b) => b.into(),
+        V::Char(c) => c.to_string().into(),
+        V::F32(f) => f.into(),
+        V::F64(f) => f.into(),
+        V::I8(i) => i.into(),
+        V::I16(i) => i.into(),
+        V::I32(i) => i.into(),
+        V::I64(i) => i.into(),
+        V::Isize(i) => i.into(),
+        // JSON numbers beyond 64 bits lose precision in most parsers
+        V::I128(i) => i64::try_from(i).map_or_else(|_| i.to_string().into(), Value::from),
+        V::U8(u) => u.into(),
+        V::U16(u) => u.into(),
+        V::U32(u) => u.into(),
+        V::U64(u) => u.into(),
+        V::Usize(u) => u.into(),
+        V::U128(u) => u64::try_from(u).map_or_else(|_| u.to_string().into(), Value::from),
+        V::String(s) => s.into(),
+        V::Path(path) => path.display().to_string().into(),
+        V::Error(e) => e.to_string().into(),
+        V::Unit => Value::Null,
+        V::Listable(list) => Collected::of(list).array(),
+        // `()` is the empty tuple, written as null like serde does
+        V::Tuplable(tuple) => match Collected::of(tuple) {
+            collected if collected.array.is_empty() => Value::Null,
+            collected => collected.array(),
+        },
+        V::Mappable(map) => Collected::of(map).object(),
+        V::Structable(structure) => {
+            let collected = Collected::of(structure);
+            if structure.definition().fields().is_named() {
+                collected.object()
+            } else {
+                collected.array()
+            }
+        }
+        V::Enumerable(enumeration) => {
+            let variant = enumeration.variant();
+            let collected = Collected::of(enumeration);
+            let fields = match variant.fields() {
+                Fields::Named(names) if !names.is_empty() => collected.object(),
+                Fields::Unnamed(1) => collected.array.into_iter().next().unwrap_or_default(),
+                Fields::Unnamed(len) if *len > 1 => collected.array(),
+                _ => return variant.name().into(),
+            };
+            let mut tagged = Map::new();
+            tagged.insert(variant.name().to_string(), fields);
+            Value::Object(tagged)
+        }
+        // Kinds of values added to `valuable` later
+        other => format!("{:?}", other).into(),
+    }
+}
+
+/// Everything a valuable value passes to its visitor
+#[cfg(feature = "valuable")]
+#[derive(Default)]
+struct Collected {
+    object: Map<String, Value>,
+    array: Vec<Value>,
+}
+
+#[cfg(feature = "valuable")]
+impl Collected {
+    fn of(value: &(impl valuable::Valuable + ?Sized)) -> Self {
+        let mut collected = Self::default();
+        value.visit(&mut collected);
+        collected
+    }
+
+    fn array(self) -> Value {
+        Value::Array(self.array)
+    }
+
+    fn object(self) -> Value {
+        Value::Object(self.object)
+    }
+}
+
+#[cfg(feature = "valuable")]
+impl valuable::Visit for Collected {
+    fn visit_value(&mut self, value: valuable::Value<'_>) {
+        self.array.push(to_json(value));
+    }
+
+    fn visit_named_fields(&mut self, named_values: &valuable::NamedValues<'_>) {
+        for (field, value) in named_values {
+            self.object
+                .insert(field.name().to_string(), to_json(*value));
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[valuable::Value<'_>]) {
+        self.array
+            .extend(values.iter().map(|value| to_json(*value)));
+    }
+
+    fn visit_entry(&mut self, key: valuable::Value<'_>, value: valuable::Value<'_>) {
+        let key = match to_json(key) {
+            Value::String(key) => key,
+            other => other.to_string(),
+        };
+        self.object.insert(key, to_json(value));
+    }
+}
+
+#[cfg(all(test, feature = "valuable"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use valuable::{
+        Enumerable, Fields, NamedField, NamedValues, StructDef, Structable, Valuable, Value as V,
+        Variant, VariantDef, Visit,
+    };
+
+    struct Order {
+        id: u64,
+        items: Vec<&'static str>,
+        status: Status,
+    }
+
+    enum Status {
+        Pending,
+        Paid(u32),
+    }
+
+    static ORDER_FIELDS: &[NamedField<'static>] = &[
+        NamedField::new("id"),
+        NamedField::new("items"),
+        NamedField::new("status"),
+    ];
+
+    impl Valuable for Order {
+        fn as_value(&self) -> V<'_> {
+            V::Structable(self)
+        }
+
+        fn visit(&self, visit: &mut dyn Visit) {
+            visit.visit_named_fields(&NamedValues::new(
+                ORDER_FIELDS,
+                &[
+                    self.id.as_value(),
+                    self.items.as_value(),
+                    self.status.as_value(),
+                ],
+            ));
+        }
+    }
+
+    impl Structable for Order {
+        fn definition(&self) -> StructDef<'_> {
+            StructDef::new_static("Order", Fields::Named(ORDER_FIELDS))
+        }
+    }
+
+    static STATUS_VARIANTS: &[VariantDef<'static>] = &[
+        VariantDef::new("Pending", Fields::Unnamed(0)),
+        VariantDef::new("Paid", Fields::Unnamed(1)),
+    ];
+
+    impl Valuable for Status {
+        fn as_value(&self) -> V<'_> {
+            V::Enumerable(self)
+        }
+
+        fn visit(&self, visit: &mut dyn Visit) {
+            if let Status::Paid(cents) = self {
+                visit.visit_unnamed_fields(&[cents.as_value()]);
+            }
+        }
+    }
+
+    impl Enumerable for Status {
+        fn definition(&self) -> valuable::EnumDef<'_> {
+            valuable::EnumDef::new_static("Status", STATUS_VARIANTS)
+        }
+
+        fn variant(&self) -> Variant<'_> {
+            match self {
+                Status::Pending => Variant::Static(&STATUS_VARIANTS[0]),
+                Status::Paid(_) => Variant::Static(&STATUS_VARIANTS[1]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_values_become_nested_json() {
+        let order = Order {
+            id: 7,
+            items: vec!["A1", "B2"],
+            status: Status::Paid(1250),
+        };
+        assert_eq!(
+            to_json(order.as_value()),
+            json!({"id": 7, "items": ["A1", "B2"], "status": {"Paid": 1250}})
+        );
+        assert_eq!(to_json(Status::Pending.as_value()), json!("Pending"));
+        assert_eq!(to_json(u128::MAX.as_value()), json!(u128::MAX.to_string()));
+        assert_eq!(to_json(().as_value()), Value::Null);
+
+        let merged = merge(
+            r#"{"order":"Order { id: 7 }","user":"ann"}"#.to_string(),
+            Some(Map::from_iter([("order".to_string(), json!({"id": 7}))])),
+        );
+        assert_eq!(merged, r#"{"order":{"id":7},"user":"ann"}"#);
+    }
+
+    #[test]
+    fn test_wrapped_values_through_formatter() {
+        use crate::format::{tests::capture_with, EventFormat};
+        use crate::structured;
+
+        let order = Order {
+            id: 7,
+            items: vec!["A1"],
+            status: Status::Pending,
+        };
+        let records = capture_with(EventFormat::new(true), || {
+            let span =
+                tracing::info_span!("checkout", status = %structured::valuable(&Status::Paid(5)));
+            let _entered = span.enter();
+            tracing::info!(order = %structured::valuable(&order), "Order created");
+        });
+        assert_eq!(
+            records[0]["fields"]["order"],
+            json!({"id": 7, "items": ["A1"], "status": "Pending"})
+        );
+        assert_eq!(records[0]["fields"]["message"], "Order created");
+        assert_eq!(records[0]["span"]["status"], json!({"Paid": 5}));
+    }
+}
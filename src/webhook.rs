@@ -0,0 +1,324 @@
+//! Webhook sink for critical events
+//!
+//! POSTs ERROR events (or another minimum level) as JSON to a URL, such as
+//! a Slack incoming webhook or an incident tool, without running a log
+//! pipeline. Events are batched on a background thread; a failed push is
+//! retried with backoff, and after repeated failures a circuit breaker stops
+//! pushing for a while instead of stalling on a dead endpoint. Enabled with
+//! the `webhook` feature and configured through `LOG_WEBHOOK_*` variables.
+//!
+//! Each push carries the events both as records and as a readable `text`,
+//! which is what Slack displays:
+//!
+//! ```json
+//! {"text":"ERROR payments: Charge failed","events":[{"level":"ERROR","target":"payments",...}]}
+//! ```
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tracing::Level;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::batch::{BatchEventWriter, BatchPolicy, BatchWriter, Entry};
+
+/// Longest `text` sent in one push; further events are only counted
+const MAX_TEXT_LINES: usize = 20;
+
+/// Webhook sink configuration
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// URL the events are POSTed to
+    pub url: String,
+    /// Least severe level sent
+    pub level: Level,
+    /// Maximum number of events sent in one push
+    pub batch_size: usize,
+    /// Maximum time an event waits before being pushed
+    pub batch_interval: Duration,
+    /// Attempts per push before its events are dropped
+    pub max_retries: u32,
+    /// Consecutive failed pushes that open the circuit breaker
+    pub breaker_threshold: u32,
+    /// Time the open breaker drops pushes before trying again
+    pub breaker_cooldown: Duration,
+}
+
+impl WebhookConfig {
+    /// Create a configuration sending ERROR events to `url` with default batching
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            level: Level::ERROR,
+            batch_size: 20,
+            batch_interval: Duration::from_millis(5000),
+            max_retries: 3,
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(60),
+        }
+    }
+
+    /// Read the configuration from the environment
+    ///
+    /// - `LOG_WEBHOOK_URL`: Endpoint (required, sink is disabled without it)
+    /// - `LOG_WEBHOOK_LEVEL`: Least severe level sent (default: "error")
+    /// - `LOG_WEBHOOK_BATCH_SIZE`: Events per push (default: 20)
+    /// - `LOG_WEBHOOK_BATCH_INTERVAL_MS`: Maximum push delay (default: 5000)
+    /// - `LOG_WEBHOOK_MAX_RETRIES`: Attempts per push (default: 3)
+    /// - `LOG_WEBHOOK_BREAKER_THRESHOLD`: Failed pushes that open the breaker (default: 5)
+    /// - `LOG_WEBHOOK_BREAKER_COOLDOWN_SECS`: Time the breaker stays open (default: 60)
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(url) = crate::config_file::var("LOG_WEBHOOK_URL") else {
+            return Ok(None);
+        };
+        let mut config = Self::new(url.trim());
+
+        if let Ok(level) = crate::config_file::var("LOG_WEBHOOK_LEVEL") {
+            config.level = level
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' is not a level", level))?;
+        }
+        if let Some(size) = env_parse::<usize>("LOG_WEBHOOK_BATCH_SIZE")? {
+            config.batch_size = size.max(1);
+        }
+        if let Some(ms) = env_parse::<u64>("LOG_WEBHOOK_BATCH_INTERVAL_MS")? {
+            config.batch_interval = Duration::from_millis(ms);
+        }
+        if let Some(retries) = env_parse::<u32>("LOG_WEBHOOK_MAX_RETRIES")? {
+            config.max_retries = retries.max(1);
+        }
+        if let Some(threshold) = env_parse::<u32>("LOG_WEBHOOK_BREAKER_THRESHOLD")? {
+            config.breaker_threshold = threshold.max(1);
+        }
+        if let Some(secs) = env_parse::<u64>("LOG_WEBHOOK_BREAKER_COOLDOWN_SECS")? {
+            config.breaker_cooldown = Duration::from_secs(secs);
+        }
+
+        Ok(Some(config))
+    }
+
+    /// The URL without its path and query, which often hold the webhook's secret
+    pub fn redacted_url(&self) -> String {
+        let (scheme, rest) = self.url.split_once("://").unwrap_or(("", &self.url));
+        let host = rest.split(['/', '?']).next().unwrap_or_default();
+        let host = host.rsplit('@').next().unwrap_or_default();
+        let scheme = if scheme.is_empty() {
+            String::new()
+        } else {
+            format!("{}://", scheme)
+        };
+        format!("{}{}/***", scheme, host)
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match crate::config_file::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} is not a number: '{}'", name, value)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Stops pushes to an endpoint that keeps failing
+#[derive(Debug)]
+struct Breaker {
+    threshold: u32,
+    cooldown: Duration,
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            failures: 0,
+            open_until: None,
+        }
+    }
+
+    /// Whether a push may be attempted at `now`; after the cooldown one trial push is let through
+    fn allow(&mut self, now: Instant) -> bool {
+        match self.open_until {
+            Some(until) if now < until => false,
+            Some(_) => {
+                self.open_until = None;
+                // A failed trial push opens the breaker again right away
+                self.failures = self.threshold.saturating_sub(1);
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn record(&mut self, delivered: bool, now: Instant) {
+        if delivered {
+            self.failures = 0;
+            return;
+        }
+        self.failures += 1;
+        if self.failures >= self.threshold {
+            self.open_until = Some(now + self.cooldown);
+            eprintln!(
+                "Webhook failed {} times in a row; pausing pushes for {}s",
+                self.failures,
+                self.cooldown.as_secs()
+            );
+        }
+    }
+}
+
+/// `MakeWriter` that hands formatted events to the webhook push thread
+///
+/// Events are dropped rather than blocking the application when the push
+/// thread falls behind or the breaker is open.
+pub struct WebhookWriter {
+    inner: BatchWriter,
+}
+
+impl WebhookWriter {
+    /// Spawn the push thread and return a writer feeding it
+    ///
+    /// Fails only when the thread can't be spawned.
+    pub fn new(config: WebhookConfig) -> io::Result<Self> {
+        let policy = BatchPolicy {
+            max_events: config.batch_size,
+            max_bytes: usize::MAX,
+            overhead: 0,
+            interval: config.batch_interval,
+        };
+        let mut breaker = Breaker::new(config.breaker_threshold, config.breaker_cooldown);
+
+        let inner = BatchWriter::spawn("webhook-push", policy, move |batch| {
+            if !breaker.allow(Instant::now()) {
+                crate::metrics::add_dropped(batch.len());
+                return;
+            }
+            let body = push_body(&batch).to_string();
+            let delivered = push(&config, &body);
+            breaker.record(delivered, Instant::now());
+            if !delivered {
+                crate::metrics::add_sink_failure();
+                crate::metrics::add_dropped(batch.len());
+            }
+        })?;
+        Ok(Self { inner })
+    }
+}
+
+impl<'a> MakeWriter<'a> for WebhookWriter {
+    type Writer = BatchEventWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.inner.make_writer()
+    }
+}
+
+/// POST `body`, retrying server errors and timeouts; `true` once delivered
+fn push(config: &WebhookConfig, body: &str) -> bool {
+    let mut backoff = Duration::from_millis(200);
+    for attempt in 1..=config.max_retries {
+        let result = ureq::post(&config.url)
+            .set("Content-Type", "application/json")
+            .timeout(Duration::from_secs(10))
+            .send_string(body);
+        match result {
+            Ok(_) => return true,
+            Err(ureq::Error::Status(status, _)) if status != 429 && status < 500 => {
+                eprintln!("Webhook rejected the push with status {}", status);
+                return false;
+            }
+            Err(e) => eprintln!("Webhook push attempt {} failed: {}", attempt, e),
+        }
+        if attempt < config.max_retries {
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_secs(10));
+        }
+    }
+    false
+}
+
+/// Build the payload: the records, and a line of text per record
+fn push_body(batch: &[Entry]) -> Value {
+    let events: Vec<Value> = batch
+        .iter()
+        .map(|entry| {
+            serde_json::from_str(&entry.line).unwrap_or_else(|_| entry.line.clone().into())
+        })
+        .collect();
+    let mut lines: Vec<String> = events.iter().take(MAX_TEXT_LINES).map(summary).collect();
+    if events.len() > MAX_TEXT_LINES {
+        lines.push(format!("... and {} more", events.len() - MAX_TEXT_LINES));
+    }
+    json!({ "text": lines.join("\n"), "events": events })
+}
+
+/// One readable line for a record in any layout
+fn summary(record: &Value) -> String {
+    let Some(object) = record.as_object() else {
+        return record.as_str().unwrap_or_default().to_string();
+    };
+    let text = |pointers: &[&str]| {
+        pointers
+            .iter()
+            .find_map(|pointer| record.pointer(pointer).and_then(Value::as_str))
+            .unwrap_or_default()
+    };
+    let level = text(&["/level", "/status", "/severity", "/log/level"]).to_uppercase();
+    let target = text(&["/target", "/logger.name", "/log/logger"]);
+    let message = text(&["/fields/message", "/message", "/msg"]);
+    let message = if message.is_empty() && !object.is_empty() {
+        record.to_string()
+    } else {
+        message.to_string()
+    };
+    format!("{} {}: {}", level, target, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_body_has_text_and_events() {
+        let batch = vec![Entry::new(
+            r#"{"level":"ERROR","fields":{"message":"Charge failed","order_id":42},"target":"payments"}"#.to_string(),
+        )];
+        let body = push_body(&batch);
+        assert_eq!(body["text"], "ERROR payments: Charge failed");
+        assert_eq!(body["events"][0]["fields"]["order_id"], 42);
+    }
+
+    #[test]
+    fn test_redacted_url_hides_path() {
+        let config = WebhookConfig::new("https://hooks.slack.com/services/T000/B000/XXXX");
+        assert_eq!(config.redacted_url(), "https://hooks.slack.com/***");
+        let config = WebhookConfig::new("https://user:pw@alerts.example.com?key=1");
+        assert_eq!(config.redacted_url(), "https://alerts.example.com/***");
+    }
+
+    #[test]
+    fn test_breaker_opens_and_retries_after_cooldown() {
+        let mut breaker = Breaker::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(breaker.allow(start));
+        breaker.record(false, start);
+        breaker.record(false, start);
+        assert!(!breaker.allow(start + Duration::from_secs(30)));
+
+        // One trial push after the cooldown; failing it reopens the breaker
+        assert!(breaker.allow(start + Duration::from_secs(61)));
+        breaker.record(false, start + Duration::from_secs(61));
+        assert!(!breaker.allow(start + Duration::from_secs(62)));
+
+        assert!(breaker.allow(start + Duration::from_secs(122)));
+        breaker.record(true, start + Duration::from_secs(122));
+        assert!(breaker.allow(start + Duration::from_secs(123)));
+    }
+}